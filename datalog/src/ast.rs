@@ -0,0 +1,152 @@
+//! The abstract syntax of Datalog programs.
+
+use std::collections::BTreeMap;
+
+use crate::Value;
+
+/// A term in the argument of an atom.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Term {
+    /// A variable, bound to the same value wherever it occurs in a rule.
+    Var(String),
+    /// A constant value.
+    Const(Value),
+    /// The wildcard `_`, which matches any value.
+    Wildcard,
+}
+
+/// A relation applied to terms, as in `edge(?x, ?y)`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Atom {
+    /// The name of the relation.
+    pub relation: String,
+    /// One term for each column of the relation.
+    pub terms: Vec<Term>,
+}
+
+/// An expression over the values of variables.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    /// A term, which may not be a wildcard.
+    Term(Term),
+    /// The concatenation of the string forms of several expressions, written `cat(a, b, ..)`.
+    Cat(Vec<Expr>),
+    /// An arithmetic operation on two integer expressions.
+    Arith(ArithOp, Box<Expr>, Box<Expr>),
+}
+
+/// Arithmetic operations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArithOp {
+    /// Addition, written `+`.
+    Add,
+    /// Subtraction, written `-`.
+    Sub,
+    /// Multiplication, written `*`.
+    Mul,
+}
+
+/// Comparison operations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CmpOp {
+    /// Equality, written `=`. An equation binds a variable that does not otherwise occur in the body.
+    Eq,
+    /// Inequality, written `!=`.
+    Ne,
+    /// Less than, written `<`.
+    Lt,
+    /// Less than or equal, written `<=`.
+    Le,
+    /// Greater than, written `>`.
+    Gt,
+    /// Greater than or equal, written `>=`.
+    Ge,
+}
+
+/// A literal in the body of a rule.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Literal {
+    /// Satisfied by each tuple of the relation matching the atom.
+    Positive(Atom),
+    /// Satisfied if no tuple of the relation matches the atom, written `!atom`.
+    Negative(Atom),
+    /// Satisfied if the comparison holds.
+    Compare(Expr, CmpOp, Expr),
+}
+
+/// Aggregation functions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Aggregate {
+    /// The number of distinct values.
+    Count,
+    /// The sum of the distinct integer values.
+    Sum,
+    /// The least value.
+    Min,
+    /// The greatest value.
+    Max,
+}
+
+/// A term in the head of a rule.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HeadTerm {
+    /// The value of an expression.
+    Expr(Expr),
+    /// An aggregate of the values of a variable, written as in `count(?y)`.
+    ///
+    /// The other terms of the head group the bindings of the body, and the aggregate is applied to the
+    /// distinct values the variable takes within each group.
+    Aggregate(Aggregate, String),
+}
+
+/// The head of a rule.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Head {
+    /// The name of the relation.
+    pub relation: String,
+    /// One term for each column of the relation.
+    pub terms: Vec<HeadTerm>,
+}
+
+impl Head {
+    /// The aggregate of the head and its position, if any.
+    pub fn aggregate(&self) -> Option<(usize, Aggregate, &str)> {
+        self.terms.iter().enumerate().find_map(|(index, term)| match term {
+            HeadTerm::Aggregate(aggregate, var) => Some((index, *aggregate, &var[..])),
+            HeadTerm::Expr(_) => None,
+        })
+    }
+}
+
+/// A rule, which derives its head from each binding of variables that satisfies its body.
+///
+/// A fact is a rule with an empty body.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rule {
+    /// The derived tuples.
+    pub head: Head,
+    /// The conjunction of literals that bindings must satisfy.
+    pub body: Vec<Literal>,
+    /// The line of the program at which the rule starts.
+    pub line: usize,
+}
+
+/// The declaration of a relation.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Declaration {
+    /// The number of columns of the relation.
+    pub arity: usize,
+    /// Set if tuples of the relation are supplied as input, by `.input`.
+    pub input: bool,
+    /// Set if the relation is marked as an output, by `.output`.
+    pub output: bool,
+}
+
+/// A Datalog program.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Program {
+    /// The declared relations, by name.
+    pub relations: BTreeMap<String, Declaration>,
+    /// The rules and facts, in the order they appear.
+    pub rules: Vec<Rule>,
+}
@@ -0,0 +1,108 @@
+//! A Datalog frontend for differential dataflow.
+//!
+//! Programs are written in a dialect of Datalog with stratified negation and aggregation, close enough to
+//! Soufflé to run rule files like Doop's. The `parse` function reads a program, and the `render` function
+//! builds a dataflow for it, with an `InputSession` for each relation marked as an input and a collection
+//! of the tuples of each relation.
+//!
+//! Relations are partitioned into strata, each of which depends only on itself and on earlier strata, and
+//! only positively on itself. The relations of a stratum that depends on itself are computed as the fixed
+//! point of their rules in an iterative scope, and the arrangements of relations of earlier strata are
+//! shared by each of the rules that look them up.
+//!
+//! # Examples
+//!
+//! ```
+//! use differential_dataflow::input::Input;
+//! use datalog::Value;
+//!
+//! let program = datalog::parse(r#"
+//!     .decl edge(a: number, b: number)
+//!     .input edge
+//!     .decl reach(a: number, b: number)
+//!     .decl reached(a: number, n: number)
+//!
+//!     reach(?a, ?b) :- edge(?a, ?b).
+//!     reach(?a, ?c) :- reach(?a, ?b), edge(?b, ?c).
+//!     reached(?a, count(?b)) :- reach(?a, ?b).
+//! "#).unwrap();
+//!
+//! timely::execute_directly(move |worker| {
+//!     let mut inputs = worker.dataflow::<u64, _, _>(|scope| {
+//!         let dataflow = datalog::render(&program, scope).unwrap();
+//!         let expected = scope.new_collection_from(vec![
+//!             vec![Value::Int(1), Value::Int(2)],
+//!             vec![Value::Int(2), Value::Int(1)],
+//!         ]).1;
+//!         dataflow.relations["reached"].assert_eq(&expected);
+//!         dataflow.inputs
+//!     });
+//!
+//!     let edge = inputs.get_mut("edge").unwrap();
+//!     edge.insert(vec![Value::Int(1), Value::Int(2)]);
+//!     edge.insert(vec![Value::Int(2), Value::Int(3)]);
+//! });
+//! ```
+
+#![forbid(missing_docs)]
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub mod ast;
+pub mod parse;
+pub mod stratify;
+pub mod render;
+
+pub use parse::parse;
+pub use render::{render, Dataflow};
+
+/// A value in a tuple of a relation.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Value {
+    /// A signed integer, of type `number`.
+    Int(i64),
+    /// A string, of type `symbol`.
+    Str(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// An error in a program, from parsing, checking, or stratifying it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    /// The line of the program at which the error occurs, if it occurs at a specific line.
+    pub line: Option<usize>,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl Error {
+    /// An error that does not occur at a specific line.
+    pub fn new(message: impl Into<String>) -> Self {
+        Error { line: None, message: message.into() }
+    }
+    /// An error at `line`.
+    pub fn at(line: usize, message: impl Into<String>) -> Self {
+        Error { line: Some(line), message: message.into() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error { }
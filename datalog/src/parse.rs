@@ -0,0 +1,669 @@
+//! Parsing of Datalog programs.
+//!
+//! The dialect follows Soufflé closely enough to read Doop's rule files. A program is a sequence of
+//! directives, rules, and facts. Comments are written `// ..` and `/* .. */`.
+//!
+//! * `.decl name(a: type, b: type)` declares a relation and its columns. Types are not checked.
+//! * `.input name(..)` and `.output name(..)` mark relations as inputs and outputs, and ignore their
+//!   parenthesized parameters.
+//! * `.type ..` declarations are ignored, up to the end of their line.
+//! * `.plan 1:(3,2,1), ..` gives orders of the atoms of the preceding rule, which are checked and ignored.
+//! * `.comp Name { .. }` declares a component, and `.init inst = Name` includes its directives and rules
+//!   with each relation it declares renamed to `inst.relation`.
+//! * `head(..), head2(..) :- body.` derives each head from each binding that satisfies the body, and
+//!   `head(..).` states a fact.
+//!
+//! A body is a conjunction of literals separated by `,`, and may combine conjunctions with `;` and
+//! parentheses. A literal is an atom `rel(t1, t2, ..)`, a negated atom `!rel(..)`, or a comparison of
+//! expressions with `=`, `!=`, `<`, `<=`, `>`, or `>=`. Terms of atoms are variables, which are identifiers
+//! optionally starting with `?`, the wildcard `_`, integers, and strings in double quotes. Expressions are
+//! built from terms with `+`, `-`, `*`, and `cat(..)`. A head term may be an aggregate `count(?v)`,
+//! `sum(?v)`, `min(?v)`, or `max(?v)` of a variable of the body.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{Error, Value};
+use crate::ast::*;
+
+/// Parses and checks a program.
+///
+/// Each relation must be declared, and used with as many terms as it has columns. Each variable of a
+/// rule must occur in a positive atom of its body, or be bound by an equation to an expression of such
+/// variables, and wildcards may only occur in the atoms of bodies.
+pub fn parse(text: &str) -> Result<Program, Error> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser::new(tokens, HashMap::new());
+    parser.items()?;
+    let program = parser.finish()?;
+    check(&program)?;
+    Ok(program)
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    /// An identifier, possibly qualified or starting with `?`.
+    Ident(String),
+    Int(i64),
+    Str(String),
+    /// A directive such as `.decl`, without its leading period.
+    Directive(String),
+    Punct(&'static str),
+}
+
+/// Punctuation, with longer tokens before their prefixes.
+const PUNCTUATION: &[&str] = &[":-", "!=", "<=", ">=", "(", ")", ",", ".", ";", "!", "=", "<", ">", "+", "-", "*", ":", "{", "}"];
+
+fn is_ident_start(c: char) -> bool { c.is_alphabetic() || c == '_' || c == '?' }
+fn is_ident(c: char) -> bool { c.is_alphanumeric() || c == '_' || c == '?' }
+
+/// Splits `text` into tokens, each paired with its line number.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        let next = chars.get(pos + 1).copied();
+        if c == '\n' {
+            line += 1;
+            pos += 1;
+        }
+        else if c.is_whitespace() {
+            pos += 1;
+        }
+        else if c == '/' && next == Some('/') {
+            while pos < chars.len() && chars[pos] != '\n' { pos += 1; }
+        }
+        else if c == '/' && next == Some('*') {
+            pos += 2;
+            while pos < chars.len() && !(chars[pos] == '*' && chars.get(pos + 1) == Some(&'/')) {
+                if chars[pos] == '\n' { line += 1; }
+                pos += 1;
+            }
+            if pos == chars.len() { return Err(Error::at(line, "unterminated comment")); }
+            pos += 2;
+        }
+        else if c == '"' {
+            let mut string = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None | Some('\n') => return Err(Error::at(line, "unterminated string")),
+                    Some('"') => break,
+                    Some('\\') => {
+                        match chars.get(pos + 1) {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(&other) => string.push(other),
+                            None => return Err(Error::at(line, "unterminated string")),
+                        }
+                        pos += 2;
+                    },
+                    Some(&other) => {
+                        string.push(other);
+                        pos += 1;
+                    },
+                }
+            }
+            pos += 1;
+            tokens.push((Token::Str(string), line));
+        }
+        else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && chars[pos].is_ascii_digit() { pos += 1; }
+            let digits = chars[start .. pos].iter().collect::<String>();
+            let value = digits.parse().map_err(|_| Error::at(line, format!("integer out of range: {}", digits)))?;
+            tokens.push((Token::Int(value), line));
+        }
+        else if c == '.' && next.map(char::is_alphabetic).unwrap_or(false) {
+            let start = pos + 1;
+            pos += 1;
+            while pos < chars.len() && is_ident(chars[pos]) { pos += 1; }
+            tokens.push((Token::Directive(chars[start .. pos].iter().collect()), line));
+        }
+        else if is_ident_start(c) {
+            let start = pos;
+            // A period continues an identifier only if an identifier follows it, as in `inst.relation`.
+            while pos < chars.len() && (is_ident(chars[pos]) || (chars[pos] == '.' && chars.get(pos + 1).map(|&c| is_ident_start(c)).unwrap_or(false))) {
+                pos += 1;
+            }
+            tokens.push((Token::Ident(chars[start .. pos].iter().collect()), line));
+        }
+        else if let Some(punct) = PUNCTUATION.iter().find(|punct| punct.chars().enumerate().all(|(i, p)| chars.get(pos + i) == Some(&p))) {
+            pos += punct.len();
+            tokens.push((Token::Punct(punct), line));
+        }
+        else {
+            return Err(Error::at(line, format!("unexpected character: {:?}", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// The tokens of the body of each declared component.
+    components: HashMap<String, Vec<(Token, usize)>>,
+    program: Program,
+    /// Relations marked by `.input` and `.output`, with the line of the directive.
+    inputs: Vec<(String, usize)>,
+    outputs: Vec<(String, usize)>,
+}
+
+impl Parser {
+
+    fn new(tokens: Vec<(Token, usize)>, components: HashMap<String, Vec<(Token, usize)>>) -> Self {
+        Parser {
+            tokens,
+            pos: 0,
+            components,
+            program: Program::default(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Applies `.input` and `.output` directives, which may precede the declarations they refer to.
+    fn finish(mut self) -> Result<Program, Error> {
+        for (name, line) in self.inputs {
+            let declaration = self.program.relations.get_mut(&name).ok_or_else(|| Error::at(line, format!("undeclared relation: {}", name)))?;
+            declaration.input = true;
+        }
+        for (name, line) in self.outputs {
+            let declaration = self.program.relations.get_mut(&name).ok_or_else(|| Error::at(line, format!("undeclared relation: {}", name)))?;
+            declaration.output = true;
+        }
+        Ok(self.program)
+    }
+
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos).map(|(token, _)| token) }
+    fn peek_at(&self, offset: usize) -> Option<&Token> { self.tokens.get(self.pos + offset).map(|(token, _)| token) }
+
+    /// The line of the next token, or of the last token at the end of input.
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos).or(self.tokens.last()).map(|(_, line)| *line).unwrap_or(1)
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone()).ok_or_else(|| Error::at(self.line(), "unexpected end of input"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, Error> {
+        match self.peek() {
+            Some(token) => Err(Error::at(self.line(), format!("expected {}, found {:?}", expected, token))),
+            None => Err(Error::at(self.line(), format!("expected {}, found end of input", expected))),
+        }
+    }
+
+    /// Consumes `punct` if it is the next token.
+    fn accept(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(Token::Punct(next)) if *next == punct => { self.pos += 1; true },
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), Error> {
+        if self.accept(punct) { Ok(()) } else { self.error(&format!("`{}`", punct)) }
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some(Token::Ident(name)) => { let name = name.clone(); self.pos += 1; Ok(name) },
+            _ => self.error("an identifier"),
+        }
+    }
+
+    fn integer(&mut self, expected: &str) -> Result<i64, Error> {
+        match self.peek() {
+            Some(Token::Int(value)) => { let value = *value; self.pos += 1; Ok(value) },
+            _ => self.error(expected),
+        }
+    }
+
+    /// Skips a parenthesized list of tokens, if present.
+    fn skip_parameters(&mut self) -> Result<(), Error> {
+        if self.accept("(") {
+            let mut depth = 1;
+            while depth > 0 {
+                match self.next()? {
+                    Token::Punct("(") => depth += 1,
+                    Token::Punct(")") => depth -= 1,
+                    _ => { },
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses directives, rules, and facts to the end of the tokens.
+    fn items(&mut self) -> Result<(), Error> {
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Directive(directive) => {
+                    let line = self.line();
+                    self.pos += 1;
+                    self.directive(&directive, line)?;
+                },
+                _ => self.rule()?,
+            }
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &str, line: usize) -> Result<(), Error> {
+        match directive {
+            "type" => {
+                while self.pos < self.tokens.len() && self.tokens[self.pos].1 == line { self.pos += 1; }
+            },
+            "decl" => {
+                let name = self.ident()?;
+                self.expect("(")?;
+                let mut arity = 0;
+                if !self.accept(")") {
+                    loop {
+                        self.ident()?;
+                        if self.accept(":") { self.ident()?; }
+                        arity += 1;
+                        if self.accept(")") { break; }
+                        self.expect(",")?;
+                    }
+                }
+                // Qualifiers on the same line, such as `output` or `btree`.
+                while let Some((Token::Ident(qualifier), qualifier_line)) = self.tokens.get(self.pos) {
+                    if *qualifier_line != line { break; }
+                    match &qualifier[..] {
+                        "input" => self.inputs.push((name.clone(), line)),
+                        "output" => self.outputs.push((name.clone(), line)),
+                        _ => { },
+                    }
+                    self.pos += 1;
+                }
+                let declaration = Declaration { arity, input: false, output: false };
+                if self.program.relations.insert(name.clone(), declaration).is_some() {
+                    return Err(Error::at(line, format!("relation declared twice: {}", name)));
+                }
+            },
+            "input" | "output" | "printsize" => {
+                let name = self.ident()?;
+                self.skip_parameters()?;
+                if directive == "input" { self.inputs.push((name, line)); }
+                else { self.outputs.push((name, line)); }
+            },
+            "plan" => {
+                // Each item is a version of the rule and an order of its atoms, numbered from one.
+                loop {
+                    self.integer("a version number")?;
+                    self.expect(":")?;
+                    self.expect("(")?;
+                    loop {
+                        self.integer("an atom number")?;
+                        if self.accept(")") { break; }
+                        self.expect(",")?;
+                    }
+                    if !self.accept(",") { break; }
+                }
+            },
+            "comp" => {
+                let name = self.ident()?;
+                if !self.accept("{") {
+                    return self.error("`{` (components may not have parameters or base components)");
+                }
+                let start = self.pos;
+                let mut depth = 1;
+                while depth > 0 {
+                    match self.next()? {
+                        Token::Punct("{") => depth += 1,
+                        Token::Punct("}") => depth -= 1,
+                        _ => { },
+                    }
+                }
+                let body = self.tokens[start .. self.pos - 1].to_vec();
+                if self.components.insert(name.clone(), body).is_some() {
+                    return Err(Error::at(line, format!("component declared twice: {}", name)));
+                }
+            },
+            "init" => {
+                let instance = self.ident()?;
+                self.expect("=")?;
+                let component = self.ident()?;
+                let body = self.components.get(&component).cloned().ok_or_else(|| Error::at(line, format!("undeclared component: {}", component)))?;
+                let mut parser = Parser::new(body, self.components.clone());
+                parser.items()?;
+                let local = parser.program.relations.keys().cloned().collect::<BTreeSet<_>>();
+                let rename = |name: &mut String| if local.contains(name) { *name = format!("{}.{}", instance, name); };
+                for (name, declaration) in parser.program.relations {
+                    let name = format!("{}.{}", instance, name);
+                    if self.program.relations.insert(name.clone(), declaration).is_some() {
+                        return Err(Error::at(line, format!("relation declared twice: {}", name)));
+                    }
+                }
+                for mut rule in parser.program.rules {
+                    rename(&mut rule.head.relation);
+                    for literal in rule.body.iter_mut() {
+                        if let Literal::Positive(atom) | Literal::Negative(atom) = literal {
+                            rename(&mut atom.relation);
+                        }
+                    }
+                    self.program.rules.push(rule);
+                }
+                for (mut name, line) in parser.inputs { rename(&mut name); self.inputs.push((name, line)); }
+                for (mut name, line) in parser.outputs { rename(&mut name); self.outputs.push((name, line)); }
+            },
+            other => {
+                return Err(Error::at(line, format!("unsupported directive: .{}", other)));
+            },
+        }
+        Ok(())
+    }
+
+    /// Parses a rule or a fact, and adds a rule for each head and each conjunction of the body.
+    fn rule(&mut self) -> Result<(), Error> {
+        let line = self.line();
+        let mut heads = vec![self.head()?];
+        while self.accept(",") {
+            heads.push(self.head()?);
+        }
+        let bodies = if self.accept(":-") { self.disjunction()? } else { vec![Vec::new()] };
+        self.expect(".")?;
+        for head in heads {
+            for body in bodies.iter() {
+                self.program.rules.push(Rule { head: head.clone(), body: body.clone(), line });
+            }
+        }
+        Ok(())
+    }
+
+    fn head(&mut self) -> Result<Head, Error> {
+        let relation = self.ident()?;
+        self.expect("(")?;
+        let mut terms = Vec::new();
+        if !self.accept(")") {
+            loop {
+                let aggregate = match (self.peek(), self.peek_at(1)) {
+                    (Some(Token::Ident(name)), Some(Token::Punct("("))) => match &name[..] {
+                        "count" => Some(Aggregate::Count),
+                        "sum" => Some(Aggregate::Sum),
+                        "min" => Some(Aggregate::Min),
+                        "max" => Some(Aggregate::Max),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(aggregate) = aggregate {
+                    self.pos += 2;
+                    let var = self.ident()?;
+                    self.expect(")")?;
+                    terms.push(HeadTerm::Aggregate(aggregate, var));
+                }
+                else {
+                    terms.push(HeadTerm::Expr(self.expr()?));
+                }
+                if self.accept(")") { break; }
+                self.expect(",")?;
+            }
+        }
+        Ok(Head { relation, terms })
+    }
+
+    /// Parses a disjunction of conjunctions, and returns the literals of each conjunction.
+    fn disjunction(&mut self) -> Result<Vec<Vec<Literal>>, Error> {
+        let mut result = self.conjunction()?;
+        while self.accept(";") {
+            result.extend(self.conjunction()?);
+        }
+        Ok(result)
+    }
+
+    /// Parses a conjunction of literals and disjunctions, and returns the literals of each alternative.
+    fn conjunction(&mut self) -> Result<Vec<Vec<Literal>>, Error> {
+        let mut result = vec![Vec::new()];
+        loop {
+            let alternatives = if self.accept("(") {
+                let alternatives = self.disjunction()?;
+                self.expect(")")?;
+                alternatives
+            }
+            else {
+                vec![vec![self.literal()?]]
+            };
+            result =
+            result
+                .iter()
+                .flat_map(|prefix| alternatives.iter().map(move |alternative| {
+                    let mut literals = prefix.clone();
+                    literals.extend(alternative.iter().cloned());
+                    literals
+                }))
+                .collect();
+            if !self.accept(",") { break; }
+        }
+        Ok(result)
+    }
+
+    fn literal(&mut self) -> Result<Literal, Error> {
+        if self.accept("!") {
+            return Ok(Literal::Negative(self.atom()?));
+        }
+        if let (Some(Token::Ident(name)), Some(Token::Punct("("))) = (self.peek(), self.peek_at(1)) {
+            if name != "cat" {
+                return Ok(Literal::Positive(self.atom()?));
+            }
+        }
+        let left = self.expr()?;
+        let op = match self.next()? {
+            Token::Punct("=") => CmpOp::Eq,
+            Token::Punct("!=") => CmpOp::Ne,
+            Token::Punct("<") => CmpOp::Lt,
+            Token::Punct("<=") => CmpOp::Le,
+            Token::Punct(">") => CmpOp::Gt,
+            Token::Punct(">=") => CmpOp::Ge,
+            _ => { self.pos -= 1; return self.error("a comparison"); },
+        };
+        let right = self.expr()?;
+        Ok(Literal::Compare(left, op, right))
+    }
+
+    fn atom(&mut self) -> Result<Atom, Error> {
+        let relation = self.ident()?;
+        self.expect("(")?;
+        let mut terms = Vec::new();
+        if !self.accept(")") {
+            loop {
+                terms.push(self.term()?);
+                if self.accept(")") { break; }
+                self.expect(",")?;
+            }
+        }
+        Ok(Atom { relation, terms })
+    }
+
+    fn term(&mut self) -> Result<Term, Error> {
+        match self.peek().cloned() {
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                Ok(if name == "_" { Term::Wildcard } else { Term::Var(name) })
+            },
+            Some(Token::Int(value)) => { self.pos += 1; Ok(Term::Const(Value::Int(value))) },
+            Some(Token::Str(value)) => { self.pos += 1; Ok(Term::Const(Value::Str(value))) },
+            Some(Token::Punct("-")) => {
+                if let Some(Token::Int(value)) = self.peek_at(1).cloned() {
+                    self.pos += 2;
+                    Ok(Term::Const(Value::Int(-value)))
+                }
+                else { self.error("a term") }
+            },
+            _ => self.error("a term"),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.product()?;
+        loop {
+            let op = if self.accept("+") { ArithOp::Add } else if self.accept("-") { ArithOp::Sub } else { break };
+            expr = Expr::Arith(op, Box::new(expr), Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+        while self.accept("*") {
+            expr = Expr::Arith(ArithOp::Mul, Box::new(expr), Box::new(self.primary()?));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        if self.accept("(") {
+            let expr = self.expr()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        if let (Some(Token::Ident(name)), Some(Token::Punct("("))) = (self.peek(), self.peek_at(1)) {
+            if name != "cat" {
+                return self.error("an expression");
+            }
+            self.pos += 2;
+            let mut args = Vec::new();
+            loop {
+                args.push(self.expr()?);
+                if self.accept(")") { break; }
+                self.expect(",")?;
+            }
+            return Ok(Expr::Cat(args));
+        }
+        Ok(Expr::Term(self.term()?))
+    }
+}
+
+/// Adds the variables of `expr` to `vars`, and returns whether it contains a wildcard.
+pub(crate) fn expr_vars<'a>(expr: &'a Expr, vars: &mut Vec<&'a str>) -> bool {
+    match expr {
+        Expr::Term(Term::Var(var)) => { vars.push(var); false },
+        Expr::Term(Term::Const(_)) => false,
+        Expr::Term(Term::Wildcard) => true,
+        Expr::Cat(args) => {
+            let mut wild = false;
+            for arg in args.iter() {
+                wild |= expr_vars(arg, vars);
+            }
+            wild
+        },
+        Expr::Arith(_, left, right) => expr_vars(left, vars) | expr_vars(right, vars),
+    }
+}
+
+/// Checks that the relations of `program` are used with their arity, and that its rules are safe.
+///
+/// Errors are reported at the line of the rule in which they occur.
+pub(crate) fn check(program: &Program) -> Result<(), Error> {
+
+    let arity = |line: usize, atom_relation: &str, terms: usize| -> Result<(), Error> {
+        let declaration = program.relations.get(atom_relation).ok_or_else(|| Error::at(line, format!("undeclared relation: {}", atom_relation)))?;
+        if declaration.arity != terms {
+            return Err(Error::at(line, format!("relation {} has {} columns, but is used with {}", atom_relation, declaration.arity, terms)));
+        }
+        Ok(())
+    };
+
+    for rule in program.rules.iter() {
+        arity(rule.line, &rule.head.relation, rule.head.terms.len())?;
+        let unsafe_rule = |message: String| Error::at(rule.line, format!("in rule for {}: {}", rule.head.relation, message));
+
+        let mut bound = BTreeSet::new();
+        for literal in rule.body.iter() {
+            match literal {
+                Literal::Positive(atom) => {
+                    arity(rule.line, &atom.relation, atom.terms.len())?;
+                    bound.extend(atom.terms.iter().filter_map(|term| if let Term::Var(var) = term { Some(&var[..]) } else { None }));
+                },
+                Literal::Negative(atom) => arity(rule.line, &atom.relation, atom.terms.len())?,
+                Literal::Compare(..) => { },
+            }
+        }
+
+        // Equations bind variables in the order their other sides become bound.
+        let mut pending = rule.body.iter().filter_map(|literal| if let Literal::Compare(l, op, r) = literal { Some((l, op, r)) } else { None }).collect::<Vec<_>>();
+        while !pending.is_empty() {
+            let position = pending.iter().position(|(left, op, right)| {
+                let (mut left_vars, mut right_vars) = (Vec::new(), Vec::new());
+                expr_vars(left, &mut left_vars);
+                expr_vars(right, &mut right_vars);
+                let bound_left = left_vars.iter().all(|var| bound.contains(var));
+                let bound_right = right_vars.iter().all(|var| bound.contains(var));
+                (bound_left && bound_right) || (**op == CmpOp::Eq && (
+                    (bound_right && matches!(left, Expr::Term(Term::Var(_)))) ||
+                    (bound_left && matches!(right, Expr::Term(Term::Var(_))))
+                ))
+            });
+            let (left, _op, right) = match position {
+                Some(position) => pending.remove(position),
+                None => return Err(unsafe_rule("comparison of unbound variables".to_string())),
+            };
+            for expr in [left, right] {
+                let mut vars = Vec::new();
+                if expr_vars(expr, &mut vars) {
+                    return Err(unsafe_rule("wildcard in comparison".to_string()));
+                }
+                bound.extend(vars);
+            }
+        }
+
+        for literal in rule.body.iter() {
+            if let Literal::Negative(atom) = literal {
+                for term in atom.terms.iter() {
+                    if let Term::Var(var) = term {
+                        if !bound.contains(&var[..]) {
+                            return Err(unsafe_rule(format!("variable {} occurs only in a negated atom", var)));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut aggregates = 0;
+        for term in rule.head.terms.iter() {
+            let mut vars = Vec::new();
+            match term {
+                HeadTerm::Expr(expr) => if expr_vars(expr, &mut vars) {
+                    return Err(unsafe_rule("wildcard in head".to_string()));
+                },
+                HeadTerm::Aggregate(_, var) => {
+                    aggregates += 1;
+                    vars.push(var);
+                },
+            }
+            if let Some(var) = vars.iter().find(|var| !bound.contains(*var)) {
+                return Err(unsafe_rule(format!("variable {} of the head does not occur in the body", var)));
+            }
+        }
+        if aggregates > 1 {
+            return Err(unsafe_rule("more than one aggregate".to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// The relations that each relation depends on, and whether through negation or aggregation.
+pub(crate) fn dependencies(program: &Program) -> BTreeMap<&str, BTreeMap<&str, bool>> {
+    let mut dependencies = program.relations.keys().map(|name| (&name[..], BTreeMap::new())).collect::<BTreeMap<_, _>>();
+    for rule in program.rules.iter() {
+        let aggregate = rule.head.aggregate().is_some();
+        let entry = dependencies.get_mut(&rule.head.relation[..]).unwrap();
+        for literal in rule.body.iter() {
+            let (relation, negative) = match literal {
+                Literal::Positive(atom) => (&atom.relation[..], aggregate),
+                Literal::Negative(atom) => (&atom.relation[..], true),
+                Literal::Compare(..) => continue,
+            };
+            *entry.entry(relation).or_insert(false) |= negative;
+        }
+    }
+    dependencies
+}
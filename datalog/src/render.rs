@@ -0,0 +1,452 @@
+//! Rendering of Datalog programs as differential dataflows.
+//!
+//! Each stratum is rendered after the strata it depends on. The relations of a recursive stratum are
+//! `Variable`s of an iterative scope, and each is set to the distinct tuples of its input and its rules.
+//! Rules join their atoms in the order they are written, by looking up arrangements of the relations
+//! keyed by the columns bound so far. Each relation is arranged once for each such set of columns, and
+//! the arrangements of relations from earlier strata are shared by all later strata, including those
+//! rendered in iterative scopes.
+
+use std::collections::{BTreeMap, HashMap};
+
+use timely::dataflow::{Scope, ScopeParent};
+use timely::dataflow::scopes::child::Iterative;
+use timely::dataflow::operators::generic::operator::empty;
+use timely::order::Product;
+
+use differential_dataflow::{AsCollection, Collection};
+use differential_dataflow::input::{Input, InputSession};
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, JoinCore, Reduce, Threshold};
+use differential_dataflow::operators::arrange::{ArrangeByKey, Arranged, TraceAgent};
+use differential_dataflow::operators::iterate::Variable;
+use differential_dataflow::trace::implementations::ValSpine;
+use differential_dataflow::trace::wrappers::enter::TraceEnter;
+
+use crate::{Error, Value};
+use crate::ast::*;
+use crate::parse::{check, expr_vars};
+use crate::stratify::stratify;
+
+type Tuple = Vec<Value>;
+type Spine<T> = ValSpine<Tuple, Tuple, T, isize>;
+type Arrangement<G> = Arranged<G, TraceAgent<Spine<<G as ScopeParent>::Timestamp>>>;
+/// Arrangements of relations by sets of their columns.
+type Arrangements<S> = HashMap<(String, Vec<usize>), Arrangement<S>>;
+/// An arrangement of the outer scope `G`, entered into an iterative scope.
+type Entered<'b, G> = Arranged<Iterative<'b, G, u32>, TraceEnter<TraceAgent<Spine<<G as ScopeParent>::Timestamp>>, Product<<G as ScopeParent>::Timestamp, u32>>>;
+/// Pairs of a key and a binding, or of a binding and a tuple.
+type Pairs<S> = Collection<S, (Tuple, Tuple), isize>;
+
+/// The inputs and relations of a rendered program.
+pub struct Dataflow<G: Scope> {
+    /// An input session for each relation marked by `.input`.
+    pub inputs: BTreeMap<String, InputSession<G::Timestamp, Tuple, isize>>,
+    /// The distinct tuples of each relation.
+    pub relations: BTreeMap<String, Collection<G, Tuple, isize>>,
+}
+
+/// Renders `program` in `scope`.
+///
+/// Returns an error if the program fails the checks of `parse`, or cannot be stratified.
+pub fn render<G>(program: &Program, scope: &mut G) -> Result<Dataflow<G>, Error>
+where
+    G: Input,
+    G::Timestamp: Lattice+Ord,
+{
+    check(program)?;
+    let strata = stratify(program)?;
+
+    let mut inputs = BTreeMap::new();
+    let mut sources = BTreeMap::new();
+    for (name, declaration) in program.relations.iter() {
+        if declaration.input {
+            let (input, collection) = scope.new_collection();
+            inputs.insert(name.clone(), input);
+            sources.insert(name.clone(), collection);
+        }
+    }
+
+    let mut outer = Outer {
+        unit: scope.new_collection_from(Some(Vec::new())).1,
+        collections: BTreeMap::new(),
+        arrangements: HashMap::new(),
+    };
+
+    for stratum in strata {
+        let rules = program.rules.iter().filter(|rule| stratum.relations.contains(&rule.head.relation)).collect::<Vec<_>>();
+        if !stratum.recursive {
+            let name = &stratum.relations[0];
+            let source = sources.get(name).cloned().unwrap_or_else(|| empty(scope).as_collection());
+            let productions = rules.iter().map(|rule| render_rule(rule, &mut outer)).collect::<Result<Vec<_>, _>>()?;
+            let relation = source.concatenate(productions).distinct();
+            outer.collections.insert(name.clone(), relation);
+        }
+        else {
+            let results = scope.iterative::<u32, _, _>(|scope| -> Result<_, Error> {
+                let mut variables = Vec::new();
+                let mut inner = Inner {
+                    unit: outer.unit.enter(scope),
+                    outer: &mut outer,
+                    scope: scope.clone(),
+                    variables: BTreeMap::new(),
+                    entered: HashMap::new(),
+                    arrangements: HashMap::new(),
+                    entered_arrangements: HashMap::new(),
+                };
+                for name in stratum.relations.iter() {
+                    let source = sources.get(name).map(|source| source.enter(scope)).unwrap_or_else(|| empty(scope).as_collection());
+                    let variable = Variable::new_from(source.clone(), Product::new(Default::default(), 1));
+                    inner.variables.insert(name.clone(), (*variable).clone());
+                    variables.push((name.clone(), source, variable));
+                }
+                let mut productions = rules.iter().map(|rule| Ok((&rule.head.relation, render_rule(rule, &mut inner)?))).collect::<Result<Vec<_>, Error>>()?;
+                let results =
+                variables
+                    .into_iter()
+                    .map(|(name, source, variable)| {
+                        let (mine, others): (Vec<_>, Vec<_>) = productions.drain(..).partition(|(relation, _)| **relation == name);
+                        productions = others;
+                        let relation = source.concatenate(mine.into_iter().map(|(_, production)| production)).distinct();
+                        variable.set(&relation);
+                        (name, relation.leave())
+                    })
+                    .collect::<Vec<_>>();
+                Ok(results)
+            })?;
+            outer.collections.extend(results);
+        }
+    }
+
+    Ok(Dataflow { inputs, relations: outer.collections })
+}
+
+/// Access to the relations of earlier strata, and of the stratum being rendered.
+trait Relations<S: Scope> {
+    /// A collection containing only the empty tuple.
+    fn unit(&mut self) -> Collection<S, Tuple, isize>;
+    /// The tuples of `relation`.
+    fn collection(&mut self, relation: &str) -> Collection<S, Tuple, isize>;
+    /// Pairs each `(key, prefix)` with each tuple of `relation` whose `columns` equal `key`.
+    fn join(&mut self, relation: &str, columns: &[usize], prefixes: &Pairs<S>) -> Pairs<S>;
+}
+
+/// The relations rendered in the outer scope.
+struct Outer<G: Scope> where G::Timestamp: Lattice+Ord {
+    unit: Collection<G, Tuple, isize>,
+    collections: BTreeMap<String, Collection<G, Tuple, isize>>,
+    /// Arrangements of relations by sets of their columns.
+    arrangements: Arrangements<G>,
+}
+
+impl<G: Scope> Outer<G> where G::Timestamp: Lattice+Ord {
+    fn arrangement(&mut self, relation: &str, columns: &[usize]) -> Arrangement<G> {
+        let collection = &self.collections[relation];
+        self.arrangements
+            .entry((relation.to_string(), columns.to_vec()))
+            .or_insert_with(|| {
+                let columns = columns.to_vec();
+                collection
+                    .map(move |tuple| (select(&tuple, &columns), tuple))
+                    .arrange_by_key_named("DatalogArrange")
+            })
+            .clone()
+    }
+}
+
+impl<G: Scope> Relations<G> for Outer<G> where G::Timestamp: Lattice+Ord {
+    fn unit(&mut self) -> Collection<G, Tuple, isize> { self.unit.clone() }
+    fn collection(&mut self, relation: &str) -> Collection<G, Tuple, isize> { self.collections[relation].clone() }
+    fn join(&mut self, relation: &str, columns: &[usize], prefixes: &Pairs<G>) -> Pairs<G> {
+        prefixes.join_core(&self.arrangement(relation, columns), |_key, prefix, tuple| Some((prefix.clone(), tuple.clone())))
+    }
+}
+
+/// The relations of a recursive stratum, and those of earlier strata entered into its scope.
+struct Inner<'a, 'b, G: Scope> where G::Timestamp: Lattice+Ord {
+    outer: &'a mut Outer<G>,
+    scope: Iterative<'b, G, u32>,
+    unit: Collection<Iterative<'b, G, u32>, Tuple, isize>,
+    /// The variables of the relations of the stratum.
+    variables: BTreeMap<String, Collection<Iterative<'b, G, u32>, Tuple, isize>>,
+    entered: HashMap<String, Collection<Iterative<'b, G, u32>, Tuple, isize>>,
+    /// Arrangements of the relations of the stratum.
+    arrangements: Arrangements<Iterative<'b, G, u32>>,
+    /// Arrangements of relations of earlier strata, shared with the outer scope.
+    entered_arrangements: HashMap<(String, Vec<usize>), Entered<'b, G>>,
+}
+
+impl<'a, 'b, G: Scope> Relations<Iterative<'b, G, u32>> for Inner<'a, 'b, G> where G::Timestamp: Lattice+Ord {
+    fn unit(&mut self) -> Collection<Iterative<'b, G, u32>, Tuple, isize> { self.unit.clone() }
+    fn collection(&mut self, relation: &str) -> Collection<Iterative<'b, G, u32>, Tuple, isize> {
+        if let Some(variable) = self.variables.get(relation) {
+            return variable.clone();
+        }
+        let (outer, scope) = (&mut self.outer, &self.scope);
+        self.entered
+            .entry(relation.to_string())
+            .or_insert_with(|| outer.collection(relation).enter(scope))
+            .clone()
+    }
+    fn join(&mut self, relation: &str, columns: &[usize], prefixes: &Pairs<Iterative<'b, G, u32>>) -> Pairs<Iterative<'b, G, u32>> {
+        let key = (relation.to_string(), columns.to_vec());
+        let result = |_key: &Tuple, prefix: &Tuple, tuple: &Tuple| Some((prefix.clone(), tuple.clone()));
+        if let Some(variable) = self.variables.get(relation) {
+            let arranged = self.arrangements.entry(key).or_insert_with(|| {
+                let columns = columns.to_vec();
+                variable
+                    .map(move |tuple| (select(&tuple, &columns), tuple))
+                    .arrange_by_key_named("DatalogArrange")
+            });
+            prefixes.join_core(arranged, result)
+        }
+        else {
+            let (outer, scope) = (&mut self.outer, &self.scope);
+            let arranged = self.entered_arrangements.entry(key).or_insert_with(|| outer.arrangement(relation, columns).enter(scope));
+            prefixes.join_core(arranged, result)
+        }
+    }
+}
+
+/// The values of `tuple` at each of `positions`.
+fn select(tuple: &[Value], positions: &[usize]) -> Tuple {
+    positions.iter().map(|&position| tuple[position].clone()).collect()
+}
+
+/// The source of the value of a column of an atom, in a binding.
+#[derive(Clone)]
+enum Source {
+    /// The value at a position in the binding.
+    Bound(usize),
+    /// A constant value.
+    Const(Value),
+}
+
+impl Source {
+    fn value(&self, binding: &[Value]) -> Value {
+        match self {
+            Source::Bound(position) => binding[*position].clone(),
+            Source::Const(value) => value.clone(),
+        }
+    }
+}
+
+/// An expression whose variables are replaced by their positions in a binding.
+#[derive(Clone)]
+enum Compiled {
+    Source(Source),
+    Cat(Vec<Compiled>),
+    Arith(ArithOp, Box<Compiled>, Box<Compiled>),
+}
+
+impl Compiled {
+    /// Compiles `expr` of the rule at `line`, or returns an error if it has a wildcard or a variable not in `vars`.
+    fn new(expr: &Expr, vars: &[String], line: usize) -> Result<Self, Error> {
+        Ok(match expr {
+            Expr::Term(Term::Var(var)) => {
+                let position = vars.iter().position(|v| v == var).ok_or_else(|| Error::at(line, format!("unbound variable {} in expression", var)))?;
+                Compiled::Source(Source::Bound(position))
+            },
+            Expr::Term(Term::Const(value)) => Compiled::Source(Source::Const(value.clone())),
+            Expr::Term(Term::Wildcard) => return Err(Error::at(line, "wildcard in expression")),
+            Expr::Cat(args) => Compiled::Cat(args.iter().map(|arg| Compiled::new(arg, vars, line)).collect::<Result<_, _>>()?),
+            Expr::Arith(op, left, right) => Compiled::Arith(*op, Box::new(Compiled::new(left, vars, line)?), Box::new(Compiled::new(right, vars, line)?)),
+        })
+    }
+    /// Evaluates the expression, or returns `None` for arithmetic that overflows or involves strings.
+    fn eval(&self, binding: &[Value]) -> Option<Value> {
+        match self {
+            Compiled::Source(source) => Some(source.value(binding)),
+            Compiled::Cat(args) => {
+                let mut result = String::new();
+                for arg in args.iter() {
+                    result.push_str(&arg.eval(binding)?.to_string());
+                }
+                Some(Value::Str(result))
+            },
+            Compiled::Arith(op, left, right) => {
+                match (left.eval(binding)?, right.eval(binding)?) {
+                    (Value::Int(left), Value::Int(right)) => match op {
+                        ArithOp::Add => left.checked_add(right),
+                        ArithOp::Sub => left.checked_sub(right),
+                        ArithOp::Mul => left.checked_mul(right),
+                    }.map(Value::Int),
+                    _ => None,
+                }
+            },
+        }
+    }
+}
+
+/// Renders the tuples `rule` derives for its head.
+///
+/// Returns an error if the rule is unsafe, which `check` reports first for programs it accepts.
+fn render_rule<S, R>(rule: &Rule, relations: &mut R) -> Result<Collection<S, Tuple, isize>, Error>
+where
+    S: Scope,
+    S::Timestamp: Lattice+Ord,
+    R: Relations<S>,
+{
+    // The variables bound so far, and the values bound to them by each binding.
+    let mut vars: Vec<String> = Vec::new();
+    let mut bindings: Option<Collection<S, Tuple, isize>> = None;
+
+    for literal in rule.body.iter() {
+        if let Literal::Positive(atom) = literal {
+
+            // Columns matched against the binding, columns that repeat a new variable, and new variables.
+            let mut key = Vec::new();
+            let mut sources = Vec::new();
+            let mut repeats = Vec::new();
+            let mut extend: Vec<usize> = Vec::new();
+            for (column, term) in atom.terms.iter().enumerate() {
+                match term {
+                    Term::Const(value) => { key.push(column); sources.push(Source::Const(value.clone())); },
+                    Term::Var(var) => {
+                        if let Some(position) = vars.iter().position(|v| v == var) {
+                            key.push(column);
+                            sources.push(Source::Bound(position));
+                        }
+                        else if let Some(&first) = extend.iter().find(|&&first| atom.terms[first] == *term) {
+                            repeats.push((first, column));
+                        }
+                        else {
+                            extend.push(column);
+                        }
+                    },
+                    Term::Wildcard => { },
+                }
+            }
+            let matches = move |tuple: &Tuple| repeats.iter().all(|&(first, column)| tuple[first] == tuple[column]);
+
+            bindings = Some(match bindings {
+                // The first atom binds variables directly from the tuples of its relation.
+                None => {
+                    let key_values = sources.iter().map(|source| source.value(&[])).collect::<Vec<_>>();
+                    relations
+                        .collection(&atom.relation)
+                        .flat_map(move |tuple| {
+                            if select(&tuple, &key) == key_values && matches(&tuple) { Some(select(&tuple, &extend)) }
+                            else { None }
+                        })
+                },
+                Some(bindings) => {
+                    let prefixes = bindings.map(move |binding| (sources.iter().map(|source| source.value(&binding)).collect::<Tuple>(), binding));
+                    relations
+                        .join(&atom.relation, &key, &prefixes)
+                        .flat_map(move |(mut binding, tuple)| {
+                            if matches(&tuple) {
+                                binding.extend(extend.iter().map(|&column| tuple[column].clone()));
+                                Some(binding)
+                            }
+                            else { None }
+                        })
+                },
+            });
+            for term in atom.terms.iter() {
+                if let Term::Var(var) = term {
+                    if !vars.contains(var) { vars.push(var.clone()); }
+                }
+            }
+        }
+    }
+
+    let mut bindings = bindings.unwrap_or_else(|| relations.unit());
+
+    // Apply each comparison once its variables are bound, or once it binds a variable by an equation.
+    let mut pending = rule.body.iter().filter_map(|literal| if let Literal::Compare(l, op, r) = literal { Some((l, *op, r)) } else { None }).collect::<Vec<_>>();
+    while !pending.is_empty() {
+        let bound = |expr: &Expr| {
+            let mut names = Vec::new();
+            expr_vars(expr, &mut names);
+            names.iter().all(|name| vars.iter().any(|var| var == name))
+        };
+        let unbound_var = |expr: &Expr| match expr {
+            Expr::Term(Term::Var(var)) if !vars.contains(var) => Some(var.clone()),
+            _ => None,
+        };
+        let position = pending.iter().position(|(left, op, right)| {
+            (bound(left) && bound(right)) || (*op == CmpOp::Eq && ((bound(right) && unbound_var(left).is_some()) || (bound(left) && unbound_var(right).is_some())))
+        }).ok_or_else(|| Error::at(rule.line, "comparison of unbound variables"))?;
+        let (left, op, right) = pending.remove(position);
+
+        let binds = if bound(left) && bound(right) { None } else if bound(right) { Some((left, right)) } else { Some((right, left)) };
+        if let Some((var, expr)) = binds {
+            let var = unbound_var(var).unwrap();
+            let expr = Compiled::new(expr, &vars, rule.line)?;
+            vars.push(var);
+            bindings = bindings.flat_map(move |mut binding| {
+                let value = expr.eval(&binding)?;
+                binding.push(value);
+                Some(binding)
+            });
+        }
+        else {
+            let (left, right) = (Compiled::new(left, &vars, rule.line)?, Compiled::new(right, &vars, rule.line)?);
+            bindings = bindings.filter(move |binding| {
+                match (left.eval(binding), right.eval(binding)) {
+                    (Some(left), Some(right)) => match op {
+                        CmpOp::Eq => left == right,
+                        CmpOp::Ne => left != right,
+                        CmpOp::Lt => left < right,
+                        CmpOp::Le => left <= right,
+                        CmpOp::Gt => left > right,
+                        CmpOp::Ge => left >= right,
+                    },
+                    _ => false,
+                }
+            });
+        }
+    }
+
+    // Remove bindings that match a tuple of a negated relation.
+    for literal in rule.body.iter() {
+        if let Literal::Negative(atom) = literal {
+            let columns = (0 .. atom.terms.len()).filter(|&column| atom.terms[column] != Term::Wildcard).collect::<Vec<_>>();
+            let sources = columns.iter().map(|&column| match &atom.terms[column] {
+                Term::Var(var) => Source::Bound(vars.iter().position(|v| v == var).unwrap()),
+                Term::Const(value) => Source::Const(value.clone()),
+                Term::Wildcard => unreachable!(),
+            }).collect::<Vec<_>>();
+            let negated = relations.collection(&atom.relation).map(move |tuple| select(&tuple, &columns)).distinct();
+            bindings =
+            bindings
+                .map(move |binding| (sources.iter().map(|source| source.value(&binding)).collect::<Tuple>(), binding))
+                .antijoin(&negated)
+                .map(|(_key, binding)| binding);
+        }
+    }
+
+    let terms = rule.head.terms.iter().map(|term| match term {
+        HeadTerm::Expr(expr) => Compiled::new(expr, &vars, rule.line).map(Some),
+        HeadTerm::Aggregate(..) => Ok(None),
+    }).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(match rule.head.aggregate() {
+        None => {
+            bindings.flat_map(move |binding| terms.iter().map(|term| term.as_ref().unwrap().eval(&binding)).collect::<Option<Tuple>>())
+        },
+        Some((index, aggregate, var)) => {
+            let position = vars.iter().position(|v| v == var).unwrap();
+            bindings
+                .flat_map(move |binding| {
+                    let group = terms.iter().flatten().map(|term| term.eval(&binding)).collect::<Option<Tuple>>()?;
+                    Some((group, binding[position].clone()))
+                })
+                .distinct()
+                .reduce_named("DatalogAggregate", move |_group, input: &[(&Value, isize)], output: &mut Vec<(Value, isize)>| {
+                    let result = match aggregate {
+                        Aggregate::Count => Some(Value::Int(input.len() as i64)),
+                        Aggregate::Sum => Some(Value::Int(input.iter().filter_map(|(value, _)| if let Value::Int(int) = value { Some(*int) } else { None }).sum())),
+                        Aggregate::Min => input.first().map(|(value, _)| (*value).clone()),
+                        Aggregate::Max => input.last().map(|(value, _)| (*value).clone()),
+                    };
+                    output.extend(result.map(|value| (value, 1)));
+                })
+                .map(move |(mut group, value)| {
+                    group.insert(index, value);
+                    group
+                })
+        },
+    })
+}
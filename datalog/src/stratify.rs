@@ -0,0 +1,98 @@
+//! Stratification of Datalog programs.
+//!
+//! Relations that depend on each other, directly or through other relations, must be computed together
+//! as the fixed point of their rules. A relation that depends on another through negation or aggregation
+//! must instead wait until the other is complete, and so may not depend on itself in this way. Each
+//! stratum is a strongly connected component of the dependency graph, and strata are ordered so that
+//! each follows the strata it depends on.
+
+use std::collections::BTreeMap;
+
+use crate::Error;
+use crate::ast::Program;
+use crate::parse::dependencies;
+
+/// A set of relations whose rules are evaluated together.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Stratum {
+    /// The names of the relations.
+    pub relations: Vec<String>,
+    /// Set if the relations depend on themselves, and must be computed iteratively.
+    pub recursive: bool,
+}
+
+/// Partitions the relations of `program` into strata, each after the strata it depends on.
+///
+/// Returns an error if a relation depends on itself through negation or aggregation.
+pub fn stratify(program: &Program) -> Result<Vec<Stratum>, Error> {
+
+    let dependencies = dependencies(program);
+    let mut tarjan = Tarjan {
+        dependencies: &dependencies,
+        index: BTreeMap::new(),
+        lowlink: BTreeMap::new(),
+        stack: Vec::new(),
+        components: Vec::new(),
+    };
+    for relation in dependencies.keys() {
+        if !tarjan.index.contains_key(relation) {
+            tarjan.visit(relation);
+        }
+    }
+
+    let mut strata = Vec::with_capacity(tarjan.components.len());
+    for component in tarjan.components {
+        let mut recursive = component.len() > 1;
+        for relation in component.iter() {
+            for (dependency, negative) in dependencies[relation].iter() {
+                if component.contains(dependency) {
+                    recursive = true;
+                    if *negative {
+                        return Err(Error::new(format!("relation {} depends on {} through negation or aggregation, and {} depends on {}", relation, dependency, dependency, relation)));
+                    }
+                }
+            }
+        }
+        let relations = component.into_iter().map(String::from).collect();
+        strata.push(Stratum { relations, recursive });
+    }
+    Ok(strata)
+}
+
+/// Tarjan's algorithm for strongly connected components, which are found after those they reach.
+struct Tarjan<'a> {
+    dependencies: &'a BTreeMap<&'a str, BTreeMap<&'a str, bool>>,
+    index: BTreeMap<&'a str, usize>,
+    lowlink: BTreeMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, relation: &'a str) {
+        let index = self.index.len();
+        self.index.insert(relation, index);
+        self.lowlink.insert(relation, index);
+        self.stack.push(relation);
+
+        let dependencies = self.dependencies;
+        for dependency in dependencies[relation].keys() {
+            if !self.index.contains_key(dependency) {
+                self.visit(dependency);
+                let lowlink = self.lowlink[relation].min(self.lowlink[dependency]);
+                self.lowlink.insert(relation, lowlink);
+            }
+            else if self.stack.contains(dependency) {
+                let lowlink = self.lowlink[relation].min(self.index[dependency]);
+                self.lowlink.insert(relation, lowlink);
+            }
+        }
+
+        if self.lowlink[relation] == index {
+            let position = self.stack.iter().rposition(|other| *other == relation).unwrap();
+            let mut component = self.stack.split_off(position);
+            component.sort();
+            self.components.push(component);
+        }
+    }
+}
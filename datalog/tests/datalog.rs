@@ -0,0 +1,181 @@
+use timely::dataflow::operators::capture::{Capture, Extract};
+
+use datalog::Value;
+use datalog::ast::{Expr, HeadTerm, Term};
+use datalog::stratify::stratify;
+
+/// Renders `text`, applies each round of changes to its inputs, and returns the changes to `relation`.
+fn run(text: &str, relation: &'static str, rounds: Vec<Vec<(&'static str, Vec<Value>, isize)>>) -> Vec<(Vec<Value>, u64, isize)> {
+
+    let program = datalog::parse(text).unwrap();
+    let captured = timely::execute_directly(move |worker| {
+
+        let (mut inputs, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let dataflow = datalog::render(&program, scope).unwrap();
+            (dataflow.inputs, dataflow.relations[relation].inner.capture())
+        });
+
+        for (round, changes) in rounds.into_iter().enumerate() {
+            for input in inputs.values_mut() {
+                input.advance_to(round as u64);
+            }
+            for (name, tuple, diff) in changes {
+                inputs.get_mut(name).unwrap().update(tuple, diff);
+            }
+        }
+        for (_, input) in inputs {
+            input.close();
+        }
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+    updates
+}
+
+fn ints(values: &[i64]) -> Vec<Value> {
+    values.iter().map(|&value| Value::Int(value)).collect()
+}
+
+#[test]
+fn negation_of_recursive_relation() {
+
+    let program = r#"
+        .decl node(x: number)
+        .input node
+        .decl edge(x: number, y: number)
+        .input edge
+        .decl reach(x: number)
+        .decl unreached(x: number)
+
+        reach(1).
+        reach(?y) :- reach(?x), edge(?x, ?y).
+        unreached(?x) :- node(?x), !reach(?x).
+    "#;
+
+    let updates = run(program, "unreached", vec![
+        vec![("node", ints(&[1]), 1), ("node", ints(&[2]), 1), ("node", ints(&[3]), 1), ("edge", ints(&[1, 2]), 1)],
+        vec![("edge", ints(&[2, 3]), 1)],
+        vec![("edge", ints(&[1, 2]), -1)],
+    ]);
+
+    assert_eq!(updates, vec![
+        (ints(&[2]), 2, 1),
+        (ints(&[3]), 0, 1),
+        (ints(&[3]), 1, -1),
+        (ints(&[3]), 2, 1),
+    ]);
+}
+
+#[test]
+fn aggregates_and_equations() {
+
+    let program = r#"
+        .decl sale(item: symbol, price: number)
+        .input sale
+        .decl summary(item: symbol, total: number, least: number, most: number, label: symbol)
+        .decl total(item: symbol, total: number)
+        .decl least(item: symbol, least: number)
+        .decl most(item: symbol, most: number)
+
+        total(?i, sum(?p)) :- sale(?i, ?p).
+        least(?i, min(?p)) :- sale(?i, ?p).
+        most(?i, max(?p)) :- sale(?i, ?p).
+        summary(?i, ?t, ?l, ?m, ?label) :-
+            total(?i, ?t), least(?i, ?l), most(?i, ?m),
+            ?m - ?l > 1,
+            ?label = cat(?i, ":", ?t).
+    "#;
+
+    let sale = |item: &str, price: i64| ("sale", vec![Value::Str(item.to_string()), Value::Int(price)], 1);
+    let updates = run(program, "summary", vec![
+        vec![sale("apple", 1), sale("apple", 3), sale("pear", 2), sale("pear", 3)],
+    ]);
+
+    let summary = vec![
+        Value::Str("apple".to_string()),
+        Value::Int(4),
+        Value::Int(1),
+        Value::Int(3),
+        Value::Str("apple:4".to_string()),
+    ];
+    assert_eq!(updates, vec![(summary, 0, 1)]);
+}
+
+#[test]
+fn negation_within_recursion_is_rejected() {
+
+    let program = datalog::parse(r#"
+        .decl node(x: number)
+        .decl even(x: number)
+        .decl odd(x: number)
+        even(?x) :- node(?x), !odd(?x).
+        odd(?x) :- node(?x), !even(?x).
+    "#).unwrap();
+
+    assert!(stratify(&program).is_err());
+}
+
+#[test]
+fn errors_report_lines() {
+
+    let error = datalog::parse(".decl edge(x: number, y: number)\nedge(1, 2)\n").unwrap_err();
+    assert_eq!(error.line, Some(2));
+
+    let error = datalog::parse(".decl edge(x: number, y: number)\nedge(?x, ?y) :- edge(?x, _).\n").unwrap_err();
+    assert_eq!(error.line, Some(2));
+
+    let error = datalog::parse(".decl edge(x: number, y: number)\n\nedge(?x, ?y) :-\n  edge(?x, ?y), ?z < ?w.\n").unwrap_err();
+    assert_eq!(error.line, Some(3));
+
+    let error = datalog::parse(".decl edge(x: number, y: number)\nedge(?x, ?y) :- edge(?x, ?y), ?x = _ + 1.\n").unwrap_err();
+    assert_eq!(error.line, Some(2));
+
+    let error = datalog::parse(".decl edge(x: number, y: number)\nedge(?x, ?y) :- edge(?y, ?x).\n.plan 1:(x)\n").unwrap_err();
+    assert_eq!(error.line, Some(3));
+}
+
+#[test]
+fn render_reports_unsafe_rules() {
+
+    // Programs built directly are checked as parsed programs are.
+    let mut program = datalog::parse(".decl edge(x: number, y: number)\n.input edge\n\nedge(?y, ?x) :- edge(?x, ?y).\n").unwrap();
+    program.rules[0].head.terms[1] = HeadTerm::Expr(Expr::Term(Term::Wildcard));
+
+    let error = timely::execute_directly(move |worker| {
+        worker.dataflow::<u64, _, _>(|scope| datalog::render(&program, scope).err())
+    });
+    assert_eq!(error.map(|error| error.line), Some(Some(4)));
+}
+
+#[test]
+fn plans_are_ignored() {
+
+    let program = datalog::parse(r#"
+        .decl edge(x: number, y: number)
+        .decl path(x: number, y: number)
+        path(?x, ?y) :- edge(?x, ?y).
+        path(?x, ?z) :- path(?x, ?y), edge(?y, ?z).
+        .plan 1:(2,1), 2:(1,2)
+    "#).unwrap();
+
+    assert_eq!(program.rules.len(), 2);
+    assert_eq!(program.rules[1].line, 5);
+}
+
+#[test]
+fn doop_rules_stratify() {
+
+    let program = datalog::parse(include_str!("../../doop/self-contained.dl")).unwrap();
+    assert!(program.relations["_ClassType"].input);
+    assert!(program.relations["VarPointsTo"].output);
+    assert!(program.relations.contains_key("basic.MethodLookup"));
+
+    let strata = stratify(&program).unwrap();
+    let reachable = strata.iter().find(|stratum| stratum.relations.iter().any(|relation| relation == "Reachable")).unwrap();
+    assert!(reachable.recursive);
+    assert!(reachable.relations.iter().any(|relation| relation == "VarPointsTo"));
+}
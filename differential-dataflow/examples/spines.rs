@@ -41,6 +41,14 @@ fn main() {
                     keys.join_core(&data, |_k, &(), &()| Option::<()>::None)
                         .probe_with(&mut probe);
                 },
+                #[cfg(feature = "bincode")]
+                "spill" => {
+                    use differential_dataflow::trace::implementations::spill::{SpillKeyBatcher, SpillKeyBuilder, SpillKeySpine};
+                    let data = data.arrange::<SpillKeyBatcher<_,_,_>, SpillKeyBuilder<_,_,_>, SpillKeySpine<_,_,_>>();
+                    let keys = keys.arrange::<SpillKeyBatcher<_,_,_>, SpillKeyBuilder<_,_,_>, SpillKeySpine<_,_,_>>();
+                    keys.join_core(&data, |_k, &(), &()| Option::<()>::None)
+                        .probe_with(&mut probe);
+                },
                 "rhh" => {
                     use differential_dataflow::trace::implementations::rhh::{HashWrapper, VecBatcher, VecBuilder, VecSpine};
                     let data = data.map(|x| HashWrapper { inner: x }).arrange::<VecBatcher<_,(),_,_>, VecBuilder<_,(),_,_>, VecSpine<_,(),_,_>>();
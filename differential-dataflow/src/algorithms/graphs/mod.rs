@@ -4,4 +4,5 @@ pub mod scc;
 pub mod sequential;
 pub mod bijkstra;
 pub mod bfs;
+pub mod pagerank;
 pub mod propagate;
\ No newline at end of file
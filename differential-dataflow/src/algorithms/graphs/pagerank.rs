@@ -0,0 +1,218 @@
+//! Ranking of nodes by the random surfer model.
+//!
+//! Ranks are the multiplicities of nodes in the output collections, and count the surfers at each
+//! node. In each round every node sends a damped fraction of its surfers along its out-edges, split
+//! evenly and rounded down, and the surfers that do not follow an edge reset to their starting nodes.
+//! Surfers at nodes without out-edges do not continue.
+//!
+//! Starting from the reset surfers, the ranks only increase from round to round, and so converge even
+//! with rounding. The rounds can be bounded by a fixed number of iterations, and a threshold rounds
+//! the surfers each node sends down to a multiple of the threshold, so that changes smaller than the
+//! threshold are not propagated and the ranks converge sooner.
+//!
+//! Both computations are built on `damped`, which performs the rounds for surfers moved by any step.
+
+use std::hash::Hash;
+
+use timely::order::Product;
+use timely::dataflow::*;
+use timely::dataflow::scopes::Child;
+
+use crate::{Collection, ExchangeData};
+use crate::operators::*;
+use crate::operators::iterate::Variable;
+use crate::lattice::Lattice;
+
+/// Parameters of a PageRank computation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+    /// The fraction of its surfers each node sends along its out-edges in each round.
+    pub damping: f64,
+    /// The number of surfers per starting node; the remaining fraction reset in each round.
+    pub surfers: isize,
+    /// The number of rounds to perform, or `None` to continue until the ranks converge.
+    pub iterations: Option<u32>,
+    /// The surfers each node sends along each out-edge are rounded down to a multiple of this.
+    ///
+    /// Thresholds less than one are treated as one, and do not round.
+    pub threshold: isize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            damping: 0.85,
+            surfers: 1_000_000,
+            iterations: None,
+            threshold: 1,
+        }
+    }
+}
+
+impl Config {
+    /// The number of surfers a node with `surfers` surfers and `degree` out-edges sends along each out-edge.
+    pub fn sent(&self, surfers: isize, degree: isize) -> isize {
+        let sent = ((surfers as f64 * self.damping) / degree as f64) as isize;
+        sent - sent % self.threshold.max(1)
+    }
+    /// The number of surfers reset to each starting node in each round.
+    pub fn reset(&self) -> isize {
+        (self.surfers as f64 * (1.0 - self.damping)) as isize
+    }
+}
+
+/// Returns the nodes of `edges`, with multiplicities their ranks.
+pub fn pagerank<G, N>(edges: &Collection<G, (N,N)>, config: Config) -> Collection<G, N>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: ExchangeData+Hash,
+{
+    use crate::operators::arrange::arrangement::ArrangeByKey;
+    let edges = edges.arrange_by_key();
+    pagerank_arranged(&edges, config)
+}
+
+/// Returns pairs (node, source) for each of `sources`, with multiplicities the ranks of nodes
+/// when surfers start from and reset to the source.
+pub fn personalized_pagerank<G, N>(edges: &Collection<G, (N,N)>, sources: &Collection<G, N>, config: Config) -> Collection<G, (N, N)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: ExchangeData+Hash,
+{
+    use crate::operators::arrange::arrangement::ArrangeByKey;
+    let edges = edges.arrange_by_key();
+    personalized_pagerank_arranged(&edges, sources, config)
+}
+
+use crate::trace::TraceReader;
+use crate::operators::arrange::Arranged;
+
+/// Returns the nodes of `edges`, with multiplicities their ranks.
+pub fn pagerank_arranged<G, N, Tr>(edges: &Arranged<G, Tr>, config: Config) -> Collection<G, N>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    N: ExchangeData+Hash,
+    Tr: for<'a> TraceReader<Key<'a>=&'a N, Val<'a>=&'a N, Diff=isize>+Clone+'static,
+{
+    // surfers start from and reset to every node.
+    let reset = config.reset();
+    let resets =
+    edges
+        .as_collection(|src, dst| (src.clone(), dst.clone()))
+        .flat_map(|(src, dst)| Some(src).into_iter().chain(Some(dst)))
+        .distinct()
+        .explode(move |node| Some(((node, ()), reset)));
+
+    surf(edges, &resets, config)
+        .map(|(node, ())| node)
+}
+
+/// Returns pairs (node, source) for each of `sources`, with multiplicities the ranks of nodes
+/// when surfers start from and reset to the source.
+pub fn personalized_pagerank_arranged<G, N, Tr>(edges: &Arranged<G, Tr>, sources: &Collection<G, N>, config: Config) -> Collection<G, (N, N)>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    N: ExchangeData+Hash,
+    Tr: for<'a> TraceReader<Key<'a>=&'a N, Val<'a>=&'a N, Diff=isize>+Clone+'static,
+{
+    let reset = config.reset();
+    let resets =
+    sources
+        .distinct()
+        .explode(move |source| Some(((source.clone(), source), reset)));
+
+    surf(edges, &resets, config)
+}
+
+/// Moves labeled surfers along `edges` in each round, keeping their labels.
+fn surf<G, N, L, Tr>(edges: &Arranged<G, Tr>, resets: &Collection<G, (N, L)>, config: Config) -> Collection<G, (N, L)>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    N: ExchangeData+Hash,
+    L: ExchangeData+Hash,
+    Tr: for<'a> TraceReader<Key<'a>=&'a N, Val<'a>=&'a N, Diff=isize>+Clone+'static,
+{
+    // the number of out-edges of each node.
+    let degrees = edges.as_collection(|src, _dst| src.clone()).count();
+
+    damped(resets, &degrees, config, |sent| {
+        let edges = edges.enter(&sent.scope());
+        sent.join_core(&edges, |_src, label, dst| Some((dst.clone(), label.clone())))
+    })
+}
+
+/// The scope of the rounds of `damped`.
+pub type Rounds<'a, G> = Child<'a, G, Product<<G as ScopeParent>::Timestamp, u32>>;
+
+/// Repeatedly sends a damped fraction of the surfers at each key to the keys `step` moves them to, and adds `resets`.
+///
+/// Surfers are pairs (key, label), with multiplicities their number. In each round the surfers at each key
+/// are damped and split among the `degrees` of the key, with the damping and threshold of `config`, and
+/// `step` moves the surfers sent along each of the key's `degrees` to their next keys. The iteration starts
+/// from `resets`, and returns the surfers after the number of rounds of `config`, or once they no longer change.
+///
+/// # Examples
+///
+/// ```
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::pagerank::{damped, Config};
+///
+/// ::timely::example(|scope| {
+///
+///     // Surfers move from each key to the next, along a chain of three keys.
+///     let config = Config { damping: 0.5, surfers: 100, iterations: Some(2), threshold: 1 };
+///     let resets = scope.new_collection_from(Some((0, ()))).1.explode(|surfer| Some((surfer, 50)));
+///     let degrees = scope.new_collection_from(vec![(0, 1), (1, 1), (2, 1)]).1;
+///     let expected = scope.new_collection_from(vec![((0, ()), 50), ((1, ()), 25), ((2, ()), 12)]).1.explode(|(surfer, count)| Some((surfer, count)));
+///
+///     damped(&resets, &degrees, config, |sent| sent.map(|(key, label)| (key + 1, label)))
+///         .assert_eq(&expected);
+/// });
+/// ```
+pub fn damped<G, K, L, F>(resets: &Collection<G, (K, L)>, degrees: &Collection<G, (K, isize)>, config: Config, step: F) -> Collection<G, (K, L)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: ExchangeData+Hash,
+    L: ExchangeData+Hash,
+    F: for<'a> FnOnce(&Collection<Rounds<'a, G>, (K, L)>) -> Collection<Rounds<'a, G>, (K, L)>,
+{
+    use timely::dataflow::operators::Filter;
+    use crate::AsCollection;
+
+    if config.iterations == Some(0) {
+        return resets.clone();
+    }
+
+    resets.scope().iterative::<u32,_,_>(|inner| {
+
+        let degrees = degrees.enter(inner);
+        let resets = resets.enter(inner);
+
+        let surfers = Variable::new_from(resets.clone(), Product::new(Default::default(), 1));
+
+        // the surfers each key sends along each of its degrees.
+        let sent =
+        surfers
+            .join_map(&degrees, |key, label, degree| ((key.clone(), label.clone()), *degree))
+            .threshold(move |(_surfer, degree), surfers| config.sent(*surfers, *degree))
+            .map(|((key, label), _degree)| (key, label));
+
+        let mut next =
+        step(&sent)
+            .concat(&resets)
+            .consolidate();
+
+        if let Some(iterations) = config.iterations {
+            next =
+            next.inner
+                .filter(move |(_data, time, _diff)| time.inner < iterations)
+                .as_collection();
+        }
+
+        surfers.set(&next);
+        next.leave()
+    })
+}
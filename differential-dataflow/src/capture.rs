@@ -5,9 +5,13 @@
 //! about the collection that once true stay true, such as the exact changes data undergo
 //! at each time, and the number of distinct updates at each time.
 //!
-//! The methods are parameterized by implementors of byte sources and byte sinks. For
-//! example implementations of these traits, consult the commented text at the end of
-//! this file.
+//! The methods are parameterized by implementors of message sources and message sinks.
+//! The `broker` module adapts these to log brokers that exchange byte records, and
+//! includes an in-process broker; the `kafka` module (behind the `kafka` feature)
+//! provides an implementation backed by Kafka. The `file` and `tcp` modules provide
+//! writers and source iterators for append-only files and TCP connections, which
+//! exchange messages using the versioned byte framing described in `framing`. These
+//! modules encode messages with `bincode`, and require the `bincode` feature.
 
 use std::time::Duration;
 use serde::{Deserialize, Serialize};
@@ -41,6 +45,9 @@ pub struct Progress<T> {
     pub counts: Vec<(T, usize)>,
 }
 
+/// A token that keeps a source running while it is held, and the stream of updates from the source.
+pub type SourceOutput<G, D, T, R> = (Box<dyn std::any::Any + Send + Sync>, timely::dataflow::Stream<G, (D, T, R)>);
+
 /// A simple sink for byte slices.
 pub trait Writer<T> {
     /// Returns an amount of time to wait before retrying, or `None` for success.
@@ -227,6 +234,7 @@ pub mod source {
     use std::rc::Rc;
     use std::marker::{Send, Sync};
     use std::sync::Arc;
+    use std::time::Duration;
     use timely::dataflow::{Scope, Stream, operators::{Capability, CapabilitySet}};
     use timely::progress::Timestamp;
     use timely::scheduling::SyncActivator;
@@ -253,6 +261,43 @@ pub mod source {
         scope: G,
         source_builder: B,
     ) -> (Box<dyn std::any::Any + Send + Sync>, Stream<G, (D, T, R)>)
+    where
+        G: Scope<Timestamp = T>,
+        B: FnOnce(SyncActivator) -> I,
+        I: Iterator<Item = Message<D, T, R>> + 'static,
+        D: ExchangeData + Hash,
+        T: ExchangeData + Hash + Timestamp + Lattice,
+        R: ExchangeData + Hash,
+    {
+        build_core(scope, source_builder, None)
+    }
+
+    /// Constructs a stream of updates from a source of messages that must be polled.
+    ///
+    /// This method behaves as `build`, except that once the source has no available messages
+    /// the operator re-activates itself after `interval`, rather than relying on the source to
+    /// activate it when messages become available.
+    pub fn build_polled<G, B, I, D, T, R>(
+        scope: G,
+        interval: Duration,
+        source_builder: B,
+    ) -> super::SourceOutput<G, D, T, R>
+    where
+        G: Scope<Timestamp = T>,
+        B: FnOnce(SyncActivator) -> I,
+        I: Iterator<Item = Message<D, T, R>> + 'static,
+        D: ExchangeData + Hash,
+        T: ExchangeData + Hash + Timestamp + Lattice,
+        R: ExchangeData + Hash,
+    {
+        build_core(scope, source_builder, Some(interval))
+    }
+
+    fn build_core<G, B, I, D, T, R>(
+        scope: G,
+        source_builder: B,
+        poll: Option<Duration>,
+    ) -> super::SourceOutput<G, D, T, R>
     where
         G: Scope<Timestamp = T>,
         B: FnOnce(SyncActivator) -> I,
@@ -311,6 +356,7 @@ pub mod source {
         let address = messages_op.operator_info().address;
         let activator = scope.sync_activator_for(address.to_vec());
         let activator2 = scope.activator_for(Rc::clone(&address));
+        let poll_activator = scope.activator_for(Rc::clone(&address));
         let drop_activator = DropActivator { activator: Arc::new(scope.sync_activator_for(address.to_vec())) };
         let mut source = source_builder(activator);
         let (mut updates_out, updates) = messages_op.new_output();
@@ -380,6 +426,11 @@ pub mod source {
                             }
                         }
                     }
+
+                    // Sources that must be polled are revisited once there may be more messages.
+                    if let Some(interval) = poll {
+                        poll_activator.activate_after(interval);
+                    }
                 }
             }
         });
@@ -718,182 +769,933 @@ pub mod sink {
     }
 }
 
-// pub mod kafka {
-
-//     use serde::{Serialize, Deserialize};
-//     use timely::scheduling::SyncActivator;
-//     use rdkafka::{ClientContext, config::ClientConfig};
-//     use rdkafka::consumer::{BaseConsumer, ConsumerContext};
-//     use rdkafka::error::{KafkaError, RDKafkaError};
-//     use super::BytesSink;
-
-//     use std::hash::Hash;
-//     use timely::progress::Timestamp;
-//     use timely::dataflow::{Scope, Stream};
-//     use crate::ExchangeData;
-//     use crate::lattice::Lattice;
-
-//     /// Creates a Kafka source from supplied configuration information.
-//     pub fn create_source<G, D, T, R>(scope: G, addr: &str, topic: &str, group: &str) -> (Box<dyn std::any::Any>, Stream<G, (D, T, R)>)
-//     where
-//         G: Scope<Timestamp = T>,
-//         D: ExchangeData + Hash + for<'a> serde::Deserialize<'a>,
-//         T: ExchangeData + Hash + for<'a> serde::Deserialize<'a> + Timestamp + Lattice,
-//         R: ExchangeData + Hash + for<'a> serde::Deserialize<'a>,
-//     {
-//         super::source::build(scope, |activator| {
-//             let source = KafkaSource::new(addr, topic, group, activator);
-//             // An iterator combinator that yields every "duration" even if more items exist.
-//             // The implementation of such an iterator exists in the git history, or can be rewritten easily.
-//             super::YieldingIter::new_from(Iter::<D,T,R>::new_from(source), std::time::Duration::from_millis(10))
-//         })
-//     }
-
-//     pub fn create_sink<G, D, T, R>(stream: &Stream<G, (D, T, R)>, addr: &str, topic: &str) -> Box<dyn std::any::Any>
-//     where
-//         G: Scope<Timestamp = T>,
-//         D: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
-//         T: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a> + Timestamp + Lattice,
-//         R: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
-//     {
-//         use std::rc::Rc;
-//         use std::cell::RefCell;
-//         use crate::hashable::Hashable;
-
-//         let sink = KafkaSink::new(addr, topic);
-//         let result = Rc::new(RefCell::new(sink));
-//         let sink_hash = (addr.to_string(), topic.to_string()).hashed();
-//         super::sink::build(
-//             &stream,
-//             sink_hash,
-//             Rc::downgrade(&result),
-//             Rc::downgrade(&result),
-//         );
-//         Box::new(result)
-
-//     }
-
-//     pub struct KafkaSource {
-//         consumer: BaseConsumer<ActivationConsumerContext>,
-//     }
-
-//     impl KafkaSource {
-//         pub fn new(addr: &str, topic: &str, group: &str, activator: SyncActivator) -> Self {
-//             let mut kafka_config = ClientConfig::new();
-//             // This is mostly cargo-cult'd in from `source/kafka.rs`.
-//             kafka_config.set("bootstrap.servers", &addr.to_string());
-//             kafka_config
-//                 .set("enable.auto.commit", "false")
-//                 .set("auto.offset.reset", "earliest");
-
-//             kafka_config.set("topic.metadata.refresh.interval.ms", "30000"); // 30 seconds
-//             kafka_config.set("fetch.message.max.bytes", "134217728");
-//             kafka_config.set("group.id", group);
-//             kafka_config.set("isolation.level", "read_committed");
-//             let activator = ActivationConsumerContext(activator);
-//             let consumer = kafka_config.create_with_context::<_, BaseConsumer<_>>(activator).unwrap();
-//             use rdkafka::consumer::Consumer;
-//             consumer.subscribe(&[topic]).unwrap();
-//             Self {
-//                 consumer,
-//             }
-//         }
-//     }
-
-//     pub struct Iter<D, T, R> {
-//         pub source: KafkaSource,
-//         phantom: std::marker::PhantomData<(D, T, R)>,
-//     }
-
-//     impl<D, T, R> Iter<D, T, R> {
-//         /// Constructs a new iterator from a bytes source.
-//         pub fn new_from(source: KafkaSource) -> Self {
-//             Self {
-//                 source,
-//                 phantom: std::marker::PhantomData,
-//             }
-//         }
-//     }
-
-//     impl<D, T, R> Iterator for Iter<D, T, R>
-//     where
-//         D: for<'a>Deserialize<'a>,
-//         T: for<'a>Deserialize<'a>,
-//         R: for<'a>Deserialize<'a>,
-//     {
-//         type Item = super::Message<D, T, R>;
-//         fn next(&mut self) -> Option<Self::Item> {
-//             use rdkafka::message::Message;
-//             self.source
-//                 .consumer
-//                 .poll(std::time::Duration::from_millis(0))
-//                 .and_then(|result| result.ok())
-//                 .and_then(|message| {
-//                     message.payload().and_then(|message| bincode::deserialize::<super::Message<D, T, R>>(message).ok())
-//                 })
-//         }
-//     }
-
-//     /// An implementation of [`ConsumerContext`] that unparks the wrapped thread
-//     /// when the message queue switches from nonempty to empty.
-//     struct ActivationConsumerContext(SyncActivator);
-
-//     impl ClientContext for ActivationConsumerContext { }
-
-//     impl ActivationConsumerContext {
-//         fn activate(&self) {
-//             self.0.activate().unwrap();
-//         }
-//     }
-
-//     impl ConsumerContext for ActivationConsumerContext {
-//         fn message_queue_nonempty_callback(&self) {
-//             self.activate();
-//         }
-//     }
-
-//     use std::time::Duration;
-//     use rdkafka::producer::DefaultProducerContext;
-//     use rdkafka::producer::{BaseRecord, ThreadedProducer};
-
-//     pub struct KafkaSink {
-//         topic: String,
-//         producer: ThreadedProducer<DefaultProducerContext>,
-//     }
-
-//     impl KafkaSink {
-//         pub fn new(addr: &str, topic: &str) -> Self {
-//             let mut config = ClientConfig::new();
-//             config.set("bootstrap.servers", &addr);
-//             config.set("queue.buffering.max.kbytes", &format!("{}", 16 << 20));
-//             config.set("queue.buffering.max.messages", &format!("{}", 10_000_000));
-//             config.set("queue.buffering.max.ms", &format!("{}", 10));
-//             let producer = config
-//                 .create_with_context::<_, ThreadedProducer<_>>(DefaultProducerContext)
-//                 .expect("creating kafka producer for kafka sinks failed");
-//             Self {
-//                 producer,
-//                 topic: topic.to_string(),
-//             }
-//         }
-//     }
-
-//     impl BytesSink for KafkaSink {
-//         fn poll(&mut self, bytes: &[u8]) -> Option<Duration> {
-//             let record = BaseRecord::<[u8], _>::to(&self.topic).payload(bytes);
-
-//             self.producer.send(record).err().map(|(e, _)| {
-//                 if let KafkaError::MessageProduction(RDKafkaError::QueueFull) = e {
-//                     Duration::from_secs(1)
-//                 } else {
-//                     // TODO(frank): report this error upwards so the user knows the sink is dead.
-//                     Duration::from_secs(1)
-//                 }
-//             })
-//         }
-//         fn done(&self) -> bool {
-//             self.producer.in_flight_count() == 0
-//         }
-//     }
-
-// }
+/// Methods for exchanging messages through log brokers.
+///
+/// A log broker accepts records of bytes appended to a topic, and presents them to consumers
+/// in some order. The CDC V2 protocol tolerates both duplication and reordering of messages, and
+/// so a broker need only deliver each message at least once. Messages are encoded with `bincode`.
+#[cfg(feature = "bincode")]
+pub mod broker {
+
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::hash::Hash;
+    use std::marker::PhantomData;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+    use timely::dataflow::{Scope, Stream};
+    use timely::progress::Timestamp;
+    use timely::scheduling::SyncActivator;
+
+    use crate::{lattice::Lattice, ExchangeData};
+    use super::{Message, Writer};
+
+    /// Appends records to a topic of a log broker.
+    pub trait Producer {
+        /// Appends `bytes` to the topic, or returns an amount of time to wait before retrying.
+        fn send(&mut self, bytes: &[u8]) -> Option<Duration>;
+        /// Indicates if all appended records have been acknowledged by the broker.
+        fn flushed(&self) -> bool;
+    }
+
+    /// Reads records from a topic of a log broker.
+    ///
+    /// Implementors used with `create_source` are responsible for activating the source operator
+    /// when records become available, typically through a `SyncActivator` supplied when the consumer
+    /// is created. Implementors without notification of new records should use `create_polled_source`.
+    pub trait Consumer {
+        /// Returns the next available record, or `None` if no record is currently available.
+        fn poll(&mut self) -> Option<Vec<u8>>;
+    }
+
+    /// A `Writer` of messages that encodes them as records for a `Producer`.
+    pub struct BrokerSink<P, D, T, R> {
+        producer: P,
+        buffer: Vec<u8>,
+        phantom: PhantomData<(D, T, R)>,
+    }
+
+    impl<P, D, T, R> BrokerSink<P, D, T, R> {
+        /// Creates a new sink from a producer.
+        pub fn new(producer: P) -> Self {
+            Self {
+                producer,
+                buffer: Vec::new(),
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    impl<P, D, T, R> Writer<Message<D, T, R>> for BrokerSink<P, D, T, R>
+    where
+        P: Producer,
+        D: Serialize,
+        T: Serialize,
+        R: Serialize,
+    {
+        fn poll(&mut self, item: &Message<D, T, R>) -> Option<Duration> {
+            self.buffer.clear();
+            bincode::serialize_into(&mut self.buffer, item).expect("failed to encode CDC message");
+            self.producer.send(&self.buffer[..])
+        }
+        fn done(&self) -> bool {
+            self.producer.flushed()
+        }
+    }
+
+    /// An iterator over the messages decoded from the records of a `Consumer`.
+    pub struct BrokerSource<C, D, T, R> {
+        consumer: C,
+        phantom: PhantomData<(D, T, R)>,
+    }
+
+    impl<C, D, T, R> BrokerSource<C, D, T, R> {
+        /// Creates a new source from a consumer.
+        pub fn new(consumer: C) -> Self {
+            Self {
+                consumer,
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    impl<C, D, T, R> Iterator for BrokerSource<C, D, T, R>
+    where
+        C: Consumer,
+        D: for<'a> Deserialize<'a>,
+        T: for<'a> Deserialize<'a>,
+        R: for<'a> Deserialize<'a>,
+    {
+        type Item = Message<D, T, R>;
+        fn next(&mut self) -> Option<Self::Item> {
+            // Records that do not decode as messages cannot be part of the stream, and are skipped.
+            while let Some(bytes) = self.consumer.poll() {
+                if let Ok(message) = bincode::deserialize(&bytes[..]) {
+                    return Some(message);
+                }
+            }
+            None
+        }
+    }
+
+    /// Records the updates in `stream` to a log broker, through the producer `producer`.
+    ///
+    /// As with `sink::build`, the stream should be consolidated. The `sink_hash` determines the
+    /// worker responsible for progress messages, and is commonly a hash of the topic name.
+    /// The sink continues to write messages only as long as the returned token is held.
+    pub fn create_sink<G, P, D, T, R>(stream: &Stream<G, (D, T, R)>, sink_hash: u64, producer: P) -> Box<dyn std::any::Any>
+    where
+        G: Scope<Timestamp = T>,
+        P: Producer + 'static,
+        D: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
+        T: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        R: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
+    {
+        let sink = Rc::new(RefCell::new(BrokerSink::new(producer)));
+        super::sink::build(stream, sink_hash, Rc::downgrade(&sink), Rc::downgrade(&sink));
+        Box::new(sink)
+    }
+
+    /// Constructs a stream of updates from the records of a log broker.
+    ///
+    /// The `consumer` closure is called with an activator for the source operator, and should
+    /// return a consumer that uses the activator to signal the availability of new records.
+    /// As with `source::build`, the stream continues until the returned token is dropped.
+    pub fn create_source<G, C, F, D, T, R>(scope: G, consumer: F) -> super::SourceOutput<G, D, T, R>
+    where
+        G: Scope<Timestamp = T>,
+        C: Consumer + 'static,
+        F: FnOnce(SyncActivator) -> C,
+        D: ExchangeData + Hash + for<'a> Deserialize<'a>,
+        T: ExchangeData + Hash + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        R: ExchangeData + Hash + for<'a> Deserialize<'a>,
+    {
+        super::source::build(scope, |activator| BrokerSource::new(consumer(activator)))
+    }
+
+    /// Constructs a stream of updates from the records of a log broker, polling for new records.
+    ///
+    /// This method behaves as `create_source`, except that `consumer` need not activate the source
+    /// operator. Instead, once the consumer has no available records the operator polls it again
+    /// after `interval`.
+    pub fn create_polled_source<G, C, D, T, R>(scope: G, interval: Duration, consumer: C) -> super::SourceOutput<G, D, T, R>
+    where
+        G: Scope<Timestamp = T>,
+        C: Consumer + 'static,
+        D: ExchangeData + Hash + for<'a> Deserialize<'a>,
+        T: ExchangeData + Hash + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        R: ExchangeData + Hash + for<'a> Deserialize<'a>,
+    {
+        super::source::build_polled(scope, interval, |_activator| BrokerSource::new(consumer))
+    }
+
+    /// A log broker that lives in the memory of the process.
+    ///
+    /// The broker retains all records appended to each topic, and each consumer reads a topic
+    /// from its beginning. Clones of the broker share the same topics, and may be sent between
+    /// threads, which makes the broker a stand-in for an external broker in tests and examples.
+    #[derive(Clone, Default)]
+    pub struct InMemoryBroker {
+        topics: Arc<Mutex<HashMap<String, Topic>>>,
+    }
+
+    /// The records and consumer activators of a topic.
+    #[derive(Default)]
+    struct Topic {
+        records: Vec<Arc<[u8]>>,
+        activators: Vec<SyncActivator>,
+    }
+
+    impl InMemoryBroker {
+        /// Creates a new broker without any topics.
+        pub fn new() -> Self { Self::default() }
+        /// Creates a producer for `topic`.
+        pub fn producer(&self, topic: &str) -> InMemoryProducer {
+            InMemoryProducer {
+                topics: self.topics.clone(),
+                topic: topic.to_string(),
+            }
+        }
+        /// Creates a consumer for `topic`, which reads the topic from its beginning.
+        ///
+        /// The activator is activated each time a record is appended to the topic.
+        pub fn consumer(&self, topic: &str, activator: SyncActivator) -> InMemoryConsumer {
+            self.topics
+                .lock()
+                .unwrap()
+                .entry(topic.to_string())
+                .or_default()
+                .activators
+                .push(activator);
+            InMemoryConsumer {
+                topics: self.topics.clone(),
+                topic: topic.to_string(),
+                offset: 0,
+            }
+        }
+        /// The number of records appended to `topic`.
+        pub fn len(&self, topic: &str) -> usize {
+            self.topics.lock().unwrap().get(topic).map(|t| t.records.len()).unwrap_or(0)
+        }
+    }
+
+    /// Appends records to a topic of an `InMemoryBroker`.
+    pub struct InMemoryProducer {
+        topics: Arc<Mutex<HashMap<String, Topic>>>,
+        topic: String,
+    }
+
+    impl Producer for InMemoryProducer {
+        fn send(&mut self, bytes: &[u8]) -> Option<Duration> {
+            let mut topics = self.topics.lock().unwrap();
+            let topic = topics.entry(self.topic.clone()).or_default();
+            topic.records.push(bytes.into());
+            // Activators whose operators have shut down are no longer of interest.
+            topic.activators.retain(|activator| activator.activate().is_ok());
+            None
+        }
+        fn flushed(&self) -> bool { true }
+    }
+
+    /// Reads records from a topic of an `InMemoryBroker`.
+    pub struct InMemoryConsumer {
+        topics: Arc<Mutex<HashMap<String, Topic>>>,
+        topic: String,
+        offset: usize,
+    }
+
+    impl Consumer for InMemoryConsumer {
+        fn poll(&mut self) -> Option<Vec<u8>> {
+            let topics = self.topics.lock().unwrap();
+            let record = topics.get(&self.topic)?.records.get(self.offset)?.to_vec();
+            self.offset += 1;
+            Some(record)
+        }
+    }
+}
+
+/// Methods for exchanging messages through Kafka.
+///
+/// Each message is a Kafka record whose payload is the `bincode` encoding of the message.
+/// Consumers should read all partitions of the topic, in any order; the source deduplicates
+/// and reorders the messages it receives.
+#[cfg(feature = "kafka")]
+pub mod kafka {
+
+    use std::hash::Hash;
+    use std::time::Duration;
+
+    use rdkafka::config::ClientConfig;
+    use rdkafka::consumer::{BaseConsumer, Consumer as _, DefaultConsumerContext};
+    use rdkafka::producer::{BaseRecord, DefaultProducerContext, Producer as _, ThreadedProducer};
+    use serde::{Deserialize, Serialize};
+    use timely::dataflow::{Scope, Stream};
+    use timely::progress::Timestamp;
+
+    use crate::{lattice::Lattice, ExchangeData};
+    use super::broker::{Consumer, Producer};
+
+    /// The interval at which sources poll Kafka once they have read all available records.
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Creates a Kafka source from supplied configuration information.
+    pub fn create_source<G, D, T, R>(scope: G, addr: &str, topic: &str, group: &str) -> super::SourceOutput<G, D, T, R>
+    where
+        G: Scope<Timestamp = T>,
+        D: ExchangeData + Hash + for<'a> Deserialize<'a>,
+        T: ExchangeData + Hash + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        R: ExchangeData + Hash + for<'a> Deserialize<'a>,
+    {
+        super::broker::create_polled_source(scope, POLL_INTERVAL, KafkaConsumer::new(addr, topic, group))
+    }
+
+    /// Creates a Kafka sink from supplied configuration information.
+    ///
+    /// The sink continues to write messages only as long as the returned token is held.
+    pub fn create_sink<G, D, T, R>(stream: &Stream<G, (D, T, R)>, addr: &str, topic: &str) -> Box<dyn std::any::Any>
+    where
+        G: Scope<Timestamp = T>,
+        D: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
+        T: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        R: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
+    {
+        use crate::hashable::Hashable;
+        let sink_hash = (addr.to_string(), topic.to_string()).hashed();
+        super::broker::create_sink(stream, sink_hash, KafkaProducer::new(addr, topic))
+    }
+
+    /// Reads records from a Kafka topic.
+    ///
+    /// The consumer does not receive notification of new records, and should be used with
+    /// `create_polled_source` so that the topic is polled periodically.
+    pub struct KafkaConsumer {
+        consumer: BaseConsumer<DefaultConsumerContext>,
+    }
+
+    impl KafkaConsumer {
+        /// Creates a consumer of `topic` in consumer group `group`, reading from its earliest offset.
+        pub fn new(addr: &str, topic: &str, group: &str) -> Self {
+            let mut kafka_config = ClientConfig::new();
+            kafka_config.set("bootstrap.servers", addr);
+            kafka_config
+                .set("enable.auto.commit", "false")
+                .set("auto.offset.reset", "earliest");
+
+            kafka_config.set("topic.metadata.refresh.interval.ms", "30000"); // 30 seconds
+            kafka_config.set("fetch.message.max.bytes", "134217728");
+            kafka_config.set("group.id", group);
+            kafka_config.set("isolation.level", "read_committed");
+            let consumer: BaseConsumer<DefaultConsumerContext> = kafka_config.create().expect("creating kafka consumer for kafka sources failed");
+            consumer.subscribe(&[topic]).expect("subscribing kafka consumer to topic failed");
+            Self { consumer }
+        }
+    }
+
+    impl Consumer for KafkaConsumer {
+        fn poll(&mut self) -> Option<Vec<u8>> {
+            use rdkafka::message::Message;
+            loop {
+                match self.consumer.poll(Duration::from_millis(0)) {
+                    Some(Ok(message)) => {
+                        if let Some(payload) = message.payload() {
+                            return Some(payload.to_vec());
+                        }
+                    },
+                    // Errors are reported through the consumer, and are transient from our perspective.
+                    Some(Err(_)) => { },
+                    None => { return None; }
+                }
+            }
+        }
+    }
+
+    /// Appends records to a Kafka topic.
+    pub struct KafkaProducer {
+        topic: String,
+        producer: ThreadedProducer<DefaultProducerContext>,
+    }
+
+    impl KafkaProducer {
+        /// Creates a producer for `topic`.
+        pub fn new(addr: &str, topic: &str) -> Self {
+            let mut config = ClientConfig::new();
+            config.set("bootstrap.servers", addr);
+            config.set("queue.buffering.max.kbytes", format!("{}", 16 << 20));
+            config.set("queue.buffering.max.messages", format!("{}", 10_000_000));
+            config.set("queue.buffering.max.ms", format!("{}", 10));
+            let producer = config
+                .create_with_context::<_, ThreadedProducer<_>>(DefaultProducerContext)
+                .expect("creating kafka producer for kafka sinks failed");
+            Self {
+                producer,
+                topic: topic.to_string(),
+            }
+        }
+    }
+
+    impl Producer for KafkaProducer {
+        fn send(&mut self, bytes: &[u8]) -> Option<Duration> {
+            let record = BaseRecord::<(), [u8]>::to(&self.topic).payload(bytes);
+            // The most common error is a full queue, from which we recover by waiting.
+            self.producer.send(record).err().map(|_| Duration::from_secs(1))
+        }
+        fn flushed(&self) -> bool {
+            self.producer.in_flight_count() == 0
+        }
+    }
+}
+
+/// A versioned framing of messages as bytes, shared by the `file` and `tcp` transports.
+///
+/// A framed byte stream starts with a header of eight bytes: the four bytes `MAGIC` and then
+/// `VERSION` as a little-endian `u32`. The header is followed by any number of frames, each of
+/// which is a little-endian `u64` length followed by that many bytes of the `bincode` encoding
+/// of a `Message<D, T, R>`. Readers reject streams whose magic bytes or version do not match,
+/// and frames longer than `MAX_FRAME_LEN`.
+#[cfg(feature = "bincode")]
+pub mod framing {
+
+    use std::io::{Error, ErrorKind, Result};
+
+    use serde::{Deserialize, Serialize};
+
+    /// The bytes that start each framed byte stream.
+    pub const MAGIC: [u8; 4] = *b"DDCF";
+    /// The version of the framing, which changes with any change to the framing or message encoding.
+    pub const VERSION: u32 = 1;
+    /// The number of bytes in the header of a framed byte stream.
+    pub const HEADER_LEN: usize = 8;
+    /// The largest frame a reader accepts, in bytes.
+    pub const MAX_FRAME_LEN: usize = 1 << 30;
+
+    /// Appends the header of a framed byte stream to `buffer`.
+    pub fn encode_header(buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&MAGIC);
+        buffer.extend_from_slice(&VERSION.to_le_bytes());
+    }
+
+    /// Appends a frame containing `message` to `buffer`.
+    pub fn encode<M: Serialize>(message: &M, buffer: &mut Vec<u8>) {
+        let length = bincode::serialized_size(message).expect("failed to size CDC message");
+        buffer.extend_from_slice(&length.to_le_bytes());
+        bincode::serialize_into(&mut *buffer, message).expect("failed to encode CDC message");
+    }
+
+    /// Decodes frames from bytes as they become available.
+    #[derive(Default)]
+    pub struct Decoder {
+        buffer: Vec<u8>,
+        offset: usize,
+        header: bool,
+    }
+
+    impl Decoder {
+        /// Creates a decoder for a new framed byte stream.
+        pub fn new() -> Self { Self::default() }
+        /// Introduces further bytes from the stream.
+        pub fn push(&mut self, bytes: &[u8]) {
+            self.buffer.drain(.. self.offset);
+            self.offset = 0;
+            self.buffer.extend_from_slice(bytes);
+        }
+        /// Decodes the next complete frame, if one is available.
+        ///
+        /// An error indicates a stream with an unexpected header, a frame longer than `MAX_FRAME_LEN`,
+        /// or a frame that cannot be decoded. The decoder makes no further progress after an error.
+        pub fn decode<M: for<'a> Deserialize<'a>>(&mut self) -> Result<Option<M>> {
+            if !self.header {
+                let available = &self.buffer[self.offset ..];
+                if available.len() < HEADER_LEN {
+                    return Ok(None);
+                }
+                if available[.. 4] != MAGIC {
+                    return Err(Error::new(ErrorKind::InvalidData, "unrecognized CDC stream header"));
+                }
+                let version = u32::from_le_bytes([available[4], available[5], available[6], available[7]]);
+                if version != VERSION {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("CDC stream version {} does not match expected version {}", version, VERSION)));
+                }
+                self.offset += HEADER_LEN;
+                self.header = true;
+            }
+            let available = &self.buffer[self.offset ..];
+            if available.len() < 8 {
+                return Ok(None);
+            }
+            let mut length = [0u8; 8];
+            length.copy_from_slice(&available[.. 8]);
+            // The length is untrusted, and must be bounded before we compute with it.
+            let length = u64::from_le_bytes(length);
+            if length > MAX_FRAME_LEN as u64 {
+                return Err(Error::new(ErrorKind::InvalidData, format!("CDC frame of {} bytes exceeds the limit of {} bytes", length, MAX_FRAME_LEN)));
+            }
+            let length = length as usize;
+            if available.len() < 8 + length {
+                return Ok(None);
+            }
+            let message = bincode::deserialize(&available[8 .. 8 + length]).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+            self.offset += 8 + length;
+            Ok(Some(message))
+        }
+        /// Indicates that no bytes remain to be decoded.
+        pub fn is_empty(&self) -> bool {
+            self.offset == self.buffer.len()
+        }
+    }
+}
+
+/// Append-only files of framed messages, rotated once they reach a size limit.
+///
+/// Messages are written to files in a directory, named by a prefix and a sequence number, as in
+/// `prefix-00000000.cdc`. Each file is a framed byte stream (as described in `framing`), and a
+/// writer moves to the next file in the sequence once a file would exceed its size limit. A
+/// reader follows the sequence of files, and waits at the end of the most recent file for more
+/// messages. Writers that restart continue after the last file, and readers see all files; the
+/// protocol tolerates the duplicated messages this may produce.
+///
+/// A writer that fails to write a message retries it in a new file, as the failed write may
+/// have left part of the message in the old file. Readers discard an incomplete message at the
+/// end of a file once its successor exists.
+#[cfg(feature = "bincode")]
+pub mod file {
+
+    use std::fs::{File, OpenOptions};
+    use std::hash::Hash;
+    use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
+    use std::marker::PhantomData;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+    use timely::dataflow::Scope;
+    use timely::progress::Timestamp;
+
+    use crate::{lattice::Lattice, ExchangeData};
+    use super::{Message, Writer};
+    use super::framing::{self, Decoder};
+
+    /// The path of the file in `directory` with `prefix` and sequence number `index`.
+    pub fn file_path(directory: &Path, prefix: &str, index: usize) -> PathBuf {
+        directory.join(format!("{}-{:08}.cdc", prefix, index))
+    }
+
+    /// Constructs a stream of updates from the messages in files in `directory` named by `prefix`.
+    ///
+    /// The source checks the files for new messages every `interval`. As with `source::build`,
+    /// the stream continues until the returned token is dropped.
+    pub fn create_source<G, P, D, T, R>(scope: G, directory: P, prefix: &str, interval: Duration) -> super::SourceOutput<G, D, T, R>
+    where
+        G: Scope<Timestamp = T>,
+        P: AsRef<Path>,
+        D: ExchangeData + Hash + for<'a> Deserialize<'a>,
+        T: ExchangeData + Hash + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        R: ExchangeData + Hash + for<'a> Deserialize<'a>,
+    {
+        let source = FileSource::new(directory, prefix);
+        super::source::build_polled(scope, interval, |_activator| source)
+    }
+
+    /// A `Writer` that appends framed messages to a sequence of files.
+    pub struct FileSink {
+        directory: PathBuf,
+        prefix: String,
+        index: usize,
+        limit: usize,
+        written: usize,
+        file: BufWriter<File>,
+        buffer: Vec<u8>,
+        error: Option<Error>,
+    }
+
+    impl FileSink {
+        /// Creates a sink writing to files in `directory` named by `prefix`.
+        ///
+        /// The sink starts a new file after any existing files, and moves to a new file
+        /// before a file would exceed `limit` bytes (unless the file holds no messages).
+        pub fn new<P: AsRef<Path>>(directory: P, prefix: &str, limit: usize) -> Result<Self> {
+            let directory = directory.as_ref().to_path_buf();
+            std::fs::create_dir_all(&directory)?;
+            let mut index = 0;
+            while file_path(&directory, prefix, index).exists() {
+                index += 1;
+            }
+            let (file, written) = Self::create(&directory, prefix, index)?;
+            Ok(Self {
+                directory,
+                prefix: prefix.to_string(),
+                index,
+                limit,
+                written,
+                file,
+                buffer: Vec::new(),
+                error: None,
+            })
+        }
+
+        /// The most recent error writing a message, if the sink is retrying that message.
+        pub fn error(&self) -> Option<&Error> { self.error.as_ref() }
+
+        /// Creates the file with sequence number `index`, and writes its header.
+        fn create(directory: &Path, prefix: &str, index: usize) -> Result<(BufWriter<File>, usize)> {
+            let file = OpenOptions::new().write(true).create_new(true).open(file_path(directory, prefix, index))?;
+            let mut file = BufWriter::new(file);
+            let mut header = Vec::new();
+            framing::encode_header(&mut header);
+            file.write_all(&header)?;
+            file.flush()?;
+            Ok((file, header.len()))
+        }
+
+        /// Writes the frame in `self.buffer`, first moving to a new file if required.
+        fn write_frame(&mut self) -> Result<()> {
+            let failed = self.error.is_some();
+            if failed || (self.written > framing::HEADER_LEN && self.written + self.buffer.len() > self.limit) {
+                // Flush the current file completely before creating its successor,
+                // as readers move to the next file once they observe it. A failed
+                // file cannot be completed, and readers discard its incomplete end.
+                if !failed {
+                    self.file.flush()?;
+                    self.file.get_ref().sync_data()?;
+                }
+                // A failed attempt may have created files we could not complete.
+                let mut index = self.index + 1;
+                while file_path(&self.directory, &self.prefix, index).exists() {
+                    index += 1;
+                }
+                let (file, written) = Self::create(&self.directory, &self.prefix, index)?;
+                self.index = index;
+                self.file = file;
+                self.written = written;
+            }
+            self.file.write_all(&self.buffer)?;
+            self.file.flush()?;
+            self.written += self.buffer.len();
+            Ok(())
+        }
+    }
+
+    impl<D: Serialize, T: Serialize, R: Serialize> Writer<Message<D, T, R>> for FileSink {
+        fn poll(&mut self, item: &Message<D, T, R>) -> Option<Duration> {
+            self.buffer.clear();
+            framing::encode(item, &mut self.buffer);
+            match self.write_frame() {
+                Ok(()) => {
+                    self.error = None;
+                    None
+                },
+                Err(error) => {
+                    self.error = Some(error);
+                    Some(Duration::from_secs(1))
+                },
+            }
+        }
+        fn done(&self) -> bool { true }
+    }
+
+    /// An iterator over the messages in a sequence of files.
+    ///
+    /// The iterator returns `None` when it has read all available messages, and will return
+    /// further messages as they are written. The iterator ends if it cannot read the files or
+    /// decode their contents, and reports the reason through `error()`.
+    pub struct FileSource<D, T, R> {
+        directory: PathBuf,
+        prefix: String,
+        index: usize,
+        file: Option<File>,
+        decoder: Decoder,
+        error: Option<Error>,
+        phantom: PhantomData<(D, T, R)>,
+    }
+
+    impl<D, T, R> FileSource<D, T, R> {
+        /// Creates a source reading files in `directory` named by `prefix`, starting from the first.
+        pub fn new<P: AsRef<Path>>(directory: P, prefix: &str) -> Self {
+            Self {
+                directory: directory.as_ref().to_path_buf(),
+                prefix: prefix.to_string(),
+                index: 0,
+                file: None,
+                decoder: Decoder::new(),
+                error: None,
+                phantom: PhantomData,
+            }
+        }
+
+        /// The error that ended the iterator, if any.
+        pub fn error(&self) -> Option<&Error> { self.error.as_ref() }
+
+        /// Attaches the path of the current file to `error`.
+        fn context(&self, error: Error) -> Error {
+            Error::new(error.kind(), format!("{:?}: {}", file_path(&self.directory, &self.prefix, self.index), error))
+        }
+
+        /// Reads all available bytes from the current file into the decoder.
+        fn read_available(&mut self) -> Result<()> {
+            if let Some(file) = &mut self.file {
+                let mut bytes = [0u8; 1 << 16];
+                loop {
+                    match file.read(&mut bytes) {
+                        Ok(0) => break,
+                        Ok(count) => self.decoder.push(&bytes[.. count]),
+                        Err(error) if error.kind() == ErrorKind::Interrupted => { },
+                        Err(error) => return Err(self.context(error)),
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<D, T, R> FileSource<D, T, R>
+    where
+        D: for<'a> Deserialize<'a>,
+        T: for<'a> Deserialize<'a>,
+        R: for<'a> Deserialize<'a>,
+    {
+        /// Decodes the next message from the decoder, if one is available.
+        fn decode(&mut self) -> Result<Option<Message<D, T, R>>> {
+            self.decoder.decode().map_err(|error| self.context(error))
+        }
+
+        /// Reads the next available message, moving through the sequence of files as required.
+        fn advance(&mut self) -> Result<Option<Message<D, T, R>>> {
+            loop {
+                if self.file.is_none() {
+                    match File::open(file_path(&self.directory, &self.prefix, self.index)) {
+                        Ok(file) => { self.file = Some(file); }
+                        Err(error) if error.kind() == ErrorKind::NotFound => { return Ok(None); }
+                        Err(error) => { return Err(self.context(error)); }
+                    }
+                }
+                if let Some(message) = self.decode()? {
+                    return Ok(Some(message));
+                }
+                self.read_available()?;
+                if let Some(message) = self.decode()? {
+                    return Ok(Some(message));
+                }
+                // Move to the next file only once it exists, as the writer completes
+                // a file before creating its successor. There may be bytes written to
+                // the current file since we last read, and we must read those first.
+                // Any remaining bytes are from a failed write, retried in a later file.
+                if file_path(&self.directory, &self.prefix, self.index + 1).exists() {
+                    self.read_available()?;
+                    if let Some(message) = self.decode()? {
+                        return Ok(Some(message));
+                    }
+                    self.index += 1;
+                    self.file = None;
+                    self.decoder = Decoder::new();
+                }
+                else {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    impl<D, T, R> Iterator for FileSource<D, T, R>
+    where
+        D: for<'a> Deserialize<'a>,
+        T: for<'a> Deserialize<'a>,
+        R: for<'a> Deserialize<'a>,
+    {
+        type Item = Message<D, T, R>;
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.error.is_some() {
+                return None;
+            }
+            match self.advance() {
+                Ok(message) => message,
+                Err(error) => {
+                    self.error = Some(error);
+                    None
+                },
+            }
+        }
+    }
+}
+
+/// Framed messages exchanged over TCP connections.
+///
+/// Each connection carries one framed byte stream (as described in `framing`) from a sink to a
+/// source. Establishing connections is left to the user, for example with a `TcpListener` at one
+/// end and `TcpStream::connect` at the other. Both ends put their sockets in non-blocking mode.
+#[cfg(feature = "bincode")]
+pub mod tcp {
+
+    use std::hash::Hash;
+    use std::io::{Error, ErrorKind, Read, Result, Write};
+    use std::marker::PhantomData;
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+    use timely::dataflow::Scope;
+    use timely::progress::Timestamp;
+
+    use crate::{lattice::Lattice, ExchangeData};
+    use super::{Message, Writer};
+    use super::framing::{self, Decoder};
+
+    /// Constructs a stream of updates from the messages received on `stream`.
+    ///
+    /// The source checks the connection for new messages every `interval`. As with
+    /// `source::build`, the stream continues until the returned token is dropped.
+    pub fn create_source<G, D, T, R>(scope: G, stream: TcpStream, interval: Duration) -> Result<super::SourceOutput<G, D, T, R>>
+    where
+        G: Scope<Timestamp = T>,
+        D: ExchangeData + Hash + for<'a> Deserialize<'a>,
+        T: ExchangeData + Hash + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        R: ExchangeData + Hash + for<'a> Deserialize<'a>,
+    {
+        let source = TcpSource::new(stream)?;
+        Ok(super::source::build_polled(scope, interval, |_activator| source))
+    }
+
+    /// A `Writer` that sends framed messages over a TCP connection.
+    ///
+    /// If the connection fails, the sink discards this and all further messages,
+    /// and reports the reason through `error()`.
+    pub struct TcpSink {
+        stream: TcpStream,
+        /// Bytes yet to be written to `stream`.
+        pending: Vec<u8>,
+        /// Indicates that the message being offered is already in `pending`.
+        accepted: bool,
+        error: Option<Error>,
+    }
+
+    impl TcpSink {
+        /// Creates a sink sending messages over `stream`.
+        pub fn new(stream: TcpStream) -> Result<Self> {
+            stream.set_nonblocking(true)?;
+            stream.set_nodelay(true)?;
+            let mut pending = Vec::new();
+            framing::encode_header(&mut pending);
+            Ok(Self { stream, pending, accepted: false, error: None })
+        }
+        /// The error that failed the connection, if any.
+        pub fn error(&self) -> Option<&Error> { self.error.as_ref() }
+        /// Records the failure of the connection, and discards unwritten bytes.
+        fn fail(&mut self, error: Error) {
+            self.error = Some(error);
+            self.pending.clear();
+            self.accepted = false;
+        }
+    }
+
+    impl<D: Serialize, T: Serialize, R: Serialize> Writer<Message<D, T, R>> for TcpSink {
+        fn poll(&mut self, item: &Message<D, T, R>) -> Option<Duration> {
+            if self.error.is_some() {
+                return None;
+            }
+            // The sink offers the same message until we accept it, and so we should
+            // only encode the message once, and then work to write all of its bytes.
+            if !self.accepted {
+                framing::encode(item, &mut self.pending);
+                self.accepted = true;
+            }
+            while !self.pending.is_empty() {
+                match self.stream.write(&self.pending) {
+                    Ok(0) => {
+                        self.fail(Error::new(ErrorKind::WriteZero, "CDC connection closed by peer"));
+                        return None;
+                    },
+                    Ok(count) => { self.pending.drain(.. count); },
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => { return Some(Duration::from_millis(1)); },
+                    Err(error) if error.kind() == ErrorKind::Interrupted => { },
+                    Err(error) => {
+                        self.fail(error);
+                        return None;
+                    },
+                }
+            }
+            self.accepted = false;
+            None
+        }
+        fn done(&self) -> bool {
+            self.pending.is_empty()
+        }
+    }
+
+    /// An iterator over the messages received on a TCP connection.
+    ///
+    /// The iterator returns `None` when it has read all available messages, and will return
+    /// further messages as they arrive. The iterator ends once the connection is closed, or
+    /// if it cannot read or decode the connection's bytes, which it reports through `error()`.
+    pub struct TcpSource<D, T, R> {
+        stream: TcpStream,
+        decoder: Decoder,
+        closed: bool,
+        error: Option<Error>,
+        phantom: PhantomData<(D, T, R)>,
+    }
+
+    impl<D, T, R> TcpSource<D, T, R> {
+        /// Creates a source receiving messages from `stream`.
+        pub fn new(stream: TcpStream) -> Result<Self> {
+            stream.set_nonblocking(true)?;
+            Ok(Self {
+                stream,
+                decoder: Decoder::new(),
+                closed: false,
+                error: None,
+                phantom: PhantomData,
+            })
+        }
+        /// Indicates that the connection has been closed by the sink, or has failed.
+        pub fn closed(&self) -> bool { self.closed }
+        /// The error that ended the iterator, if any.
+        pub fn error(&self) -> Option<&Error> { self.error.as_ref() }
+        /// Records the failure of the connection.
+        fn fail(&mut self, error: Error) {
+            self.error = Some(error);
+            self.closed = true;
+        }
+    }
+
+    impl<D, T, R> Iterator for TcpSource<D, T, R>
+    where
+        D: for<'a> Deserialize<'a>,
+        T: for<'a> Deserialize<'a>,
+        R: for<'a> Deserialize<'a>,
+    {
+        type Item = Message<D, T, R>;
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.error.is_some() {
+                return None;
+            }
+            let mut bytes = [0u8; 1 << 16];
+            loop {
+                match self.decoder.decode() {
+                    Ok(Some(message)) => { return Some(message); },
+                    Ok(None) => { },
+                    Err(error) => {
+                        self.fail(error);
+                        return None;
+                    },
+                }
+                if self.closed {
+                    if !self.decoder.is_empty() {
+                        self.fail(Error::new(ErrorKind::UnexpectedEof, "CDC connection closed within a message"));
+                    }
+                    return None;
+                }
+                match self.stream.read(&mut bytes) {
+                    Ok(0) => { self.closed = true; },
+                    Ok(count) => { self.decoder.push(&bytes[.. count]); },
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => { return None; },
+                    Err(error) if error.kind() == ErrorKind::Interrupted => { },
+                    Err(error) => {
+                        self.fail(error);
+                        return None;
+                    },
+                }
+            }
+        }
+    }
+}
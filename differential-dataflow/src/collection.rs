@@ -581,6 +581,61 @@ impl<G: Scope, D, R, C: Container+Data> Collection<Child<'_, G, G::Timestamp>, D
 
 /// Methods requiring an Abelian difference, to support negation.
 impl<G: Scope, D: Clone+'static, R: Abelian+'static> Collection<G, D, R> where G::Timestamp: Data {
+    /// Restricts each record to an interval of times supplied by a function.
+    ///
+    /// The function `func` indicates for each record the times `(valid_from, valid_until)` at which the
+    /// record should be introduced and then retracted. Each update at time `t` is introduced at time
+    /// `t.join(valid_from)` and retracted at `t.join(valid_from).join(valid_until)`, which for partially
+    /// ordered times (e.g. `Product` times) means the record is present at those times in advance of both
+    /// `t` and `valid_from`, but not in advance of `valid_until`. Records whose interval is empty, because
+    /// `valid_until` is less or equal to `valid_from`, are never present.
+    ///
+    /// As with `delay`, the updates are held back until their times are reached, which means that this
+    /// operator retains the retractions of records until they expire.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use timely::dataflow::operators::ToStream;
+    /// use differential_dataflow::AsCollection;
+    /// use differential_dataflow::input::Input;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     // Each number is valid from time `1` until the time of its value.
+    ///     let data = scope.new_collection_from(1 .. 4).1;
+    ///     let expiring = data.temporal_filter(|x| (1, *x as u64));
+    ///
+    ///     let expected =
+    ///     vec![(2, 1, 1), (2, 2, -1), (3, 1, 1), (3, 3, -1)]
+    ///         .into_iter()
+    ///         .to_stream(scope)
+    ///         .as_collection();
+    ///
+    ///     expiring.assert_eq(&expected);
+    /// });
+    /// ```
+    pub fn temporal_filter<F>(&self, mut func: F) -> Collection<G, D, R>
+    where
+        G::Timestamp: Lattice,
+        F: FnMut(&D) -> (G::Timestamp, G::Timestamp) + 'static,
+    {
+        self.inner
+            .flat_map(move |(data, time, diff)| {
+                let (valid_from, valid_until) = func(&data);
+                let lower = time.join(&valid_from);
+                let upper = lower.join(&valid_until);
+                // Records with empty intervals produce no updates, and are not cloned.
+                let updates = (lower != upper).then(|| {
+                    let mut retraction = diff.clone();
+                    retraction.negate();
+                    [(data.clone(), lower, diff), (data, upper, retraction)]
+                });
+                updates.into_iter().flatten()
+            })
+            .delay(|(_, time, _), _| time.clone())
+            .as_collection()
+    }
     /// Assert if the collections are ever different.
     ///
     /// Because this is a dataflow fragment, the test is only applied as the computation is run. If the computation
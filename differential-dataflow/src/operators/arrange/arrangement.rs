@@ -399,6 +399,31 @@ where
 /// It uses the supplied parallelization contract to distribute the data, which does not need to
 /// be consistently by key (though this is the most common).
 pub fn arrange_core<G, P, Ba, Bu, Tr>(stream: &StreamCore<G, Ba::Input>, pact: P, name: &str) -> Arranged<G, TraceAgent<Tr>>
+where
+    G: Scope,
+    G::Timestamp: Lattice,
+    P: ParallelizationContract<G::Timestamp, Ba::Input>,
+    Ba: Batcher<Time=G::Timestamp> + 'static,
+    Ba::Input: Container + Clone + 'static,
+    Bu: Builder<Time=G::Timestamp, Input=Ba::Output, Output = Tr::Batch>,
+    Tr: Trace<Time=G::Timestamp>+'static,
+    Tr::Batch: Batch,
+{
+    arrange_core_from::<_, _, Ba, Bu, _>(stream, pact, name, None)
+}
+
+/// Arranges a stream of updates by a key, starting from an initial batch of updates.
+///
+/// This operator behaves as `arrange_core`, except that when `initial` is supplied its batch is
+/// introduced into the trace and sent along the output stream before any other updates. The batch
+/// must have a lower bound of the minimal time and a distinct upper bound, and the input stream
+/// should only contain updates at times greater or equal to that upper bound. The operator will
+/// not produce further batches until its input frontier reaches the upper bound of `initial`.
+///
+/// Updates in `initial` are not exchanged, and should already be on the worker that the parallelization
+/// contract would have routed them to. This is the case for batches extracted from a trace arranged with
+/// the same contract and number of workers.
+pub fn arrange_core_from<G, P, Ba, Bu, Tr>(stream: &StreamCore<G, Ba::Input>, pact: P, name: &str, initial: Option<Tr::Batch>) -> Arranged<G, TraceAgent<Tr>>
 where
     G: Scope,
     G::Timestamp: Lattice,
@@ -430,7 +455,7 @@ where
     let reader_ref = &mut reader;
     let scope = stream.scope();
 
-    let stream = stream.unary_frontier(pact, name, move |_capability, info| {
+    let stream = stream.unary_frontier(pact, name, move |capability, info| {
 
         // Acquire a logger for arrange events.
         let logger = {
@@ -458,8 +483,23 @@ where
         // Initialize to the minimal input frontier.
         let mut prev_frontier = Antichain::from_elem(<G::Timestamp as Timestamp>::minimum());
 
+        // Introduce any initial batch, and retain the capability to send it.
+        // Until the input frontier reaches the upper of the batch, the frontier trails it.
+        let mut seeded = initial.is_some();
+        let mut initial = initial.map(|batch| {
+            // Advance the batcher past the times the batch describes.
+            let _batch = batcher.seal::<Bu>(batch.upper().clone());
+            writer.insert(batch.clone(), Some(<G::Timestamp as Timestamp>::minimum()));
+            prev_frontier.clone_from(batch.upper());
+            (capability, batch)
+        });
+
         move |input, output| {
 
+            if let Some((capability, batch)) = initial.take() {
+                output.session(&capability).give(batch);
+            }
+
             // As we receive data, we need to (i) stash the data and (ii) keep *enough* capabilities.
             // We don't have to keep all capabilities, but we need to be able to form output messages
             // when we realize that time intervals are complete.
@@ -474,13 +514,15 @@ where
             // must pretend to process the frontier advances one element at a time, batching
             // and sending smaller bites than we might have otherwise done.
 
-            // Assert that the frontier never regresses.
-            assert!(PartialOrder::less_equal(&prev_frontier.borrow(), &input.frontier().frontier()));
+            // Assert that the frontier never regresses, other than to trail an initial batch.
+            let advanced = PartialOrder::less_equal(&prev_frontier.borrow(), &input.frontier().frontier());
+            assert!(seeded || advanced);
+            seeded = seeded && !advanced;
 
             // Test to see if strict progress has occurred, which happens whenever the new
             // frontier isn't equal to the previous. It is only in this case that we have any
             // data processing to do.
-            if prev_frontier.borrow() != input.frontier().frontier() {
+            if advanced && prev_frontier.borrow() != input.frontier().frontier() {
                 // There are two cases to handle with some care:
                 //
                 // 1. If any held capabilities are not in advance of the new input frontier,
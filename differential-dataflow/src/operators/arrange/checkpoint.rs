@@ -0,0 +1,201 @@
+//! Checkpoints of arranged collections, and arrangements resumed from them.
+//!
+//! A `Checkpoint` records the accumulated contents of a trace, with times advanced to the trace's
+//! logical compaction frontier, along with a `Description` of the lower, upper, and since frontiers
+//! of the recorded updates. Checkpoints can be written to and read from files (with the `bincode`
+//! feature), and a new dataflow can resume an arrangement from a checkpoint with
+//! `arrange_from_checkpoint`, rather than replay the history of updates that produced it.
+//!
+//! Each worker holds only its own portion of an arrangement, and so each worker should write and
+//! read its own checkpoint (e.g. by including `worker.index()` in the file name). A checkpoint must
+//! be resumed with the same number of workers as produced it, so that keys land on the same workers.
+//!
+//! # Examples
+//!
+//! ```
+//! use timely::progress::Antichain;
+//! use differential_dataflow::input::Input;
+//! use differential_dataflow::operators::arrange::ArrangeByKey;
+//! use differential_dataflow::operators::arrange::checkpoint::{self, Checkpoint};
+//! use differential_dataflow::trace::TraceReader;
+//! use differential_dataflow::trace::implementations::{ValBatcher, ValBuilder, ValSpine};
+//!
+//! # #[cfg(feature = "bincode")]
+//! ::timely::execute(::timely::Config::thread(), |worker| {
+//!
+//!     let path = std::env::temp_dir().join(format!("checkpoint-doc-{}.bin", worker.index()));
+//!
+//!     // Arrange some data and write its checkpoint once time `0` is complete.
+//!     let (mut input, mut trace) = worker.dataflow::<u32,_,_>(|scope| {
+//!         let (input, data) = scope.new_collection_from(vec![(0u32, 1u32), (2, 3)]);
+//!         (input, data.arrange_by_key().trace)
+//!     });
+//!     input.advance_to(1); input.flush();
+//!     let mut upper = Antichain::new();
+//!     while { trace.read_upper(&mut upper); upper.less_equal(&0) } { worker.step(); }
+//!     Checkpoint::<u32, u32, u32, isize>::from_trace(&mut trace).write(&path).unwrap();
+//!
+//!     // Resume the arrangement in a new dataflow, and continue to update it.
+//!     let checkpoint = Checkpoint::<u32, u32, u32, isize>::read(&path).unwrap();
+//!     assert_eq!(checkpoint.updates, vec![((0, 1), 0, 1), ((2, 3), 0, 1)]);
+//!     let resume = checkpoint.description.upper().elements()[0];
+//!     worker.dataflow::<u32,_,_>(|scope| {
+//!         let (mut input, data) = scope.new_collection::<(u32, u32), isize>();
+//!         input.advance_to(resume);
+//!         input.insert((4, 5));
+//!         checkpoint::arrange_from_checkpoint::<_, _, _, _, ValBatcher<_,_,_,_>, ValBuilder<_,_,_,_>, ValSpine<_,_,_,_>>(&data, checkpoint);
+//!     });
+//!
+//!     let _ = std::fs::remove_file(&path);
+//! }).unwrap();
+//! ```
+
+#[cfg(feature = "bincode")]
+use std::fs::File;
+#[cfg(feature = "bincode")]
+use std::io::{BufReader, BufWriter, Write};
+#[cfg(feature = "bincode")]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use timely::container::PushInto;
+use timely::dataflow::Scope;
+use timely::dataflow::channels::pact::Exchange;
+use timely::progress::{Antichain, Timestamp};
+
+use crate::{Collection, ExchangeData, Hashable, IntoOwned};
+use crate::difference::Semigroup;
+use crate::lattice::Lattice;
+use crate::trace::{Batch, Batcher, Builder, Cursor, Description, Trace, TraceReader};
+
+use super::{Arranged, TraceAgent};
+use super::arrangement::arrange_core_from;
+
+/// The version of the checkpoint file format.
+///
+/// Files record this version first, and reading a file with a different version is an error.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// The accumulated contents of a trace, and the frontiers that describe them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint<K, V, T, R> {
+    /// The lower, upper, and since frontiers of the updates.
+    ///
+    /// The lower frontier is always the minimal time, and update times have been advanced by `since`.
+    pub description: Description<T>,
+    /// Consolidated updates, sorted by data and then time.
+    pub updates: Vec<((K, V), T, R)>,
+}
+
+impl<K, V, T, R> Checkpoint<K, V, T, R>
+where
+    K: Ord + Clone,
+    V: Ord + Clone,
+    T: Timestamp + Lattice,
+    R: Semigroup,
+{
+    /// Records the contents of `trace`.
+    ///
+    /// The recorded updates are those of batches the trace has completed, and the upper frontier
+    /// of the checkpoint is the upper frontier of those batches. Update times are advanced by the
+    /// trace's logical compaction frontier, which becomes the `since` frontier of the checkpoint.
+    pub fn from_trace<Tr>(trace: &mut Tr) -> Self
+    where
+        Tr: TraceReader<Time=T, Diff=R>,
+        for<'a> Tr::Key<'a>: IntoOwned<'a, Owned = K>,
+        for<'a> Tr::Val<'a>: IntoOwned<'a, Owned = V>,
+    {
+        let since = trace.get_logical_compaction().to_owned();
+        let mut upper = Antichain::new();
+        trace.read_upper(&mut upper);
+
+        let mut updates = Vec::new();
+        let (mut cursor, storage) = trace.cursor();
+        while let Some(key) = cursor.get_key(&storage) {
+            while let Some(val) = cursor.get_val(&storage) {
+                cursor.map_times(&storage, |time, diff| {
+                    let mut time = time.into_owned();
+                    time.advance_by(since.borrow());
+                    updates.push(((key.into_owned(), val.into_owned()), time, diff.into_owned()));
+                });
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        crate::consolidation::consolidate_updates(&mut updates);
+
+        Checkpoint {
+            description: Description::new(Antichain::from_elem(T::minimum()), upper, since),
+            updates,
+        }
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<K, V, T, R> Checkpoint<K, V, T, R>
+where
+    K: Serialize + for<'a> Deserialize<'a>,
+    V: Serialize + for<'a> Deserialize<'a>,
+    T: Serialize + for<'a> Deserialize<'a>,
+    R: Serialize + for<'a> Deserialize<'a>,
+{
+    /// Writes the checkpoint to the file at `path`, replacing any existing file.
+    ///
+    /// The checkpoint is first written to a temporary file which is then renamed,
+    /// so that an interrupted write does not replace a prior checkpoint.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let temp = path.with_extension("partial");
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        bincode::serialize_into(&mut writer, &CHECKPOINT_VERSION).map_err(std::io::Error::other)?;
+        bincode::serialize_into(&mut writer, self).map_err(std::io::Error::other)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        std::fs::rename(&temp, path)
+    }
+
+    /// Reads a checkpoint from the file at `path`.
+    pub fn read<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let version: u32 = bincode::deserialize_from(&mut reader).map_err(std::io::Error::other)?;
+        if version != CHECKPOINT_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("checkpoint version {} does not match expected version {}", version, CHECKPOINT_VERSION),
+            ));
+        }
+        bincode::deserialize_from(&mut reader).map_err(std::io::Error::other)
+    }
+}
+
+/// Arranges `collection` starting from the contents of `checkpoint`.
+///
+/// The resulting arrangement contains the updates of `checkpoint` and of `collection`, where the
+/// updates of `collection` must be at times greater or equal to the upper frontier of the checkpoint.
+/// The arrangement produces no further batches until the frontier of `collection` reaches this upper
+/// frontier, and thereafter it proceeds as if it had been arranged by `arrange` all along.
+pub fn arrange_from_checkpoint<G, K, V, R, Ba, Bu, Tr>(collection: &Collection<G, (K, V), R>, checkpoint: Checkpoint<K, V, G::Timestamp, R>) -> Arranged<G, TraceAgent<Tr>>
+where
+    G: Scope,
+    G::Timestamp: Lattice,
+    K: ExchangeData + Hashable,
+    V: ExchangeData,
+    R: ExchangeData + Semigroup,
+    Ba: Batcher<Input=Vec<((K, V), G::Timestamp, R)>, Time=G::Timestamp> + 'static,
+    Bu: Builder<Time=G::Timestamp, Input=Ba::Output, Output = Tr::Batch>,
+    Bu::Input: Default + PushInto<((K, V), G::Timestamp, R)>,
+    Tr: Trace<Time=G::Timestamp> + 'static,
+    Tr::Batch: Batch,
+{
+    let initial = if checkpoint.description.lower() != checkpoint.description.upper() {
+        let mut chunk = Bu::Input::default();
+        for update in checkpoint.updates {
+            chunk.push_into(update);
+        }
+        Some(Bu::seal(&mut vec![chunk], checkpoint.description))
+    }
+    else { None };
+
+    let exchange = Exchange::new(move |update: &((K,V),G::Timestamp,R)| (update.0).0.hashed().into());
+    arrange_core_from::<_, _, Ba, Bu, _>(&collection.inner, exchange, "ArrangeFromCheckpoint", initial)
+}
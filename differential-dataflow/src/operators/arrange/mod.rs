@@ -68,6 +68,7 @@ pub mod agent;
 pub mod arrangement;
 
 pub mod upsert;
+pub mod checkpoint;
 
 pub use self::writer::TraceWriter;
 pub use self::agent::{TraceAgent, ShutdownButton};
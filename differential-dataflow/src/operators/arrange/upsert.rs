@@ -13,12 +13,27 @@
 //!
 //! # Notes
 //!
-//! Upserts currently only work with totally ordered timestamps.
+//! Upserts work with partially ordered timestamps, including those of iterative scopes.
+//! Each upsert determines the value of its key at its time, and at later times until
+//! another upsert applies. For totally ordered timestamps this is simply the most recent
+//! upsert. Times are processed in the order of the `Ord` implementation of the timestamp,
+//! which must extend the partial order, as it does for totally ordered times, and for
+//! `Product` and tuples of such times.
+//!
+//! Upserts at incomparable times are concurrent, and are resolved deterministically where
+//! their changes meet, at the join of their times, in favor of the greatest value either
+//! introduced. For example, with `Product` times the upserts of values `1` at `(0, 1)` and
+//! `2` at `(1, 0)` each determine the value at their own time, and from `(1, 1)` onward the
+//! value is `2`. An upsert that leaves the value unchanged introduces no value.
 //!
 //! In the case of ties in timestamps (concurrent updates to the same key) they choose
 //! the *greatest* value according to `Option<Val>` ordering, which will prefer a value
 //! to `None` and choose the greatest value (informally, as if applied in order of value).
 //!
+//! The `arrange_from_upsert_with` variant accepts a closure that merges each upsert with
+//! the existing value, rather than replacing it. This allows upserts to express partial
+//! updates, counters, or last-writer-wins by a version number embedded in the value.
+//!
 //! If the same value is repeated, no change will occur in the output. That may make this
 //! operator effective at determining the difference between collections of keyed values,
 //! but note that it will not notice the absence of keys in a collection.
@@ -98,9 +113,9 @@
 //! }).unwrap();
 //! ```
 
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use timely::order::{PartialOrder, TotalOrder};
+use timely::order::PartialOrder;
 use timely::dataflow::{Scope, Stream};
 use timely::dataflow::operators::generic::Operator;
 use timely::dataflow::channels::pact::Exchange;
@@ -108,6 +123,7 @@ use timely::progress::Timestamp;
 use timely::progress::Antichain;
 use timely::dataflow::operators::Capability;
 
+use crate::lattice::Lattice;
 use crate::operators::arrange::arrangement::Arranged;
 use crate::trace::{Builder, Description};
 use crate::trace::{self, Trace, TraceReader, Batch, Cursor};
@@ -122,9 +138,9 @@ use super::TraceAgent;
 /// value in sequence either replaces or removes the existing value, should it
 /// exist.
 ///
-/// This method is only implemented for totally ordered times, as we do not yet
-/// understand what a "sequence" of upserts would mean for partially ordered
-/// timestamps.
+/// For partially ordered times, concurrent upserts at incomparable times are
+/// resolved where they meet in favor of the greatest value, as described in the
+/// module documentation.
 pub fn arrange_from_upsert<G, K, V, Bu, Tr>(
     stream: &Stream<G, (K, Option<V>, G::Timestamp)>,
     name: &str,
@@ -136,8 +152,83 @@ where
     K: ExchangeData+Hashable+std::hash::Hash,
     V: ExchangeData,
     for<'a> Tr::Val<'a> : IntoOwned<'a, Owned = V>,
-    Tr::Time: TotalOrder+ExchangeData,
+    Tr::Time: Lattice+ExchangeData,
+    Tr::Batch: Batch,
+    Bu: Builder<Time=G::Timestamp, Input = Vec<((K, V), Tr::Time, Tr::Diff)>, Output = Tr::Batch>,
+{
+    upsert_core::<_, _, _, _, Bu, _>(stream, name, true, |_key, _old, new| new)
+}
+
+/// Arrange data from a stream of keyed upserts, merging each into the existing value.
+///
+/// The input should be a stream of timestamped pairs of Key and Option<Val>.
+/// The contents of the collection are defined key-by-key, where each optional
+/// value in sequence is presented to `merge` along with the existing value, if
+/// it exists, and the result becomes the value (or removes it, if `None`).
+///
+/// Upserts are applied in the order of their times, and upserts at the same time
+/// in the order of their values. For partially ordered times, concurrent upserts
+/// are not merged with each other: where they meet the value is the greatest of
+/// those they produce, as for `arrange_from_upsert`.
+///
+/// # Examples
+///
+/// ```
+/// use timely::dataflow::operators::ToStream;
+/// use differential_dataflow::operators::arrange::upsert;
+/// use differential_dataflow::trace::implementations::{ValBuilder, ValSpine};
+///
+/// ::timely::example(|scope| {
+///
+///     // Accumulate counts for each key, and reset them with `None`.
+///     let upserts = vec![(0u64, Some(1u64), 0u64), (0, Some(2), 1), (0, None, 2), (0, Some(3), 3)].into_iter().to_stream(scope);
+///     upsert::arrange_from_upsert_with::<_, _, _, _, ValBuilder<u64, u64, _, _>, ValSpine<u64, u64, _, _>>(&upserts, "Counts", |_key, old, new| {
+///         new.map(|new| old.copied().unwrap_or(0) + new)
+///     });
+/// });
+/// ```
+pub fn arrange_from_upsert_with<G, K, V, F, Bu, Tr>(
+    stream: &Stream<G, (K, Option<V>, G::Timestamp)>,
+    name: &str,
+    merge: F,
+) -> Arranged<G, TraceAgent<Tr>>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    Tr: Trace+TraceReader<Diff=isize>+'static,
+    for<'a> Tr::Key<'a> : IntoOwned<'a, Owned = K>,
+    K: ExchangeData+Hashable+std::hash::Hash,
+    V: ExchangeData,
+    for<'a> Tr::Val<'a> : IntoOwned<'a, Owned = V>,
+    Tr::Time: Lattice+ExchangeData,
+    Tr::Batch: Batch,
+    F: FnMut(&K, Option<&V>, Option<V>)->Option<V>+'static,
+    Bu: Builder<Time=G::Timestamp, Input = Vec<((K, V), Tr::Time, Tr::Diff)>, Output = Tr::Batch>,
+{
+    upsert_core::<_, _, _, _, Bu, _>(stream, name, false, merge)
+}
+
+/// Arranges upserts, either replacing existing values or merging into them with `merge`.
+///
+/// When `replace` is set each upsert replaces the existing value and `merge` is not called.
+///
+/// The operator retains only upserts at incomplete times, and times at which values must be
+/// re-evaluated. Prior values are read from the arrangement itself.
+fn upsert_core<G, K, V, F, Bu, Tr>(
+    stream: &Stream<G, (K, Option<V>, G::Timestamp)>,
+    name: &str,
+    replace: bool,
+    mut merge: F,
+) -> Arranged<G, TraceAgent<Tr>>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    Tr: Trace+TraceReader<Diff=isize>+'static,
+    for<'a> Tr::Key<'a> : IntoOwned<'a, Owned = K>,
+    K: ExchangeData+Hashable+std::hash::Hash,
+    V: ExchangeData,
+    for<'a> Tr::Val<'a> : IntoOwned<'a, Owned = V>,
+    Tr::Time: Lattice+ExchangeData,
     Tr::Batch: Batch,
+    F: FnMut(&K, Option<&V>, Option<V>)->Option<V>+'static,
     Bu: Builder<Time=G::Timestamp, Input = Vec<((K, V), Tr::Time, Tr::Diff)>, Output = Tr::Batch>,
 {
     let mut reader: Option<TraceAgent<Tr>> = None;
@@ -158,7 +249,7 @@ where
                 register.get::<crate::logging::DifferentialEventBuilder>("differential/arrange").map(Into::into)
             };
 
-            // Tracks the lower envelope of times in `priority_queue`.
+            // Tracks the lower envelope of times in `pending_upserts` and `pending_times`.
             let mut capabilities = Antichain::<Capability<G::Timestamp>>::new();
             // Form the trace we will both use internally and publish.
             let activator = Some(stream.scope().activator_for(info.address.clone()));
@@ -175,17 +266,22 @@ where
             // Tracks the input frontier, used to populate the lower bound of new batches.
             let mut prev_frontier = Antichain::from_elem(<G::Timestamp as Timestamp>::minimum());
 
-            // For stashing input upserts, ordered increasing by time (`BinaryHeap` is a max-heap).
-            let mut priority_queue = BinaryHeap::<std::cmp::Reverse<(G::Timestamp, K, Option<V>)>>::new();
+            // For stashing input upserts until the input frontier passes their times, indexed by time.
+            let mut pending_upserts = BTreeMap::<G::Timestamp, Vec<(K, Option<V>)>>::new();
+            // Keys whose values must be re-evaluated at times, once the input frontier passes them.
+            //
+            // For partially ordered times, changes at times `t1` and `t2` may conflict at `t1.join(t2)`,
+            // and this time may not yet be complete when we process `t1` and `t2`.
+            let mut pending_times = BTreeMap::<G::Timestamp, Vec<K>>::new();
             let mut updates = Vec::new();
 
             move |input, output| {
 
-                // Stash capabilities and associated data (ordered by time).
+                // Stash capabilities and associated data.
                 input.for_each(|cap, data| {
                     capabilities.insert(cap.retain());
                     for (key, val, time) in data.drain(..) {
-                        priority_queue.push(std::cmp::Reverse((time, key, val)))
+                        pending_upserts.entry(time).or_default().push((key, val));
                     }
                 });
 
@@ -218,64 +314,118 @@ where
                                     upper.insert(other_capability.time().clone());
                                 }
 
-                                // Extract upserts available to process as of this `upper`.
-                                let mut to_process = HashMap::new();
-                                while priority_queue.peek().map(|std::cmp::Reverse((t,_k,_v))| !upper.less_equal(t)).unwrap_or(false) {
-                                    let std::cmp::Reverse((time, key, val)) = priority_queue.pop().expect("Priority queue just ensured non-empty");
-                                    to_process.entry(key).or_insert(Vec::new()).push((time, std::cmp::Reverse(val)));
+                                // Extract upserts and re-evaluation times available to process as of this `upper`.
+                                let mut to_process = HashMap::<K, (Vec<(G::Timestamp, Option<V>)>, Vec<G::Timestamp>)>::new();
+                                for (time, list) in extract_ready(&mut pending_upserts, &upper) {
+                                    for (key, val) in list {
+                                        to_process.entry(key).or_default().0.push((time.clone(), val));
+                                    }
                                 }
-                                // Reduce the allocation behind the priority queue if it is presently excessive.
-                                // A factor of four is used to avoid repeated doubling and shrinking.
-                                // TODO: if the queue were a sequence of geometrically sized allocations, we could
-                                // shed the additional capacity without copying any data.
-                                if priority_queue.capacity() > 4 * priority_queue.len() {
-                                    priority_queue.shrink_to_fit();
+                                for (time, keys) in extract_ready(&mut pending_times, &upper) {
+                                    for key in keys {
+                                        to_process.entry(key).or_default().1.push(time.clone());
+                                    }
                                 }
 
-                                // Put (key, list) into key order, to match cursor enumeration.
+                                // Put (key, lists) into key order, to match cursor enumeration.
                                 let mut to_process = to_process.into_iter().collect::<Vec<_>>();
-                                to_process.sort();
+                                to_process.sort_by(|x, y| x.0.cmp(&y.0));
 
                                 // Prepare a cursor to the existing arrangement, and a batch builder for
                                 // new stuff that we add.
                                 let (mut trace_cursor, trace_storage) = reader_local.cursor();
                                 let mut builder = Bu::new();
-                                for (key, mut list) in to_process.drain(..) {
+                                for (key, (mut list, times)) in to_process.drain(..) {
 
-                                    // The prior value associated with the key.
-                                    let mut prev_value: Option<V> = None;
+                                    // The existing updates associated with the key, and those we produce.
+                                    let mut state = Vec::new();
 
                                     // Attempt to find the key in the trace.
                                     trace_cursor.seek_key(&trace_storage, IntoOwned::borrow_as(&key));
                                     if trace_cursor.get_key(&trace_storage).map(|k| k.eq(&IntoOwned::borrow_as(&key))).unwrap_or(false) {
-                                        // Determine the prior value associated with the key.
                                         while let Some(val) = trace_cursor.get_val(&trace_storage) {
-                                            let mut count = 0;
-                                            trace_cursor.map_times(&trace_storage, |_time, diff| count += diff.into_owned());
-                                            assert!(count == 0 || count == 1);
-                                            if count == 1 {
-                                                assert!(prev_value.is_none());
-                                                prev_value = Some(val.into_owned());
-                                            }
+                                            trace_cursor.map_times(&trace_storage, |time, diff| {
+                                                state.push((val.into_owned(), time.into_owned(), diff.into_owned()));
+                                            });
                                             trace_cursor.step_val(&trace_storage);
                                         }
                                         trace_cursor.step_key(&trace_storage);
                                     }
 
-                                    // Sort the list of upserts to `key` by their time, suppress multiple updates.
+                                    // Upserts apply in order of time, and then value. We visit times in
+                                    // the same order, which extends the partial order on times.
                                     list.sort();
-                                    list.dedup_by(|(t1,_), (t2,_)| t1 == t2);
-                                    for (time, std::cmp::Reverse(next)) in list {
-                                        if prev_value != next {
-                                            if let Some(prev) = prev_value {
-                                                updates.push(((key.clone(), prev), time.clone(), -1));
+                                    let mut times = times.into_iter().chain(list.iter().map(|(t,_)| t.clone())).collect::<BTreeSet<_>>();
+                                    let mut list = list.into_iter().peekable();
+
+                                    // The existing updates only need to be distinguished at times we process, all of
+                                    // which are in advance of `prev_frontier`. If their times and those we visit form
+                                    // a chain, as they always do for totally ordered times, each visited time follows
+                                    // all updates and the prior value is simply the accumulation of all updates.
+                                    let mut existing = state.iter().map(|(_,t,_)| t.clone()).collect::<Vec<G::Timestamp>>();
+                                    for time in existing.iter_mut() {
+                                        time.advance_by(prev_frontier.borrow());
+                                    }
+                                    existing.sort();
+                                    existing.dedup();
+                                    let visited = || existing.iter().chain(times.iter());
+                                    let chain = visited().zip(visited().skip(1)).all(|(t1, t2)| t1.less_equal(t2));
+                                    let mut accumulated = state.iter().map(|(v,_,d)| (v.clone(), *d)).collect::<Vec<_>>();
+                                    crate::consolidation::consolidate(&mut accumulated);
+
+                                    while let Some(time) = times.pop_first() {
+
+                                        // Retract the accumulated values at `time`, the greatest of which is the prior value.
+                                        let mut changes = if chain {
+                                            accumulated.iter().map(|(v,d)| (v.clone(), -d)).collect::<Vec<_>>()
+                                        }
+                                        else {
+                                            state
+                                                .iter()
+                                                .filter(|(_,t,_)| t.less_equal(&time))
+                                                .map(|(v,_,d)| (v.clone(), -d))
+                                                .collect::<Vec<_>>()
+                                        };
+                                        crate::consolidation::consolidate(&mut changes);
+                                        let mut next = changes.iter().rev().find(|(_,d)| *d < 0).map(|(v,_)| v.clone());
+
+                                        // Apply the upserts at `time`, and introduce the resulting value.
+                                        while let Some((_, val)) = list.next_if(|(t,_)| t == &time) {
+                                            next = if replace { val } else { merge(&key, next.as_ref(), val) };
+                                        }
+                                        changes.extend(next.clone().map(|v| (v, 1)));
+                                        crate::consolidation::consolidate(&mut changes);
+
+                                        if !changes.is_empty() {
+                                            if chain {
+                                                // The value at `time` is the value at all later times we visit.
+                                                accumulated.clear();
+                                                accumulated.extend(next.map(|v| (v, 1)));
+                                                for (val, diff) in changes {
+                                                    updates.push(((key.clone(), val), time.clone(), diff));
+                                                }
+                                                continue;
+                                            }
+                                            // Changes at `time` may conflict with changes at incomparable times where they
+                                            // meet, and the values there must be re-evaluated.
+                                            for (_, t, _) in state.iter() {
+                                                if !t.less_equal(&time) && !time.less_equal(t) {
+                                                    let join = time.join(t);
+                                                    if upper.less_equal(&join) {
+                                                        pending_times.entry(join).or_default().push(key.clone());
+                                                    }
+                                                    else {
+                                                        times.insert(join);
+                                                    }
+                                                }
                                             }
-                                            if let Some(next) = next.as_ref() {
-                                                updates.push(((key.clone(), next.clone()), time.clone(), 1));
+                                            for (val, diff) in changes {
+                                                updates.push(((key.clone(), val.clone()), time.clone(), diff));
+                                                state.push((val, time.clone(), diff));
                                             }
-                                            prev_value = next;
                                         }
                                     }
+
                                     // Must insert updates in (key, val, time) order.
                                     updates.sort();
                                     builder.push(&mut updates);
@@ -291,12 +441,17 @@ where
                         }
 
                         // Having extracted and sent batches between each capability and the input frontier,
-                        // we should downgrade all capabilities to match the batcher's lower update frontier.
+                        // we should downgrade all capabilities to match the lower frontier of pending times.
                         // This may involve discarding capabilities, which is fine as any new updates arrive
                         // in messages with new capabilities.
 
+                        let mut lower = Antichain::new();
+                        for time in pending_upserts.keys().chain(pending_times.keys()) {
+                            lower.insert(time.clone());
+                        }
+
                         let mut new_capabilities = Antichain::new();
-                        if let Some(std::cmp::Reverse((time, _, _))) = priority_queue.peek() {
+                        for time in lower.elements().iter() {
                             if let Some(capability) = capabilities.elements().iter().find(|c| c.time().less_equal(time)) {
                                 new_capabilities.insert(capability.delayed(time));
                             }
@@ -329,3 +484,19 @@ where
     Arranged { stream, trace: reader.unwrap() }
 
 }
+
+/// Removes and returns the entries of `pending` at times not greater or equal to any element of `upper`.
+///
+/// Times less than the least element of `upper` are removed without further comparison, as `Ord`
+/// extends the partial order; for totally ordered times these are exactly the times removed.
+fn extract_ready<T: Timestamp, D>(pending: &mut BTreeMap<T, D>, upper: &Antichain<T>) -> Vec<(T, D)> {
+    let mut later = match upper.elements().iter().min() {
+        Some(least) => pending.split_off(least),
+        None => BTreeMap::new(),
+    };
+    let mut ready = std::mem::take(pending).into_iter().collect::<Vec<_>>();
+    let times = later.keys().filter(|time| !upper.less_equal(time)).cloned().collect::<Vec<_>>();
+    ready.extend(times.into_iter().map(|time| later.remove_entry(&time).expect("time just found")));
+    *pending = later;
+    ready
+}
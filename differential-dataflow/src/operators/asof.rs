@@ -0,0 +1,336 @@
+//! Matches records with the most recent record of another collection, as of an ordering key.
+//!
+//! The `as_of_join` operator pairs each record of one collection with the record of another
+//! collection with the same key and the greatest ordering key at or before its own, for example
+//! each trade with the most recent quote for its symbol as of the time of the trade.
+//!
+//! A direct implementation with `reduce` would present all records of a key to the reduction logic
+//! whenever any of them changes. Instead, the operator arranges both collections by key, with values
+//! ordered by their ordering keys, and finds the match for a record by seeking in the arrangement of
+//! the other collection to the record's ordering key. A change to a record of the first collection
+//! requires one such lookup, and a change to a record of the second collection requires lookups only
+//! for the records of the first collection whose match it may change, those between its ordering key
+//! and the next ordering key of the second collection.
+//!
+//! The operator evaluates its output one timestamp at a time, and requires totally ordered timestamps.
+
+use std::cmp::Reverse;
+
+use timely::order::TotalOrder;
+use timely::progress::Antichain;
+use timely::dataflow::Scope;
+use timely::dataflow::channels::pact::Pipeline;
+use timely::dataflow::operators::{Capability, Operator};
+
+use crate::{AsCollection, Collection, ExchangeData, Hashable};
+use crate::lattice::Lattice;
+use crate::operators::arrange::{Arranged, ArrangeByKey, TraceAgent};
+use crate::trace::{BatchReader, Cursor, TraceReader};
+use crate::trace::implementations::ValSpine;
+
+/// Records of the first input, keyed by key with values `(order, Some(val))`.
+///
+/// Values are always `Some`. As `None` orders before any value, `(order, None)` seeks to the first
+/// record with ordering key `order` or greater.
+type Records<G, K, O, V, T> = Arranged<G, TraceAgent<ValSpine<K, (O, Option<V>), T, isize>>>;
+/// Records of the second input, keyed by key with values `(Reverse(order), Some(val))`.
+///
+/// Values are always `Some`. As `None` orders before any value, `(Reverse(order), None)` seeks to the
+/// first record with ordering key `order` or less, in decreasing order of ordering key.
+type Latest<G, K, O, V, T> = Arranged<G, TraceAgent<ValSpine<K, (Reverse<O>, Option<V>), T, isize>>>;
+/// A value of `Latest`, which may be matched by a record.
+type Match<O, V> = (Reverse<O>, Option<V>);
+
+/// Extension trait for the `as_of_join` differential dataflow method.
+pub trait AsOfJoin<G: Scope, K: ExchangeData, V: ExchangeData> where G::Timestamp: Lattice+TotalOrder {
+    /// Matches each `(key, val1)` with the `(key, val2)` with the greatest ordering key at or before that of `val1`.
+    ///
+    /// Ordering keys are produced by `order1` and `order2`, and ties among values of `other` are broken by
+    /// the greatest value. Records of `self` without any record of `other` at or before them produce no output.
+    /// Records of `other` are considered only if they are present, independent of their multiplicity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::AsOfJoin;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     // Trades and quotes, as `(symbol, (time, price))` pairs.
+    ///     let trades = scope.new_collection_from(vec![(0, (5u64, 100)), (0, (12, 101)), (1, (3, 200))]).1;
+    ///     let quotes = scope.new_collection_from(vec![(0, (2u64, 99)), (0, (5, 98)), (0, (10, 102)), (1, (4, 201))]).1;
+    ///
+    ///     let expected = scope.new_collection_from(vec![
+    ///         (0, ((5, 100), (5, 98))),
+    ///         (0, ((12, 101), (10, 102))),
+    ///     ]).1;
+    ///
+    ///     trades
+    ///         .as_of_join(&quotes, |trade| trade.0, |quote| quote.0)
+    ///         .assert_eq(&expected);
+    /// });
+    /// ```
+    fn as_of_join<V2, O, F1, F2>(&self, other: &Collection<G, (K, V2), isize>, order1: F1, order2: F2) -> Collection<G, (K, (V, V2)), isize>
+    where
+        V2: ExchangeData,
+        O: ExchangeData,
+        F1: Fn(&V)->O+'static,
+        F2: Fn(&V2)->O+'static;
+}
+
+impl<G, K, V> AsOfJoin<G, K, V> for Collection<G, (K, V), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+TotalOrder,
+    K: ExchangeData+Hashable,
+    V: ExchangeData,
+{
+    fn as_of_join<V2, O, F1, F2>(&self, other: &Collection<G, (K, V2), isize>, order1: F1, order2: F2) -> Collection<G, (K, (V, V2)), isize>
+    where
+        V2: ExchangeData,
+        O: ExchangeData,
+        F1: Fn(&V)->O+'static,
+        F2: Fn(&V2)->O+'static,
+    {
+        let records = self.map(move |(key, val)| (key, (order1(&val), Some(val)))).arrange_by_key_named("AsOfJoinRecords");
+        let latest = other.map(move |(key, val)| (key, (Reverse(order2(&val)), Some(val)))).arrange_by_key_named("AsOfJoinLatest");
+        as_of_join_traces(&records, &latest)
+    }
+}
+
+/// Joins two arrangements produced by `as_of_join`.
+///
+/// Once both inputs are complete through a timestamp, the operator determines the records of the first input
+/// whose matches may have changed at that timestamp, and for each compares its matches before and after the
+/// changes at the timestamp. Both are read from the traces, which accumulate all updates at or before the
+/// timestamp, by setting aside the changes at the timestamp itself for the former.
+fn as_of_join_traces<G, K, O, V, V2>(
+    records: &Records<G, K, O, V, G::Timestamp>,
+    latest: &Latest<G, K, O, V2, G::Timestamp>,
+) -> Collection<G, (K, (V, V2)), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+TotalOrder,
+    K: ExchangeData,
+    O: ExchangeData,
+    V: ExchangeData,
+    V2: ExchangeData,
+{
+    let mut trace1_option = Some(records.trace.clone());
+    let mut trace2_option = Some(latest.trace.clone());
+
+    records.stream.binary_frontier(&latest.stream, Pipeline, Pipeline, "AsOfJoin", move |_capability, _info| {
+
+        // Holds back output times, and is always less or equal to the times of pending changes.
+        let mut capability: Option<Capability<G::Timestamp>> = None;
+        // Changes to either input, at times that are not yet complete for both inputs.
+        let mut pending1 = Vec::new();
+        let mut pending2 = Vec::new();
+
+        move |input1, input2, output| {
+
+            input1.for_each(|cap, data| {
+                if capability.as_ref().map(|c| cap.time() < c.time()).unwrap_or(true) {
+                    capability = Some(cap.retain());
+                }
+                for batch in data.drain(..) {
+                    let mut cursor = batch.cursor();
+                    while let Some(key) = cursor.get_key(&batch) {
+                        while let Some(val) = cursor.get_val(&batch) {
+                            cursor.map_times(&batch, |time, diff| pending1.push((time.clone(), key.clone(), val.clone(), *diff)));
+                            cursor.step_val(&batch);
+                        }
+                        cursor.step_key(&batch);
+                    }
+                }
+            });
+            input2.for_each(|cap, data| {
+                if capability.as_ref().map(|c| cap.time() < c.time()).unwrap_or(true) {
+                    capability = Some(cap.retain());
+                }
+                for batch in data.drain(..) {
+                    let mut cursor = batch.cursor();
+                    while let Some(key) = cursor.get_key(&batch) {
+                        while let Some(val) = cursor.get_val(&batch) {
+                            cursor.map_times(&batch, |time, diff| pending2.push((time.clone(), key.clone(), val.clone(), *diff)));
+                            cursor.step_val(&batch);
+                        }
+                        cursor.step_key(&batch);
+                    }
+                }
+            });
+
+            // Process changes at times both input frontiers have passed, in order of time and then key.
+            let complete = |time: &G::Timestamp| !input1.frontier().less_equal(time) && !input2.frontier().less_equal(time);
+            let (mut ready1, unready1): (Vec<_>, Vec<_>) = pending1.drain(..).partition(|(time,_,_,_)| complete(time));
+            let (mut ready2, unready2): (Vec<_>, Vec<_>) = pending2.drain(..).partition(|(time,_,_,_)| complete(time));
+            pending1 = unready1;
+            pending2 = unready2;
+
+            if !ready1.is_empty() || !ready2.is_empty() {
+
+                let trace1 = trace1_option.as_mut().expect("`trace1_option` dropped with changes outstanding");
+                let trace2 = trace2_option.as_mut().expect("`trace2_option` dropped with changes outstanding");
+                let (mut cursor1, storage1) = trace1.cursor();
+                let (mut cursor2, storage2) = trace2.cursor();
+
+                ready1.sort();
+                ready2.sort();
+                let mut work = ready1.iter().map(|(t,k,_,_)| (t.clone(), k.clone())).chain(ready2.iter().map(|(t,k,_,_)| (t.clone(), k.clone()))).collect::<Vec<_>>();
+                work.sort();
+                work.dedup();
+
+                let mut ready1 = ready1.into_iter().peekable();
+                let mut ready2 = ready2.into_iter().peekable();
+                let mut prev_time = None;
+                let mut changes = Vec::new();
+                for (time, key) in work {
+
+                    // Keys are visited in order for each time, and the cursors restart with each new time.
+                    if prev_time.as_ref() != Some(&time) {
+                        cursor1.rewind_keys(&storage1);
+                        cursor2.rewind_keys(&storage2);
+                        prev_time = Some(time.clone());
+                    }
+
+                    let mut deltas1 = Vec::new();
+                    while let Some((_, _, val, diff)) = ready1.next_if(|(t,k,_,_)| t == &time && k == &key) {
+                        deltas1.push((val, diff));
+                    }
+                    crate::consolidation::consolidate(&mut deltas1);
+                    let mut deltas2 = Vec::new();
+                    while let Some((_, _, val, diff)) = ready2.next_if(|(t,k,_,_)| t == &time && k == &key) {
+                        deltas2.push((val, diff));
+                    }
+                    crate::consolidation::consolidate(&mut deltas2);
+
+                    cursor1.seek_key(&storage1, &key);
+                    let present1 = cursor1.get_key(&storage1) == Some(&key);
+                    cursor2.seek_key(&storage2, &key);
+                    let present2 = cursor2.get_key(&storage2) == Some(&key);
+
+                    // Records of the first input whose matches may have changed: those that changed, and those
+                    // from the least changed ordering key of the second input up to the first record whose
+                    // match is after the greatest changed ordering key, as are the matches of all later records.
+                    let mut affected = deltas1.iter().map(|(val, _)| val.clone()).collect::<Vec<_>>();
+                    if let (Some(((Reverse(greatest), _), _)), Some(((Reverse(least), _), _))) = (deltas2.first(), deltas2.last()) {
+                        if present1 {
+                            cursor1.rewind_vals(&storage1);
+                            cursor1.seek_val(&storage1, &(least.clone(), None));
+                            while let Some(val) = cursor1.get_val(&storage1) {
+                                if &val.0 > greatest && present2 {
+                                    let [_, after] = latest_at(&mut cursor2, &storage2, &val.0, &time, &deltas2);
+                                    if after.map(|(Reverse(order), _)| &order > greatest).unwrap_or(false) { break; }
+                                }
+                                affected.push(val.clone());
+                                cursor1.step_val(&storage1);
+                            }
+                        }
+                    }
+                    affected.sort();
+                    affected.dedup();
+
+                    // Retract the matches of affected records before the changes, and introduce those after.
+                    if present1 { cursor1.rewind_vals(&storage1); }
+                    for (order, val) in affected {
+                        let record = (order, val);
+                        let mut count_after = 0;
+                        if present1 {
+                            cursor1.seek_val(&storage1, &record);
+                            if cursor1.get_val(&storage1) == Some(&record) {
+                                count_after = count_at(&mut cursor1, &storage1, &time);
+                            }
+                        }
+                        let count_before = count_after - deltas1.iter().find(|(val, _)| val == &record).map(|(_, diff)| *diff).unwrap_or(0);
+                        if count_before != 0 || count_after != 0 {
+                            let matches = if present2 { latest_at(&mut cursor2, &storage2, &record.0, &time, &deltas2) } else { [None, None] };
+                            let val1 = record.1.expect("records are always present");
+                            for (matched, count) in matches.into_iter().zip([-count_before, count_after]) {
+                                if let Some((_, Some(val2))) = matched {
+                                    changes.push(((key.clone(), (val1.clone(), val2)), count));
+                                }
+                            }
+                        }
+                    }
+
+                    crate::consolidation::consolidate(&mut changes);
+                    if !changes.is_empty() {
+                        let capability = capability.as_ref().expect("changes without capability").delayed(&time);
+                        let mut session = output.session(&capability);
+                        for (data, diff) in changes.drain(..) {
+                            session.give((data, time.clone(), diff));
+                        }
+                    }
+                }
+            }
+
+            // Downgrade the capability to the least pending time, or drop it if nothing is pending.
+            let least = pending1.iter().map(|(time,_,_,_)| time).chain(pending2.iter().map(|(time,_,_,_)| time)).min().cloned();
+            match least {
+                Some(time) => { capability.as_mut().expect("changes without capability").downgrade(&time); },
+                None => { capability = None; },
+            }
+
+            // Both traces are read only at times not less than the meet of the input frontiers.
+            let mut frontier = Antichain::new();
+            for time in input1.frontier().frontier().iter().chain(input2.frontier().frontier().iter()) {
+                frontier.insert(time.clone());
+            }
+            let meet = frontier.elements().iter().min().cloned().map(Antichain::from_elem).unwrap_or_default();
+            if input1.frontier().is_empty() && input2.frontier().is_empty() {
+                trace1_option = None;
+                trace2_option = None;
+            }
+            if let Some(trace1) = trace1_option.as_mut() {
+                trace1.set_logical_compaction(meet.borrow());
+                trace1.set_physical_compaction(meet.borrow());
+            }
+            if let Some(trace2) = trace2_option.as_mut() {
+                trace2.set_logical_compaction(meet.borrow());
+                trace2.set_physical_compaction(meet.borrow());
+            }
+        }
+    })
+    .as_collection()
+}
+
+/// Accumulates the updates of the cursor's current value at times less or equal to `time`.
+fn count_at<C, T>(cursor: &mut C, storage: &C::Storage, time: &T) -> isize
+where
+    T: Lattice,
+    C: for<'a> Cursor<TimeGat<'a> = &'a T, DiffGat<'a> = &'a isize>,
+{
+    let mut count = 0;
+    cursor.map_times(storage, |t, diff| if t.less_equal(time) { count += *diff; });
+    count
+}
+
+/// Finds the matches for ordering key `order` at `time`, before and after the changes `deltas` at `time`.
+///
+/// The cursor must be positioned at the key of `deltas`. A match is the greatest present value with the greatest
+/// ordering key at or before `order`.
+fn latest_at<C, T, O, V2>(cursor: &mut C, storage: &C::Storage, order: &O, time: &T, deltas: &[(Match<O, V2>, isize)]) -> [Option<Match<O, V2>>; 2]
+where
+    T: Lattice,
+    O: Ord+Clone,
+    V2: Ord+Clone,
+    C: for<'a> Cursor<Val<'a> = &'a Match<O, V2>, TimeGat<'a> = &'a T, DiffGat<'a> = &'a isize>,
+{
+    let mut matches: [Option<Match<O, V2>>; 2] = [None, None];
+    cursor.rewind_vals(storage);
+    cursor.seek_val(storage, &(Reverse(order.clone()), None));
+    while let Some(val) = cursor.get_val(storage) {
+        // Values are ordered by decreasing ordering key, then by increasing value; stop once past both matches.
+        if matches.iter().all(|m| m.as_ref().map(|m| m.0 != val.0).unwrap_or(false)) { break; }
+        let count_after = count_at(cursor, storage, time);
+        let count_before = count_after - deltas.iter().find(|(v, _)| v == val).map(|(_, diff)| *diff).unwrap_or(0);
+        for (matched, count) in matches.iter_mut().zip([count_before, count_after]) {
+            if count > 0 && matched.as_ref().map(|m| m.0 == val.0).unwrap_or(true) {
+                *matched = Some(val.clone());
+            }
+        }
+        cursor.step_val(storage);
+    }
+    matches
+}
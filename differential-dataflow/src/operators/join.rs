@@ -18,11 +18,12 @@ use timely::dataflow::operators::Capability;
 use timely::dataflow::channels::pushers::tee::Tee;
 
 use crate::hashable::Hashable;
-use crate::{Data, ExchangeData, Collection};
+use crate::{Data, ExchangeData, Collection, IntoOwned};
 use crate::difference::{Semigroup, Abelian, Multiply};
 use crate::lattice::Lattice;
 use crate::operators::arrange::{Arranged, ArrangeByKey, ArrangeBySelf};
 use crate::trace::{BatchReader, Cursor};
+use crate::trace::implementations::{KeyBuilder, KeySpine};
 use crate::operators::ValueHistory;
 
 use crate::trace::TraceReader;
@@ -195,6 +196,233 @@ where
     }
 }
 
+/// Matched pairs of values from an outer join, in which either value may be optional.
+pub type OuterJoined<G, K, V1, V2, R> = Collection<G, (K, (V1, V2)), R>;
+
+/// Outer join implementations for `(key,val)` data.
+///
+/// Records whose key has no match in the other input are reported with `None` in place of the
+/// missing value. The inputs must have the same difference type, which must be multiplicative
+/// so that matched and unmatched records can be reported in the same collection.
+pub trait OuterJoin<G: Scope, K: Data, V: Data, R: Semigroup> {
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and also yields records of `self` without matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::join::OuterJoin;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1;
+    ///     let y = scope.new_collection_from(vec![(0, 'a'), (2, 'c')]).1;
+    ///     let z = scope.new_collection_from(vec![(0, (1, Some('a'))), (1, (3, None))]).1;
+    ///
+    ///     x.left_join(&y)
+    ///      .assert_eq(&z);
+    /// });
+    /// ```
+    fn left_join<V2: ExchangeData>(&self, other: &Collection<G, (K, V2), R>) -> OuterJoined<G, K, V, Option<V2>, R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and also yields records of `other` without matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::join::OuterJoin;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1;
+    ///     let y = scope.new_collection_from(vec![(0, 'a'), (2, 'c')]).1;
+    ///     let z = scope.new_collection_from(vec![(0, (Some(1), 'a')), (2, (None, 'c'))]).1;
+    ///
+    ///     x.right_join(&y)
+    ///      .assert_eq(&z);
+    /// });
+    /// ```
+    fn right_join<V2: ExchangeData>(&self, other: &Collection<G, (K, V2), R>) -> OuterJoined<G, K, Option<V>, V2, R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and also yields records of either input without matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::join::OuterJoin;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1;
+    ///     let y = scope.new_collection_from(vec![(0, 'a'), (2, 'c')]).1;
+    ///     let z = scope.new_collection_from(vec![(0, (Some(1), Some('a'))), (1, (Some(3), None)), (2, (None, Some('c')))]).1;
+    ///
+    ///     x.full_outer_join(&y)
+    ///      .assert_eq(&z);
+    /// });
+    /// ```
+    fn full_outer_join<V2: ExchangeData>(&self, other: &Collection<G, (K, V2), R>) -> OuterJoined<G, K, Option<V>, Option<V2>, R>;
+}
+
+impl<G, K, V, R> OuterJoin<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    K: ExchangeData+Hashable,
+    V: ExchangeData,
+    R: ExchangeData+Abelian+Multiply<Output=R>+From<i8>,
+    G::Timestamp: Lattice+Ord,
+{
+    fn left_join<V2: ExchangeData>(&self, other: &Collection<G, (K, V2), R>) -> OuterJoined<G, K, V, Option<V2>, R> {
+        self.arrange_by_key().left_join_core(&other.arrange_by_key())
+    }
+
+    fn right_join<V2: ExchangeData>(&self, other: &Collection<G, (K, V2), R>) -> OuterJoined<G, K, Option<V>, V2, R> {
+        self.arrange_by_key().right_join_core(&other.arrange_by_key())
+    }
+
+    fn full_outer_join<V2: ExchangeData>(&self, other: &Collection<G, (K, V2), R>) -> OuterJoined<G, K, Option<V>, Option<V2>, R> {
+        self.arrange_by_key().full_outer_join_core(&other.arrange_by_key())
+    }
+}
+
+/// Outer join implementations for arranged `(key,val)` data.
+///
+/// This trait mirrors `OuterJoin`, but as with `JoinCore` the other input is an arrangement,
+/// which is used both to find matches and to determine which keys are unmatched.
+pub trait OuterJoinCore<G: Scope, K: 'static, V: 'static, R: Semigroup> where G::Timestamp: Lattice+Ord {
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and also yields records of `self` without matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    /// use differential_dataflow::operators::join::OuterJoinCore;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1.arrange_by_key();
+    ///     let y = scope.new_collection_from(vec![(0, 'a'), (2, 'c')]).1.arrange_by_key();
+    ///     let z = scope.new_collection_from(vec![(0, (1, Some('a'))), (1, (3, None))]).1;
+    ///
+    ///     x.left_join_core(&y)
+    ///      .assert_eq(&z);
+    /// });
+    /// ```
+    fn left_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, V, Option<V2>, R>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=R>+Clone+'static;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and also yields records of `other` without matches.
+    fn right_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, Option<V>, V2, R>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=R>+Clone+'static;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and also yields records of either input without matches.
+    fn full_outer_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, Option<V>, Option<V2>, R>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=R>+Clone+'static;
+}
+
+impl<G, K, V, R> OuterJoinCore<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    K: ExchangeData+Hashable,
+    V: ExchangeData,
+    R: ExchangeData+Abelian+Multiply<Output=R>+From<i8>,
+    G::Timestamp: Lattice+Ord,
+{
+    fn left_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, V, Option<V2>, R>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=R>+Clone+'static,
+    {
+        self.arrange_by_key().left_join_core(other)
+    }
+
+    fn right_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, Option<V>, V2, R>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=R>+Clone+'static,
+    {
+        self.arrange_by_key().right_join_core(other)
+    }
+
+    fn full_outer_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, Option<V>, Option<V2>, R>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=R>+Clone+'static,
+    {
+        self.arrange_by_key().full_outer_join_core(other)
+    }
+}
+
+impl<G, K, V, Tr> OuterJoinCore<G, K, V, Tr::Diff> for Arranged<G, Tr>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    Tr: for<'a> TraceReader<Key<'a> = &'a K, Val<'a> = &'a V>+Clone+'static,
+    K: ExchangeData+Hashable,
+    V: Data + 'static,
+    Tr::Diff: ExchangeData+Abelian+Multiply<Output=Tr::Diff>+From<i8>,
+{
+    fn left_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, V, Option<V2>, Tr::Diff>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=Tr::Diff>+Clone+'static,
+    {
+        unmatched(self, other)
+            .map(|(k, v1)| (k, (v1, None)))
+            .concat(&self.join_core(other, |k, v1, v2| Some((k.clone(), (v1.clone(), Some(v2.clone()))))))
+    }
+
+    fn right_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, Option<V>, V2, Tr::Diff>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=Tr::Diff>+Clone+'static,
+    {
+        unmatched(other, self)
+            .map(|(k, v2)| (k, (None, v2)))
+            .concat(&self.join_core(other, |k, v1, v2| Some((k.clone(), (Some(v1.clone()), v2.clone())))))
+    }
+
+    fn full_outer_join_core<V2, Tr2>(&self, other: &Arranged<G, Tr2>) -> OuterJoined<G, K, Option<V>, Option<V2>, Tr::Diff>
+    where
+        V2: Data,
+        Tr2: for<'a> TraceReader<Key<'a>=&'a K, Val<'a>=&'a V2, Time=G::Timestamp, Diff=Tr::Diff>+Clone+'static,
+    {
+        unmatched(self, other)
+            .map(|(k, v1)| (k, (Some(v1), None)))
+            .concat(&unmatched(other, self).map(|(k, v2)| (k, (None, Some(v2)))))
+            .concat(&self.join_core(other, |k, v1, v2| Some((k.clone(), (Some(v1.clone()), Some(v2.clone()))))))
+    }
+}
+
+/// The records of `arranged` whose keys are absent from `other`.
+///
+/// The keys of `other` are reduced to a distinct set, which is then subtracted from `arranged` by way of a semijoin.
+fn unmatched<G, T1, T2, K, V, R>(arranged: &Arranged<G, T1>, other: &Arranged<G, T2>) -> Collection<G, (K, V), R>
+where
+    G: Scope<Timestamp=T1::Time>,
+    T1: for<'a> TraceReader<Key<'a> = &'a K, Val<'a> = &'a V, Diff = R>+Clone+'static,
+    T2: for<'a> TraceReader<Key<'a> = &'a K, Time = T1::Time>+Clone+'static,
+    for<'a> T2::Key<'a>: IntoOwned<'a, Owned = K>,
+    K: ExchangeData,
+    V: Data,
+    R: ExchangeData+Abelian+Multiply<Output=R>+From<i8>,
+{
+    let keys = other.reduce_abelian::<_,K,(),KeyBuilder<K,G::Timestamp,R>,KeySpine<K,G::Timestamp,R>>("OuterJoinKeys", |_k, _s, t| t.push(((), R::from(1i8))));
+    arranged
+        .as_collection(|k, v| (k.clone(), v.clone()))
+        .concat(&arranged.join_core(&keys, |k, v, _| Some((k.clone(), v.clone()))).negate())
+}
+
 /// Matches the elements of two arranged traces.
 ///
 /// This method is used by the various `join` implementations, but it can also be used
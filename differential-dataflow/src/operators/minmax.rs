@@ -0,0 +1,78 @@
+//! Maintains the least or greatest value for each key.
+//!
+//! A direct implementation with `reduce` would present all values of a key to the reduction logic
+//! whenever any of them changes, including when the current least or greatest value is retracted.
+//! Instead, these operators arrange the values of each key in a tree of buckets (as does `top_k`),
+//! where each bucket retains only its extreme value, and a change re-evaluates one bucket per level.
+
+use timely::dataflow::Scope;
+
+use crate::{Collection, ExchangeData, Hashable};
+use crate::lattice::Lattice;
+use crate::operators::topk::hierarchical;
+
+/// Extension trait for the `min` and `max` differential dataflow methods.
+pub trait MinMax<G: Scope, K: ExchangeData, V: ExchangeData> where G::Timestamp: Lattice+Ord {
+    /// Retains the least value for each key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::MinMax;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let data = scope.new_collection_from(1 .. 10).1;
+    ///     let expected = scope.new_collection_from(vec![(0, 3), (1, 1), (2, 2)]).1;
+    ///
+    ///     data.map(|x| (x % 3, x))
+    ///         .min()
+    ///         .assert_eq(&expected);
+    /// });
+    /// ```
+    fn min(&self) -> Collection<G, (K, V), isize>;
+    /// Retains the greatest value for each key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::MinMax;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let data = scope.new_collection_from(1 .. 10).1;
+    ///     let expected = scope.new_collection_from(vec![(0, 9), (1, 7), (2, 8)]).1;
+    ///
+    ///     data.map(|x| (x % 3, x))
+    ///         .max()
+    ///         .assert_eq(&expected);
+    /// });
+    /// ```
+    fn max(&self) -> Collection<G, (K, V), isize>;
+}
+
+impl<G, K, V> MinMax<G, K, V> for Collection<G, (K, V), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: ExchangeData+Hashable+std::hash::Hash,
+    V: ExchangeData+Hashable,
+{
+    fn min(&self) -> Collection<G, (K, V), isize> {
+        // Values are presented in increasing order.
+        hierarchical(self, "Min", || |_key: &(K, u64), input: &[(&V, isize)], output: &mut Vec<(V, isize)>| {
+            if let Some((val, _)) = input.iter().find(|(_, count)| *count > 0) {
+                output.push(((*val).clone(), 1));
+            }
+        })
+    }
+    fn max(&self) -> Collection<G, (K, V), isize> {
+        hierarchical(self, "Max", || |_key: &(K, u64), input: &[(&V, isize)], output: &mut Vec<(V, isize)>| {
+            if let Some((val, _)) = input.iter().rev().find(|(_, count)| *count > 0) {
+                output.push(((*val).clone(), 1));
+            }
+        })
+    }
+}
@@ -7,9 +7,13 @@
 pub use self::negate::Negate;
 pub use self::reduce::{Reduce, Threshold, Count};
 pub use self::iterate::{Iterate, ResultsIn};
-pub use self::join::{Join, JoinCore};
+pub use self::join::{Join, JoinCore, OuterJoin, OuterJoinCore};
 pub use self::count::CountTotal;
 pub use self::threshold::ThresholdTotal;
+pub use self::topk::TopK;
+pub use self::minmax::MinMax;
+pub use self::range_join::JoinRange;
+pub use self::asof::AsOfJoin;
 
 pub mod arrange;
 pub mod negate;
@@ -17,8 +21,14 @@ pub mod reduce;
 pub mod consolidate;
 pub mod iterate;
 pub mod join;
+pub mod range_join;
+pub mod asof;
 pub mod count;
 pub mod threshold;
+pub mod topk;
+pub mod minmax;
+pub mod window;
+pub mod multiway_join;
 
 use crate::lattice::Lattice;
 use crate::trace::Cursor;
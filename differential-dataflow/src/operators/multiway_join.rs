@@ -0,0 +1,413 @@
+//! Multiway joins of arranged relations, by worst-case optimal delta queries.
+//!
+//! A multiway join binds each of a set of variables to values, such that each relation contains
+//! the values bound to its variables. Rather than a tree of binary joins, each of which maintains
+//! its intermediate results, `multiway_join` builds one "delta query" for each relation: a dataflow
+//! that extends the changes to that relation one variable at a time, by looking up the other relations
+//! in indices shared by all of the delta queries. Only the relations and their indices are maintained.
+//!
+//! When several relations bind a variable, each prefix is extended by the relation proposing the fewest
+//! extensions for it, and the proposals are validated against the other relations. This bounds the work
+//! of each delta query by the size of the largest possible output, rather than the largest intermediate
+//! result of a sequence of binary joins.
+//!
+//! Changes to several relations at the same time must be combined so that each combination is produced
+//! exactly once. The delta query for a relation sees the changes at the same time to relations before it,
+//! and not those to relations after it. The dataflow distinguishes the two with an internal timestamp that
+//! refines the timestamp of the scope. Each change is introduced and then retracted immediately after, so
+//! that the delta queries look up relations as of the time of each change, and not as they change later.
+//!
+//! # Examples
+//!
+//! ```
+//! use differential_dataflow::input::Input;
+//! use differential_dataflow::operators::arrange::ArrangeBySelf;
+//! use differential_dataflow::operators::multiway_join::multiway_join;
+//!
+//! ::timely::example(|scope| {
+//!
+//!     let edges = scope.new_collection_from(vec![vec![1, 2], vec![2, 3], vec![1, 3], vec![3, 4]]).1.arrange_by_self();
+//!     let expected = scope.new_collection_from(vec![vec![1, 2, 3]]).1;
+//!
+//!     // Triangles: edge(a, b), edge(b, c), edge(a, c), binding variables in the order a, b, c.
+//!     let (a, b, c) = (0, 1, 2);
+//!     let relations = vec![
+//!         (edges.clone(), vec![a, b]),
+//!         (edges.clone(), vec![b, c]),
+//!         (edges.clone(), vec![a, c]),
+//!     ];
+//!
+//!     multiway_join(&relations, &[a, b, c])
+//!         .assert_eq(&expected);
+//! });
+//! ```
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+use timely::dataflow::Scope;
+use timely::dataflow::operators::{Filter, Map};
+use timely::order::PartialOrder;
+use timely::progress::{PathSummary, Timestamp};
+use timely::progress::timestamp::Refines;
+
+use crate::{AsCollection, Collection, ExchangeData, IntoOwned};
+use crate::difference::{Abelian, Multiply, Semigroup};
+use crate::lattice::Lattice;
+use crate::operators::JoinCore;
+use crate::operators::arrange::{Arranged, ArrangeByKey, ArrangeBySelf};
+use crate::trace::TraceReader;
+use crate::trace::implementations::{ValBuilder, ValSpine};
+
+/// Joins `relations` and yields the values bound to the variables of `order`, in that order.
+///
+/// Each relation is an arrangement of tuples, one value for each variable it binds, paired with the
+/// variable bound by each column of the tuples. The delta query for each relation binds the variables
+/// of its changes, and then binds the remaining variables in the order of `order`. Relations whose only
+/// unbound variable is the next variable propose and validate its values; if there are none, the first
+/// relation with the variable binds all of its unbound variables. Relations whose variables are all
+/// bound validate the prefixes. The arrangements are used to validate prefixes and to produce changes,
+/// and the relations are indexed by other sets of columns once, for all of the delta queries.
+///
+/// Relations are multisets: the multiplicity of each result is the product of the multiplicities
+/// of the tuples it is formed from.
+///
+/// # Panics
+///
+/// Panics if `relations` is empty, if `order` repeats a variable or has a variable in no relation, or
+/// if a relation has no variables, repeats a variable, or has a variable not in `order`.
+pub fn multiway_join<G, V, Tr>(relations: &[(Arranged<G, Tr>, Vec<usize>)], order: &[usize]) -> Collection<G, Vec<V>, Tr::Diff>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    G::Timestamp: Lattice+ExchangeData,
+    Tr: for<'a> TraceReader<Key<'a>=&'a Vec<V>, Val<'a>=&'a ()>+Clone+'static,
+    V: ExchangeData+Hash,
+    Tr::Diff: ExchangeData+Abelian+Multiply<Output=Tr::Diff>+From<i8>,
+{
+    assert!(!relations.is_empty(), "multiway_join requires at least one relation");
+    for (index, var) in order.iter().enumerate() {
+        assert!(!order[.. index].contains(var), "variable {} repeated in order", var);
+        assert!(relations.iter().any(|(_, vars)| vars.contains(var)), "variable {} in no relation", var);
+    }
+    let vars = relations.iter().map(|(_, vars)| vars.clone()).collect::<Vec<_>>();
+    for (relation, columns) in vars.iter().enumerate() {
+        assert!(!columns.is_empty(), "relation {} has no variables", relation);
+        for (index, var) in columns.iter().enumerate() {
+            assert!(!columns[.. index].contains(var), "variable {} repeated in relation {}", var, relation);
+            assert!(order.contains(var), "variable {} of relation {} not in order", var, relation);
+        }
+    }
+
+    let plans = (0 .. relations.len()).map(|delta| plan(&vars, order, delta)).collect::<Vec<_>>();
+
+    let mut scope = relations[0].0.stream.scope();
+    scope.scoped::<AltNeu<G::Timestamp>, _, _>("MultiwayJoin", |inner| {
+
+        // The tuples of each relation, and the arrangements of tuples as of each time.
+        let collections =
+        relations
+            .iter()
+            .map(|(arranged, _)| arranged.as_collection(|tuple, &()| tuple.clone()).enter(inner))
+            .collect::<Vec<_>>();
+        let arrangements =
+        relations
+            .iter()
+            .map(|(arranged, _)| arranged.enter_at(inner, |_, _, time| AltNeu::alt(time.into_owned()), |time| time.time.clone()))
+            .collect::<Vec<_>>();
+
+        // Relations after the delta query's relation are delayed to exclude their changes at the same time.
+        let version = |relation: usize, neu: bool| {
+            let collection = collections[relation].clone();
+            if neu { collection.delay(|time| AltNeu::neu(time.time.clone())) } else { collection }
+        };
+        let mut delayed = HashMap::new();
+        let mut indices = HashMap::new();
+        let mut counts = HashMap::new();
+
+        let mut results = Vec::with_capacity(plans.len());
+        for (delta, steps) in plans.iter().enumerate() {
+
+            // Each change is introduced, and then retracted immediately after.
+            let mut prefixes =
+            collections[delta]
+                .inner
+                .flat_map(|(tuple, time, diff)| {
+                    let mut negated = diff.clone();
+                    negated.negate();
+                    let retraction = (tuple.clone(), AltNeu::neu(time.time.clone()), negated);
+                    Some((tuple, time, diff)).into_iter().chain(Some(retraction))
+                })
+                .as_collection();
+
+            let mut bound = vars[delta].clone();
+            for step in steps.iter() {
+                match step {
+                    Step::Validate(relation) => {
+                        let positions = locate(&bound, &vars[*relation]);
+                        prefixes = if *relation > delta {
+                            let tuples = delayed.entry(*relation).or_insert_with(|| version(*relation, true).arrange_by_self_named("MultiwayJoinTuples"));
+                            validate(&prefixes, tuples, positions)
+                        }
+                        else {
+                            validate(&prefixes, &arrangements[*relation], positions)
+                        };
+                    },
+                    Step::Propose(relation) => {
+                        let (key, extend) = columns(&bound, &vars[*relation]);
+                        let positions = locate(&bound, &select(&vars[*relation], &key));
+                        let neu = *relation > delta;
+                        let index = indices.entry((*relation, key.clone(), extend.clone(), neu)).or_insert_with(|| {
+                            version(*relation, neu)
+                                .map(move |tuple| (select(&tuple, &key), select(&tuple, &extend)))
+                                .arrange_by_key_named("MultiwayJoinIndex")
+                        });
+                        prefixes = propose(&prefixes, index, positions);
+                        bound.extend(vars[*relation].iter().filter(|var| !bound.contains(var)).cloned().collect::<Vec<_>>());
+                    },
+                    Step::Extend(var, extenders) => {
+
+                        // Each prefix is tagged with the fewest extensions proposed, and the extender proposing them.
+                        let mut tagged = prefixes.map(|prefix| (prefix, (usize::MAX, 0)));
+                        for (extender, relation) in extenders.iter().enumerate() {
+                            let (key, extend) = columns(&bound, &vars[*relation]);
+                            let positions = locate(&bound, &select(&vars[*relation], &key));
+                            let neu = *relation > delta;
+                            let signature = (*relation, key.clone(), extend.clone(), neu);
+                            let index = indices.entry(signature.clone()).or_insert_with(|| {
+                                version(*relation, neu)
+                                    .map(move |tuple| (select(&tuple, &key), select(&tuple, &extend)))
+                                    .arrange_by_key_named("MultiwayJoinIndex")
+                            });
+                            let count = counts.entry(signature).or_insert_with(|| {
+                                index.reduce_abelian::<_,Vec<V>,usize,ValBuilder<Vec<V>,usize,AltNeu<G::Timestamp>,Tr::Diff>,ValSpine<Vec<V>,usize,AltNeu<G::Timestamp>,Tr::Diff>>("MultiwayJoinCount", |_key, source, target| {
+                                    target.push((source.len(), Tr::Diff::from(1i8)));
+                                })
+                            });
+                            tagged =
+                            tagged
+                                .map(move |(prefix, best)| (select(&prefix, &positions), (prefix, best)))
+                                .join_core(count, move |_key, (prefix, best), &count| {
+                                    let best = if count < best.0 { (count, extender) } else { *best };
+                                    Some((prefix.clone(), best))
+                                });
+                        }
+
+                        // Each extender proposes extensions for the prefixes tagged with it, which the others validate.
+                        let mut extended = Vec::with_capacity(extenders.len());
+                        for (extender, relation) in extenders.iter().enumerate() {
+                            let (key, extend) = columns(&bound, &vars[*relation]);
+                            let positions = locate(&bound, &select(&vars[*relation], &key));
+                            let index = &indices[&(*relation, key, extend, *relation > delta)];
+                            let proposed = tagged.filter(move |(_, best)| best.1 == extender).map(|(prefix, _)| prefix);
+                            let mut proposed = propose(&proposed, index, positions);
+                            let mut bound = bound.clone();
+                            bound.push(*var);
+                            for other in extenders.iter().filter(|other| *other != relation) {
+                                let positions = locate(&bound, &vars[*other]);
+                                proposed = if *other > delta {
+                                    let tuples = delayed.entry(*other).or_insert_with(|| version(*other, true).arrange_by_self_named("MultiwayJoinTuples"));
+                                    validate(&proposed, tuples, positions)
+                                }
+                                else {
+                                    validate(&proposed, &arrangements[*other], positions)
+                                };
+                            }
+                            extended.push(proposed);
+                        }
+                        prefixes = crate::collection::concatenate(&mut inner.clone(), extended);
+                        bound.push(*var);
+                    },
+                }
+            }
+
+            let output = locate(&bound, order);
+            results.push(prefixes.map(move |prefix| select(&prefix, &output)));
+        }
+
+        // Retain only the changes introduced, not their retractions.
+        crate::collection::concatenate(&mut inner.clone(), results)
+            .inner
+            .filter(|(_tuple, time, _diff)| !time.neu)
+            .as_collection()
+            .leave()
+    })
+}
+
+/// Extends each prefix with the values `index` holds for the values of the prefix at `positions`.
+fn propose<G, V, Tr>(prefixes: &Collection<G, Vec<V>, Tr::Diff>, index: &Arranged<G, Tr>, positions: Vec<usize>) -> Collection<G, Vec<V>, Tr::Diff>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    G::Timestamp: Lattice+Ord,
+    Tr: for<'a> TraceReader<Key<'a>=&'a Vec<V>, Val<'a>=&'a Vec<V>>+Clone+'static,
+    V: ExchangeData+Hash,
+    Tr::Diff: ExchangeData+Semigroup+Multiply<Output=Tr::Diff>,
+{
+    prefixes
+        .map(move |prefix| (select(&prefix, &positions), prefix))
+        .join_core(index, |_key, prefix, extension| {
+            let mut prefix = prefix.clone();
+            prefix.extend(extension.iter().cloned());
+            Some(prefix)
+        })
+}
+
+/// Retains each prefix whose values at `positions` form a tuple of `tuples`, with its multiplicity.
+fn validate<G, V, Tr>(prefixes: &Collection<G, Vec<V>, Tr::Diff>, tuples: &Arranged<G, Tr>, positions: Vec<usize>) -> Collection<G, Vec<V>, Tr::Diff>
+where
+    G: Scope<Timestamp=Tr::Time>,
+    G::Timestamp: Lattice+Ord,
+    Tr: for<'a> TraceReader<Key<'a>=&'a Vec<V>, Val<'a>=&'a ()>+Clone+'static,
+    V: ExchangeData+Hash,
+    Tr::Diff: ExchangeData+Semigroup+Multiply<Output=Tr::Diff>,
+{
+    prefixes
+        .map(move |prefix| (select(&prefix, &positions), prefix))
+        .join_core(tuples, |_tuple, prefix, &()| Some(prefix.clone()))
+}
+
+/// The values of `tuple` at each of `positions`.
+fn select<V: Clone>(tuple: &[V], positions: &[usize]) -> Vec<V> {
+    positions.iter().map(|&position| tuple[position].clone()).collect()
+}
+
+/// The position in `bound` of each of `vars`.
+fn locate(bound: &[usize], vars: &[usize]) -> Vec<usize> {
+    vars.iter().map(|var| bound.iter().position(|x| x == var).unwrap()).collect()
+}
+
+/// The columns of a relation with variables `vars` that are bound, and those that are not.
+fn columns(bound: &[usize], vars: &[usize]) -> (Vec<usize>, Vec<usize>) {
+    (0 .. vars.len()).partition(|&column| bound.contains(&vars[column]))
+}
+
+/// A step of a delta query.
+enum Step {
+    /// Retains prefixes whose values form a tuple of the relation, all of whose variables are bound.
+    Validate(usize),
+    /// Extends prefixes with the values of all unbound variables of the relation.
+    Propose(usize),
+    /// Extends prefixes with values of the variable, proposed by one of the relations and validated by the others.
+    ///
+    /// The variable is the only unbound variable of each of the relations.
+    Extend(usize, Vec<usize>),
+}
+
+/// Plans the delta query for changes to relation `delta`.
+fn plan(vars: &[Vec<usize>], order: &[usize], delta: usize) -> Vec<Step> {
+
+    let mut bound = vars[delta].clone();
+    let mut remaining = (0 .. vars.len()).filter(|&relation| relation != delta).collect::<Vec<_>>();
+    let mut steps = Vec::new();
+
+    // Relations whose variables are all bound validate the prefixes.
+    let validate = |bound: &[usize], remaining: &mut Vec<usize>, steps: &mut Vec<Step>| {
+        remaining.retain(|&relation| {
+            let complete = vars[relation].iter().all(|var| bound.contains(var));
+            if complete { steps.push(Step::Validate(relation)); }
+            !complete
+        });
+    };
+
+    validate(&bound, &mut remaining, &mut steps);
+    for &var in order.iter().filter(|var| !vars[delta].contains(var)) {
+        if bound.contains(&var) { continue; }
+        let extenders =
+        remaining
+            .iter()
+            .filter(|&&relation| vars[relation].contains(&var) && vars[relation].iter().all(|x| x == &var || bound.contains(x)))
+            .cloned()
+            .collect::<Vec<_>>();
+        if extenders.is_empty() {
+            let relation = *remaining.iter().find(|&&relation| vars[relation].contains(&var)).unwrap();
+            remaining.retain(|&other| other != relation);
+            bound.extend(vars[relation].iter().filter(|x| !bound.contains(x)).cloned().collect::<Vec<_>>());
+            steps.push(Step::Propose(relation));
+        }
+        else {
+            remaining.retain(|relation| !extenders.contains(relation));
+            bound.push(var);
+            steps.push(Step::Extend(var, extenders));
+        }
+        validate(&bound, &mut remaining, &mut steps);
+    }
+
+    steps
+}
+
+/// A timestamp that distinguishes changes at a time from accumulated changes through that time.
+///
+/// Two timestamps are ordered if their times are ordered and distinct, or if their times are equal
+/// and their `neu` flags are ordered, with `false` before `true`.
+#[derive(Debug, Hash, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+struct AltNeu<T> {
+    time: T,
+    neu: bool,
+}
+
+impl<T> AltNeu<T> {
+    fn alt(time: T) -> Self { AltNeu { time, neu: false } }
+    fn neu(time: T) -> Self { AltNeu { time, neu: true } }
+}
+
+impl<T: PartialOrder> PartialOrder for AltNeu<T> {
+    fn less_equal(&self, other: &Self) -> bool {
+        if self.time.eq(&other.time) {
+            self.neu <= other.neu
+        }
+        else {
+            self.time.less_equal(&other.time)
+        }
+    }
+}
+
+impl<T: Timestamp> PathSummary<AltNeu<T>> for () {
+    fn results_in(&self, timestamp: &AltNeu<T>) -> Option<AltNeu<T>> {
+        Some(timestamp.clone())
+    }
+    fn followed_by(&self, _other: &Self) -> Option<Self> {
+        Some(())
+    }
+}
+
+impl<T: Timestamp> Timestamp for AltNeu<T> {
+    type Summary = ();
+    fn minimum() -> Self { AltNeu::alt(T::minimum()) }
+}
+
+impl<T: Timestamp> Refines<T> for AltNeu<T> {
+    fn to_inner(other: T) -> Self {
+        AltNeu::alt(other)
+    }
+    fn to_outer(self: AltNeu<T>) -> T {
+        self.time
+    }
+    fn summarize(_path: ()) -> <T as Timestamp>::Summary {
+        Default::default()
+    }
+}
+
+impl<T: Lattice> Lattice for AltNeu<T> {
+    fn join(&self, other: &Self) -> Self {
+        let time = self.time.join(&other.time);
+        let mut neu = false;
+        if time == self.time {
+            neu = neu || self.neu;
+        }
+        if time == other.time {
+            neu = neu || other.neu;
+        }
+        AltNeu { time, neu }
+    }
+    fn meet(&self, other: &Self) -> Self {
+        let time = self.time.meet(&other.time);
+        let mut neu = true;
+        if time == self.time {
+            neu = neu && self.neu;
+        }
+        if time == other.time {
+            neu = neu && other.neu;
+        }
+        AltNeu { time, neu }
+    }
+}
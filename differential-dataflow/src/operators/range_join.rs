@@ -0,0 +1,269 @@
+//! Joins that match values within a range, rather than on equality.
+//!
+//! The `join_range` operator pairs records with equal keys whose values satisfy a range
+//! constraint: each value `v` of the first collection matches those values of the second
+//! collection that lie in `range(v)`. Both inputs are arranged by key and value, so that
+//! each probe into an arrangement seeks directly to the start of its range and scans only
+//! the records within it, rather than every record with the same key.
+//!
+//! A common use is the time-interval join, which matches events with the same key whose
+//! timestamps are within some bound of each other.
+//!
+//! # Examples
+//!
+//! ```
+//! use differential_dataflow::input::Input;
+//! use differential_dataflow::operators::JoinRange;
+//!
+//! ::timely::example(|scope| {
+//!
+//!     // Clicks and purchases, as `(user, minute)` pairs.
+//!     let clicks = scope.new_collection_from(vec![(0, 10u64), (0, 30), (1, 10)]).1;
+//!     let purchases = scope.new_collection_from(vec![(0, 14u64), (0, 50), (1, 7)]).1;
+//!
+//!     // Purchases within five minutes of a click by the same user.
+//!     let expected = scope.new_collection_from(vec![(0, (10, 14)), (1, (10, 7))]).1;
+//!
+//!     clicks
+//!         .join_range(&purchases, |minute| minute.saturating_sub(5) .. minute + 6)
+//!         .assert_eq(&expected);
+//! });
+//! ```
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use timely::order::PartialOrder;
+use timely::progress::{Antichain, Timestamp};
+use timely::dataflow::Scope;
+use timely::dataflow::channels::pact::{Exchange, Pipeline};
+use timely::dataflow::operators::Operator;
+
+use crate::{AsCollection, Collection, Data, ExchangeData, Hashable};
+use crate::difference::{Multiply, Semigroup};
+use crate::lattice::Lattice;
+use crate::operators::arrange::{Arranged, TraceAgent};
+use crate::operators::arrange::arrangement::arrange_core;
+use crate::trace::{BatchReader, Cursor, TraceReader};
+use crate::trace::implementations::{KeyBatcher, KeyBuilder, KeySpine, ValBatcher, ValBuilder, ValSpine};
+
+/// Matched pairs of values from `join_range`, with the product of their differences.
+pub type RangeJoined<G, K, V, V2, R, R2> = Collection<G, (K, (V, V2)), <R as Multiply<R2>>::Output>;
+
+/// The first input of `join_range`, keyed by `(key, range(val).end)` with values `val`.
+type Ends<G, K, V, V2, T, R> = Arranged<G, TraceAgent<ValSpine<(K, V2), V, T, R>>>;
+/// The second input of `join_range`, keyed by `(key, val2)`.
+type Vals<G, K, V2, T, R2> = Arranged<G, TraceAgent<KeySpine<(K, V2), T, R2>>>;
+
+/// Joins on equal keys and values within a range.
+pub trait JoinRange<G: Scope, K: Data, V: Data, R: Semigroup> {
+    /// Matches pairs `(key,val1)` and `(key,val2)` where `val2` lies in `range(val1)`, and yields `(key, (val1, val2))`.
+    ///
+    /// The bounds of `range(val)` must not decrease as `val` increases. This is the case for ranges that
+    /// are a fixed offset from `val`, as in "within five minutes of", and it allows updates to either input
+    /// to locate their matches in the other input by seeking to a single interval of its arrangement.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::JoinRange;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let x = scope.new_collection_from(vec![(0, 10), (1, 20)]).1;
+    ///     let y = scope.new_collection_from(vec![(0, 8), (0, 12), (0, 13), (1, 20)]).1;
+    ///     let z = scope.new_collection_from(vec![(0, (10, 8)), (0, (10, 12)), (1, (20, 20))]).1;
+    ///
+    ///     x.join_range(&y, |v| v - 2 .. v + 3)
+    ///      .assert_eq(&z);
+    /// });
+    /// ```
+    fn join_range<V2, R2, F>(&self, other: &Collection<G, (K, V2), R2>, range: F) -> RangeJoined<G, K, V, V2, R, R2>
+    where
+        K: ExchangeData,
+        V2: ExchangeData,
+        R2: ExchangeData+Semigroup,
+        R: Multiply<R2>,
+        <R as Multiply<R2>>::Output: Semigroup+'static,
+        F: Fn(&V)->Range<V2>+'static;
+}
+
+impl<G, K, V, R> JoinRange<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: ExchangeData+Hashable,
+    V: ExchangeData,
+    R: ExchangeData+Semigroup,
+{
+    fn join_range<V2, R2, F>(&self, other: &Collection<G, (K, V2), R2>, range: F) -> RangeJoined<G, K, V, V2, R, R2>
+    where
+        K: ExchangeData,
+        V2: ExchangeData,
+        R2: ExchangeData+Semigroup,
+        R: Multiply<R2>,
+        <R as Multiply<R2>>::Output: Semigroup+'static,
+        F: Fn(&V)->Range<V2>+'static,
+    {
+        let range = Rc::new(range);
+
+        // Records of `self` are indexed by the end of their range, and records of `other` by their value.
+        // Both are partitioned by key alone, so that matching records land on the same worker.
+        let bounds = Rc::clone(&range);
+        let ends = self.map(move |(key, val)| { let end = bounds(&val).end; ((key, end), val) });
+        let exchange = Exchange::new(|update: &(((K,V2),V),G::Timestamp,R)| ((update.0).0).0.hashed().into());
+        let arranged1 = arrange_core::<_, _, ValBatcher<_,_,_,_>, ValBuilder<_,_,_,_>, ValSpine<_,_,_,_>>(&ends.inner, exchange, "JoinRangeArrange1");
+
+        let vals = other.map(|(key, val)| ((key, val), ()));
+        let exchange = Exchange::new(|update: &(((K,V2),()),G::Timestamp,R2)| ((update.0).0).0.hashed().into());
+        let arranged2 = arrange_core::<_, _, KeyBatcher<_,_,_>, KeyBuilder<_,_,_>, KeySpine<_,_,_>>(&vals.inner, exchange, "JoinRangeArrange2");
+
+        join_range_traces(&arranged1, &arranged2, move |val: &V| (*range)(val))
+    }
+}
+
+/// Joins two arrangements produced by `join_range`.
+///
+/// The first arrangement is keyed by `(key, range(val).end)` with values `val`, and the second is keyed
+/// by `(key, val2)`. Each batch of either input is joined with the updates of the other input that have
+/// been accepted so far, which ensures that each pair of updates is joined exactly once.
+fn join_range_traces<G, K, V, V2, R, R2, F>(
+    arranged1: &Ends<G, K, V, V2, G::Timestamp, R>,
+    arranged2: &Vals<G, K, V2, G::Timestamp, R2>,
+    range: F,
+) -> RangeJoined<G, K, V, V2, R, R2>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: ExchangeData,
+    V: ExchangeData,
+    V2: ExchangeData,
+    R: ExchangeData+Semigroup+Multiply<R2>,
+    R2: ExchangeData+Semigroup,
+    <R as Multiply<R2>>::Output: Semigroup+'static,
+    F: Fn(&V)->Range<V2>+'static,
+{
+    let mut trace1_option = Some(arranged1.trace.clone());
+    let mut trace2_option = Some(arranged2.trace.clone());
+
+    arranged1.stream.binary_frontier(&arranged2.stream, Pipeline, Pipeline, "JoinRange", move |_capability, _info| {
+
+        // The traces are created alongside this operator, and so hold no batches it has not seen.
+        let mut acknowledged1 = Antichain::from_elem(<G::Timestamp>::minimum());
+        let mut acknowledged2 = Antichain::from_elem(<G::Timestamp>::minimum());
+
+        let mut times1 = Vec::new();
+        let mut times2 = Vec::new();
+
+        move |input1, input2, output| {
+
+            // Join batches of `input1` with accepted updates of `input2`, seeking to the range of each value.
+            input1.for_each(|capability, data| {
+                let trace2 = trace2_option.as_mut().expect("`trace2_option` dropped before `input1` emptied!");
+                let capability = capability.retain();
+                let mut session = output.session(&capability);
+                for batch1 in data.drain(..) {
+                    if PartialOrder::less_equal(&acknowledged1, batch1.lower()) {
+                        if !batch1.is_empty() {
+                            let (mut cursor2, storage2) = trace2.cursor_through(acknowledged2.borrow()).unwrap();
+                            let mut cursor1 = batch1.cursor();
+                            while let Some(key1) = cursor1.get_key(&batch1) {
+                                while let Some(val1) = cursor1.get_val(&batch1) {
+                                    times1.clear();
+                                    cursor1.map_times(&batch1, |time, diff| times1.push((time.clone(), diff.clone())));
+                                    let bounds = range(val1);
+                                    let lower = (key1.0.clone(), bounds.start);
+                                    let upper = (key1.0.clone(), bounds.end);
+                                    cursor2.rewind_keys(&storage2);
+                                    cursor2.seek_key(&storage2, &lower);
+                                    while let Some(key2) = cursor2.get_key(&storage2) {
+                                        if key2 >= &upper { break; }
+                                        cursor2.map_times(&storage2, |time2, diff2| {
+                                            for (time1, diff1) in times1.iter() {
+                                                let data = (key1.0.clone(), (val1.clone(), key2.1.clone()));
+                                                session.give((data, time1.join(time2), diff1.clone().multiply(diff2)));
+                                            }
+                                        });
+                                        cursor2.step_key(&storage2);
+                                    }
+                                    cursor1.step_val(&batch1);
+                                }
+                                cursor1.step_key(&batch1);
+                            }
+                        }
+                        debug_assert!(PartialOrder::less_equal(&acknowledged1, batch1.upper()));
+                        acknowledged1.clone_from(batch1.upper());
+                    }
+                }
+            });
+
+            // Join batches of `input2` with accepted updates of `input1`, seeking to the first range that ends after each value.
+            input2.for_each(|capability, data| {
+                let trace1 = trace1_option.as_mut().expect("`trace1_option` dropped before `input2` emptied!");
+                let capability = capability.retain();
+                let mut session = output.session(&capability);
+                for batch2 in data.drain(..) {
+                    if PartialOrder::less_equal(&acknowledged2, batch2.lower()) {
+                        if !batch2.is_empty() {
+                            let (mut cursor1, storage1) = trace1.cursor_through(acknowledged1.borrow()).unwrap();
+                            let mut cursor2 = batch2.cursor();
+                            while let Some(key2) = cursor2.get_key(&batch2) {
+                                times2.clear();
+                                cursor2.map_times(&batch2, |time, diff| times2.push((time.clone(), diff.clone())));
+                                cursor1.rewind_keys(&storage1);
+                                cursor1.seek_key(&storage1, key2);
+                                // Ranges exclude their end, and so cannot match if they end at `key2`.
+                                if cursor1.get_key(&storage1) == Some(key2) { cursor1.step_key(&storage1); }
+                                // As range bounds do not decrease, neither do range starts in this order.
+                                'scan: while let Some(key1) = cursor1.get_key(&storage1) {
+                                    if key1.0 != key2.0 { break; }
+                                    while let Some(val1) = cursor1.get_val(&storage1) {
+                                        if range(val1).start > key2.1 { break 'scan; }
+                                        cursor1.map_times(&storage1, |time1, diff1| {
+                                            for (time2, diff2) in times2.iter() {
+                                                let data = (key2.0.clone(), (val1.clone(), key2.1.clone()));
+                                                session.give((data, time1.join(time2), diff1.clone().multiply(diff2)));
+                                            }
+                                        });
+                                        cursor1.step_val(&storage1);
+                                    }
+                                    cursor1.step_key(&storage1);
+                                }
+                                cursor2.step_key(&batch2);
+                            }
+                        }
+                        debug_assert!(PartialOrder::less_equal(&acknowledged2, batch2.upper()));
+                        acknowledged2.clone_from(batch2.upper());
+                    }
+                }
+            });
+
+            // Advance acknowledged frontiers through any empty regions that we may not receive as batches.
+            if let Some(trace1) = trace1_option.as_mut() {
+                trace1.advance_upper(&mut acknowledged1);
+            }
+            if let Some(trace2) = trace2_option.as_mut() {
+                trace2.advance_upper(&mut acknowledged2);
+            }
+
+            // Each trace need only be accurate for times the opposing input may yet produce,
+            // and need only be read as of the batches its own input has delivered.
+            if let Some(trace1) = trace1_option.as_mut() {
+                if input2.frontier().is_empty() { trace1_option = None; }
+                else {
+                    trace1.set_logical_compaction(input2.frontier().frontier());
+                    trace1.set_physical_compaction(acknowledged1.borrow());
+                }
+            }
+            if let Some(trace2) = trace2_option.as_mut() {
+                if input1.frontier().is_empty() { trace2_option = None; }
+                else {
+                    trace2.set_logical_compaction(input1.frontier().frontier());
+                    trace2.set_physical_compaction(acknowledged2.borrow());
+                }
+            }
+        }
+    })
+    .as_collection()
+}
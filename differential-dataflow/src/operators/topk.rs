@@ -0,0 +1,122 @@
+//! Maintains the `k` least values for each key, according to a supplied order.
+//!
+//! The `top_k` operator is the incremental form of `ORDER BY .. LIMIT`. A direct implementation
+//! with `reduce` would present all values of a key to the reduction logic whenever any of them
+//! changes, which is expensive for keys with many values. Instead, `top_k` groups each key's values
+//! into buckets by a prefix of the hash of the value, retains the least `k` values of each bucket,
+//! and then repeats with progressively shorter prefixes until a single bucket remains for each key.
+//! A change to a value then only requires the re-evaluation of one bucket at each level, each of
+//! which holds at most `k` values from each of its children.
+
+use timely::dataflow::Scope;
+
+use crate::{Collection, ExchangeData, Hashable};
+use crate::lattice::Lattice;
+use crate::operators::Reduce;
+
+/// The number of bits of the value hash used to form the initial buckets.
+const BUCKET_BITS: u32 = 24;
+/// The number of bits by which the bucket prefix shrinks at each level.
+const LEVEL_BITS: u32 = 8;
+
+/// Extension trait for the `top_k` differential dataflow method.
+pub trait TopK<G: Scope, K: ExchangeData, V: ExchangeData> where G::Timestamp: Lattice+Ord {
+    /// Retains the `k` least values for each key, ordered by `order_by` and then by value.
+    ///
+    /// Values are counted with their multiplicity, and the result contains at most `k` copies of values
+    /// for each key. To retain the greatest values, wrap the result of `order_by` in `std::cmp::Reverse`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::topk::TopK;
+    ///
+    /// ::timely::example(|scope| {
+    ///
+    ///     let data = scope.new_collection_from(1 .. 10).1;
+    ///
+    ///     // the two greatest values for each residue modulo three.
+    ///     let expected = scope.new_collection_from(vec![(0, 6), (0, 9), (1, 4), (1, 7), (2, 5), (2, 8)]).1;
+    ///
+    ///     data.map(|x| (x % 3, x))
+    ///         .top_k(2, |x| std::cmp::Reverse(*x))
+    ///         .assert_eq(&expected);
+    /// });
+    /// ```
+    fn top_k<O, L>(&self, k: usize, order_by: L) -> Collection<G, (K, V), isize>
+    where
+        O: Ord,
+        L: Fn(&V)->O+Clone+'static;
+}
+
+impl<G, K, V> TopK<G, K, V> for Collection<G, (K, V), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: ExchangeData+Hashable+std::hash::Hash,
+    V: ExchangeData+Hashable,
+{
+    fn top_k<O, L>(&self, k: usize, order_by: L) -> Collection<G, (K, V), isize>
+    where
+        O: Ord,
+        L: Fn(&V)->O+Clone+'static,
+    {
+        hierarchical(self, "TopK", || {
+            let order_by = order_by.clone();
+            move |_key: &(K, u64), input: &[(&V, isize)], output: &mut Vec<(V, isize)>| limit(k, &order_by, input, output)
+        })
+    }
+}
+
+/// Applies reduction logic to buckets of the values of each key, from the finest buckets to the coarsest.
+///
+/// The logic is applied to a bucket of the values of a key, and should produce a subset of these values.
+/// The values are first bucketed by a prefix of their hash, and then by progressively shorter prefixes of
+/// the hash, until the final application of the logic to a single bucket containing the values of the key
+/// produced by the logic at the previous level.
+pub(crate) fn hierarchical<G, K, V, F, L>(collection: &Collection<G, (K, V), isize>, name: &str, logic: F) -> Collection<G, (K, V), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: ExchangeData+Hashable+std::hash::Hash,
+    V: ExchangeData+Hashable,
+    F: Fn()->L,
+    L: FnMut(&(K, u64), &[(&V, isize)], &mut Vec<(V, isize)>)+'static,
+{
+    let mut bits = BUCKET_BITS;
+    let mut buckets =
+    collection
+        .map(move |(key, val)| {
+            let bucket = val.hashed().into() >> (64 - BUCKET_BITS);
+            ((key, bucket), val)
+        })
+        .reduce_named(name, logic());
+
+    while bits > 0 {
+        bits -= LEVEL_BITS;
+        buckets =
+        buckets
+            .map(|((key, bucket), val)| ((key, bucket >> LEVEL_BITS), val))
+            .reduce_named(name, logic());
+    }
+
+    buckets.map(|((key, _bucket), val)| (key, val))
+}
+
+/// Reduction logic retaining the `k` least values of `input` ordered by `order_by` and then by value.
+fn limit<V, O, L>(k: usize, order_by: &L, input: &[(&V, isize)], output: &mut Vec<(V, isize)>)
+where
+    V: Ord+Clone,
+    O: Ord,
+    L: Fn(&V)->O,
+{
+    let mut sorted = input.iter().filter(|(_, count)| *count > 0).map(|(val, count)| (order_by(val), *val, *count)).collect::<Vec<_>>();
+    sorted.sort_by(|x, y| x.0.cmp(&y.0).then(x.1.cmp(y.1)));
+    let mut remaining = k as isize;
+    for (_, val, count) in sorted {
+        if remaining <= 0 { break; }
+        output.push((val.clone(), std::cmp::min(count, remaining)));
+        remaining -= count;
+    }
+}
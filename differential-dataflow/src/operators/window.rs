@@ -0,0 +1,399 @@
+//! Aggregation of keyed collections into windows of event time.
+//!
+//! Each record carries an event time, extracted by a user function, which places it in one or
+//! more windows: tumbling windows partition event time into intervals of a fixed size, hopping
+//! windows are intervals of a fixed size that start at multiples of a smaller slide, and session
+//! windows group events of the same key separated by less than a gap. For each key and window,
+//! the operator reports the accumulated differences of its records, retracting and re-reporting
+//! the accumulation as it changes. To aggregate something other than the number of records,
+//! use a difference type that accumulates the aggregate (e.g. by way of `explode`).
+//!
+//! The operator relates event times to the timestamps of the collection through the `EventTime`
+//! trait. Once a timestamp's event time reaches the end of a window plus a `lateness` bound, the
+//! window is closed: updates at that and later timestamps no longer change it, and the operator
+//! discards its state. Such late updates are discarded without being reported. The results for
+//! closed windows remain in the output collection.
+//!
+//! # Examples
+//!
+//! ```
+//! use timely::dataflow::operators::ToStream;
+//! use differential_dataflow::AsCollection;
+//! use differential_dataflow::operators::window::{Window, WindowSpec, Windows};
+//!
+//! ::timely::example(|scope| {
+//!
+//!     // Clicks by user, at event times.
+//!     let clicks =
+//!     vec![((0u32, 3u64), 0, 1), ((0, 12), 0, 1), ((0, 17), 0, 1)]
+//!         .into_iter()
+//!         .to_stream(scope)
+//!         .as_collection();
+//!
+//!     let expected =
+//!     vec![
+//!         ((0u32, Window { start: 0, end: 10 }, 1isize), 0, 1),
+//!         ((0, Window { start: 10, end: 20 }, 2), 0, 1),
+//!     ]
+//!         .into_iter()
+//!         .to_stream(scope)
+//!         .as_collection();
+//!
+//!     clicks.window(WindowSpec::tumbling(10), 0, |time: &u64| *time)
+//!           .assert_eq(&expected);
+//! });
+//! ```
+
+use std::collections::BTreeMap;
+
+use timely::order::TotalOrder;
+use timely::progress::Timestamp;
+use timely::dataflow::*;
+use timely::dataflow::operators::{Capability, Operator};
+use timely::dataflow::channels::pact::Exchange;
+
+use crate::{Collection, ExchangeData, Hashable};
+use crate::collection::AsCollection;
+use crate::difference::Semigroup;
+
+/// Timestamps that indicate the progress of event time.
+///
+/// Updates at a timestamp are assumed to be at least the event time it reports, such that
+/// the frontier of a collection bounds the event times of windows that may yet change.
+pub trait EventTime: Timestamp+TotalOrder {
+    /// The event time corresponding to the timestamp.
+    fn event_time(&self) -> u64;
+}
+
+impl EventTime for u64 {
+    fn event_time(&self) -> u64 { *self }
+}
+
+impl EventTime for u32 {
+    fn event_time(&self) -> u64 { *self as u64 }
+}
+
+impl EventTime for usize {
+    fn event_time(&self) -> u64 { *self as u64 }
+}
+
+/// An interval of event times, including `start` and excluding `end`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Window {
+    /// The first event time in the window.
+    pub start: u64,
+    /// The first event time after the window.
+    pub end: u64,
+}
+
+/// The manner in which event times are grouped into windows.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WindowSpec {
+    /// Windows of `size` event times, starting at multiples of `size`.
+    Tumbling {
+        /// The number of event times in each window.
+        size: u64,
+    },
+    /// Windows of `size` event times, starting at multiples of `slide`.
+    Hopping {
+        /// The number of event times in each window.
+        size: u64,
+        /// The number of event times between the starts of consecutive windows.
+        slide: u64,
+    },
+    /// Windows of the events of a key, each less than `gap` from the previous event.
+    ///
+    /// Each window starts at its first event, and ends `gap` after its last event.
+    Session {
+        /// The number of event times after an event before the session ends.
+        gap: u64,
+    },
+}
+
+impl WindowSpec {
+    /// Windows of `size` event times, starting at multiples of `size`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn tumbling(size: u64) -> Self {
+        let spec = WindowSpec::Tumbling { size };
+        spec.validate();
+        spec
+    }
+    /// Windows of `size` event times, starting at multiples of `slide`.
+    ///
+    /// If `slide` exceeds `size` the windows do not cover all event times, and events between
+    /// windows are in no window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` or `slide` is zero.
+    pub fn hopping(size: u64, slide: u64) -> Self {
+        let spec = WindowSpec::Hopping { size, slide };
+        spec.validate();
+        spec
+    }
+    /// Windows of the events of a key, each less than `gap` from the previous event.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gap` is zero.
+    pub fn session(gap: u64) -> Self {
+        let spec = WindowSpec::Session { gap };
+        spec.validate();
+        spec
+    }
+
+    /// Asserts that the windows are non-empty, and that hopping windows have a non-zero slide.
+    fn validate(&self) {
+        match *self {
+            WindowSpec::Tumbling { size } => assert!(size > 0, "tumbling windows must have a non-zero size"),
+            WindowSpec::Hopping { size, slide } => {
+                assert!(size > 0, "hopping windows must have a non-zero size");
+                assert!(slide > 0, "hopping windows must have a non-zero slide");
+            },
+            WindowSpec::Session { gap } => assert!(gap > 0, "session windows must have a non-zero gap"),
+        }
+    }
+
+    /// The fixed windows containing `event`, from latest to earliest.
+    ///
+    /// Session windows depend on other events, and are not reported by this method.
+    fn windows(&self, event: u64, windows: &mut Vec<Window>) {
+        let (size, slide) = match *self {
+            WindowSpec::Tumbling { size } => (size, size),
+            WindowSpec::Hopping { size, slide } => (size, slide),
+            WindowSpec::Session { .. } => { return; },
+        };
+        let mut start = event - event % slide;
+        while start.saturating_add(size) > event {
+            windows.push(Window { start, end: start.saturating_add(size) });
+            if start < slide { break; }
+            start -= slide;
+        }
+    }
+}
+
+/// Extension trait for the `window` differential dataflow method.
+pub trait Windows<G: Scope, K: ExchangeData, V: ExchangeData, R: Semigroup> where G::Timestamp: EventTime {
+    /// Accumulates the differences of the records of each key in windows of event time.
+    ///
+    /// The function `event_time` extracts the event time of each value. Once the event time of a timestamp
+    /// reaches the end of a window plus `lateness`, updates at that timestamp no longer change the window,
+    /// and once the input frontier reaches such a timestamp the state for the window is discarded.
+    ///
+    /// Late updates are discarded without being reported. For fixed windows, an update is discarded for
+    /// each of its windows that is closed at its timestamp. For session windows, an update to an event is
+    /// discarded if the session of the event alone, from the event to `gap` after it, is closed at its
+    /// timestamp; otherwise, if the session it would join has closed, it starts a new session rather than
+    /// extending the closed session.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `spec` describes empty windows, or hopping windows with a zero slide.
+    fn window<E>(&self, spec: WindowSpec, lateness: u64, event_time: E) -> Collection<G, (K, Window, R), isize>
+    where E: FnMut(&V)->u64+'static;
+}
+
+impl<G, K, V, R> Windows<G, K, V, R> for Collection<G, (K, V), R>
+where
+    G: Scope,
+    G::Timestamp: EventTime,
+    K: ExchangeData+Hashable,
+    V: ExchangeData,
+    R: ExchangeData+Semigroup,
+{
+    fn window<E>(&self, spec: WindowSpec, lateness: u64, mut event_time: E) -> Collection<G, (K, Window, R), isize>
+    where E: FnMut(&V)->u64+'static {
+
+        spec.validate();
+
+        let exchange = Exchange::new(|update: &((K,V),G::Timestamp,R)| (update.0).0.hashed().into());
+        self.inner.unary_frontier(exchange, "Window", move |_,_| {
+
+            // Holds back output times, and is always less or equal to the times of pending updates.
+            let mut capability: Option<Capability<G::Timestamp>> = None;
+            let mut pending = Vec::new();
+            let mut state = WindowState::<K, R>::new(spec, lateness);
+            let mut changes = Vec::new();
+
+            move |input, output| {
+
+                input.for_each(|cap, data| {
+                    if capability.as_ref().map(|c| cap.time() < c.time()).unwrap_or(true) {
+                        capability = Some(cap.retain());
+                    }
+                    for ((key, val), time, diff) in data.drain(..) {
+                        let event = event_time(&val);
+                        pending.push((time, key, event, diff));
+                    }
+                });
+
+                // Process updates at times the input frontier has passed, in order of time.
+                let (mut ready, unready): (Vec<_>, Vec<_>) = pending.drain(..).partition(|(time,_,_,_)| !input.frontier().less_equal(time));
+                pending = unready;
+                ready.sort_by(|x, y| x.0.cmp(&y.0));
+
+                let mut ready = ready.into_iter().peekable();
+                while let Some((time, key, event, diff)) = ready.next() {
+                    let mut updates = vec![(key, event, diff)];
+                    while ready.peek().map(|x| x.0 == time).unwrap_or(false) {
+                        let (_, key, event, diff) = ready.next().unwrap();
+                        updates.push((key, event, diff));
+                    }
+                    state.update(updates, time.event_time(), &mut changes);
+                    if !changes.is_empty() {
+                        let capability = capability.as_ref().expect("updates without capability").delayed(&time);
+                        let mut session = output.session(&capability);
+                        for (data, diff) in changes.drain(..) {
+                            session.give((data, time.clone(), diff));
+                        }
+                    }
+                }
+
+                // Discard the state of windows closed at all future times.
+                state.expire(input.frontier().frontier().first().map(|time| time.event_time()));
+
+                // Downgrade the capability to the least pending time, if any.
+                if let Some(time) = pending.iter().map(|(time,_,_,_)| time).min() {
+                    capability.as_mut().expect("updates without capability").downgrade(time);
+                }
+                else {
+                    capability = None;
+                }
+            }
+        })
+        .as_collection()
+    }
+}
+
+/// The per-key window state of the `window` operator.
+struct WindowState<K, R> {
+    spec: WindowSpec,
+    lateness: u64,
+    /// Accumulations for each key and fixed window.
+    aggregates: BTreeMap<(K, Window), R>,
+    /// Accumulations for each key and event time, for session windows.
+    events: BTreeMap<K, BTreeMap<u64, R>>,
+    /// Re-used allocation for windows containing an event.
+    windows: Vec<Window>,
+}
+
+impl<K: Ord+Clone, R: Semigroup+Eq> WindowState<K, R> {
+
+    fn new(spec: WindowSpec, lateness: u64) -> Self {
+        WindowState {
+            spec,
+            lateness,
+            aggregates: BTreeMap::new(),
+            events: BTreeMap::new(),
+            windows: Vec::new(),
+        }
+    }
+
+    /// Indicates whether `window` is closed once event time reaches `watermark`.
+    fn closed(&self, window: &Window, watermark: u64) -> bool {
+        window.end.saturating_add(self.lateness) <= watermark
+    }
+
+    /// Applies updates of `(key, event, diff)` at a timestamp with event time `watermark`, and reports changes.
+    fn update(&mut self, updates: Vec<(K, u64, R)>, watermark: u64, changes: &mut Vec<((K, Window, R), isize)>) {
+        match self.spec {
+            WindowSpec::Session { gap } => {
+                let mut by_key = BTreeMap::<K, Vec<(u64, R)>>::new();
+                for (key, event, diff) in updates {
+                    if !self.closed(&Window { start: event, end: event.saturating_add(gap) }, watermark) {
+                        by_key.entry(key).or_default().push((event, diff));
+                    }
+                }
+                for (key, updates) in by_key {
+                    let events = self.events.entry(key.clone()).or_default();
+                    let before = sessions(events, gap);
+                    for (event, diff) in updates {
+                        events.entry(event)
+                              .and_modify(|accum| accum.plus_equals(&diff))
+                              .or_insert(diff);
+                    }
+                    events.retain(|_, diff| !diff.is_zero());
+                    let after = sessions(events, gap);
+                    if events.is_empty() {
+                        self.events.remove(&key);
+                    }
+                    for (window, diff) in before.iter() {
+                        if !after.contains(&(*window, diff.clone())) {
+                            changes.push(((key.clone(), *window, diff.clone()), -1));
+                        }
+                    }
+                    for (window, diff) in after.iter() {
+                        if !before.contains(&(*window, diff.clone())) {
+                            changes.push(((key.clone(), *window, diff.clone()), 1));
+                        }
+                    }
+                }
+            },
+            _ => {
+                let mut deltas = BTreeMap::<(K, Window), R>::new();
+                for (key, event, diff) in updates {
+                    self.windows.clear();
+                    self.spec.windows(event, &mut self.windows);
+                    for window in self.windows.iter() {
+                        if !self.closed(window, watermark) {
+                            deltas.entry((key.clone(), *window))
+                                  .and_modify(|delta| delta.plus_equals(&diff))
+                                  .or_insert_with(|| diff.clone());
+                        }
+                    }
+                }
+                for ((key, window), delta) in deltas {
+                    if !delta.is_zero() {
+                        let prev = self.aggregates.remove(&(key.clone(), window));
+                        let mut next = delta;
+                        if let Some(prev) = prev {
+                            next.plus_equals(&prev);
+                            changes.push(((key.clone(), window, prev), -1));
+                        }
+                        if !next.is_zero() {
+                            changes.push(((key.clone(), window, next.clone()), 1));
+                            self.aggregates.insert((key, window), next);
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    /// Discards the state of windows closed once event time reaches `watermark`, or all state if `None`.
+    fn expire(&mut self, watermark: Option<u64>) {
+        if let Some(watermark) = watermark {
+            let lateness = self.lateness;
+            self.aggregates.retain(|(_, window), _| window.end.saturating_add(lateness) > watermark);
+            if let WindowSpec::Session { gap } = self.spec {
+                for events in self.events.values_mut() {
+                    let closed = sessions(events, gap).into_iter().map(|(window, _)| window).filter(|window| window.end.saturating_add(lateness) <= watermark).collect::<Vec<_>>();
+                    events.retain(|event, _| !closed.iter().any(|window| window.start <= *event && *event < window.end));
+                }
+                self.events.retain(|_, events| !events.is_empty());
+            }
+        }
+        else {
+            self.aggregates.clear();
+            self.events.clear();
+        }
+    }
+}
+
+/// The session windows of accumulated events, and their accumulations.
+fn sessions<R: Semigroup>(events: &BTreeMap<u64, R>, gap: u64) -> Vec<(Window, R)> {
+    let mut result: Vec<(Window, R)> = Vec::new();
+    for (event, diff) in events.iter() {
+        match result.last_mut() {
+            Some((window, accum)) if *event < window.end => {
+                window.end = event.saturating_add(gap);
+                accum.plus_equals(diff);
+            },
+            _ => result.push((Window { start: *event, end: event.saturating_add(gap) }, diff.clone())),
+        }
+    }
+    result
+}
@@ -45,6 +45,8 @@ pub mod ord_neu;
 pub mod rhh;
 pub mod huffman_container;
 pub mod chunker;
+#[cfg(feature = "bincode")]
+pub mod spill;
 
 // Opinionated takes on default spines.
 pub use self::ord_neu::OrdValSpine as ValSpine;
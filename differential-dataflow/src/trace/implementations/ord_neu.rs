@@ -18,8 +18,8 @@ use crate::trace::rc_blanket_impls::RcBuilder;
 
 use super::{Update, Layout, Vector, TStack, Preferred};
 
-pub use self::val_batch::{OrdValBatch, OrdValBuilder};
-pub use self::key_batch::{OrdKeyBatch, OrdKeyBuilder};
+pub use self::val_batch::{OrdValBatch, OrdValBuilder, OrdValStorage};
+pub use self::key_batch::{OrdKeyBatch, OrdKeyBuilder, OrdKeyStorage};
 
 /// A trace implementation using a spine of ordered lists.
 pub type OrdValSpine<K, V, T, R> = Spine<Rc<OrdValBatch<Vector<((K,V),T,R)>>>>;
@@ -0,0 +1,664 @@
+//! Batches whose contents may be spilled to local files.
+//!
+//! The `SpillBatch` type wraps another batch implementation, and once a batch reaches a size
+//! determined by a `SpillPolicy` it writes the batch to a file and releases its memory. The file
+//! holds the batch as a sequence of pages, each containing the updates for a range of keys. A
+//! cursor reads only those pages it visits, locating the page for a sought key from an index of
+//! the first key of each page, and retains the pages it has read until it is dropped. A merge
+//! reads all pages of its inputs, and releases them once the merge completes.
+//!
+//! Batches only become large through merging in the `Spine`, which means that the larger and
+//! older batches are those that find their way to disk, whereas the recently introduced and
+//! small batches remain in memory.
+//!
+//! The types `SpillValSpine`, `SpillValBatcher`, and `SpillValBuilder` (and their `Key` analogues)
+//! can be supplied to `arrange_core` and its relatives just as `ColValSpine` and its partners are.
+//! Any batch type implementing `Paged` can be spilled; `OrdValBatch` and `OrdKeyBatch` do so.
+
+use std::cell::{OnceCell, RefCell};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use serde::{Deserialize, Serialize};
+use timely::progress::{Antichain, frontier::AntichainRef};
+
+use crate::IntoOwned;
+use crate::trace::{Batch, BatchReader, Builder, Cursor, Description, Merger};
+use crate::trace::implementations::spine_fueled::Spine;
+use crate::trace::implementations::ord_neu::{OrdValBatch, OrdValBuilder, OrdValBatcher, OrdValStorage};
+use crate::trace::implementations::ord_neu::{OrdKeyBatch, OrdKeyBuilder, OrdKeyBatcher, OrdKeyStorage};
+
+use super::{BatchContainer, Layout, Vector};
+
+/// A trace implementation using a spine of ordered lists, spilling large batches to disk.
+pub type SpillValSpine<K, V, T, R, P = DefaultSpill> = Spine<SpillBatch<OrdValBatch<Vector<((K,V),T,R)>>, P>>;
+/// A batcher for ordered lists.
+pub type SpillValBatcher<K, V, T, R> = OrdValBatcher<K, V, T, R>;
+/// A builder for ordered lists, spilling large batches to disk.
+pub type SpillValBuilder<K, V, T, R, P = DefaultSpill> = SpillBuilder<OrdValBuilder<Vector<((K,V),T,R)>, Vec<((K,V),T,R)>>, P>;
+
+/// A trace implementation for empty values using a spine of ordered lists, spilling large batches to disk.
+pub type SpillKeySpine<K, T, R, P = DefaultSpill> = Spine<SpillBatch<OrdKeyBatch<Vector<((K,()),T,R)>>, P>>;
+/// A batcher for ordered lists.
+pub type SpillKeyBatcher<K, T, R> = OrdKeyBatcher<K, T, R>;
+/// A builder for ordered lists, spilling large batches to disk.
+pub type SpillKeyBuilder<K, T, R, P = DefaultSpill> = SpillBuilder<OrdKeyBuilder<Vector<((K,()),T,R)>, Vec<((K,()),T,R)>>, P>;
+
+/// Determines which batches are spilled, and where they are written.
+pub trait SpillPolicy: 'static {
+    /// Batches with at least this many updates are written to disk.
+    fn threshold() -> usize;
+    /// Spilled batches are written in pages of at least this many updates, other than the last page.
+    fn page_size() -> usize;
+    /// The directory in which to write spilled batches.
+    fn directory() -> PathBuf;
+}
+
+/// Spills batches of at least one million updates to the system's temporary directory,
+/// in pages of at least sixteen thousand updates.
+pub struct DefaultSpill;
+
+impl SpillPolicy for DefaultSpill {
+    fn threshold() -> usize { 1 << 20 }
+    fn page_size() -> usize { 1 << 14 }
+    fn directory() -> PathBuf { std::env::temp_dir() }
+}
+
+/// A batch that can be divided into pages of consecutive keys, and reassembled from them.
+pub trait Paged: BatchReader + Serialize + for<'a> Deserialize<'a> + Sized {
+    /// An owned key, used to record the first key of each page.
+    type KeyOwned;
+    /// Divides the keys into ranges of at least `size` updates, other than the last range.
+    ///
+    /// The result lists the index of the first key of each range, followed by the number of keys.
+    fn paginate(&self, size: usize) -> Vec<usize>;
+    /// A batch with the keys at indexes `lower .. upper` and their updates, and the first of these keys.
+    ///
+    /// The page has the same description as `self`.
+    fn page(&self, lower: usize, upper: usize) -> (Self::KeyOwned, Self);
+    /// Compares an owned key to a key of the batch.
+    fn compare(owned: &Self::KeyOwned, key: Self::Key<'_>) -> Ordering;
+    /// Reassembles a batch from its pages, in order.
+    fn concatenate(pages: &[Rc<Self>], description: Description<Self::Time>) -> Self;
+}
+
+/// A batch written to a file as a sequence of pages, which is removed when the last reference is dropped.
+struct SpillFile<B: Paged> {
+    path: PathBuf,
+    /// The offset of each page in the file, followed by the length of the file.
+    offsets: Vec<u64>,
+    /// The first key of each page.
+    bounds: Vec<B::KeyOwned>,
+    /// The most recently read copy of each page, if it is still in use.
+    ///
+    /// This prevents concurrent users (e.g. a merge and a cursor) from reading separate copies.
+    resident: RefCell<Vec<Weak<B>>>,
+    /// The first error encountered reading a page, if any.
+    error: OnceCell<std::io::Error>,
+}
+
+impl<B: Paged> SpillFile<B> {
+    /// Writes `batch` to a new file in `directory`, in pages of at least `size` updates.
+    fn write(batch: &B, size: usize, directory: PathBuf) -> std::io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        std::fs::create_dir_all(&directory)?;
+        let name = format!("dd-spill-{}-{}.bin", std::process::id(), COUNTER.fetch_add(1, AtomicOrdering::Relaxed));
+        let mut writer = BufWriter::new(File::create(directory.join(&name))?);
+        // Constructed before writing, so that the file is removed should writing fail.
+        let mut file = SpillFile {
+            path: directory.join(name),
+            offsets: vec![0],
+            bounds: Vec::new(),
+            resident: RefCell::new(Vec::new()),
+            error: OnceCell::new(),
+        };
+        for range in batch.paginate(size).windows(2) {
+            let (bound, page) = batch.page(range[0], range[1]);
+            bincode::serialize_into(&mut writer, &page).map_err(std::io::Error::other)?;
+            file.offsets.push(writer.stream_position()?);
+            file.bounds.push(bound);
+        }
+        writer.flush()?;
+        file.resident = RefCell::new(file.bounds.iter().map(|_| Weak::new()).collect());
+        Ok(file)
+    }
+
+    /// The number of pages in the file.
+    fn pages(&self) -> usize { self.bounds.len() }
+
+    /// The index of the page that would contain `key`.
+    fn seek(&self, key: B::Key<'_>) -> usize {
+        self.bounds.partition_point(|bound| B::compare(bound, key) != Ordering::Greater).saturating_sub(1)
+    }
+
+    /// Reads the page at `index` from the file, or shares a copy already read.
+    ///
+    /// Should the page not be readable, the error is recorded and an empty page with `description`
+    /// is returned in its place. The empty page is not shared, so that later reads try again.
+    fn load(&self, index: usize, description: &Description<B::Time>) -> Rc<B> {
+        let resident = self.resident.borrow()[index].upgrade();
+        if let Some(page) = resident {
+            return page;
+        }
+        match self.read(index) {
+            Ok(page) => {
+                let page = Rc::new(page);
+                self.resident.borrow_mut()[index] = Rc::downgrade(&page);
+                page
+            },
+            Err(error) => {
+                let _ = self.error.set(error);
+                Rc::new(B::concatenate(&[], description.clone()))
+            },
+        }
+    }
+
+    /// Reads and decodes the bytes of the page at `index`.
+    fn read(&self, index: usize) -> std::io::Result<B> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offsets[index]))?;
+        let reader = BufReader::new(file.take(self.offsets[index+1] - self.offsets[index]));
+        bincode::deserialize_from(reader).map_err(std::io::Error::other)
+    }
+}
+
+impl<B: Paged> Drop for SpillFile<B> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The contents of a `SpillBatch`.
+enum Contents<B: Paged> {
+    /// The batch is held in memory.
+    Resident(Rc<B>),
+    /// The batch is held in a file, and some of its pages may have been read into memory by this handle.
+    Spilled {
+        file: Rc<SpillFile<B>>,
+        /// Pages read by cursors.
+        pages: Box<[OnceCell<Rc<B>>]>,
+        /// All pages reassembled, as read by merges.
+        whole: OnceCell<B>,
+    },
+}
+
+/// A batch that may reside in memory or in a file.
+///
+/// Clones of a spilled batch share the file, but not the memory used to read it back:
+/// each clone reads the pages it needs when they are first used, and releases the memory
+/// when dropped. This allows the `Spine` to hold spilled batches without their contents,
+/// while cursors (which hold clones of the batches) read them as needed.
+pub struct SpillBatch<B: Paged, P = DefaultSpill> {
+    description: Description<B::Time>,
+    updates: usize,
+    contents: Contents<B>,
+    phantom: PhantomData<P>,
+}
+
+impl<B: Paged, P> Clone for SpillBatch<B, P> {
+    fn clone(&self) -> Self {
+        let contents = match &self.contents {
+            Contents::Resident(batch) => Contents::Resident(batch.clone()),
+            Contents::Spilled { file, .. } => Contents::Spilled {
+                file: file.clone(),
+                pages: (0 .. file.pages()).map(|_| OnceCell::new()).collect(),
+                whole: OnceCell::new(),
+            },
+        };
+        SpillBatch {
+            description: self.description.clone(),
+            updates: self.updates,
+            contents,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<B: Paged, P> SpillBatch<B, P> {
+    /// Wraps `batch` without consulting the spill policy.
+    pub fn resident(batch: B) -> Self {
+        SpillBatch {
+            description: batch.description().clone(),
+            updates: batch.len(),
+            contents: Contents::Resident(Rc::new(batch)),
+            phantom: PhantomData,
+        }
+    }
+    /// Wraps `batch`, writing it to a file if the spill policy indicates that we should.
+    ///
+    /// Should the file not be writeable, the batch is retained in memory.
+    pub fn new(batch: B) -> Self where P: SpillPolicy {
+        if batch.is_empty() || batch.len() < P::threshold() {
+            return Self::resident(batch);
+        }
+        match SpillFile::write(&batch, P::page_size(), P::directory()) {
+            Ok(file) => SpillBatch {
+                description: batch.description().clone(),
+                updates: batch.len(),
+                contents: Contents::Spilled {
+                    pages: (0 .. file.pages()).map(|_| OnceCell::new()).collect(),
+                    file: Rc::new(file),
+                    whole: OnceCell::new(),
+                },
+                phantom: PhantomData,
+            },
+            Err(_) => Self::resident(batch),
+        }
+    }
+    /// True when the batch contents are held in a file.
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.contents, Contents::Spilled { .. })
+    }
+    /// The first error encountered reading the batch from its file, if any.
+    ///
+    /// Pages that could not be read are presented as empty by cursors and merges, and so once
+    /// this reports an error, the contents of the batch (and of batches merged from it) are incomplete.
+    pub fn error(&self) -> Option<&std::io::Error> {
+        match &self.contents {
+            Contents::Resident(_) => None,
+            Contents::Spilled { file, .. } => file.error.get(),
+        }
+    }
+    /// The batch contents, reading all pages from the file if the batch has been spilled.
+    pub fn batch(&self) -> &B {
+        match &self.contents {
+            Contents::Resident(batch) => batch,
+            Contents::Spilled { file, whole, .. } => whole.get_or_init(|| {
+                let pages = (0 .. file.pages()).map(|index| file.load(index, &self.description)).collect::<Vec<_>>();
+                B::concatenate(&pages, self.description.clone())
+            }),
+        }
+    }
+    /// The number of pages of the batch; a resident batch is a single page.
+    fn pages(&self) -> usize {
+        match &self.contents {
+            Contents::Resident(_) => 1,
+            Contents::Spilled { file, .. } => file.pages(),
+        }
+    }
+    /// The page at `index`, read from the file if it has not yet been read.
+    fn page(&self, index: usize) -> &B {
+        match &self.contents {
+            Contents::Resident(batch) => batch,
+            Contents::Spilled { file, pages, .. } => pages[index].get_or_init(|| file.load(index, &self.description)),
+        }
+    }
+    /// The index of the page that would contain `key`.
+    fn seek(&self, key: B::Key<'_>) -> usize {
+        match &self.contents {
+            Contents::Resident(_) => 0,
+            Contents::Spilled { file, .. } => file.seek(key),
+        }
+    }
+}
+
+impl<B, P> BatchReader for SpillBatch<B, P>
+where
+    B: Paged,
+    for<'a> B::Val<'a>: Ord,
+    P: 'static,
+{
+    type Key<'a> = B::Key<'a>;
+    type Val<'a> = B::Val<'a>;
+    type Time = B::Time;
+    type TimeGat<'a> = B::TimeGat<'a>;
+    type Diff = B::Diff;
+    type DiffGat<'a> = B::DiffGat<'a>;
+
+    type Cursor = SpillCursor<B, P>;
+    fn cursor(&self) -> Self::Cursor {
+        SpillCursor {
+            page: 0,
+            cursor: OnceCell::new(),
+            phantom: PhantomData,
+        }
+    }
+    fn len(&self) -> usize { self.updates }
+    fn description(&self) -> &Description<B::Time> { &self.description }
+}
+
+impl<B, P> Batch for SpillBatch<B, P>
+where
+    B: Batch + Paged,
+    for<'a> B::Val<'a>: Ord,
+    P: SpillPolicy,
+{
+    type Merger = SpillMerger<B, P>;
+
+    fn empty(lower: Antichain<Self::Time>, upper: Antichain<Self::Time>) -> Self {
+        Self::resident(B::empty(lower, upper))
+    }
+}
+
+/// A cursor over a `SpillBatch`.
+///
+/// The cursor moves through the pages of the batch, and only forms a cursor into a page
+/// once it is first used, so that acquiring cursors does not require reading spilled batches.
+pub struct SpillCursor<B: Paged, P> {
+    /// The index of the page the cursor is in.
+    page: usize,
+    /// A cursor into the page, formed when first used.
+    cursor: OnceCell<B::Cursor>,
+    phantom: PhantomData<P>,
+}
+
+impl<B: Paged, P> SpillCursor<B, P> {
+    fn inner(&self, storage: &SpillBatch<B, P>) -> &B::Cursor {
+        self.cursor.get_or_init(|| storage.page(self.page).cursor())
+    }
+    fn inner_mut(&mut self, storage: &SpillBatch<B, P>) -> &mut B::Cursor {
+        if self.cursor.get().is_none() {
+            let _ = self.cursor.set(storage.page(self.page).cursor());
+        }
+        self.cursor.get_mut().unwrap()
+    }
+    /// Moves to the first key of the page at `index`.
+    fn move_to(&mut self, index: usize) {
+        self.page = index;
+        self.cursor = OnceCell::new();
+    }
+    /// Moves past exhausted pages, so that keys are only invalid once the last page is exhausted.
+    fn settle(&mut self, storage: &SpillBatch<B, P>) {
+        while self.page + 1 < storage.pages() && !self.inner(storage).key_valid(storage.page(self.page)) {
+            self.move_to(self.page + 1);
+        }
+    }
+}
+
+impl<B, P> Cursor for SpillCursor<B, P>
+where
+    B: Paged,
+    for<'a> B::Val<'a>: Ord,
+    P: 'static,
+{
+    type Key<'a> = B::Key<'a>;
+    type Val<'a> = B::Val<'a>;
+    type Time = B::Time;
+    type TimeGat<'a> = B::TimeGat<'a>;
+    type Diff = B::Diff;
+    type DiffGat<'a> = B::DiffGat<'a>;
+
+    type Storage = SpillBatch<B, P>;
+
+    #[inline] fn key_valid(&self, storage: &Self::Storage) -> bool { self.inner(storage).key_valid(storage.page(self.page)) }
+    #[inline] fn val_valid(&self, storage: &Self::Storage) -> bool { self.inner(storage).val_valid(storage.page(self.page)) }
+
+    #[inline] fn key<'a>(&self, storage: &'a Self::Storage) -> Self::Key<'a> { self.inner(storage).key(storage.page(self.page)) }
+    #[inline] fn val<'a>(&self, storage: &'a Self::Storage) -> Self::Val<'a> { self.inner(storage).val(storage.page(self.page)) }
+
+    #[inline]
+    fn map_times<L: FnMut(Self::TimeGat<'_>, Self::DiffGat<'_>)>(&mut self, storage: &Self::Storage, logic: L) {
+        let page = storage.page(self.page);
+        self.inner_mut(storage).map_times(page, logic)
+    }
+
+    #[inline]
+    fn step_key(&mut self, storage: &Self::Storage) {
+        let page = storage.page(self.page);
+        self.inner_mut(storage).step_key(page);
+        self.settle(storage);
+    }
+    #[inline]
+    fn seek_key(&mut self, storage: &Self::Storage, key: Self::Key<'_>) {
+        let target = storage.seek(key);
+        if target > self.page {
+            self.move_to(target);
+        }
+        let page = storage.page(self.page);
+        self.inner_mut(storage).seek_key(page, key);
+        self.settle(storage);
+    }
+
+    #[inline]
+    fn step_val(&mut self, storage: &Self::Storage) {
+        let page = storage.page(self.page);
+        self.inner_mut(storage).step_val(page)
+    }
+    #[inline]
+    fn seek_val(&mut self, storage: &Self::Storage, val: Self::Val<'_>) {
+        let page = storage.page(self.page);
+        self.inner_mut(storage).seek_val(page, val)
+    }
+
+    #[inline] fn rewind_keys(&mut self, _storage: &Self::Storage) { self.move_to(0) }
+    #[inline]
+    fn rewind_vals(&mut self, storage: &Self::Storage) {
+        let page = storage.page(self.page);
+        self.inner_mut(storage).rewind_vals(page)
+    }
+}
+
+/// Wrapper type for building batches that may spill.
+pub struct SpillBuilder<B: Builder, P = DefaultSpill> {
+    builder: B,
+    phantom: PhantomData<P>,
+}
+
+impl<B, P> Builder for SpillBuilder<B, P>
+where
+    B: Builder,
+    B::Output: Paged,
+    for<'a> <B::Output as BatchReader>::Val<'a>: Ord,
+    P: SpillPolicy,
+{
+    type Input = B::Input;
+    type Time = B::Time;
+    type Output = SpillBatch<B::Output, P>;
+
+    fn with_capacity(keys: usize, vals: usize, upds: usize) -> Self {
+        SpillBuilder { builder: B::with_capacity(keys, vals, upds), phantom: PhantomData }
+    }
+    fn push(&mut self, input: &mut Self::Input) { self.builder.push(input) }
+    fn done(self, description: Description<Self::Time>) -> Self::Output {
+        SpillBatch::new(self.builder.done(description))
+    }
+    fn seal(chain: &mut Vec<Self::Input>, description: Description<Self::Time>) -> Self::Output {
+        SpillBatch::new(B::seal(chain, description))
+    }
+}
+
+/// State for an in-progress merge of batches that may spill.
+///
+/// The merge reads both inputs into memory for its duration, and consults the spill
+/// policy when it completes.
+pub struct SpillMerger<B: Batch, P> {
+    merger: B::Merger,
+    phantom: PhantomData<P>,
+}
+
+impl<B, P> Merger<SpillBatch<B, P>> for SpillMerger<B, P>
+where
+    B: Batch + Paged,
+    for<'a> B::Val<'a>: Ord,
+    P: SpillPolicy,
+{
+    fn new(source1: &SpillBatch<B, P>, source2: &SpillBatch<B, P>, compaction_frontier: AntichainRef<B::Time>) -> Self {
+        SpillMerger {
+            merger: source1.batch().begin_merge(source2.batch(), compaction_frontier),
+            phantom: PhantomData,
+        }
+    }
+    fn work(&mut self, source1: &SpillBatch<B, P>, source2: &SpillBatch<B, P>, fuel: &mut isize) {
+        self.merger.work(source1.batch(), source2.batch(), fuel)
+    }
+    fn done(self) -> SpillBatch<B, P> {
+        SpillBatch::new(self.merger.done())
+    }
+}
+
+impl<L: Layout> Paged for OrdValBatch<L>
+where
+    OrdValBatch<L>: Serialize + for<'a> Deserialize<'a>,
+{
+    type KeyOwned = <L::KeyContainer as BatchContainer>::Owned;
+
+    fn paginate(&self, size: usize) -> Vec<usize> {
+        let storage = &self.storage;
+        let mut bounds = vec![0];
+        let mut updates = 0;
+        for key in 0 .. storage.keys.len() {
+            for val in storage.keys_offs.index(key) .. storage.keys_offs.index(key+1) {
+                // An empty range encodes a single update.
+                updates += std::cmp::max(storage.vals_offs.index(val+1) - storage.vals_offs.index(val), 1);
+            }
+            if updates >= size || key + 1 == storage.keys.len() {
+                bounds.push(key + 1);
+                updates = 0;
+            }
+        }
+        bounds
+    }
+    fn page(&self, lower: usize, upper: usize) -> (Self::KeyOwned, Self) {
+        let source = &self.storage;
+        let mut storage = val_storage(upper - lower, source.keys_offs.index(upper) - source.keys_offs.index(lower));
+        let updates = copy_vals(&mut storage, source, lower, upper);
+        let page = OrdValBatch { storage, description: self.description.clone(), updates };
+        (source.keys.index(lower).into_owned(), page)
+    }
+    fn compare(owned: &Self::KeyOwned, key: Self::Key<'_>) -> Ordering {
+        let owned = <<L::KeyContainer as BatchContainer>::ReadItem<'_> as IntoOwned>::borrow_as(owned);
+        L::KeyContainer::reborrow(owned).cmp(&L::KeyContainer::reborrow(key))
+    }
+    fn concatenate(pages: &[Rc<Self>], description: Description<Self::Time>) -> Self {
+        let keys = pages.iter().map(|page| page.storage.keys.len()).sum();
+        let vals = pages.iter().map(|page| page.storage.vals.len()).sum();
+        let mut storage = val_storage(keys, vals);
+        let mut updates = 0;
+        for page in pages.iter() {
+            updates += copy_vals(&mut storage, &page.storage, 0, page.storage.keys.len());
+        }
+        OrdValBatch { storage, description, updates }
+    }
+}
+
+impl<L: Layout> Paged for OrdKeyBatch<L>
+where
+    OrdKeyBatch<L>: Serialize + for<'a> Deserialize<'a>,
+{
+    type KeyOwned = <L::KeyContainer as BatchContainer>::Owned;
+
+    fn paginate(&self, size: usize) -> Vec<usize> {
+        let storage = &self.storage;
+        let mut bounds = vec![0];
+        let mut updates = 0;
+        for key in 0 .. storage.keys.len() {
+            // An empty range encodes a single update.
+            updates += std::cmp::max(storage.keys_offs.index(key+1) - storage.keys_offs.index(key), 1);
+            if updates >= size || key + 1 == storage.keys.len() {
+                bounds.push(key + 1);
+                updates = 0;
+            }
+        }
+        bounds
+    }
+    fn page(&self, lower: usize, upper: usize) -> (Self::KeyOwned, Self) {
+        let source = &self.storage;
+        let mut storage = key_storage(upper - lower);
+        let updates = copy_keys(&mut storage, source, lower, upper);
+        let page = OrdKeyBatch { storage, description: self.description.clone(), updates };
+        (source.keys.index(lower).into_owned(), page)
+    }
+    fn compare(owned: &Self::KeyOwned, key: Self::Key<'_>) -> Ordering {
+        let owned = <<L::KeyContainer as BatchContainer>::ReadItem<'_> as IntoOwned>::borrow_as(owned);
+        L::KeyContainer::reborrow(owned).cmp(&L::KeyContainer::reborrow(key))
+    }
+    fn concatenate(pages: &[Rc<Self>], description: Description<Self::Time>) -> Self {
+        let keys = pages.iter().map(|page| page.storage.keys.len()).sum();
+        let mut storage = key_storage(keys);
+        let mut updates = 0;
+        for page in pages.iter() {
+            updates += copy_keys(&mut storage, &page.storage, 0, page.storage.keys.len());
+        }
+        OrdKeyBatch { storage, description, updates }
+    }
+}
+
+/// Empty storage for `keys` keys and `vals` values.
+fn val_storage<L: Layout>(keys: usize, vals: usize) -> OrdValStorage<L> {
+    let mut storage = OrdValStorage::<L> {
+        keys: L::KeyContainer::with_capacity(keys),
+        keys_offs: L::OffsetContainer::with_capacity(keys + 1),
+        vals: L::ValContainer::with_capacity(vals),
+        vals_offs: L::OffsetContainer::with_capacity(vals + 1),
+        times: L::TimeContainer::with_capacity(vals),
+        diffs: L::DiffContainer::with_capacity(vals),
+    };
+    storage.keys_offs.push(0);
+    storage.vals_offs.push(0);
+    storage
+}
+
+/// Empty storage for `keys` keys.
+fn key_storage<L: Layout>(keys: usize) -> OrdKeyStorage<L> {
+    let mut storage = OrdKeyStorage::<L> {
+        keys: L::KeyContainer::with_capacity(keys),
+        keys_offs: L::OffsetContainer::with_capacity(keys + 1),
+        times: L::TimeContainer::with_capacity(keys),
+        diffs: L::DiffContainer::with_capacity(keys),
+    };
+    storage.keys_offs.push(0);
+    storage
+}
+
+/// Copies the keys of `source` at indexes `lower .. upper` and their updates into `result`,
+/// returning the number of updates copied.
+fn copy_vals<L: Layout>(result: &mut OrdValStorage<L>, source: &OrdValStorage<L>, lower: usize, upper: usize) -> usize {
+    let mut updates = 0;
+    let first = source.keys_offs.index(lower);
+    for key in lower .. upper {
+        for val in source.keys_offs.index(key) .. source.keys_offs.index(key+1) {
+            let range = (source.vals_offs.index(val), source.vals_offs.index(val+1));
+            updates += copy_updates::<L>((&mut result.times, &mut result.diffs), (&source.times, &source.diffs), range, val == first);
+            result.vals.push(source.vals.index(val));
+            result.vals_offs.push(result.times.len());
+        }
+        result.keys.push(source.keys.index(key));
+        result.keys_offs.push(result.vals.len());
+    }
+    updates
+}
+
+/// Copies the keys of `source` at indexes `lower .. upper` and their updates into `result`,
+/// returning the number of updates copied.
+fn copy_keys<L: Layout>(result: &mut OrdKeyStorage<L>, source: &OrdKeyStorage<L>, lower: usize, upper: usize) -> usize {
+    let mut updates = 0;
+    for key in lower .. upper {
+        let range = (source.keys_offs.index(key), source.keys_offs.index(key+1));
+        updates += copy_updates::<L>((&mut result.times, &mut result.diffs), (&source.times, &source.diffs), range, key == lower);
+        result.keys.push(source.keys.index(key));
+        result.keys_offs.push(result.times.len());
+    }
+    updates
+}
+
+/// Copies the updates of `source` in `range` into `result`, returning the number of updates copied.
+///
+/// An empty range encodes the single update just before it, which `result` already holds as its
+/// most recent update unless this is the `first` range copied from `source`; in that case only we
+/// must copy the update.
+fn copy_updates<L: Layout>(
+    result: (&mut L::TimeContainer, &mut L::DiffContainer),
+    source: (&L::TimeContainer, &L::DiffContainer),
+    (lower, upper): (usize, usize),
+    first: bool,
+) -> usize {
+    if lower == upper {
+        if first {
+            result.0.push(source.0.index(lower - 1));
+            result.1.push(source.1.index(lower - 1));
+        }
+        1
+    }
+    else {
+        for index in lower .. upper {
+            result.0.push(source.0.index(index));
+            result.1.push(source.1.index(index));
+        }
+        upper - lower
+    }
+}
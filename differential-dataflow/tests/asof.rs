@@ -0,0 +1,138 @@
+use timely::dataflow::operators::capture::{Capture, Extract};
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::{AsOfJoin, Join, Reduce, Threshold};
+
+#[test]
+fn as_of_join_updates() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut trades, mut quotes, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (trades_input, trades) = scope.new_collection::<(u32, (u64, u32)), isize>();
+            let (quotes_input, quotes) = scope.new_collection::<(u32, (u64, u32)), isize>();
+            let joined = trades.as_of_join(&quotes, |trade| trade.0, |quote| quote.0);
+            (trades_input, quotes_input, joined.inner.capture())
+        });
+
+        quotes.insert((0, (10, 1)));
+        quotes.insert((0, (20, 2)));
+        trades.insert((0, (15, 100)));
+        trades.insert((0, (25, 200)));
+        // No quote precedes this trade.
+        trades.insert((0, (5, 300)));
+        trades.advance_to(1); quotes.advance_to(1);
+        // A more recent quote for the first trade.
+        quotes.insert((0, (12, 3)));
+        trades.advance_to(2); quotes.advance_to(2);
+        // The quote for the second trade is retracted, and a trade at the greatest time arrives.
+        quotes.remove((0, (20, 2)));
+        trades.insert((0, (u64::MAX, 400)));
+        trades.close(); quotes.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ((0, ((15, 100), (10, 1))), 0, 1),
+        ((0, ((15, 100), (10, 1))), 1, -1),
+        ((0, ((15, 100), (12, 3))), 1, 1),
+        ((0, ((25, 200), (12, 3))), 2, 1),
+        ((0, ((25, 200), (20, 2))), 0, 1),
+        ((0, ((25, 200), (20, 2))), 2, -1),
+        ((0, ((u64::MAX, 400), (12, 3))), 2, 1),
+    ]);
+}
+
+#[test]
+fn as_of_join_string_orders() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut trades, mut quotes, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (trades_input, trades) = scope.new_collection::<(u32, (String, u32)), isize>();
+            let (quotes_input, quotes) = scope.new_collection::<(u32, (String, u32)), isize>();
+            let joined = trades.as_of_join(&quotes, |trade| trade.0.clone(), |quote| quote.0.clone());
+            (trades_input, quotes_input, joined.inner.capture())
+        });
+
+        // Ties at an ordering key are broken by the greatest value, and multiplicities of quotes are ignored.
+        quotes.update((0, ("b".to_string(), 1)), 2);
+        quotes.insert((0, ("b".to_string(), 2)));
+        quotes.insert((0, ("d".to_string(), 3)));
+        trades.update((0, ("c".to_string(), 100)), 2);
+        trades.insert((0, ("a".to_string(), 200)));
+        trades.advance_to(1); quotes.advance_to(1);
+        // Retracting one copy of a quote leaves it present; retracting the tied greatest value changes the match.
+        quotes.remove((0, ("b".to_string(), 1)));
+        quotes.remove((0, ("b".to_string(), 2)));
+        trades.advance_to(2); quotes.advance_to(2);
+        // A trade is retracted, and a quote arrives between the remaining trade and the earlier quote.
+        trades.remove((0, ("c".to_string(), 100)));
+        quotes.insert((0, ("bb".to_string(), 4)));
+        trades.close(); quotes.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    let b = |val| ("b".to_string(), val);
+    let c = ("c".to_string(), 100);
+    assert_eq!(updates, vec![
+        ((0, (c.clone(), b(1))), 1, 2),
+        ((0, (c.clone(), b(1))), 2, -2),
+        ((0, (c.clone(), b(2))), 0, 2),
+        ((0, (c.clone(), b(2))), 1, -2),
+        ((0, (c.clone(), ("bb".to_string(), 4))), 2, 1),
+    ]);
+}
+
+#[test]
+fn as_of_join_reference() {
+
+    timely::execute_directly(|worker| {
+
+        let (mut trades, mut quotes) = worker.dataflow::<u64, _, _>(|scope| {
+            let (trades_input, trades) = scope.new_collection::<(u32, (u32, u32)), isize>();
+            let (quotes_input, quotes) = scope.new_collection::<(u32, (u32, u32)), isize>();
+            let joined = trades.as_of_join(&quotes, |trade| trade.0, |quote| quote.0);
+            // Each trade is matched with the greatest present quote at or before it, with the trade's multiplicity.
+            let reference = trades
+                .join(&quotes.distinct())
+                .filter(|(_, (trade, quote))| quote.0 <= trade.0)
+                .map(|(key, (trade, quote))| ((key, trade), quote))
+                .reduce(|_key, input, output| output.push((*input[input.len() - 1].0, input[0].1)))
+                .map(|((key, trade), quote)| (key, (trade, quote)));
+            joined.assert_eq(&reference);
+            (trades_input, quotes_input)
+        });
+
+        // Inserts and later retracts records with a simple linear congruential generator.
+        let mut state = 1u64;
+        let mut next = move |bound: u64| { state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); ((state >> 33) % bound) as u32 };
+        let mut history = Vec::new();
+        for round in 1 .. 20 {
+            for _ in 0 .. 10 {
+                let record = (next(3), (next(20), next(4)));
+                if next(2) == 0 { trades.insert(record); history.push((true, record)); }
+                else { quotes.insert(record); history.push((false, record)); }
+            }
+            for _ in 0 .. 5 {
+                let (trade, record) = history.swap_remove(next(history.len() as u64) as usize);
+                if trade { trades.remove(record); } else { quotes.remove(record); }
+            }
+            trades.advance_to(round); quotes.advance_to(round);
+            trades.flush(); quotes.flush();
+            worker.step();
+        }
+        trades.close(); quotes.close();
+        while worker.step() { }
+    });
+}
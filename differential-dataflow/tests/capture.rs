@@ -0,0 +1,243 @@
+#![cfg(feature = "bincode")]
+
+use timely::dataflow::operators::capture::{Capture, Extract};
+
+use differential_dataflow::input::Input;
+use differential_dataflow::capture::broker::{self, InMemoryBroker};
+
+#[test]
+fn broker_round_trip() {
+
+    let broker = InMemoryBroker::new();
+
+    let captured = timely::execute_directly(move |worker| {
+
+        let (mut input, _sink) = worker.dataflow::<u64,_,_>(|scope| {
+            let (input, data) = scope.new_collection::<String, isize>();
+            let sink = broker::create_sink(&data.consolidate().inner, 0, broker.producer("topic"));
+            (input, sink)
+        });
+
+        let (_source, captured) = worker.dataflow::<u64,_,_>(|scope| {
+            let (token, stream) = broker::create_source::<_, _, _, String, u64, isize>(scope.clone(), |activator| broker.consumer("topic", activator));
+            (token, stream.capture())
+        });
+
+        input.insert("hello".to_string());
+        input.insert("world".to_string());
+        input.advance_to(1);
+        input.remove("hello".to_string());
+        input.close();
+
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    updates.sort();
+    assert_eq!(updates, vec![
+        ("hello".to_string(), 0, 1),
+        ("hello".to_string(), 1, -1),
+        ("world".to_string(), 0, 1),
+    ]);
+}
+
+#[test]
+fn file_round_trip() {
+
+    use differential_dataflow::capture::{Message, Progress, Writer};
+    use differential_dataflow::capture::file::{self, FileSink, FileSource};
+
+    let directory = std::env::temp_dir().join(format!("dd-capture-file-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let messages: Vec<Message<String, u64, isize>> = vec![
+        Message::Updates(vec![("hello".to_string(), 0, 1), ("world".to_string(), 0, 1)]),
+        Message::Progress(Progress { lower: vec![0], upper: vec![1], counts: vec![(0, 2)] }),
+        Message::Updates(vec![("hello".to_string(), 1, -1)]),
+        Message::Progress(Progress { lower: vec![1], upper: vec![2], counts: vec![(1, 1)] }),
+    ];
+
+    // A small limit forces a new file for each message.
+    let mut sink = FileSink::new(&directory, "test", 16).unwrap();
+    let mut source = FileSource::<String, u64, isize>::new(&directory, "test");
+    for message in messages.iter() {
+        assert!(sink.poll(message).is_none());
+        assert_eq!(source.next().as_ref(), Some(message));
+        assert!(source.next().is_none());
+    }
+    assert!(file::file_path(&directory, "test", messages.len() - 1).exists());
+
+    // A new source reads all messages, across files.
+    let source = FileSource::<String, u64, isize>::new(&directory, "test");
+    assert_eq!(source.collect::<Vec<_>>(), messages);
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn file_incomplete_message() {
+
+    use std::io::Write;
+    use differential_dataflow::capture::{framing, Message, Progress, Writer};
+    use differential_dataflow::capture::file::{self, FileSink, FileSource};
+
+    let directory = std::env::temp_dir().join(format!("dd-capture-incomplete-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+
+    // A file ending partway through a message, as left by a failed write.
+    let message: Message<String, u64, isize> = Message::Progress(Progress { lower: vec![0], upper: vec![1], counts: vec![] });
+    let mut bytes = Vec::new();
+    framing::encode_header(&mut bytes);
+    framing::encode(&message, &mut bytes);
+    let mut partial = std::fs::File::create(file::file_path(&directory, "test", 0)).unwrap();
+    partial.write_all(&bytes[.. bytes.len() - 1]).unwrap();
+    drop(partial);
+
+    // The source waits for the rest of the message, until the sink retries it in a new file.
+    let mut source = FileSource::<String, u64, isize>::new(&directory, "test");
+    assert!(source.next().is_none());
+    let mut sink = FileSink::new(&directory, "test", 1 << 20).unwrap();
+    assert!(sink.poll(&message).is_none());
+    assert_eq!(source.next(), Some(message));
+    assert!(source.next().is_none());
+    assert!(source.error().is_none());
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn file_source_dataflow() {
+
+    use std::time::Duration;
+    use timely::dataflow::operators::{Inspect, Probe};
+    use differential_dataflow::capture::{Message, Progress, Writer};
+    use differential_dataflow::capture::file::{self, FileSink};
+
+    let directory = std::env::temp_dir().join(format!("dd-capture-dataflow-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let messages: Vec<Message<String, u64, isize>> = vec![
+        Message::Updates(vec![("hello".to_string(), 0, 1), ("world".to_string(), 0, 1)]),
+        Message::Progress(Progress { lower: vec![0], upper: vec![1], counts: vec![(0, 2)] }),
+        Message::Updates(vec![("hello".to_string(), 1, -1)]),
+        Message::Progress(Progress { lower: vec![1], upper: vec![2], counts: vec![(1, 1)] }),
+    ];
+
+    let path = directory.clone();
+    let updates = timely::execute_directly(move |worker| {
+
+        let updates = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let updates2 = updates.clone();
+        let (_token, probe) = worker.dataflow::<u64,_,_>(|scope| {
+            let (token, stream) = file::create_source::<_, _, String, u64, isize>(scope.clone(), &path, "test", Duration::from_millis(1));
+            let probe = stream.inspect(move |update| updates2.borrow_mut().push(update.clone())).probe();
+            (token, probe)
+        });
+
+        // Messages written after the source starts are found by polling.
+        for _ in 0 .. 10 { worker.step(); }
+        let mut sink = FileSink::new(&path, "test", 1 << 20).unwrap();
+        for message in messages.iter() {
+            assert!(sink.poll(message).is_none());
+        }
+        while probe.less_than(&2) { worker.step(); }
+
+        let mut updates = updates.borrow().clone();
+        updates.sort();
+        updates
+    });
+
+    assert_eq!(updates, vec![
+        ("hello".to_string(), 0, 1),
+        ("hello".to_string(), 1, -1),
+        ("world".to_string(), 0, 1),
+    ]);
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn tcp_round_trip() {
+
+    use std::net::{TcpListener, TcpStream};
+    use differential_dataflow::capture::{Message, Progress, Writer};
+    use differential_dataflow::capture::tcp::{TcpSink, TcpSource};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let connection = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (accepted, _) = listener.accept().unwrap();
+
+    let messages: Vec<Message<String, u64, isize>> = vec![
+        Message::Updates(vec![("hello".to_string(), 0, 1), ("world".to_string(), 0, 1)]),
+        Message::Progress(Progress { lower: vec![0], upper: vec![1], counts: vec![(0, 2)] }),
+        Message::Updates(vec![("hello".to_string(), 1, -1)]),
+        Message::Progress(Progress { lower: vec![1], upper: vec![2], counts: vec![(1, 1)] }),
+    ];
+
+    let mut sink = TcpSink::new(accepted).unwrap();
+    let mut source = TcpSource::<String, u64, isize>::new(connection).unwrap();
+    for message in messages.iter() {
+        while sink.poll(message).is_some() { }
+    }
+    assert!(Writer::<Message<String, u64, isize>>::done(&sink));
+    drop(sink);
+
+    // Read until the sink closes the connection.
+    let mut received = Vec::new();
+    while !source.closed() {
+        received.extend(source.by_ref());
+    }
+    assert_eq!(received, messages);
+    assert!(source.error().is_none());
+}
+
+#[test]
+fn tcp_oversized_frame() {
+
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use differential_dataflow::capture::framing;
+    use differential_dataflow::capture::tcp::TcpSource;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let connection = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (mut accepted, _) = listener.accept().unwrap();
+
+    // A frame whose length would overflow the reader's arithmetic.
+    let mut bytes = Vec::new();
+    framing::encode_header(&mut bytes);
+    bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+    accepted.write_all(&bytes).unwrap();
+
+    let mut source = TcpSource::<String, u64, isize>::new(connection).unwrap();
+    while !source.closed() {
+        assert!(source.next().is_none());
+    }
+    assert_eq!(source.error().map(|error| error.kind()), Some(std::io::ErrorKind::InvalidData));
+}
+
+#[test]
+fn tcp_peer_closed() {
+
+    use std::net::{TcpListener, TcpStream};
+    use differential_dataflow::capture::{Message, Progress, Writer};
+    use differential_dataflow::capture::tcp::TcpSink;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let connection = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (accepted, _) = listener.accept().unwrap();
+    drop(connection);
+
+    // Writes eventually fail, and the sink then discards messages rather than panic.
+    let message: Message<String, u64, isize> = Message::Progress(Progress { lower: vec![0], upper: vec![1], counts: vec![] });
+    let mut sink = TcpSink::new(accepted).unwrap();
+    while sink.error().is_none() {
+        sink.poll(&message);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(sink.poll(&message).is_none());
+    assert!(Writer::<Message<String, u64, isize>>::done(&sink));
+}
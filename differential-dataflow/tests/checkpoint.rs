@@ -0,0 +1,66 @@
+use timely::progress::Antichain;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::operators::arrange::checkpoint::{self, Checkpoint};
+use differential_dataflow::trace::TraceReader;
+use differential_dataflow::trace::cursor::Cursor;
+use differential_dataflow::trace::implementations::{ValBatcher, ValBuilder, ValSpine};
+
+/// Resumes an arrangement from a checkpoint, updates it, and compares it with an arrangement of the full history.
+#[test]
+fn checkpoint_resume() {
+
+    let history = [((0, 1), 0, 1), ((1, 2), 0, 1), ((2, 3), 0, 1), ((1, 2), 1, -1), ((3, 4), 1, 1)];
+    let updates = [((0, 1), 2, -1), ((4, 5), 2, 1), ((2, 3), 3, 1), ((3, 4), 3, -1)];
+
+    timely::execute(timely::Config::process(2), move |worker| {
+
+        // Arrange the history, and checkpoint it once time `1` is complete.
+        let (mut input, mut trace) = worker.dataflow::<u32,_,_>(|scope| {
+            let (input, data) = scope.new_collection::<(u32, u32), isize>();
+            (input, data.arrange_by_key().trace)
+        });
+        if worker.index() == 0 {
+            for &(data, time, diff) in history.iter() {
+                input.update_at(data, time, diff);
+            }
+        }
+        input.advance_to(2);
+        input.flush();
+        let mut upper = Antichain::new();
+        while { trace.read_upper(&mut upper); upper.less_equal(&1) } { worker.step(); }
+        let checkpoint = Checkpoint::<u32, u32, u32, isize>::from_trace(&mut trace);
+        assert_eq!(checkpoint.description.upper(), &Antichain::from_elem(2));
+        drop(trace);
+
+        // Resume from the checkpoint, and separately arrange the history, applying the same updates to each.
+        let (mut resumed_input, mut resumed, mut reference_input, mut reference) = worker.dataflow::<u32,_,_>(|scope| {
+            let (resumed_input, resumed) = scope.new_collection::<(u32, u32), isize>();
+            let resumed = checkpoint::arrange_from_checkpoint::<_, _, _, _, ValBatcher<_,_,_,_>, ValBuilder<_,_,_,_>, ValSpine<_,_,_,_>>(&resumed, checkpoint);
+            let (reference_input, reference) = scope.new_collection::<(u32, u32), isize>();
+            (resumed_input, resumed.trace, reference_input, reference.arrange_by_key().trace)
+        });
+        resumed_input.advance_to(2);
+        if worker.index() == 0 {
+            for &(data, time, diff) in history.iter() {
+                reference_input.update_at(data, time, diff);
+            }
+            for &(data, time, diff) in updates.iter() {
+                resumed_input.update_at(data, time, diff);
+                reference_input.update_at(data, time, diff);
+            }
+        }
+        resumed_input.advance_to(4);
+        resumed_input.flush();
+        reference_input.advance_to(4);
+        reference_input.flush();
+        while { resumed.read_upper(&mut upper); upper.less_equal(&3) } { worker.step(); }
+        while { reference.read_upper(&mut upper); upper.less_equal(&3) } { worker.step(); }
+
+        let (mut cursor, storage) = resumed.cursor();
+        let (mut reference_cursor, reference_storage) = reference.cursor();
+        assert_eq!(cursor.to_vec(&storage), reference_cursor.to_vec(&reference_storage));
+
+    }).unwrap();
+}
@@ -98,4 +98,123 @@ fn join_scaling(scale: u64) {
 
     let extracted = data.extract();
     assert_eq!(extracted.len(), 0);
-}
\ No newline at end of file
+}
+
+#[test]
+fn left_join_retractions() {
+
+    use differential_dataflow::input::Input;
+    use differential_dataflow::operators::OuterJoin;
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut left, mut right, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (left_input, left) = scope.new_collection::<(u32, u32), isize>();
+            let (right_input, right) = scope.new_collection::<(u32, char), isize>();
+            (left_input, right_input, left.left_join(&right).inner.capture())
+        });
+
+        left.insert((0, 10));
+        left.insert((1, 11));
+        right.insert((0, 'a'));
+        left.advance_to(1); right.advance_to(1);
+        // The match for key `0` is retracted, and a match for key `1` arrives.
+        right.remove((0, 'a'));
+        right.insert((1, 'b'));
+        right.insert((1, 'c'));
+        left.close(); right.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ((0, (10, None)), 1, 1),
+        ((0, (10, Some('a'))), 0, 1),
+        ((0, (10, Some('a'))), 1, -1),
+        ((1, (11, None)), 0, 1),
+        ((1, (11, None)), 1, -1),
+        ((1, (11, Some('b'))), 1, 1),
+        ((1, (11, Some('c'))), 1, 1),
+    ]);
+}
+
+#[test]
+fn full_outer_join_core_arrangements() {
+
+    use differential_dataflow::input::Input;
+    use differential_dataflow::operators::arrange::ArrangeByKey;
+    use differential_dataflow::operators::{OuterJoin, OuterJoinCore};
+
+    timely::execute_directly(|worker| {
+
+        let (mut left, mut right) = worker.dataflow::<u64, _, _>(|scope| {
+            let (left_input, left) = scope.new_collection::<(u32, u32), isize>();
+            let (right_input, right) = scope.new_collection::<(u32, char), isize>();
+            // Arrangements of both inputs are shared by each outer join.
+            let left_arranged = left.arrange_by_key();
+            let right_arranged = right.arrange_by_key();
+            left_arranged.left_join_core(&right_arranged).assert_eq(&left.left_join(&right));
+            left_arranged.right_join_core(&right_arranged).assert_eq(&left.right_join(&right));
+            left_arranged.full_outer_join_core(&right_arranged).assert_eq(&left.full_outer_join(&right));
+            (left_input, right_input)
+        });
+
+        left.insert((0, 10));
+        left.insert((1, 11));
+        right.insert((0, 'a'));
+        right.insert((2, 'c'));
+        left.advance_to(1); right.advance_to(1);
+        right.remove((0, 'a'));
+        right.insert((1, 'b'));
+        left.remove((1, 11));
+        left.insert((2, 12));
+        left.close(); right.close();
+        while worker.step() { }
+    });
+}
+
+#[test]
+fn join_range_matches_filtered_join() {
+
+    use differential_dataflow::input::Input;
+    use differential_dataflow::operators::JoinRange;
+
+    timely::execute_directly(|worker| {
+
+        let (mut left, mut right) = worker.dataflow::<u64, _, _>(|scope| {
+            let (left_input, left) = scope.new_collection::<(u32, u32), isize>();
+            let (right_input, right) = scope.new_collection::<(u32, u32), isize>();
+            let expected = left.join(&right).filter(|(_, (v1, v2))| v1.saturating_sub(3) <= *v2 && *v2 < v1 + 4);
+            left.join_range(&right, |v| v.saturating_sub(3) .. v + 4)
+                .assert_eq(&expected);
+            (left_input, right_input)
+        });
+
+        // Insert and later remove records in both inputs, at several rounds.
+        let mut state = 17u64;
+        let mut next = move || { state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); (state >> 33) as u32 };
+        let mut inserted = Vec::new();
+        for round in 1 .. 20u64 {
+            for _ in 0 .. 10 {
+                let record = (next() % 3, next() % 40);
+                if next() % 2 == 0 { left.insert(record); inserted.push((true, record)); }
+                else { right.insert(record); inserted.push((false, record)); }
+            }
+            if round > 5 {
+                for _ in 0 .. 5 {
+                    let (is_left, record) = inserted.remove(next() as usize % inserted.len());
+                    if is_left { left.remove(record); } else { right.remove(record); }
+                }
+            }
+            left.advance_to(round); right.advance_to(round);
+            left.flush(); right.flush();
+            worker.step();
+        }
+        left.close(); right.close();
+        while worker.step() { }
+    });
+}
@@ -0,0 +1,57 @@
+use timely::dataflow::operators::capture::{Capture, Extract};
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::MinMax;
+
+#[test]
+fn min_max_retractions() {
+
+    let (min, max) = timely::execute_directly(|worker| {
+
+        let (mut input, min, max) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, data) = scope.new_collection::<(u32, u32), isize>();
+            (input, data.min().inner.capture(), data.max().inner.capture())
+        });
+
+        input.insert((0, 5));
+        input.insert((0, 3));
+        input.insert((0, 8));
+        input.insert((0, 3));
+        input.insert((1, 7));
+        input.advance_to(1);
+        // Retract one copy of the least value, which remains, and the greatest value, which must be replaced.
+        input.remove((0, 3));
+        input.remove((0, 8));
+        // Retract the only value of a key.
+        input.remove((1, 7));
+        input.advance_to(2);
+        input.remove((0, 3));
+        input.insert((0, 9));
+        input.close();
+        while worker.step() { }
+
+        (min, max)
+    });
+
+    let mut min = min.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut min);
+    assert_eq!(min, vec![
+        ((0, 3), 0, 1),
+        ((0, 3), 2, -1),
+        ((0, 5), 2, 1),
+        ((1, 7), 0, 1),
+        ((1, 7), 1, -1),
+    ]);
+
+    let mut max = max.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut max);
+    assert_eq!(max, vec![
+        ((0, 5), 1, 1),
+        ((0, 5), 2, -1),
+        ((0, 8), 0, 1),
+        ((0, 8), 1, -1),
+        ((0, 9), 2, 1),
+        ((1, 7), 0, 1),
+        ((1, 7), 1, -1),
+    ]);
+}
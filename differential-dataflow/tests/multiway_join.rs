@@ -0,0 +1,98 @@
+use timely::dataflow::operators::capture::{Capture, Extract};
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::{Join, JoinCore};
+use differential_dataflow::operators::arrange::ArrangeBySelf;
+use differential_dataflow::operators::multiway_join::multiway_join;
+
+#[test]
+fn triangles_match_binary_joins() {
+
+    timely::execute_directly(|worker| {
+
+        let mut input = worker.dataflow::<u64, _, _>(|scope| {
+
+            let (input, edges) = scope.new_collection::<(u32, u32), isize>();
+
+            // Triangles with binary joins: edge(a, b), edge(b, c), edge(a, c), with repeated edges.
+            let expected =
+            edges
+                .map(|(a, b)| (b, a))
+                .join(&edges)
+                .map(|(b, (a, c))| ((a, c), b))
+                .join_core(&edges.arrange_by_self(), |&(a, c), &b, &()| Some(vec![a, b, c]));
+
+            let tuples = edges.map(|(x, y)| vec![x, y]).arrange_by_self();
+            let relations = vec![
+                (tuples.clone(), vec![0, 1]),
+                (tuples.clone(), vec![1, 2]),
+                (tuples.clone(), vec![0, 2]),
+            ];
+            multiway_join(&relations, &[0, 1, 2])
+                .assert_eq(&expected);
+
+            input
+        });
+
+        // Changes to the same edges arrive at each relation at the same time.
+        let mut state = 7u64;
+        let mut next = move || { state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); (state >> 33) as u32 };
+        let mut inserted = Vec::new();
+        for round in 1 .. 20u64 {
+            for _ in 0 .. 10 {
+                let edge = (next() % 8, next() % 8);
+                input.insert(edge);
+                inserted.push(edge);
+            }
+            for _ in 0 .. 3 {
+                let edge = inserted.remove(next() as usize % inserted.len());
+                input.remove(edge);
+            }
+            input.advance_to(round);
+            input.flush();
+            worker.step();
+        }
+        input.close();
+        while worker.step() { }
+    });
+}
+
+#[test]
+fn output_follows_order() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut input, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, edges) = scope.new_collection::<(u32, u32), isize>();
+            let tuples = edges.map(|(x, y)| vec![x, y]).arrange_by_self();
+            // Paths of length two, path(x, y, z) := edge(x, y), edge(y, z), with variables listed as z, x, y.
+            let (x, y, z) = (5, 9, 3);
+            let relations = vec![
+                (tuples.clone(), vec![x, y]),
+                (tuples.clone(), vec![y, z]),
+            ];
+            (input, multiway_join(&relations, &[z, x, y]).inner.capture())
+        });
+
+        input.insert((1, 2));
+        input.insert((2, 3));
+        input.insert((2, 3));
+        input.advance_to(1);
+        input.insert((3, 4));
+        input.remove((1, 2));
+        input.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    // The repeated edge (2, 3) yields its path twice, and contributes two paths to (3, 4).
+    assert_eq!(updates, vec![
+        (vec![3, 1, 2], 0, 2),
+        (vec![3, 1, 2], 1, -2),
+        (vec![4, 2, 3], 1, 2),
+    ]);
+}
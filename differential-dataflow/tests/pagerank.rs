@@ -0,0 +1,182 @@
+use rand::{Rng, SeedableRng, StdRng};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use timely::dataflow::operators::Capture;
+use timely::dataflow::operators::capture::Extract;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::algorithms::graphs::pagerank::{self, Config};
+
+type Node = usize;
+type Edge = (Node, Node);
+
+#[test] fn pagerank_10_20_10_converged() { test_sizes(10, 20, 10, Config::default(), false, 1); }
+#[test] fn pagerank_10_20_10_iterations() { test_sizes(10, 20, 10, Config { iterations: Some(5), ..Config::default() }, false, 1); }
+#[test] fn pagerank_10_20_10_threshold() { test_sizes(10, 20, 10, Config { threshold: 1000, ..Config::default() }, false, 1); }
+#[test] fn pagerank_10_20_10_zero_threshold() { test_sizes(10, 20, 10, Config { threshold: 0, ..Config::default() }, false, 1); }
+#[test] fn pagerank_100_400_5_threads() { test_sizes(100, 400, 5, Config { threshold: 100, ..Config::default() }, false, 3); }
+#[test] fn personalized_10_20_10_converged() { test_sizes(10, 20, 10, Config::default(), true, 1); }
+#[test] fn personalized_100_400_5_iterations() { test_sizes(100, 400, 5, Config { iterations: Some(10), ..Config::default() }, true, 3); }
+
+/// Compares the ranks computed by differential dataflow with those computed sequentially,
+/// for a random graph with one edge added and one removed in each round.
+fn test_sizes(nodes: usize, edges: usize, rounds: usize, config: Config, personalized: bool, threads: usize) {
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let mut current = BTreeSet::new();
+    let mut edge_list = Vec::new();
+    for _ in 0 .. edges {
+        let edge = (rng.gen_range(0, nodes), rng.gen_range(0, nodes));
+        if current.insert(edge) {
+            edge_list.push((edge, 0, 1));
+        }
+    }
+    for round in 1 .. rounds {
+        let edge = (rng.gen_range(0, nodes), rng.gen_range(0, nodes));
+        if current.insert(edge) {
+            edge_list.push((edge, round, 1));
+        }
+        let removed = *current.iter().nth(rng.gen_range(0, current.len())).unwrap();
+        current.remove(&removed);
+        edge_list.push((removed, round, -1));
+    }
+
+    let sources = vec![0, 1];
+    let updates = pagerank_differential(edge_list.clone(), sources.clone(), config, personalized, threads);
+
+    for round in 0 .. rounds {
+
+        let edges =
+        edge_list
+            .iter()
+            .filter(|(_, time, _)| *time <= round)
+            .fold(BTreeMap::new(), |mut edges, (edge, _, diff)| { *edges.entry(*edge).or_insert(0) += diff; edges })
+            .into_iter()
+            .filter(|(_, count)| *count != 0)
+            .map(|(edge, _)| edge)
+            .collect::<BTreeSet<_>>();
+
+        let expected = pagerank_sequential(&edges, &sources, config, personalized);
+
+        let mut computed = BTreeMap::new();
+        for (data, time, diff) in updates.iter() {
+            if *time <= round {
+                *computed.entry(*data).or_insert(0) += diff;
+            }
+        }
+        computed.retain(|_, rank| *rank != 0);
+
+        assert_eq!(computed, expected, "ranks differ in round {}", round);
+    }
+}
+
+fn pagerank_sequential(edges: &BTreeSet<Edge>, sources: &[Node], config: Config, personalized: bool) -> BTreeMap<(Node, Node), isize> {
+
+    let mut degrees = BTreeMap::new();
+    for (src, _dst) in edges.iter() {
+        *degrees.entry(*src).or_insert(0) += 1;
+    }
+
+    // surfers are (node, label) pairs, labeled by their source if personalized.
+    let mut resets = BTreeMap::new();
+    if personalized {
+        for source in sources.iter() {
+            resets.insert((*source, *source), config.reset());
+        }
+    }
+    else {
+        for (src, dst) in edges.iter() {
+            resets.insert((*src, 0), config.reset());
+            resets.insert((*dst, 0), config.reset());
+        }
+    }
+
+    let mut ranks = resets.clone();
+    let mut round = 0;
+    while config.iterations != Some(round) {
+        let mut next = resets.clone();
+        for (&(node, label), &rank) in ranks.iter() {
+            for (_src, dst) in edges.range((node, 0) .. (node + 1, 0)) {
+                *next.entry((*dst, label)).or_insert(0) += config.sent(rank, degrees[&node]);
+            }
+        }
+        next.retain(|_, rank| *rank != 0);
+        round += 1;
+        if next == ranks {
+            break;
+        }
+        ranks = next;
+    }
+
+    ranks
+}
+
+fn pagerank_differential(
+    edges_list: Vec<(Edge, usize, isize)>,
+    sources_list: Vec<Node>,
+    config: Config,
+    personalized: bool,
+    threads: usize,
+)
+-> Vec<((Node, Node), usize, isize)>
+{
+    let (send, recv) = ::std::sync::mpsc::channel();
+    let send = Arc::new(Mutex::new(send));
+
+    timely::execute(timely::Config::process(threads), move |worker| {
+
+        let mut edges_list = edges_list.clone();
+
+        let (mut sources, mut edges) = worker.dataflow(|scope| {
+
+            let send = send.lock().unwrap().clone();
+
+            let (source_input, sources) = scope.new_collection();
+            let (edge_input, edges) = scope.new_collection();
+
+            let ranks =
+            if personalized {
+                pagerank::personalized_pagerank(&edges, &sources, config)
+            }
+            else {
+                pagerank::pagerank(&edges, config).map(|node| (node, 0))
+            };
+
+            ranks.consolidate().inner.capture_into(send);
+
+            (source_input, edge_input)
+        });
+
+        if worker.index() == 0 {
+            for source in sources_list.iter() {
+                sources.insert(*source);
+            }
+        }
+
+        // sort by decreasing insertion time.
+        edges_list.sort_by_key(|x| std::cmp::Reverse(x.1));
+
+        let mut round = 0;
+        while !edges_list.is_empty() {
+            while edges_list.last().map(|x| x.1) == Some(round) {
+                let (edge, _time, diff) = edges_list.pop().unwrap();
+                if worker.index() == 0 {
+                    edges.update(edge, diff);
+                }
+            }
+            round += 1;
+            sources.advance_to(round);
+            edges.advance_to(round);
+        }
+
+    }).unwrap();
+
+    recv.extract()
+        .into_iter()
+        .flat_map(|(_, list)| list.into_iter())
+        .collect()
+}
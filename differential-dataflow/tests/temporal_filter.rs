@@ -0,0 +1,50 @@
+use timely::dataflow::Scope;
+use timely::dataflow::operators::capture::{Capture, Extract};
+use timely::order::Product;
+
+use differential_dataflow::input::Input;
+
+#[test]
+fn temporal_filter_product() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut input, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, records) = scope.new_collection::<(char, (u64, u64), (u64, u64)), isize>();
+            let captured = scope.iterative::<u64, _, _>(|inner| {
+                records
+                    .enter(inner)
+                    .temporal_filter(|(_, from, until)| (Product::new(from.0, from.1), Product::new(until.0, until.1)))
+                    .map(|(name, _, _)| name)
+                    .inner
+                    .capture()
+            });
+            (input, captured)
+        });
+
+        input.insert(('a', (0, 1), (1, 1)));
+        // Present from `(1, 0)` until `(1, 0).join((0, 2))`.
+        input.insert(('b', (1, 0), (0, 2)));
+        // An empty interval, as `(0, 0)` is less than `(1, 1)`.
+        input.insert(('c', (1, 1), (0, 0)));
+        input.advance_to(1);
+        // Present from the join of its time `(1, 0)` and `(0, 1)`.
+        input.insert(('d', (0, 1), (0, 3)));
+        input.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ('a', Product::new(0, 1), 1),
+        ('a', Product::new(1, 1), -1),
+        ('b', Product::new(1, 0), 1),
+        ('b', Product::new(1, 2), -1),
+        ('d', Product::new(1, 1), 1),
+        ('d', Product::new(1, 3), -1),
+    ]);
+}
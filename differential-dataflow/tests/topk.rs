@@ -0,0 +1,49 @@
+use timely::dataflow::operators::capture::{Capture, Extract};
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::TopK;
+
+#[test]
+fn top_k_retractions() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut input, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, data) = scope.new_collection::<(u32, u32), isize>();
+            (input, data.top_k(3, |x| *x).inner.capture())
+        });
+
+        for val in 0 .. 1000 {
+            input.insert((val % 2, val));
+        }
+        input.advance_to(1);
+        // Retract the least values, which must be replaced.
+        input.remove((0, 0));
+        input.remove((1, 1));
+        input.advance_to(2);
+        // Introduce a duplicate, which counts twice.
+        input.insert((0, 4));
+        input.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ((0, 0), 0, 1),
+        ((0, 0), 1, -1),
+        ((0, 2), 0, 1),
+        ((0, 4), 0, 1),
+        ((0, 4), 2, 1),
+        ((0, 6), 1, 1),
+        ((0, 6), 2, -1),
+        ((1, 1), 0, 1),
+        ((1, 1), 1, -1),
+        ((1, 3), 0, 1),
+        ((1, 5), 0, 1),
+        ((1, 7), 1, 1),
+    ]);
+}
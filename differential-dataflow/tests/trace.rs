@@ -56,3 +56,110 @@ fn test_trace() {
     let vec_4 = cursor4.to_vec(&storage4);
     assert_eq!(vec_4, vec_3);
 }
+
+#[test]
+fn test_cursor_list_seek_val() {
+
+    use differential_dataflow::trace::BatchReader;
+    use differential_dataflow::trace::cursor::CursorList;
+
+    let mut batcher = ValBatcher::<u64,u64,usize,i64>::new(None, 0);
+    batcher.push_container(&mut vec![((1, 1), 0, 1), ((1, 3), 0, 1)]);
+    let first = batcher.seal::<IntegerBuilder>(Antichain::from_elem(1));
+    batcher.push_container(&mut vec![((1, 2), 1, 1), ((2, 1), 1, 1), ((2, 3), 1, 1)]);
+    let second = batcher.seal::<IntegerBuilder>(Antichain::from_elem(2));
+
+    let storage = vec![first, second];
+    let mut cursor = CursorList::new(storage.iter().map(|batch| batch.cursor()).collect(), &storage);
+
+    // Values are sought in each cursor at the current key.
+    cursor.seek_val(&storage, &2);
+    assert_eq!(cursor.get_val(&storage), Some(&2));
+    cursor.step_val(&storage);
+    assert_eq!(cursor.get_val(&storage), Some(&3));
+
+    // Values are not sought in cursors without the current key, including those that have run out of keys.
+    cursor.step_key(&storage);
+    assert_eq!(cursor.get_key(&storage), Some(&2));
+    cursor.seek_val(&storage, &3);
+    assert_eq!(cursor.get_val(&storage), Some(&3));
+    cursor.step_val(&storage);
+    assert!(!cursor.val_valid(&storage));
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn test_spill_trace() {
+
+    use differential_dataflow::trace::implementations::spill::{SpillPolicy, SpillValBuilder, SpillValSpine};
+
+    struct SpillEverything;
+    impl SpillPolicy for SpillEverything {
+        fn threshold() -> usize { 1 }
+        fn page_size() -> usize { 3 }
+        fn directory() -> std::path::PathBuf { std::env::temp_dir().join("dd-spill-test") }
+    }
+
+    let op_info = OperatorInfo::new(0, 0, [].into());
+    let mut trace = SpillValSpine::<u64, u64, usize, i64, SpillEverything>::new(op_info.clone(), None, None);
+    let mut expected = IntegerTrace::new(op_info, None, None);
+    let mut batcher = ValBatcher::<u64,u64,usize,i64>::new(None, 0);
+    let mut expected_batcher = ValBatcher::<u64,u64,usize,i64>::new(None, 0);
+    for time in 0 .. 4 {
+        // Many keys with repeated updates, so that pages start with singleton updates.
+        let mut updates = (0 .. 10u64)
+            .flat_map(|key| (0 .. 3u64).map(move |val| ((key, val), time, if (key + val) % 4 == time as u64 { -1 } else { 1 })))
+            .collect::<Vec<_>>();
+        batcher.push_container(&mut updates.clone());
+        expected_batcher.push_container(&mut updates);
+        trace.insert(batcher.seal::<SpillValBuilder<u64, u64, usize, i64, SpillEverything>>(Antichain::from_elem(time + 1)));
+        expected.insert(expected_batcher.seal::<IntegerBuilder>(Antichain::from_elem(time + 1)));
+    }
+
+    let mut spilled = 0;
+    trace.map_batches(|batch| if batch.is_spilled() { spilled += 1; });
+    assert!(spilled > 0);
+
+    let (mut cursor, storage) = trace.cursor();
+    let (mut expected_cursor, expected_storage) = expected.cursor();
+    assert_eq!(cursor.to_vec(&storage), expected_cursor.to_vec(&expected_storage));
+
+    // Sought keys are found across pages, and values within them.
+    let (mut cursor, storage) = trace.cursor();
+    for key in [2, 3, 7, 9] {
+        cursor.seek_key(&storage, &key);
+        assert_eq!(cursor.get_key(&storage), Some(&key));
+        cursor.seek_val(&storage, &2);
+        assert_eq!(cursor.get_val(&storage), Some(&2));
+    }
+    cursor.seek_key(&storage, &10);
+    assert!(!cursor.key_valid(&storage));
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn test_spill_read_error() {
+
+    use differential_dataflow::trace::implementations::spill::{SpillPolicy, SpillValBuilder, SpillValSpine};
+
+    struct SpillEverything;
+    impl SpillPolicy for SpillEverything {
+        fn threshold() -> usize { 1 }
+        fn page_size() -> usize { 3 }
+        fn directory() -> std::path::PathBuf { std::env::temp_dir().join("dd-spill-error-test") }
+    }
+
+    let op_info = OperatorInfo::new(0, 0, [].into());
+    let mut trace = SpillValSpine::<u64, u64, usize, i64, SpillEverything>::new(op_info, None, None);
+    let mut batcher = ValBatcher::<u64,u64,usize,i64>::new(None, 0);
+    batcher.push_container(&mut (0 .. 10u64).map(|key| ((key, key), 0, 1)).collect::<Vec<_>>());
+    trace.insert(batcher.seal::<SpillValBuilder<u64, u64, usize, i64, SpillEverything>>(Antichain::from_elem(1)));
+
+    // Files removed from under the trace cannot be read, which is reported rather than panicking.
+    for entry in std::fs::read_dir(SpillEverything::directory()).expect("missing spill directory") {
+        std::fs::remove_file(entry.expect("failed to read spill directory").path()).expect("failed to remove spill file");
+    }
+    let (mut cursor, storage) = trace.cursor();
+    assert!(cursor.to_vec(&storage).is_empty());
+    assert_eq!(storage.iter().filter(|batch| batch.error().is_some()).count(), 1);
+}
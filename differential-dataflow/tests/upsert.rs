@@ -0,0 +1,143 @@
+use timely::dataflow::Scope;
+use timely::dataflow::operators::{ToStream, Capture, Enter, Map};
+use timely::dataflow::operators::capture::Extract;
+use timely::order::Product;
+
+use differential_dataflow::operators::arrange::upsert;
+use differential_dataflow::trace::implementations::{ValBuilder, ValSpine};
+
+#[test]
+fn upsert_partial_order() {
+
+    let captured = timely::example(|scope| {
+
+        let upserts = vec![
+            (0u64, Some(1u64), (0u64, 1u64)),
+            (0, Some(2), (1, 0)),
+            (1, Some(5), (0, 0)),
+            (1, None, (1, 1)),
+            (2, Some(9), (0, 1)),
+            (2, Some(3), (1, 0)),
+        ].into_iter().to_stream(scope);
+
+        scope.iterative::<u64, _, _>(|inner| {
+            let upserts = upserts.enter(inner).map(|(key, val, (outer, inner))| (key, val, Product::new(outer, inner)));
+            upsert::arrange_from_upsert::<_, _, _, ValBuilder<u64, u64, _, _>, ValSpine<u64, u64, _, _>>(&upserts, "Upsert")
+                .as_collection(|k, v| (*k, *v))
+                .inner
+                .capture()
+        })
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    // The concurrent upserts to keys `0` and `2` each apply at their own times, and from
+    // their join `(1, 1)` onward the greater of their values applies.
+    assert_eq!(updates, vec![
+        ((0, 1), Product::new(0, 1), 1),
+        ((0, 1), Product::new(1, 1), -1),
+        ((0, 2), Product::new(1, 0), 1),
+        ((1, 5), Product::new(0, 0), 1),
+        ((1, 5), Product::new(1, 1), -1),
+        ((2, 3), Product::new(1, 0), 1),
+        ((2, 3), Product::new(1, 1), -1),
+        ((2, 9), Product::new(0, 1), 1),
+    ]);
+}
+
+#[test]
+fn upsert_merge() {
+
+    use timely::dataflow::InputHandle;
+    use timely::dataflow::operators::Input;
+
+    let captured = timely::execute_directly(|worker| {
+
+        let mut input = InputHandle::new();
+        let captured = worker.dataflow::<u64, _, _>(|scope| {
+            let upserts = scope.input_from(&mut input);
+            // Accumulate values for each key, and reset them with `None`.
+            upsert::arrange_from_upsert_with::<_, _, _, _, ValBuilder<u64, u64, _, _>, ValSpine<u64, u64, _, _>>(&upserts, "Upsert", |_key, old, new| {
+                new.map(|new| old.copied().unwrap_or(0) + new)
+            })
+                .as_collection(|k, v| (*k, *v))
+                .inner
+                .capture()
+        });
+
+        input.send((0, Some(1), 0));
+        input.send((0, Some(2), 0));
+        input.send((1, Some(5), 0));
+        input.advance_to(1);
+        worker.step();
+        input.send((0, Some(4), 1));
+        input.send((1, None, 2));
+        input.advance_to(3);
+        worker.step();
+        input.send((0, Some(3), 3));
+        input.send((0, None, 4));
+        input.send((0, Some(6), 5));
+        input.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ((0, 3), 0, 1),
+        ((0, 3), 1, -1),
+        ((0, 6), 5, 1),
+        ((0, 7), 1, 1),
+        ((0, 7), 3, -1),
+        ((0, 10), 3, 1),
+        ((0, 10), 4, -1),
+        ((1, 5), 0, 1),
+        ((1, 5), 2, -1),
+    ]);
+}
+
+#[test]
+fn upsert_merge_partial_order() {
+
+    let captured = timely::example(|scope| {
+
+        let upserts = vec![
+            (0u64, Some(1u64), (0u64, 0u64)),
+            (0, Some(2), (0, 1)),
+            (0, Some(5), (1, 0)),
+            (0, Some(10), (1, 1)),
+        ].into_iter().to_stream(scope);
+
+        scope.iterative::<u64, _, _>(|inner| {
+            let upserts = upserts.enter(inner).map(|(key, val, (outer, inner))| (key, val, Product::new(outer, inner)));
+            upsert::arrange_from_upsert_with::<_, _, _, _, ValBuilder<u64, u64, _, _>, ValSpine<u64, u64, _, _>>(&upserts, "Upsert", |_key, old, new| {
+                new.map(|new| old.copied().unwrap_or(0) + new)
+            })
+                .as_collection(|k, v| (*k, *v))
+                .inner
+                .capture()
+        })
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    // The concurrent upserts at `(0, 1)` and `(1, 0)` each merge into the value at `(0, 0)`,
+    // and are not merged with each other. At their join `(1, 1)` the greater of their values
+    // applies, and the upsert at `(1, 1)` merges into it.
+    assert_eq!(updates, vec![
+        ((0, 1), Product::new(0, 0), 1),
+        ((0, 1), Product::new(0, 1), -1),
+        ((0, 1), Product::new(1, 0), -1),
+        ((0, 1), Product::new(1, 1), 1),
+        ((0, 3), Product::new(0, 1), 1),
+        ((0, 3), Product::new(1, 1), -1),
+        ((0, 6), Product::new(1, 0), 1),
+        ((0, 6), Product::new(1, 1), -1),
+        ((0, 16), Product::new(1, 1), 1),
+    ]);
+}
@@ -0,0 +1,139 @@
+use timely::dataflow::operators::capture::{Capture, Extract};
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::window::{Window, WindowSpec, Windows};
+
+#[test]
+fn session_windows() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut input, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, events) = scope.new_collection::<(u32, u64), isize>();
+            (input, events.window(WindowSpec::session(5), 0, |event| *event).inner.capture())
+        });
+
+        input.insert((0, 1));
+        input.insert((0, 3));
+        input.advance_to(1);
+        input.insert((0, 10));
+        input.advance_to(2);
+        // Joins both sessions into one.
+        input.insert((0, 6));
+        input.advance_to(20);
+        // Arrives after its session would have closed, and is discarded.
+        input.insert((0, 14));
+        input.advance_to(21);
+        input.insert((0, 30));
+        input.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ((0, Window { start: 1, end: 8 }, 2), 0, 1),
+        ((0, Window { start: 1, end: 8 }, 2), 2, -1),
+        ((0, Window { start: 1, end: 15 }, 4), 2, 1),
+        ((0, Window { start: 10, end: 15 }, 1), 1, 1),
+        ((0, Window { start: 10, end: 15 }, 1), 2, -1),
+        ((0, Window { start: 30, end: 35 }, 1), 21, 1),
+    ]);
+}
+
+#[test]
+fn tumbling_windows() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut input, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, events) = scope.new_collection::<(u32, u64), isize>();
+            (input, events.window(WindowSpec::tumbling(10), 5, |event| *event).inner.capture())
+        });
+
+        input.insert((0, 3));
+        input.insert((0, 12));
+        input.insert((1, 4));
+        input.advance_to(1);
+        input.insert((0, 7));
+        input.advance_to(2);
+        input.remove((0, 12));
+        input.advance_to(15);
+        // Arrives once its window has closed, and is discarded.
+        input.insert((0, 8));
+        input.insert((0, 16));
+        input.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ((0, Window { start: 0, end: 10 }, 1), 0, 1),
+        ((0, Window { start: 0, end: 10 }, 1), 1, -1),
+        ((0, Window { start: 0, end: 10 }, 2), 1, 1),
+        ((0, Window { start: 10, end: 20 }, 1), 0, 1),
+        ((0, Window { start: 10, end: 20 }, 1), 2, -1),
+        ((0, Window { start: 10, end: 20 }, 1), 15, 1),
+        ((1, Window { start: 0, end: 10 }, 1), 0, 1),
+    ]);
+}
+
+#[test]
+fn hopping_windows() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut input, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, events) = scope.new_collection::<(u32, u64), isize>();
+            (input, events.window(WindowSpec::hopping(10, 5), 0, |event| *event).inner.capture())
+        });
+
+        input.insert((0, 7));
+        input.insert((0, 12));
+        // Only the window starting at zero contains the event.
+        input.insert((1, 3));
+        input.advance_to(1);
+        input.remove((0, 7));
+        input.close();
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        ((0, Window { start: 0, end: 10 }, 1), 0, 1),
+        ((0, Window { start: 0, end: 10 }, 1), 1, -1),
+        ((0, Window { start: 5, end: 15 }, 1), 1, 1),
+        ((0, Window { start: 5, end: 15 }, 2), 0, 1),
+        ((0, Window { start: 5, end: 15 }, 2), 1, -1),
+        ((0, Window { start: 10, end: 20 }, 1), 0, 1),
+        ((1, Window { start: 0, end: 10 }, 1), 0, 1),
+    ]);
+}
+
+#[test]
+#[should_panic]
+fn hopping_windows_zero_slide() {
+    WindowSpec::hopping(10, 0);
+}
+
+#[test]
+#[should_panic]
+fn tumbling_windows_zero_size() {
+    timely::execute_directly(|worker| {
+        worker.dataflow::<u64, _, _>(|scope| {
+            let events = scope.new_collection::<(u32, u64), isize>().1;
+            events.window(WindowSpec::Tumbling { size: 0 }, 0, |event| *event);
+        });
+    });
+}
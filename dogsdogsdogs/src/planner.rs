@@ -0,0 +1,365 @@
+//! Planning and rendering of delta queries for conjunctive queries.
+//!
+//! A conjunctive query is a list of atoms, each of which names a relation and the variables bound
+//! by the columns of its tuples. The planner produces one delta query for each atom, which starts
+//! from the tuples of that atom and binds the remaining variables one at a time. Variables are ordered
+//! greedily, choosing next the variable with the fewest estimated extensions for each prefix. Estimates
+//! are read either from arrangements of the relations, or from the numbers of distinct tuples and values
+//! that `counts` maintains with differential's `count` operator. When several atoms constrain a variable,
+//! the rendered dataflow uses dogs3's `count` operator to choose, for each prefix, the atom that proposes
+//! the fewest extensions, and `propose` and `validate` to extend the prefix, as the examples do by hand.
+//!
+//! Relations are treated as sets, and each is made distinct before it is used.
+//!
+//! # Examples
+//!
+//! ```
+//! use differential_dataflow::input::Input;
+//! use differential_dogs3::planner::{self, Atom, Estimate};
+//!
+//! ::timely::example(|scope| {
+//!
+//!     let edges = scope.new_collection_from(vec![vec![1u32, 2], vec![2, 3], vec![1, 3], vec![3, 4]]).1;
+//!     let expected = scope.new_collection_from(vec![vec![1u32, 2, 3]]).1;
+//!
+//!     // triangles(a, b, c) := edge(a, b), edge(b, c), edge(a, c)
+//!     let atoms = vec![
+//!         Atom { relation: 0, vars: vec![0, 1] },
+//!         Atom { relation: 0, vars: vec![1, 2] },
+//!         Atom { relation: 0, vars: vec![0, 2] },
+//!     ];
+//!
+//!     let plans = planner::plan(&atoms, &[Estimate::default()]);
+//!     planner::render(&[edges], &atoms, &plans)
+//!         .assert_eq(&expected);
+//! });
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use timely::dataflow::Scope;
+
+use differential_dataflow::{Collection, ExchangeData, IntoOwned};
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Count, Threshold};
+use differential_dataflow::trace::{Cursor, TraceReader};
+
+use crate::{CollectionExtender, CollectionIndex, PrefixExtender, ProposeExtensionMethod};
+use crate::altneu::AltNeu;
+
+/// A relation and the variables bound by each of the columns of its tuples.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Atom {
+    /// The index of the relation.
+    pub relation: usize,
+    /// The variable bound by each column.
+    pub vars: Vec<usize>,
+}
+
+/// Estimates of the size of a relation.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Estimate {
+    /// The number of distinct tuples.
+    pub tuples: usize,
+    /// The number of distinct values in each column.
+    pub distinct: Vec<usize>,
+}
+
+impl Estimate {
+    /// Measures the relation arranged in `trace`, with tuples as keys.
+    ///
+    /// Tuples are counted if their accumulated multiplicity is positive.
+    pub fn from_trace<Tr, V>(trace: &mut Tr) -> Self
+    where
+        Tr: for<'a> TraceReader<Key<'a> = &'a Vec<V>, Diff = isize>,
+        V: Eq+Hash+Clone+'static,
+    {
+        let mut tuples = 0;
+        let mut values: Vec<HashSet<V>> = Vec::new();
+
+        let (mut cursor, storage) = trace.cursor();
+        while let Some(key) = cursor.get_key(&storage) {
+            let mut count = 0;
+            while cursor.val_valid(&storage) {
+                cursor.map_times(&storage, |_time, diff| count += diff.into_owned());
+                cursor.step_val(&storage);
+            }
+            if count > 0 {
+                tuples += 1;
+                if values.len() < key.len() {
+                    values.resize_with(key.len(), HashSet::new);
+                }
+                for (column, value) in key.iter().enumerate() {
+                    values[column].insert(value.clone());
+                }
+            }
+            cursor.step_key(&storage);
+        }
+
+        Estimate {
+            tuples,
+            distinct: values.iter().map(|values| values.len()).collect(),
+        }
+    }
+
+    /// Reads the counts of a relation arranged in `trace`, as produced by `counts` and arranged by self.
+    ///
+    /// Counts are read if their accumulated multiplicity is positive.
+    pub fn from_counts<Tr>(trace: &mut Tr) -> Self
+    where
+        Tr: for<'a> TraceReader<Key<'a> = &'a (Option<usize>, isize), Diff = isize>,
+    {
+        let mut estimate = Estimate::default();
+
+        let (mut cursor, storage) = trace.cursor();
+        while let Some((column, count)) = cursor.get_key(&storage) {
+            let mut present = 0;
+            while cursor.val_valid(&storage) {
+                cursor.map_times(&storage, |_time, diff| present += diff.into_owned());
+                cursor.step_val(&storage);
+            }
+            if present > 0 {
+                let count = (*count).max(0) as usize;
+                match column {
+                    None => { estimate.tuples = count; },
+                    Some(column) => {
+                        if estimate.distinct.len() <= *column {
+                            estimate.distinct.resize(*column + 1, 0);
+                        }
+                        estimate.distinct[*column] = count;
+                    },
+                }
+            }
+            cursor.step_key(&storage);
+        }
+
+        estimate
+    }
+
+    /// The number of distinct values in `column`, and at least one.
+    fn values(&self, column: usize) -> f64 {
+        self.distinct.get(column).copied().unwrap_or(0).max(1) as f64
+    }
+}
+
+/// The binding of a variable in a delta query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Step {
+    /// The variable to bind.
+    pub var: usize,
+    /// The atoms that contain the variable, by increasing estimated number of extensions.
+    pub atoms: Vec<usize>,
+}
+
+/// A delta query, which responds to changes in the relation of one atom.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeltaQuery {
+    /// The atom whose changes the query extends.
+    pub atom: usize,
+    /// Atoms whose variables are all bound by `atom`, and which only restrict its changes.
+    pub checks: Vec<usize>,
+    /// The bindings of the remaining variables, in order.
+    pub steps: Vec<Step>,
+}
+
+/// Counts the distinct tuples of `relation`, and the distinct values in each of its columns.
+///
+/// The result contains `(None, tuples)` and `(Some(column), values)` for each column, maintained with `count`
+/// as the relation changes. Arranged by self, it can be read with `Estimate::from_counts`.
+pub fn counts<G, V>(relation: &Collection<G, Vec<V>, isize>) -> Collection<G, (Option<usize>, isize), isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    V: ExchangeData+Hash,
+{
+    let tuples = relation.distinct().map(|_tuple| None);
+    let values =
+    relation
+        .flat_map(|tuple| tuple.into_iter().enumerate())
+        .distinct()
+        .map(|(column, _value)| Some(column));
+    tuples.concat(&values).count()
+}
+
+/// Plans a delta query for each of `atoms`, using `estimates` indexed by relation.
+///
+/// Relations without estimates are assumed to be empty.
+///
+/// # Panics
+///
+/// Panics if an atom has no variables or repeats a variable, or if the variables are not numbered from zero without gaps.
+pub fn plan(atoms: &[Atom], estimates: &[Estimate]) -> Vec<DeltaQuery> {
+
+    let vars = variables(atoms);
+    let empty = Estimate::default();
+
+    (0 .. atoms.len()).map(|delta| {
+
+        let mut bound = atoms[delta].vars.clone();
+        let checks =
+        (0 .. atoms.len())
+            .filter(|&atom| atom != delta && atoms[atom].vars.iter().all(|var| bound.contains(var)))
+            .collect();
+
+        let mut steps = Vec::new();
+        while bound.len() < vars {
+            // Estimated extensions of each prefix for each unbound variable, by each atom that contains it.
+            let mut candidates =
+            (0 .. vars)
+                .filter(|var| !bound.contains(var))
+                .map(|var| {
+                    let mut options =
+                    (0 .. atoms.len())
+                        .filter(|&atom| atom != delta && atoms[atom].vars.contains(&var))
+                        .map(|atom| {
+                            let estimate = estimates.get(atoms[atom].relation).unwrap_or(&empty);
+                            (extensions(estimate, &atoms[atom].vars, &bound, var), atom)
+                        })
+                        .collect::<Vec<_>>();
+                    options.sort_by(|x, y| x.0.total_cmp(&y.0).then(x.1.cmp(&y.1)));
+                    (options[0].0, var, options)
+                })
+                .collect::<Vec<_>>();
+            candidates.sort_by(|x, y| x.0.total_cmp(&y.0).then(x.1.cmp(&y.1)));
+
+            let (_, var, options) = candidates.swap_remove(0);
+            bound.push(var);
+            steps.push(Step { var, atoms: options.into_iter().map(|(_, atom)| atom).collect() });
+        }
+
+        DeltaQuery { atom: delta, checks, steps }
+    })
+    .collect()
+}
+
+/// Counts the variables of `atoms`, checking that they are well formed.
+fn variables(atoms: &[Atom]) -> usize {
+    let mut vars = Vec::new();
+    for (index, atom) in atoms.iter().enumerate() {
+        assert!(!atom.vars.is_empty(), "atom {} has no variables", index);
+        for (column, var) in atom.vars.iter().enumerate() {
+            assert!(!atom.vars[.. column].contains(var), "variable {} repeated in atom {}", var, index);
+            if !vars.contains(var) { vars.push(*var); }
+        }
+    }
+    vars.sort();
+    assert!(vars.iter().enumerate().all(|(index, var)| index == *var), "variables are not numbered from zero without gaps");
+    vars.len()
+}
+
+/// The estimated number of extensions `atom` proposes for `var`, for each prefix binding `bound`.
+///
+/// The tuples of the atom are assumed to be evenly distributed among the values of its most selective bound column.
+fn extensions(estimate: &Estimate, vars: &[usize], bound: &[usize], var: usize) -> f64 {
+    let selectivity =
+    vars.iter()
+        .enumerate()
+        .filter(|&(_, var)| bound.contains(var))
+        .map(|(column, _)| estimate.values(column))
+        .fold(None, |max: Option<f64>, distinct| Some(max.map_or(distinct, |max| max.max(distinct))));
+
+    match selectivity {
+        Some(distinct) => estimate.tuples as f64 / distinct,
+        None => estimate.values(vars.iter().position(|x| x == &var).unwrap()),
+    }
+}
+
+/// Renders the delta queries `plans` over `atoms` of `relations`.
+///
+/// The result contains, for each binding of all variables that satisfies the query, the values bound to
+/// the variables in order. The atoms of `plans` are rendered in the order they are listed, and each delta
+/// query sees the changes at the same time to the relations of atoms before its own.
+///
+/// # Panics
+///
+/// Panics if `relations` is empty.
+pub fn render<G, V>(relations: &[Collection<G, Vec<V>, isize>], atoms: &[Atom], plans: &[DeltaQuery]) -> Collection<G, Vec<V>, isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+ExchangeData,
+    V: ExchangeData+Hash+Default,
+{
+    let vars = variables(atoms);
+    let mut scope = relations[0].scope();
+    scope.scoped::<AltNeu<G::Timestamp>, _, _>("DeltaQueries", |inner| {
+
+        let relations = relations.iter().map(|relation| relation.distinct().enter(inner)).collect::<Vec<_>>();
+
+        // Indices of the projections of atoms onto key columns and an extension column.
+        // Atoms after the delta query's atom are delayed to exclude their changes at the same time.
+        let mut indices = HashMap::new();
+        let mut index = |atom: usize, key: Vec<usize>, extension: usize, neu: bool| {
+            let relation = atoms[atom].relation;
+            indices.entry((relation, key.clone(), extension, neu)).or_insert_with(|| {
+                let mut projection = relations[relation].map(move |tuple| (select(&tuple, &key), tuple[extension].clone()));
+                if neu { projection = projection.delay(|time| AltNeu::neu(time.time.clone())); }
+                CollectionIndex::index(&projection.distinct())
+            })
+            .clone()
+        };
+
+        let mut results = Vec::with_capacity(plans.len());
+        for plan in plans.iter() {
+
+            let delta = &atoms[plan.atom];
+            let mut bound = delta.vars.clone();
+            let mut prefixes = relations[delta.relation].clone();
+
+            // Atoms with all variables bound validate their last column against the others.
+            for &atom in plan.checks.iter() {
+                let columns = &atoms[atom].vars;
+                let last = columns.len() - 1;
+                let positions = columns[.. last].iter().map(|var| position(&bound, *var)).collect::<Vec<_>>();
+                let value = position(&bound, columns[last]);
+                let mut validator = index(atom, (0 .. last).collect(), last, atom > plan.atom).extend_using(move |prefix: &Vec<V>| select(prefix, &positions));
+                prefixes = validator.validate(&prefixes.map(move |prefix| { let val = prefix[value].clone(); (prefix, val) })).map(|(prefix, _)| prefix);
+            }
+
+            for step in plan.steps.iter() {
+                let mut extenders = step.atoms.iter().map(|&atom| {
+                    let columns = &atoms[atom].vars;
+                    let key = (0 .. columns.len()).filter(|&column| bound.contains(&columns[column])).collect::<Vec<_>>();
+                    let positions = key.iter().map(|&column| position(&bound, columns[column])).collect::<Vec<_>>();
+                    let extension = position(columns, step.var);
+                    index(atom, key, extension, atom > plan.atom).extend_using(move |prefix: &Vec<V>| select(prefix, &positions))
+                })
+                .collect::<Vec<_>>();
+                prefixes = extend_by(&prefixes, &mut extenders);
+                bound.push(step.var);
+            }
+
+            let output = (0 .. vars).map(|var| position(&bound, var)).collect::<Vec<_>>();
+            results.push(prefixes.map(move |prefix| select(&prefix, &output)));
+        }
+
+        differential_dataflow::collection::concatenate(inner, results).leave()
+    })
+}
+
+/// An extender of prefixes by one value, at timestamps `T`, keyed by the values `F` selects from the prefix.
+type Extender<V, T, F> = CollectionExtender<Vec<V>, V, T, isize, Vec<V>, F>;
+
+/// Extends `prefixes` with the values proposed by one of `extenders`, and validated by the others.
+fn extend_by<G, V, F>(prefixes: &Collection<G, Vec<V>, isize>, extenders: &mut [Extender<V, G::Timestamp, F>]) -> Collection<G, Vec<V>, isize>
+where
+    G: Scope,
+    G::Timestamp: Lattice+ExchangeData,
+    V: ExchangeData+Hash+Default,
+    F: Fn(&Vec<V>)->Vec<V>+Clone+'static,
+{
+    let mut extenders = extenders.iter_mut().map(|extender| extender as &mut dyn PrefixExtender<G, isize, Prefix=Vec<V>, Extension=V>).collect::<Vec<_>>();
+    prefixes
+        .extend(&mut extenders[..])
+        .map(|(mut prefix, val)| { prefix.push(val); prefix })
+}
+
+/// The values of `tuple` at each of `positions`.
+fn select<V: Clone>(tuple: &[V], positions: &[usize]) -> Vec<V> {
+    positions.iter().map(|&position| tuple[position].clone()).collect()
+}
+
+/// The position of `var` in `vars`.
+fn position(vars: &[usize], var: usize) -> usize {
+    vars.iter().position(|x| x == &var).unwrap()
+}
@@ -0,0 +1,116 @@
+use timely::dataflow::operators::capture::{Capture, Extract};
+use timely::progress::Antichain;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::arrange::ArrangeBySelf;
+use differential_dataflow::trace::TraceReader;
+use differential_dogs3::planner::{self, Atom, Estimate};
+
+/// Atoms `a(x, y), b(y, z), c(y, w)`, in which the delta query for `a` may bind `z` and `w` in either order.
+fn atoms() -> Vec<Atom> {
+    vec![
+        Atom { relation: 0, vars: vec![0, 1] },
+        Atom { relation: 1, vars: vec![1, 2] },
+        Atom { relation: 2, vars: vec![1, 3] },
+    ]
+}
+
+#[test]
+fn estimates_choose_order() {
+
+    let small = Estimate { tuples: 10, distinct: vec![10, 10] };
+    let large = Estimate { tuples: 1000, distinct: vec![10, 1000] };
+
+    // Each value of `y` has fewer extensions in the smaller relation, whose variable is bound first.
+    let plans = planner::plan(&atoms(), &[small.clone(), small.clone(), large.clone()]);
+    assert_eq!(plans[0].steps.iter().map(|step| step.var).collect::<Vec<_>>(), vec![2, 3]);
+    let plans = planner::plan(&atoms(), &[small.clone(), large.clone(), small.clone()]);
+    assert_eq!(plans[0].steps.iter().map(|step| step.var).collect::<Vec<_>>(), vec![3, 2]);
+
+    // Atoms constraining the same variable are listed by increasing estimated extensions.
+    let atoms = vec![
+        Atom { relation: 0, vars: vec![0, 1] },
+        Atom { relation: 1, vars: vec![0, 2] },
+        Atom { relation: 2, vars: vec![1, 2] },
+    ];
+    let plans = planner::plan(&atoms, &[small.clone(), large.clone(), small.clone()]);
+    assert_eq!(plans[0].steps[0].atoms, vec![2, 1]);
+    let plans = planner::plan(&atoms, &[small.clone(), small.clone(), large.clone()]);
+    assert_eq!(plans[0].steps[0].atoms, vec![1, 2]);
+}
+
+#[test]
+fn estimates_from_arrangements_and_counts() {
+
+    timely::execute_directly(|worker| {
+
+        let (mut input, mut tuples, mut counts) = worker.dataflow::<u64, _, _>(|scope| {
+            let (input, relation) = scope.new_collection::<Vec<u32>, isize>();
+            let tuples = relation.arrange_by_self().trace;
+            let counts = planner::counts(&relation).arrange_by_self().trace;
+            (input, tuples, counts)
+        });
+
+        // Repeated tuples are counted once, and retracted tuples not at all.
+        input.insert(vec![1, 2]);
+        input.insert(vec![1, 2]);
+        input.insert(vec![1, 3]);
+        input.insert(vec![2, 3]);
+        input.insert(vec![4, 5]);
+        input.advance_to(1);
+        input.remove(vec![4, 5]);
+        input.advance_to(2);
+        input.flush();
+        let mut upper = Antichain::new();
+        while { tuples.read_upper(&mut upper); upper.less_equal(&1) } { worker.step(); }
+        while { counts.read_upper(&mut upper); upper.less_equal(&1) } { worker.step(); }
+
+        let expected = Estimate { tuples: 3, distinct: vec![2, 2] };
+        assert_eq!(Estimate::from_trace(&mut tuples), expected);
+        assert_eq!(Estimate::from_counts(&mut counts), expected);
+    });
+}
+
+#[test]
+fn render_planned_queries() {
+
+    let captured = timely::execute_directly(|worker| {
+
+        let (mut inputs, captured) = worker.dataflow::<u64, _, _>(|scope| {
+            let (a_input, a) = scope.new_collection::<Vec<u32>, isize>();
+            let (b_input, b) = scope.new_collection::<Vec<u32>, isize>();
+            let (c_input, c) = scope.new_collection::<Vec<u32>, isize>();
+            let small = Estimate { tuples: 10, distinct: vec![10, 10] };
+            let large = Estimate { tuples: 1000, distinct: vec![10, 1000] };
+            let plans = planner::plan(&atoms(), &[small.clone(), large, small]);
+            let output = planner::render(&[a, b, c], &atoms(), &plans);
+            ([a_input, b_input, c_input], output.inner.capture())
+        });
+
+        inputs[0].insert(vec![1, 2]);
+        inputs[1].insert(vec![2, 3]);
+        inputs[2].insert(vec![2, 4]);
+        for input in inputs.iter_mut() { input.advance_to(1); }
+        inputs[1].insert(vec![2, 5]);
+        inputs[2].remove(vec![2, 4]);
+        inputs[0].insert(vec![6, 2]);
+        for input in inputs.iter_mut() { input.advance_to(2); }
+        inputs[2].insert(vec![2, 4]);
+        for input in inputs { input.close(); }
+        while worker.step() { }
+
+        captured
+    });
+
+    let mut updates = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    differential_dataflow::consolidation::consolidate_updates(&mut updates);
+
+    assert_eq!(updates, vec![
+        (vec![1, 2, 3, 4], 0, 1),
+        (vec![1, 2, 3, 4], 1, -1),
+        (vec![1, 2, 3, 4], 2, 1),
+        (vec![1, 2, 5, 4], 2, 1),
+        (vec![6, 2, 3, 4], 2, 1),
+        (vec![6, 2, 5, 4], 2, 1),
+    ]);
+}
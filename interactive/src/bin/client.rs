@@ -1,11 +1,38 @@
+use std::collections::BTreeMap;
+use std::net::TcpStream;
 use std::time::Duration;
-use interactive::{Command, Plan};
+
+use differential_dataflow::capture::iterator::Iter;
+use differential_dataflow::capture::tcp::TcpSource;
+
+use interactive::{Command, Plan, Diff};
 use interactive::concrete::{Session, Value};
 
+/// Updates received for a subscription, in order of time.
+type Updates = Iter<TcpSource<Vec<Value>, Duration, Diff>, Vec<Value>, Duration, Diff>;
+
+/// Applies received updates to `view` until they are complete through `time`.
+fn await_view(updates: &mut Updates, view: &mut BTreeMap<Vec<Value>, Diff>, time: Duration) {
+    loop {
+        for (batch, frontier) in updates.by_ref() {
+            for (row, _time, diff) in batch {
+                *view.entry(row.clone()).or_insert(0) += diff;
+                if view[&row] == 0 {
+                    view.remove(&row);
+                }
+            }
+            if !frontier.less_equal(&time) {
+                return;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
 fn main() {
 
-    let socket = std::net::TcpStream::connect("127.0.0.1:8000".to_string()).expect("failed to connect");
-    let mut session = Session::new(socket);
+    let socket = TcpStream::connect("127.0.0.1:8000".to_string()).expect("failed to connect");
+    let mut session = Session::new(socket.try_clone().expect("failed to clone connection"));
 
     // Create initially empty set of edges.
     session.issue(Command::CreateInput("Edges".to_string(), Vec::new()));
@@ -25,10 +52,18 @@ fn main() {
             .inspect("one-hop")
             .into_rule("One-hop"));
 
+    // Maintain a live view of the rule, from updates sent back over the connection.
+    session.issue(Command::Subscribe("One-hop".to_string()));
+    let mut updates = Iter::new(TcpSource::new(socket).expect("failed to configure connection"));
+    let mut view = BTreeMap::new();
+
     session.issue(Command::AdvanceTime(Duration::from_secs(1)));
     session.issue(Command::UpdateInput("Nodes".to_string(), vec![(vec![Value::Usize(0)], Duration::from_secs(1), 1)]));
     session.issue(Command::AdvanceTime(Duration::from_secs(2)));
 
+    await_view(&mut updates, &mut view, Duration::from_secs(1));
+    println!("One-hop at {:?}: {:?}", Duration::from_secs(1), view);
+
     session.issue(
         Plan::source("Nodes")
             .join(Plan::source("Edges"), vec![(0, 0)])
@@ -1,6 +1,7 @@
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::Sender;
 use std::thread::Thread;
+use std::net::TcpStream;
 
 use timely::synchronization::Sequencer;
 use interactive::{Command, Manager};
@@ -11,7 +12,8 @@ fn main() {
     let mut args = std::env::args();
     args.next();
 
-    let (root_send, root_recv) = std::sync::mpsc::channel::<(Sender<Command<Value>>, Thread)>();
+    // Commands, with the connection they arrived on if responses are sent back over it.
+    let (root_send, root_recv) = std::sync::mpsc::channel::<(Sender<(Command<Value>, Option<TcpStream>)>, Thread)>();
     let root_send = Arc::new(Mutex::new(root_send));
 
     std::thread::Builder::new()
@@ -30,7 +32,11 @@ fn main() {
                     .name("Client".to_string())
                     .spawn(move || {
                         while let Ok(command) = bincode::deserialize_from::<_,Command<Value>>(&mut stream) {
-                            send.send(command).expect("command send failed");
+                            let connection = match command {
+                                Command::Subscribe(_) => Some(stream.try_clone().expect("failed to clone connection")),
+                                _ => None,
+                            };
+                            send.send((command, connection)).expect("command send failed");
                             thread.unpark();
                         }
                     })
@@ -42,13 +48,15 @@ fn main() {
     // Initiate timely computation.
     timely::execute_from_args(args, move |worker| {
 
-        // Send an endpoint and thread handle to root.
+        // Send an endpoint and thread handle to root, from the first worker only.
         let (send, recv) = std::sync::mpsc::channel();
-        root_send
-            .lock()
-            .expect("lock poisoned")
-            .send((send, std::thread::current()))
-            .expect("send failed");
+        if worker.index() == 0 {
+            root_send
+                .lock()
+                .expect("lock poisoned")
+                .send((send, std::thread::current()))
+                .expect("send failed");
+        }
 
         let timer = ::std::time::Instant::now();
 
@@ -58,10 +66,19 @@ fn main() {
         while sequencer.is_some() {
 
             // Check out channel status.
-            while let Ok(command) = recv.try_recv() {
+            while let Ok((command, connection)) = recv.try_recv() {
+                manager.connections.extend(connection);
                 sequencer
                     .as_mut()
-                    .map(|s| s.push(command));
+                    .map(|s| s.push(command.optimize(&manager)));
+            }
+
+            // Subscriptions whose connections have failed are dropped by all workers.
+            for (index, error) in manager.failed_subscriptions() {
+                println!("Subscription failed: {}", error);
+                if let Some(sequencer) = sequencer.as_mut() {
+                    sequencer.push(Command::Unsubscribe(index));
+                }
             }
 
             // Dequeue and act on commands.
@@ -0,0 +1,16 @@
+use std::io::Read;
+use interactive::Query;
+use interactive::concrete::{Session, Value};
+
+fn main() {
+
+    let address = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:8000".to_string());
+
+    // Read SQL statements, e.g. `CREATE TABLE` and `CREATE VIEW`, from standard input.
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).expect("failed to read statements");
+
+    let socket = std::net::TcpStream::connect(address).expect("failed to connect");
+    let mut session = Session::new(socket);
+    session.issue(Query::<Value>::new().add_sql(&text));
+}
@@ -12,14 +12,21 @@ use differential_dataflow::logging::DifferentialEvent;
 
 use differential_dataflow::ExchangeData;
 
-use super::{Query, Rule, Plan, Time, Diff, Manager, Datum};
+use super::{Query, Rule, Plan, Time, Diff, Manager, TraceManager, Datum};
 use crate::logging::LoggingValue;
+use crate::sql::{Catalog, FromLiteral, Planned};
 
 /// Commands accepted by the system.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Command<V: Datum> {
     /// Installs the query and publishes public rules.
     Query(Query<V>),
+    /// Drops the query that installed the named rule, and releases its traces.
+    DropQuery(String),
+    /// Streams the updates of the named rule, and its progress, to the issuing connection as CDC v2 messages.
+    Subscribe(String),
+    /// Drops the subscription with the given dataflow index, whose connection has failed.
+    Unsubscribe(usize),
     /// Advances all inputs and traces to `time`, and advances computation.
     AdvanceTime(Time),
     /// Creates a new named input, with initial input.
@@ -44,9 +51,26 @@ impl<V: Datum> From<Rule<V>> for Command<V> {
 
 impl<V: Datum> Command<V>
 where
-    V: ExchangeData+Hash+LoggingValue,
+    V: ExchangeData+Hash+LoggingValue+FromLiteral,
 {
 
+    /// Rewrites the plans of a query into equivalent plans that should be cheaper to render.
+    ///
+    /// The rewrites consult the traces of the local worker, and so should be applied by the
+    /// worker that receives the command, before it is shared with the other workers.
+    pub fn optimize(self, manager: &Manager<V>) -> Self {
+        match self {
+            Command::Query(mut query) => {
+                for rule in query.rules.iter_mut() {
+                    let plan = std::mem::replace(&mut rule.plan, Plan::Source(rule.name.clone()));
+                    rule.plan = crate::plan::optimize(plan, &manager.traces);
+                }
+                Command::Query(query)
+            },
+            command => command,
+        }
+    }
+
     /// Executes a command.
     pub fn execute<A: Allocate>(self, manager: &mut Manager<V>, worker: &mut Worker<A>) {
 
@@ -54,6 +78,67 @@ where
 
             Command::Query(query) => {
 
+                // SQL statements create inputs and contribute rules, against a staged catalog.
+                let mut catalog = manager.catalog.clone();
+                let mut tables = Vec::new();
+                let mut rules = Vec::new();
+                for text in query.sql.iter() {
+                    match crate::sql::plan(text, &mut catalog) {
+                        Ok(statements) => {
+                            for statement in statements {
+                                match statement {
+                                    Planned::CreateTable(name) => tables.push(name),
+                                    Planned::CreateView(Rule { name, plan }) => {
+                                        // Views are planned by every worker, and so are rewritten without local traces.
+                                        let plan = crate::plan::optimize(plan, &TraceManager::new());
+                                        rules.push(Rule { name, plan });
+                                    },
+                                }
+                            }
+                        },
+                        Err(error) => {
+                            println!("SQL error: {}", error);
+                            return;
+                        }
+                    }
+                }
+                rules.extend(query.rules);
+
+                // Tables may not replace maintained collections.
+                if let Some(name) = tables.iter().find(|name| manager.traces.contains_unkeyed(&Plan::Source(name.to_string()))) {
+                    println!("Query error in table {:?}: a collection with this name already exists", name);
+                    return;
+                }
+
+                // Rules may refer to maintained collections, to tables, and to the rules before them,
+                // but may not replace them.
+                for (index, rule) in rules.iter().enumerate() {
+                    let bound = |name: &str| {
+                        rules[.. index].iter().any(|rule| rule.name == name) ||
+                        tables.iter().any(|table| table == name) ||
+                        manager.traces.contains_unkeyed(&Plan::Source(name.to_string()))
+                    };
+                    let validated =
+                    if bound(&rule.name) { Err("a collection with this name already exists".to_string()) }
+                    else { rule.plan.validate(&bound) };
+                    if let Err(error) = validated {
+                        println!("Query error in rule {:?}: {}", rule.name, error);
+                        return;
+                    }
+                }
+
+                // With every rule validated, commit the catalog and create the tables.
+                for name in tables {
+                    manager.traces.set_arity(&name, catalog.relations[&name].len());
+                    Self::create_input(manager, worker, name, Vec::new());
+                }
+                for rule in rules.iter() {
+                    if let Some(columns) = catalog.relations.get(&rule.name) {
+                        manager.traces.set_arity(&rule.name, columns.len());
+                    }
+                }
+                manager.catalog = catalog;
+
                 // Query construction requires a bit of guff to allow us to
                 // re-use as much stuff as possible. It *seems* we need to
                 // be able to cache and re-use:
@@ -67,6 +152,12 @@ where
                 // traces, and the types present in imported traces are not
                 // the same as those in arrangements.
 
+                // Rules and the arrangements they install are attributed to this dataflow.
+                let index = worker.next_dataflow_index();
+                let names = rules.iter().map(|rule| rule.name.clone()).collect::<Vec<_>>();
+                manager.traces.begin_dataflow(index);
+                let probe = manager.probes.entry(index).or_default();
+
                 worker.dataflow(|scope| {
 
                     use timely::dataflow::operators::Probe;
@@ -76,12 +167,12 @@ where
                     let mut collections = std::collections::HashMap::new();
                     // let mut arrangements = std::collections::HashMap::new();
 
-                    for Rule { name, plan } in query.rules.into_iter() {
+                    for Rule { name, plan } in rules.into_iter() {
                         let collection =
                         plan.render(scope, &mut collections, &mut manager.traces)
                             .arrange_by_self();
 
-                        collection.stream.probe_with(&mut manager.probe);
+                        collection.stream.probe_with(probe);
                         let trace = collection.trace;
 
                         // Can bind the trace to both the plan and the name.
@@ -90,28 +181,86 @@ where
                     }
 
                 });
+
+                manager.traces.end_dataflow();
+                for name in names {
+                    manager.queries.insert(name, index);
+                }
+            },
+
+            Command::DropQuery(name) => {
+                if !manager.drop_query(&name, worker) {
+                    println!("Query not found: {:?}", name);
+                }
+            },
+
+            Command::Subscribe(name) => {
+
+                use std::rc::Rc;
+                use std::cell::RefCell;
+                use timely::dataflow::operators::Exchange;
+                use differential_dataflow::capture::{sink, tcp::TcpSink};
+
+                // Worker zero receives commands, and holds the connection to write to.
+                let connection = if worker.index() == 0 { manager.connections.pop_front() } else { None };
+
+                if let Some(mut trace) = manager.traces.get_unkeyed(&Plan::Source(name.clone())) {
+
+                    let sink =
+                    connection
+                        .and_then(|connection| TcpSink::new(connection).map_err(|error| println!("Subscription failed: {}", error)).ok())
+                        .map(|sink| Rc::new(RefCell::new(sink)));
+                    let weak = sink.as_ref().map(Rc::downgrade).unwrap_or_default();
+
+                    let index = worker.next_dataflow_index();
+                    manager.traces.begin_dataflow(index);
+                    worker.dataflow(|scope| {
+                        // Batches may overlap once compacted, and the sink requires consolidated updates.
+                        let updates =
+                        trace
+                            .import(scope)
+                            .as_collection(|k,&()| k.clone())
+                            .consolidate()
+                            .inner
+                            .exchange(|_| 0);
+
+                        sink::build(&updates, 0, weak.clone(), weak);
+                    });
+                    manager.traces.end_dataflow();
+                    manager.subscriptions.entry(name).or_default().push((index, sink));
+                }
+                else {
+                    // Close the connection, so that the client does not await updates.
+                    if let Some(connection) = connection {
+                        let _ = connection.shutdown(std::net::Shutdown::Both);
+                    }
+                    println!("Rule not found: {:?}", name);
+                }
+            },
+
+            Command::Unsubscribe(index) => {
+                if !manager.drop_subscription(index, worker) {
+                    println!("Subscription not found: {:?}", index);
+                }
             },
 
             Command::AdvanceTime(time) => {
                 manager.advance_time(&time);
-                while manager.probe.less_than(&time) {
+                while manager.probes.values().any(|probe| probe.less_than(&time)) {
                     worker.step();
                 }
             },
 
             Command::CreateInput(name, updates) => {
-
-                use differential_dataflow::input::Input;
-                use differential_dataflow::operators::arrange::ArrangeBySelf;
-
-                let (input, trace) = worker.dataflow(|scope| {
-                    let (input, collection) = scope.new_collection_from(updates.into_iter());
-                    let trace = collection.arrange_by_self().trace;
-                    (input, trace)
-                });
-
-                manager.insert_input(name, input, trace);
-
+                // Records of the same length determine the number of values of the input.
+                // Their columns are named by position, so that SQL statements may refer to the input.
+                if let Some(record) = updates.first() {
+                    if updates.iter().all(|other| other.len() == record.len()) {
+                        manager.traces.set_arity(&name, record.len());
+                        manager.catalog.relations.insert(name.clone(), Catalog::positional(record.len()));
+                    }
+                }
+                Self::create_input(manager, worker, name, updates);
             },
 
             Command::UpdateInput(name, updates) => {
@@ -191,6 +340,23 @@ where
         }
     }
 
+    /// Creates a new named input, with initial input.
+    fn create_input<A: Allocate>(manager: &mut Manager<V>, worker: &mut Worker<A>, name: String, updates: Vec<Vec<V>>) {
+
+        use differential_dataflow::input::Input;
+        use differential_dataflow::operators::arrange::ArrangeBySelf;
+
+        manager.traces.begin_dataflow(worker.next_dataflow_index());
+        let (input, trace) = worker.dataflow(|scope| {
+            let (input, collection) = scope.new_collection_from(updates);
+            let trace = collection.arrange_by_self().trace;
+            (input, trace)
+        });
+
+        manager.insert_input(name, input, trace);
+        manager.traces.end_dataflow();
+    }
+
     /// Serialize the command at a writer.
     pub fn serialize_into<W: Write>(&self, writer: W) {
         bincode::serialize_into(writer, self).expect("bincode: serialization failed");
@@ -2,7 +2,13 @@
 
 use std::time::Duration;
 use serde::{Deserialize, Serialize};
-use super::{Datum, VectorFrom, Command};
+use super::{Datum, VectorFrom, Command, Diff};
+use crate::plan::Aggregate;
+use crate::sql::{FromLiteral, parse::Literal};
+
+pub mod expression;
+
+use self::expression::{BinaryOp, Expression};
 
 /// A session.
 pub struct Session<W: std::io::Write> {
@@ -33,23 +39,107 @@ pub enum Value {
     Vector(Vec<Value>),
     /// duration
     Duration(Duration),
+    /// signed integer
+    Int(i64),
+    /// floating point number
+    Float(Float),
+    /// byte string
+    Bytes(Vec<u8>),
+    /// date, as days since 1970-01-01
+    Date(i32),
+    /// absent value
+    Null,
+}
+
+/// A floating point number, ordered and compared by `f64::total_cmp`.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == std::cmp::Ordering::Equal }
+}
+impl Eq for Float { }
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering { self.0.total_cmp(&other.0) }
+}
+impl std::hash::Hash for Float {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) { self.0.to_bits().hash(state) }
 }
 
 impl Datum for Value {
-    type Expression = usize;
-    fn subject_to(data: &[Self], expr: &Self::Expression) -> Self { data[*expr].clone() }
-    fn projection(index: usize) -> Self::Expression { index }
+    type Expression = Expression;
+    fn subject_to(data: &[Self], expr: &Self::Expression) -> Self { expr.eval(data) }
+    fn projection(index: usize) -> Self::Expression { Expression::Column(index) }
+    fn projected(expr: &Self::Expression) -> Option<usize> {
+        if let Expression::Column(index) = expr { Some(*index) } else { None }
+    }
+    fn renumber(expr: &Self::Expression, columns: &[usize]) -> Self::Expression { expr.renumber(columns) }
+    fn aggregate(records: &[(Vec<Self>, Diff)], aggregate: &Aggregate) -> Self {
+        match aggregate {
+            Aggregate::Count => multiplicity(records.iter().map(|(_, diff)| diff).sum()),
+            Aggregate::Sum(index) => sum(records, *index).0,
+            Aggregate::Min(index) => values(records, *index).min().cloned().unwrap_or(Value::Null),
+            Aggregate::Max(index) => values(records, *index).max().cloned().unwrap_or(Value::Null),
+            Aggregate::Avg(index) => {
+                let (total, count) = sum(records, *index);
+                expression::binary(BinaryOp::Div, total, multiplicity(count))
+            },
+        }
+    }
+    fn compare(left: &Self, right: &Self) -> Option<std::cmp::Ordering> { expression::comparison(left, right) }
+}
+
+/// The values at `index` that are not null.
+fn values(records: &[(Vec<Value>, Diff)], index: usize) -> impl Iterator<Item=&Value> {
+    records.iter().map(move |(record, _)| &record[index]).filter(|value| **value != Value::Null)
+}
+
+/// A multiplicity as a value, which is negative if more records are retracted than present.
+fn multiplicity(diff: Diff) -> Value {
+    usize::try_from(diff).map_or(Value::Int(diff as i64), Value::Usize)
+}
+
+/// Sums the values at `index` that are not null, and counts them.
+fn sum(records: &[(Vec<Value>, Diff)], index: usize) -> (Value, Diff) {
+    let mut total = None;
+    let mut count = 0;
+    for (record, diff) in records.iter() {
+        if record[index] != Value::Null {
+            let value = expression::binary(BinaryOp::Mul, record[index].clone(), multiplicity(*diff));
+            total = Some(match total {
+                Some(total) => expression::binary(BinaryOp::Add, total, value),
+                None => value,
+            });
+            count += *diff;
+        }
+    }
+    (total.unwrap_or(Value::Null), count)
 }
 
 impl From<usize> for Value { fn from(x: usize) -> Self { Value::Usize(x) } }
 impl From<bool> for Value { fn from(x: bool) -> Self { Value::Bool(x) } }
 impl From<String> for Value { fn from(x: String) -> Self { Value::String(x) } }
 impl From<Duration> for Value { fn from(x: Duration) -> Self { Value::Duration(x) } }
+impl From<i64> for Value { fn from(x: i64) -> Self { Value::Int(x) } }
+impl From<f64> for Value { fn from(x: f64) -> Self { Value::Float(Float(x)) } }
 
 impl<V> From<Vec<V>> for Value where Value: From<V> {
     fn from(x: Vec<V>) -> Self { Value::Vector(x.into_iter().map(|y| y.into()).collect()) }
 }
 
+impl FromLiteral for Value {
+    fn from_literal(literal: &Literal) -> Result<Self, String> {
+        match literal {
+            Literal::Integer(x) => Ok(usize::try_from(*x).map(Value::Usize).unwrap_or(Value::Int(*x))),
+            Literal::String(x) => Ok(Value::String(x.clone())),
+            Literal::Boolean(x) => Ok(Value::Bool(*x)),
+        }
+    }
+}
+
 
 use timely::logging::TimelyEvent;
 
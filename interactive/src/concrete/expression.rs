@@ -0,0 +1,370 @@
+//! Expressions over values.
+//!
+//! Expressions are evaluated against a tuple of values, and produce a value. Operations on `Null`
+//! produce `Null`, with the exception of `IsNull`, `Coalesce`, and the three-valued logic of `And`
+//! and `Or`. Operations on values of the wrong type, arithmetic that overflows or divides by zero,
+//! and casts that fail also produce `Null`, as there is no other way to report an error.
+//!
+//! Arithmetic and comparisons on numbers of different types first convert both to the more general
+//! of the two types, where `Usize` converts to `Int`, and either converts to `Float`.
+
+use std::cmp::Ordering;
+use serde::{Deserialize, Serialize};
+
+use super::{Float, Value};
+
+/// An expression that can be evaluated against a tuple of values.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Expression {
+    /// The value at an index of the tuple.
+    Column(usize),
+    /// A constant value.
+    Literal(Value),
+    /// An operator applied to one argument.
+    Unary(UnaryOp, Box<Expression>),
+    /// An operator applied to two arguments.
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    /// A function applied to a list of arguments.
+    Function(Function, Vec<Expression>),
+    /// A conversion to another type.
+    Cast(Box<Expression>, Type),
+    /// The second argument if the first is true, and otherwise the third.
+    If(Box<Expression>, Box<Expression>, Box<Expression>),
+}
+
+/// Operators applied to one argument.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum UnaryOp {
+    /// Arithmetic negation.
+    Neg,
+    /// Logical negation.
+    Not,
+    /// True if the argument is null, and false otherwise.
+    IsNull,
+}
+
+/// Operators applied to two arguments.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum BinaryOp {
+    /// Addition, of numbers, of durations, or of days to a date.
+    Add,
+    /// Subtraction, of numbers, of durations, of days from a date, or of dates.
+    Sub,
+    /// Multiplication, of numbers, or of a duration by an integer.
+    Mul,
+    /// Division, of numbers, or of a duration by an integer.
+    Div,
+    /// Remainder of integer division.
+    Rem,
+    /// Equal.
+    Eq,
+    /// Not equal.
+    Ne,
+    /// Strictly less than.
+    Lt,
+    /// Less than or equal.
+    Le,
+    /// Strictly greater than.
+    Gt,
+    /// Greater than or equal.
+    Ge,
+    /// Logical conjunction.
+    And,
+    /// Logical disjunction.
+    Or,
+}
+
+/// Functions applied to lists of arguments.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Function {
+    /// The absolute value of a number.
+    Abs,
+    /// The concatenation of strings, or of bytes.
+    Concat,
+    /// The number of characters in a string, or of bytes.
+    Length,
+    /// A string in lower case.
+    Lower,
+    /// A string in upper case.
+    Upper,
+    /// A string without leading and trailing whitespace.
+    Trim,
+    /// The characters of a string from a position, counting from one, and optionally only so many of them.
+    Substring,
+    /// The first argument that is not null.
+    Coalesce,
+}
+
+/// Types to which values can be cast.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Type {
+    /// `Value::Bool`.
+    Bool,
+    /// `Value::Usize`.
+    Usize,
+    /// `Value::Int`.
+    Int,
+    /// `Value::Float`.
+    Float,
+    /// `Value::String`.
+    String,
+    /// `Value::Bytes`.
+    Bytes,
+    /// `Value::Date`, from strings as `YYYY-MM-DD`.
+    Date,
+}
+
+impl Expression {
+    /// Evaluates the expression against `data`.
+    pub fn eval(&self, data: &[Value]) -> Value {
+        match self {
+            Expression::Column(index) => data[*index].clone(),
+            Expression::Literal(value) => value.clone(),
+            Expression::Unary(op, expr) => unary(*op, expr.eval(data)),
+            Expression::Binary(BinaryOp::And, left, right) => {
+                match (left.eval(data), right.eval(data)) {
+                    (Value::Bool(false), _) | (_, Value::Bool(false)) => Value::Bool(false),
+                    (Value::Bool(true), Value::Bool(true)) => Value::Bool(true),
+                    _ => Value::Null,
+                }
+            },
+            Expression::Binary(BinaryOp::Or, left, right) => {
+                match (left.eval(data), right.eval(data)) {
+                    (Value::Bool(true), _) | (_, Value::Bool(true)) => Value::Bool(true),
+                    (Value::Bool(false), Value::Bool(false)) => Value::Bool(false),
+                    _ => Value::Null,
+                }
+            },
+            Expression::Binary(op, left, right) => binary(*op, left.eval(data), right.eval(data)),
+            Expression::Function(function, args) => {
+                let args = args.iter().map(|arg| arg.eval(data)).collect::<Vec<_>>();
+                apply(*function, args)
+            },
+            Expression::Cast(expr, to) => cast(expr.eval(data), *to),
+            Expression::If(condition, then, otherwise) => {
+                if condition.eval(data) == Value::Bool(true) { then.eval(data) } else { otherwise.eval(data) }
+            },
+        }
+    }
+
+    /// The expression with each column index `i` replaced by `columns[i]`.
+    pub fn renumber(&self, columns: &[usize]) -> Expression {
+        let renumber = |expr: &Expression| Box::new(expr.renumber(columns));
+        match self {
+            Expression::Column(index) => Expression::Column(columns[*index]),
+            Expression::Literal(value) => Expression::Literal(value.clone()),
+            Expression::Unary(op, expr) => Expression::Unary(*op, renumber(expr)),
+            Expression::Binary(op, left, right) => Expression::Binary(*op, renumber(left), renumber(right)),
+            Expression::Function(function, args) => Expression::Function(*function, args.iter().map(|arg| arg.renumber(columns)).collect()),
+            Expression::Cast(expr, to) => Expression::Cast(renumber(expr), *to),
+            Expression::If(condition, then, otherwise) => Expression::If(renumber(condition), renumber(then), renumber(otherwise)),
+        }
+    }
+}
+
+fn unary(op: UnaryOp, value: Value) -> Value {
+    match (op, value) {
+        (UnaryOp::IsNull, value) => Value::Bool(value == Value::Null),
+        (UnaryOp::Not, Value::Bool(x)) => Value::Bool(!x),
+        (UnaryOp::Neg, Value::Usize(x)) => i64::try_from(x).ok().and_then(|x| x.checked_neg()).map_or(Value::Null, Value::Int),
+        (UnaryOp::Neg, Value::Int(x)) => x.checked_neg().map_or(Value::Null, Value::Int),
+        (UnaryOp::Neg, Value::Float(x)) => Value::Float(Float(-x.0)),
+        _ => Value::Null,
+    }
+}
+
+/// Converts two numbers to a common type, if they are numbers.
+fn promote(left: Value, right: Value) -> (Value, Value) {
+    match (&left, &right) {
+        (Value::Usize(x), Value::Int(_)) => (i64::try_from(*x).map_or(Value::Null, Value::Int), right),
+        (Value::Int(_), Value::Usize(y)) => (left, i64::try_from(*y).map_or(Value::Null, Value::Int)),
+        (Value::Float(_), Value::Usize(_) | Value::Int(_)) => (left, cast(right, Type::Float)),
+        (Value::Usize(_) | Value::Int(_), Value::Float(_)) => (cast(left, Type::Float), right),
+        _ => (left, right),
+    }
+}
+
+/// Applies an arithmetic or comparison operator, but not `And` or `Or`.
+pub(crate) fn binary(op: BinaryOp, left: Value, right: Value) -> Value {
+    if left == Value::Null || right == Value::Null {
+        return Value::Null;
+    }
+    let (left, right) = promote(left, right);
+    let result = match (op, left, right) {
+        (BinaryOp::Eq, left, right) => compare(&left, &right).map(|o| Value::Bool(o == Ordering::Equal)),
+        (BinaryOp::Ne, left, right) => compare(&left, &right).map(|o| Value::Bool(o != Ordering::Equal)),
+        (BinaryOp::Lt, left, right) => compare(&left, &right).map(|o| Value::Bool(o == Ordering::Less)),
+        (BinaryOp::Le, left, right) => compare(&left, &right).map(|o| Value::Bool(o != Ordering::Greater)),
+        (BinaryOp::Gt, left, right) => compare(&left, &right).map(|o| Value::Bool(o == Ordering::Greater)),
+        (BinaryOp::Ge, left, right) => compare(&left, &right).map(|o| Value::Bool(o != Ordering::Less)),
+
+        (BinaryOp::Add, Value::Usize(x), Value::Usize(y)) => x.checked_add(y).map(Value::Usize),
+        (BinaryOp::Sub, Value::Usize(x), Value::Usize(y)) => x.checked_sub(y).map(Value::Usize),
+        (BinaryOp::Mul, Value::Usize(x), Value::Usize(y)) => x.checked_mul(y).map(Value::Usize),
+        (BinaryOp::Div, Value::Usize(x), Value::Usize(y)) => x.checked_div(y).map(Value::Usize),
+        (BinaryOp::Rem, Value::Usize(x), Value::Usize(y)) => x.checked_rem(y).map(Value::Usize),
+
+        (BinaryOp::Add, Value::Int(x), Value::Int(y)) => x.checked_add(y).map(Value::Int),
+        (BinaryOp::Sub, Value::Int(x), Value::Int(y)) => x.checked_sub(y).map(Value::Int),
+        (BinaryOp::Mul, Value::Int(x), Value::Int(y)) => x.checked_mul(y).map(Value::Int),
+        (BinaryOp::Div, Value::Int(x), Value::Int(y)) => x.checked_div(y).map(Value::Int),
+        (BinaryOp::Rem, Value::Int(x), Value::Int(y)) => x.checked_rem(y).map(Value::Int),
+
+        (BinaryOp::Add, Value::Float(x), Value::Float(y)) => Some(Value::Float(Float(x.0 + y.0))),
+        (BinaryOp::Sub, Value::Float(x), Value::Float(y)) => Some(Value::Float(Float(x.0 - y.0))),
+        (BinaryOp::Mul, Value::Float(x), Value::Float(y)) => Some(Value::Float(Float(x.0 * y.0))),
+        (BinaryOp::Div, Value::Float(x), Value::Float(y)) => Some(Value::Float(Float(x.0 / y.0))),
+
+        (BinaryOp::Add, Value::Duration(x), Value::Duration(y)) => x.checked_add(y).map(Value::Duration),
+        (BinaryOp::Sub, Value::Duration(x), Value::Duration(y)) => x.checked_sub(y).map(Value::Duration),
+        (BinaryOp::Mul, Value::Duration(x), Value::Usize(y)) => u32::try_from(y).ok().and_then(|y| x.checked_mul(y)).map(Value::Duration),
+        (BinaryOp::Div, Value::Duration(x), Value::Usize(y)) => u32::try_from(y).ok().and_then(|y| x.checked_div(y)).map(Value::Duration),
+
+        (BinaryOp::Add, Value::Date(x), Value::Usize(y)) => i32::try_from(y).ok().and_then(|y| x.checked_add(y)).map(Value::Date),
+        (BinaryOp::Add, Value::Date(x), Value::Int(y)) => i32::try_from(y).ok().and_then(|y| x.checked_add(y)).map(Value::Date),
+        (BinaryOp::Sub, Value::Date(x), Value::Usize(y)) => i32::try_from(y).ok().and_then(|y| x.checked_sub(y)).map(Value::Date),
+        (BinaryOp::Sub, Value::Date(x), Value::Int(y)) => i32::try_from(y).ok().and_then(|y| x.checked_sub(y)).map(Value::Date),
+        (BinaryOp::Sub, Value::Date(x), Value::Date(y)) => Some(Value::Int(i64::from(x) - i64::from(y))),
+
+        _ => None,
+    };
+    result.unwrap_or(Value::Null)
+}
+
+/// Compares values as the comparison operators do, or `None` if either is null or their types differ.
+pub(crate) fn comparison(left: &Value, right: &Value) -> Option<Ordering> {
+    if *left == Value::Null || *right == Value::Null {
+        return None;
+    }
+    let (left, right) = promote(left.clone(), right.clone());
+    compare(&left, &right)
+}
+
+/// Compares values of the same type, after promotion.
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    if std::mem::discriminant(left) == std::mem::discriminant(right) {
+        Some(left.cmp(right))
+    }
+    else {
+        None
+    }
+}
+
+fn apply(function: Function, args: Vec<Value>) -> Value {
+    if function == Function::Coalesce {
+        return args.into_iter().find(|arg| arg != &Value::Null).unwrap_or(Value::Null);
+    }
+    if args.contains(&Value::Null) {
+        return Value::Null;
+    }
+    match (function, &args[..]) {
+        (Function::Abs, [Value::Int(x)]) => x.checked_abs().map_or(Value::Null, Value::Int),
+        (Function::Abs, [Value::Float(x)]) => Value::Float(Float(x.0.abs())),
+        (Function::Abs, [x @ Value::Usize(_)]) => x.clone(),
+        (Function::Concat, args) if args.iter().all(|arg| matches!(arg, Value::String(_))) => {
+            Value::String(args.iter().map(|arg| if let Value::String(x) = arg { x.as_str() } else { unreachable!() }).collect())
+        },
+        (Function::Concat, args) if args.iter().all(|arg| matches!(arg, Value::Bytes(_))) => {
+            Value::Bytes(args.iter().flat_map(|arg| if let Value::Bytes(x) = arg { x.iter().cloned() } else { unreachable!() }).collect())
+        },
+        (Function::Length, [Value::String(x)]) => Value::Usize(x.chars().count()),
+        (Function::Length, [Value::Bytes(x)]) => Value::Usize(x.len()),
+        (Function::Lower, [Value::String(x)]) => Value::String(x.to_lowercase()),
+        (Function::Upper, [Value::String(x)]) => Value::String(x.to_uppercase()),
+        (Function::Trim, [Value::String(x)]) => Value::String(x.trim().to_string()),
+        (Function::Substring, [Value::String(x), start, rest @ ..]) if rest.len() <= 1 => {
+            let start = match cast(start.clone(), Type::Usize) { Value::Usize(start) => start.saturating_sub(1), _ => return Value::Null };
+            let length = match rest.first().map(|length| cast(length.clone(), Type::Usize)) {
+                Some(Value::Usize(length)) => length,
+                Some(_) => return Value::Null,
+                None => usize::MAX,
+            };
+            Value::String(x.chars().skip(start).take(length).collect())
+        },
+        _ => Value::Null,
+    }
+}
+
+/// Converts a value to another type.
+pub(crate) fn cast(value: Value, to: Type) -> Value {
+    let result = match (value, to) {
+        (Value::Null, _) => None,
+        (value @ Value::Bool(_), Type::Bool) => Some(value),
+        (value @ Value::Usize(_), Type::Usize) => Some(value),
+        (value @ Value::Int(_), Type::Int) => Some(value),
+        (value @ Value::Float(_), Type::Float) => Some(value),
+        (value @ Value::String(_), Type::String) => Some(value),
+        (value @ Value::Bytes(_), Type::Bytes) => Some(value),
+        (value @ Value::Date(_), Type::Date) => Some(value),
+
+        (Value::Usize(x), Type::Bool) => Some(Value::Bool(x != 0)),
+        (Value::Int(x), Type::Bool) => Some(Value::Bool(x != 0)),
+        (Value::String(x), Type::Bool) => x.parse().ok().map(Value::Bool),
+
+        (Value::Bool(x), Type::Usize) => Some(Value::Usize(x as usize)),
+        (Value::Int(x), Type::Usize) => usize::try_from(x).ok().map(Value::Usize),
+        (Value::Float(x), Type::Usize) => if x.0 >= 0.0 && x.0 < usize::MAX as f64 { Some(Value::Usize(x.0 as usize)) } else { None },
+        (Value::String(x), Type::Usize) => x.trim().parse().ok().map(Value::Usize),
+
+        (Value::Bool(x), Type::Int) => Some(Value::Int(x as i64)),
+        (Value::Usize(x), Type::Int) => i64::try_from(x).ok().map(Value::Int),
+        (Value::Float(x), Type::Int) => if x.0 >= i64::MIN as f64 && x.0 < i64::MAX as f64 { Some(Value::Int(x.0 as i64)) } else { None },
+        (Value::String(x), Type::Int) => x.trim().parse().ok().map(Value::Int),
+        (Value::Date(x), Type::Int) => Some(Value::Int(x.into())),
+
+        (Value::Usize(x), Type::Float) => Some(Value::Float(Float(x as f64))),
+        (Value::Int(x), Type::Float) => Some(Value::Float(Float(x as f64))),
+        (Value::String(x), Type::Float) => x.trim().parse().ok().map(|x| Value::Float(Float(x))),
+
+        (Value::Bytes(x), Type::String) => String::from_utf8(x).ok().map(Value::String),
+        (Value::Date(x), Type::String) => Some(Value::String(format_date(x))),
+        (Value::Bool(x), Type::String) => Some(Value::String(x.to_string())),
+        (Value::Usize(x), Type::String) => Some(Value::String(x.to_string())),
+        (Value::Int(x), Type::String) => Some(Value::String(x.to_string())),
+        (Value::Float(x), Type::String) => Some(Value::String(x.0.to_string())),
+        (Value::Duration(x), Type::String) => Some(Value::String(format!("{:?}", x))),
+
+        (Value::String(x), Type::Bytes) => Some(Value::Bytes(x.into_bytes())),
+
+        (Value::String(x), Type::Date) => parse_date(x.trim()).map(Value::Date),
+        (Value::Int(x), Type::Date) => i32::try_from(x).ok().map(Value::Date),
+        (Value::Usize(x), Type::Date) => i32::try_from(x).ok().map(Value::Date),
+
+        _ => None,
+    };
+    result.unwrap_or(Value::Null)
+}
+
+/// The number of days from 1970-01-01 to the date `YYYY-MM-DD`, if it is a valid date.
+fn parse_date(text: &str) -> Option<i32> {
+    let mut parts = text.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    if !(1 ..= 12).contains(&month) || day < 1 || day > days_in_month[(month - 1) as usize] {
+        return None;
+    }
+    // Count from March, so that leap days fall at the end of each year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    i32::try_from(era * 146097 + day_of_era - 719468).ok()
+}
+
+/// The date `YYYY-MM-DD` a number of days from 1970-01-01.
+fn format_date(days: i32) -> String {
+    let days = i64::from(days) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -19,6 +19,8 @@ pub mod logging;
 
 pub mod concrete;
 
+pub mod sql;
+
 /// System-wide notion of time.
 pub type Time = ::std::time::Duration;
 /// System-wide update type.
@@ -36,6 +38,16 @@ pub trait Datum : Hash+Sized+Debug {
     fn subject_to(data: &[Self], expr: &Self::Expression) -> Self;
     /// Creates a expression that implements projection.
     fn projection(index: usize) -> Self::Expression;
+    /// Indicates the index an expression projects, if it is a projection.
+    fn projected(expr: &Self::Expression) -> Option<usize>;
+    /// Replaces each index `i` an expression examines with `columns[i]`.
+    fn renumber(expr: &Self::Expression, columns: &[usize]) -> Self::Expression;
+    /// Applies an aggregate to records and their multiplicities.
+    ///
+    /// Multiplicities are usually positive, but may be negative for collections with more retractions than records.
+    fn aggregate(records: &[(Vec<Self>, Diff)], aggregate: &plan::Aggregate) -> Self;
+    /// Compares two values as comparison expressions do, or `None` if they are not comparable, as when either is null.
+    fn compare(left: &Self, right: &Self) -> Option<std::cmp::Ordering>;
 }
 
 /// A type that can be converted to a vector of another type.
@@ -49,18 +61,29 @@ pub trait VectorFrom<T> : Sized {
 pub struct Query<V: Datum> {
     /// A list of bindings of names to plans.
     pub rules: Vec<Rule<V>>,
+    /// SQL statements, planned before `rules` are installed.
+    pub sql: Vec<String>,
 }
 
 impl<V: Datum> Query<V> {
     /// Creates a new, empty query.
     pub fn new() -> Self {
-        Query { rules: Vec::new() }
+        Query { rules: Vec::new(), sql: Vec::new() }
     }
     /// Adds a rule to an existing query.
     pub fn add_rule(mut self, rule: Rule<V>) -> Self {
         self.rules.push(rule);
         self
     }
+    /// Adds SQL statements to an existing query.
+    ///
+    /// Each `CREATE TABLE` creates an input, and each `CREATE VIEW` installs a rule. Statements may
+    /// refer to inputs created by `Command::CreateInput`, whose columns are named `c0`, `c1`, and so on.
+    /// If any statement or rule of the query fails, no table or view is created.
+    pub fn add_sql(mut self, sql: &str) -> Self {
+        self.sql.push(sql.to_string());
+        self
+    }
 }
 
 impl<V: Datum> Query<V> {
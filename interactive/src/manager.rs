@@ -1,6 +1,9 @@
 //! Management of inputs and traces.
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::TcpStream;
+use std::rc::Rc;
 use std::hash::Hash;
 // use std::time::Duration;
 
@@ -15,10 +18,12 @@ use differential_dataflow::ExchangeData;
 use differential_dataflow::trace::implementations::{KeySpine, ValSpine};
 use differential_dataflow::operators::arrange::TraceAgent;
 use differential_dataflow::input::InputSession;
+use differential_dataflow::capture::tcp::TcpSink;
 
 use differential_dataflow::logging::DifferentialEventBuilder;
 
 use crate::{Time, Diff, Plan, Datum};
+use crate::sql::Catalog;
 
 /// A trace handle for key-only data.
 pub type TraceKeyHandle<K, T, R> = TraceAgent<KeySpine<K, T, R>>;
@@ -35,10 +40,23 @@ pub struct Manager<V: ExchangeData+Datum> {
     pub inputs: InputManager<V>,
     /// Manages maintained traces.
     pub traces: TraceManager<V>,
-    /// Probes all computations.
-    pub probe: ProbeHandle<Time>,
+    /// Probes the rules of each query, by dataflow.
+    ///
+    /// Dropping a dataflow does not advance its probe, which must be removed along with it.
+    pub probes: HashMap<usize, ProbeHandle<Time>>,
+    /// Names the columns of tables and views created by SQL.
+    pub catalog: Catalog,
+    /// The dataflow installing each rule, by name.
+    pub queries: HashMap<String, usize>,
+    /// Client connections awaiting their `Subscribe` commands, in the order the commands were issued.
+    pub connections: VecDeque<TcpStream>,
+    /// The subscriptions to each rule.
+    pub subscriptions: HashMap<String, Vec<Subscription>>,
 }
 
+/// The dataflow of a subscription to a rule, and its connection if held by this worker.
+pub type Subscription = (usize, Option<Rc<RefCell<TcpSink>>>);
+
 impl<V: ExchangeData+Datum> Manager<V>
 // where
 //     V: ExchangeData+Hash+LoggingValue,
@@ -49,7 +67,11 @@ impl<V: ExchangeData+Datum> Manager<V>
         Manager {
             inputs: InputManager::new(),
             traces: TraceManager::new(),
-            probe: ProbeHandle::new(),
+            probes: HashMap::new(),
+            catalog: Catalog::new(),
+            queries: HashMap::new(),
+            connections: VecDeque::new(),
+            subscriptions: HashMap::new(),
         }
     }
 
@@ -83,6 +105,12 @@ impl<V: ExchangeData+Datum> Manager<V>
         self.inputs.sessions.clear();
         self.traces.inputs.clear();
         self.traces.arrangements.clear();
+        self.traces.arities.clear();
+        self.traces.owners.clear();
+        self.queries.clear();
+        self.probes.clear();
+        self.connections.clear();
+        self.subscriptions.clear();
 
         // Deregister loggers, so that the logging dataflows can shut down.
         worker
@@ -94,6 +122,72 @@ impl<V: ExchangeData+Datum> Manager<V>
             .insert::<DifferentialEventBuilder,_>("differential/arrange", move |_time, _data| { });
     }
 
+    /// Drops the query that installed the rule `name`, along with the other rules it installed
+    /// and any subscriptions to them.
+    ///
+    /// The query's dataflow is dropped once no other dataflow imports its arrangements.
+    /// Returns false if no query installed the rule.
+    pub fn drop_query<A: Allocate>(&mut self, name: &str, worker: &mut Worker<A>) -> bool {
+        if let Some(index) = self.queries.remove(name) {
+            let mut names = vec![name.to_string()];
+            names.extend(self.queries.iter().filter(|(_, dataflow)| **dataflow == index).map(|(name, _)| name.clone()));
+            self.queries.retain(|_, dataflow| *dataflow != index);
+            for name in names {
+                // Subscriptions import the rule's arrangement, and must be dropped first.
+                for (subscription, _sink) in self.subscriptions.remove(&name).unwrap_or_default() {
+                    for dataflow in self.traces.retire(subscription) {
+                        self.probes.remove(&dataflow);
+                        worker.drop_dataflow(dataflow);
+                    }
+                }
+                self.traces.remove_unkeyed(&Plan::Source(name.clone()));
+                self.catalog.relations.remove(&name);
+            }
+            for dataflow in self.traces.retire(index) {
+                self.probes.remove(&dataflow);
+                worker.drop_dataflow(dataflow);
+            }
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    /// Drops the subscription whose dataflow is `index`.
+    ///
+    /// Returns false if there is no such subscription.
+    pub fn drop_subscription<A: Allocate>(&mut self, index: usize, worker: &mut Worker<A>) -> bool {
+        let mut found = false;
+        for subscriptions in self.subscriptions.values_mut() {
+            found |= subscriptions.iter().any(|(dataflow, _sink)| *dataflow == index);
+            subscriptions.retain(|(dataflow, _sink)| *dataflow != index);
+        }
+        self.subscriptions.retain(|_, subscriptions| !subscriptions.is_empty());
+        if found {
+            for dataflow in self.traces.retire(index) {
+                self.probes.remove(&dataflow);
+                worker.drop_dataflow(dataflow);
+            }
+        }
+        found
+    }
+
+    /// Reports the subscriptions whose connections have failed, by dataflow index, each only once.
+    ///
+    /// The subscriptions remain until dropped, which all workers must do, by `Command::Unsubscribe`.
+    pub fn failed_subscriptions(&mut self) -> Vec<(usize, String)> {
+        let mut failed = Vec::new();
+        for (dataflow, sink) in self.subscriptions.values_mut().flat_map(|subscriptions| subscriptions.iter_mut()) {
+            let error = sink.as_ref().and_then(|sink| sink.borrow().error().map(|error| error.to_string()));
+            if let Some(error) = error {
+                *sink = None;
+                failed.push((*dataflow, error));
+            }
+        }
+        failed
+    }
+
     /// Inserts a new input session by name.
     pub fn insert_input(
         &mut self,
@@ -169,6 +263,21 @@ pub struct TraceManager<V: ExchangeData+Datum> {
     /// Arrangements of collections by key.
     arrangements: HashMap<Plan<V>, HashMap<Vec<usize>, KeysValsHandle<V>>>,
 
+    /// The number of values in the records of named sources, where known.
+    ///
+    /// Plans are put in canonical form before lookup, which reorders joins of sources listed here.
+    arities: HashMap<String, usize>,
+
+    /// The dataflow under construction, if any, which owns installed arrangements.
+    dataflow: Option<usize>,
+    /// The dataflow maintaining each arrangement, by plan and keys (`None` if unkeyed).
+    owners: HashMap<(Plan<V>, Option<Vec<usize>>), usize>,
+    /// For each dataflow, the dataflows whose arrangements it imports.
+    dependencies: HashMap<usize, HashSet<usize>>,
+    /// For each dataflow, the number of dataflows importing its arrangements.
+    dependents: HashMap<usize, usize>,
+    /// Dataflows no longer required for themselves, but perhaps still by their dependents.
+    retired: HashSet<usize>,
 }
 
 impl<V: ExchangeData+Hash+Datum> TraceManager<V> {
@@ -177,7 +286,13 @@ impl<V: ExchangeData+Hash+Datum> TraceManager<V> {
     pub fn new() -> Self {
         Self {
             inputs: HashMap::new(),
-            arrangements: HashMap::new()
+            arrangements: HashMap::new(),
+            arities: HashMap::new(),
+            dataflow: None,
+            owners: HashMap::new(),
+            dependencies: HashMap::new(),
+            dependents: HashMap::new(),
+            retired: HashSet::new(),
         }
     }
 
@@ -199,31 +314,205 @@ impl<V: ExchangeData+Hash+Datum> TraceManager<V> {
     }
 
     /// Recover an arrangement by plan and keys, if it is cached.
-    pub fn get_unkeyed(&self, plan: &Plan<V>) -> Option<KeysOnlyHandle<V>> {
-        self.inputs
-            .get(plan)
-            .map(|x| x.clone())
+    ///
+    /// The dataflow under construction, if any, is recorded as depending on the arrangement.
+    pub fn get_unkeyed(&mut self, plan: &Plan<V>) -> Option<KeysOnlyHandle<V>> {
+        let plan = self.canonical(plan);
+        let handle = self.inputs.get(&plan).cloned();
+        if handle.is_some() {
+            self.depend_on(&(plan, None));
+        }
+        handle
     }
 
     /// Installs an unkeyed arrangement for a specified plan.
+    ///
+    /// The arrangement is owned by the dataflow under construction, if any.
     pub fn set_unkeyed(&mut self, plan: &Plan<V>, handle: &KeysOnlyHandle<V>) {
+        let plan = self.canonical(plan);
         self.inputs
             .insert(plan.clone(), handle.clone());
+        self.own(plan, None);
     }
 
     /// Recover an arrangement by plan and keys, if it is cached.
-    pub fn get_keyed(&self, plan: &Plan<V>, keys: &[usize]) -> Option<KeysValsHandle<V>> {
+    ///
+    /// The dataflow under construction, if any, is recorded as depending on the arrangement.
+    pub fn get_keyed(&mut self, plan: &Plan<V>, keys: &[usize]) -> Option<KeysValsHandle<V>> {
+        let plan = self.canonical(plan);
+        let handle =
         self.arrangements
-            .get(plan)
-            .and_then(|map| map.get(keys).map(|x| x.clone()))
+            .get(&plan)
+            .and_then(|map| map.get(keys).cloned());
+        if handle.is_some() {
+            self.depend_on(&(plan, Some(keys.to_vec())));
+        }
+        handle
+    }
+
+    /// Indicates if an unkeyed arrangement is cached for a plan.
+    ///
+    /// Unlike `get_unkeyed`, this records no dependence on the arrangement.
+    pub fn contains_unkeyed(&self, plan: &Plan<V>) -> bool {
+        self.inputs.contains_key(&self.canonical(plan))
     }
 
     /// Installs a keyed arrangement for a specified plan and sequence of keys.
+    ///
+    /// The arrangement is owned by the dataflow under construction, if any.
     pub fn set_keyed(&mut self, plan: &Plan<V>, keys: &[usize], handle: &KeysValsHandle<V>) {
+        let plan = self.canonical(plan);
         self.arrangements
             .entry(plan.clone())
-            .or_insert(HashMap::new())
+            .or_default()
             .insert(keys.to_vec(), handle.clone());
+        self.own(plan, Some(keys.to_vec()));
+    }
+
+    /// Removes an unkeyed arrangement for a specified plan, for example a published name.
+    ///
+    /// Dataflows that already import the arrangement are unaffected.
+    pub fn remove_unkeyed(&mut self, plan: &Plan<V>) {
+        if let Plan::Source(name) = plan {
+            self.arities.remove(name);
+        }
+        let plan = self.canonical(plan);
+        self.inputs.remove(&plan);
+        self.owners.remove(&(plan, None));
+    }
+
+    /// Records the number of values in the records of a named source.
+    ///
+    /// This allows joins reading from the source to be put in a canonical order,
+    /// and should be recorded before any arrangements of such joins are installed.
+    pub fn set_arity(&mut self, name: &str, arity: usize) {
+        self.arities.insert(name.to_string(), arity);
+    }
+
+    /// The canonical form of `plan`, under which its arrangements are installed.
+    fn canonical(&self, plan: &Plan<V>) -> Plan<V> {
+        crate::plan::canonicalize(plan, &self.arities)
+    }
+
+    /// The key sequences of the cached keyed arrangements of a plan.
+    ///
+    /// Unlike `get_keyed`, this records no dependence on the arrangements.
+    pub fn keys(&self, plan: &Plan<V>) -> Vec<Vec<usize>> {
+        self.arrangements
+            .get(&self.canonical(plan))
+            .map(|map| map.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The number of updates in a cached arrangement of a plan, as an estimate of its size.
+    pub fn statistics(&self, plan: &Plan<V>) -> Option<usize> {
+        use differential_dataflow::trace::{BatchReader, TraceReader};
+        let plan = self.canonical(plan);
+        let mut count = 0;
+        if let Some(trace) = self.inputs.get(&plan) {
+            trace.map_batches(|batch| count += batch.len());
+        }
+        else {
+            let trace = self.arrangements.get(&plan)?.values().next()?;
+            trace.map_batches(|batch| count += batch.len());
+        }
+        Some(count)
+    }
+
+    /// The number of values in each record of a plan, if it follows from the plan and the
+    /// recorded numbers of values of sources, or if the plan has a non-empty unkeyed arrangement.
+    pub fn arity(&self, plan: &Plan<V>) -> Option<usize> {
+        use differential_dataflow::trace::{BatchReader, TraceReader};
+        use differential_dataflow::trace::cursor::Cursor;
+        if let Some(arity) = plan.arity(&|name| self.arities.get(name).copied()) {
+            return Some(arity);
+        }
+        let mut arity = None;
+        self.inputs.get(&self.canonical(plan))?.map_batches(|batch| {
+            if arity.is_none() {
+                arity = batch.cursor().get_key(batch).map(|key| key.len());
+            }
+        });
+        arity
+    }
+
+    /// Attributes arrangements installed and used until `end_dataflow` to dataflow `index`.
+    pub fn begin_dataflow(&mut self, index: usize) {
+        self.dataflow = Some(index);
+        self.dependencies.entry(index).or_default();
+    }
+
+    /// Concludes the attribution started by `begin_dataflow`.
+    pub fn end_dataflow(&mut self) {
+        self.dataflow = None;
+    }
+
+    /// Records that the arrangement is owned by the dataflow under construction.
+    fn own(&mut self, plan: Plan<V>, keys: Option<Vec<usize>>) {
+        if let Some(dataflow) = self.dataflow {
+            self.owners.insert((plan, keys), dataflow);
+        }
+        else {
+            self.owners.remove(&(plan, keys));
+        }
+    }
+
+    /// Records that the dataflow under construction imports the arrangement.
+    fn depend_on(&mut self, key: &(Plan<V>, Option<Vec<usize>>)) {
+        if let (Some(dataflow), Some(&owner)) = (self.dataflow, self.owners.get(key)) {
+            if owner != dataflow && self.dependencies.entry(dataflow).or_default().insert(owner) {
+                *self.dependents.entry(owner).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Retires dataflow `index`, and returns the dataflows that may now be dropped.
+    ///
+    /// A retired dataflow is dropped once no other dataflow imports its arrangements, at which
+    /// point its arrangements are removed, and the dataflows it imports from are released in turn.
+    pub fn retire(&mut self, index: usize) -> Vec<usize> {
+        self.retired.insert(index);
+        let mut dropped = Vec::new();
+        let mut candidates = vec![index];
+        while let Some(dataflow) = candidates.pop() {
+            if self.retired.contains(&dataflow) && self.dependents.get(&dataflow).copied().unwrap_or(0) == 0 {
+                self.retired.remove(&dataflow);
+                self.dependents.remove(&dataflow);
+
+                // Remove the arrangements the dataflow maintains.
+                let owned =
+                self.owners
+                    .iter()
+                    .filter(|(_, owner)| **owner == dataflow)
+                    .map(|(key, _)| key.clone())
+                    .collect::<Vec<_>>();
+                for (plan, keys) in owned {
+                    match &keys {
+                        None => { self.inputs.remove(&plan); },
+                        Some(keys) => {
+                            if let Some(map) = self.arrangements.get_mut(&plan) {
+                                map.remove(keys);
+                                if map.is_empty() {
+                                    self.arrangements.remove(&plan);
+                                }
+                            }
+                        },
+                    }
+                    self.owners.remove(&(plan, keys));
+                }
+
+                // Release the dataflows it imports from.
+                for owner in self.dependencies.remove(&dataflow).unwrap_or_default() {
+                    if let Some(count) = self.dependents.get_mut(&owner) {
+                        *count -= 1;
+                    }
+                    candidates.push(owner);
+                }
+
+                dropped.push(dataflow);
+            }
+        }
+        dropped
     }
 
 }
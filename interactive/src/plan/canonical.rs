@@ -0,0 +1,205 @@
+//! Canonical forms of plans, so that equivalent plans can share maintained arrangements.
+
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+
+use differential_dataflow::ExchangeData;
+use crate::plan::{Plan, Map, Filter, Join, MultiwayJoin, Reduce, Iterate, Aggregate};
+use crate::{Datum, Rule};
+
+/// An equivalent plan in a canonical form, so that equivalent plans are more often equal.
+///
+/// The inputs of joins, multiway joins, and concatenations are put in a canonical order, and
+/// filter predicates are normalized. Plans reading from reordered inputs have their indices
+/// renumbered to match, and a projection restores the order of values where none does.
+///
+/// Ordering the inputs of a join requires the number of values of each input. The number of
+/// values of a named source is taken from `arities`, and joins of other sources are not reordered.
+pub fn canonicalize<V: ExchangeData+Hash+Datum>(plan: &Plan<V>, arities: &HashMap<String, usize>) -> Plan<V> {
+    let (plan, columns) = canonical(plan, arities);
+    restore(plan, columns)
+}
+
+/// An equivalent plan up to the order of values, and `columns` such that value `i` of `plan`
+/// is value `columns[i]` of the result, or `None` if values are in the same order.
+fn canonical<V: ExchangeData+Hash+Datum>(plan: &Plan<V>, arities: &HashMap<String, usize>) -> (Plan<V>, Option<Vec<usize>>) {
+    match plan {
+        Plan::Map(map) => {
+            let (plan, columns) = canonical(&map.plan, arities);
+            let expressions = match &columns {
+                Some(columns) => map.expressions.iter().map(|expr| V::renumber(expr, columns)).collect(),
+                None => map.expressions.clone(),
+            };
+            // Projections that only reorder values are tracked, rather than applied.
+            let projection = expressions.iter().map(V::projected).collect::<Option<Vec<_>>>();
+            if let (Some(projection), Some(arity)) = (projection, plan.arity(&|name| arities.get(name).copied())) {
+                if projection.len() == arity && (0 .. arity).all(|index| projection.contains(&index)) {
+                    return (plan, Some(projection));
+                }
+            }
+            (Plan::Map(Map { expressions, plan: Box::new(plan) }), None)
+        },
+        Plan::Distinct(plan) => {
+            let (plan, columns) = canonical(plan, arities);
+            (Plan::Distinct(Box::new(plan)), columns)
+        },
+        Plan::Concat(plans) => {
+            let mut plans = plans.iter().map(|plan| canonical(plan, arities)).collect::<Vec<_>>();
+            // Inputs with values in different orders must first be restored to the same order.
+            if plans.iter().any(|(_, columns)| columns != &plans[0].1) {
+                plans = plans.into_iter().map(|(plan, columns)| (restore(plan, columns), None)).collect();
+            }
+            let columns = plans.first().and_then(|(_, columns)| columns.clone());
+            let mut plans = plans.into_iter().map(|(plan, _)| plan).collect::<Vec<_>>();
+            plans.sort();
+            (Plan::Concat(plans), columns)
+        },
+        Plan::Consolidate(plan) => {
+            let (plan, columns) = canonical(plan, arities);
+            (Plan::Consolidate(Box::new(plan)), columns)
+        },
+        Plan::Join(join) => canonical_join(join, arities),
+        Plan::MultiwayJoin(join) => {
+            let sources = join.sources.iter().map(|plan| canonical(plan, arities)).collect::<Vec<_>>();
+            let mut order = (0 .. sources.len()).collect::<Vec<_>>();
+            order.sort_by(|index1, index2| sources[*index1].0.cmp(&sources[*index2].0));
+            let mut rank = vec![0; order.len()];
+            for (new, old) in order.iter().enumerate() {
+                rank[*old] = new;
+            }
+
+            // Attributes are renumbered within their source, and sources by their new order.
+            let renumber = |(attr, input): &(usize, usize)| {
+                let attr = sources[*input].1.as_ref().map_or(*attr, |columns| columns[*attr]);
+                (attr, rank[*input])
+            };
+            let results = join.results.iter().map(&renumber).collect();
+            let mut equalities =
+            join.equalities
+                .iter()
+                .map(|class| {
+                    let mut class = class.iter().map(&renumber).collect::<Vec<_>>();
+                    class.sort();
+                    class.dedup();
+                    class
+                })
+                .collect::<Vec<_>>();
+            equalities.sort();
+
+            let sources = order.iter().map(|index| sources[*index].0.clone()).collect();
+            (Plan::MultiwayJoin(MultiwayJoin { results, sources, equalities }), None)
+        },
+        Plan::Negate(plan) => {
+            let (plan, columns) = canonical(plan, arities);
+            (Plan::Negate(Box::new(plan)), columns)
+        },
+        Plan::Filter(filter) => {
+            let (plan, columns) = canonical(&filter.plan, arities);
+            let predicate = match &columns {
+                Some(columns) => filter.predicate.remap(&mut |index| columns[index]),
+                None => filter.predicate.clone(),
+            };
+            (Plan::Filter(Filter { predicate: predicate.normalize(), plan: Box::new(plan) }), columns)
+        },
+        Plan::Reduce(reduce) => {
+            let (plan, columns) = canonical(&reduce.plan, arities);
+            let column = |index: usize| columns.as_ref().map_or(index, |columns| columns[index]);
+            let keys = reduce.keys.iter().map(|key| column(*key)).collect();
+            let aggregates =
+            reduce.aggregates
+                .iter()
+                .map(|aggregate| match aggregate {
+                    Aggregate::Count => Aggregate::Count,
+                    Aggregate::Sum(index) => Aggregate::Sum(column(*index)),
+                    Aggregate::Min(index) => Aggregate::Min(column(*index)),
+                    Aggregate::Max(index) => Aggregate::Max(column(*index)),
+                    Aggregate::Avg(index) => Aggregate::Avg(column(*index)),
+                })
+                .collect();
+            (Plan::Reduce(Reduce { keys, aggregates, plan: Box::new(plan) }), None)
+        },
+        Plan::Iterate(iterate) => {
+            // Bindings shadow named sources, whose numbers of values may differ.
+            let mut arities = arities.clone();
+            for binding in iterate.bindings.iter() {
+                arities.remove(&binding.name);
+            }
+            let bindings =
+            iterate.bindings
+                .iter()
+                .map(|binding| Rule { name: binding.name.clone(), plan: canonicalize(&binding.plan, &arities) })
+                .collect();
+            (Plan::Iterate(Iterate { bindings, result: iterate.result.clone() }), None)
+        },
+        Plan::Source(name) => (Plan::Source(name.clone()), None),
+        Plan::Inspect(text, plan) => (Plan::Inspect(text.clone(), Box::new(canonicalize(plan, arities))), None),
+    }
+}
+
+/// The canonical form of a join, whose inputs are ordered if their numbers of values are known.
+fn canonical_join<V: ExchangeData+Hash+Datum>(join: &Join<V>, arities: &HashMap<String, usize>) -> (Plan<V>, Option<Vec<usize>>) {
+
+    let sources = |name: &str| arities.get(name).copied();
+    let (plan1, columns1) = canonical(&join.plan1, arities);
+    let (plan2, columns2) = canonical(&join.plan2, arities);
+    let arity1 = columns1.as_ref().map(|columns| columns.len()).or_else(|| plan1.arity(&sources));
+    let arity2 = columns2.as_ref().map(|columns| columns.len()).or_else(|| plan2.arity(&sources));
+
+    let (arity1, arity2) = match (arity1, arity2) {
+        (Some(arity1), Some(arity2)) => (arity1, arity2),
+        _ => {
+            // Without the numbers of values, the order of the join's values cannot be tracked.
+            let plan = Plan::Join(Join {
+                keys: join.keys.clone(),
+                plan1: Box::new(restore(plan1, columns1)),
+                plan2: Box::new(restore(plan2, columns2)),
+            });
+            return (plan, None);
+        },
+    };
+    let columns1 = columns1.unwrap_or_else(|| (0 .. arity1).collect());
+    let columns2 = columns2.unwrap_or_else(|| (0 .. arity2).collect());
+
+    // Keys renumbered for the canonical inputs, then sorted, with inputs swapped if out of order.
+    let keys = join.keys.iter().map(|(key1, key2)| (columns1[*key1], columns2[*key2])).collect::<Vec<_>>();
+    let swap = plan2 < plan1;
+    let oriented = |(key1, key2): (usize, usize)| if swap { (key2, key1) } else { (key1, key2) };
+    let mut sorted = keys.iter().map(|key| oriented(*key)).collect::<Vec<_>>();
+    sorted.sort();
+
+    // Locate each value of the join among the values of the canonical join.
+    let mut output = Vec::with_capacity(keys.len());
+    let mut used = vec![false; sorted.len()];
+    for key in keys.iter() {
+        let position = (0 .. sorted.len()).find(|position| !used[*position] && sorted[*position] == oriented(*key)).unwrap();
+        used[position] = true;
+        output.push(position);
+    }
+    let keys1 = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+    let keys2 = keys.iter().map(|key| key.1).collect::<Vec<_>>();
+    let values1 = arity1 - keys1.iter().collect::<BTreeSet<_>>().len();
+    let values2 = arity2 - keys2.iter().collect::<BTreeSet<_>>().len();
+    let (offset1, offset2) = if swap { (keys.len() + values2, keys.len()) } else { (keys.len(), keys.len() + values1) };
+    for index in (0 .. arity1).filter(|index| join.keys.iter().all(|key| key.0 != *index)) {
+        output.push(offset1 + position(&keys1, columns1[index]));
+    }
+    for index in (0 .. arity2).filter(|index| join.keys.iter().all(|key| key.1 != *index)) {
+        output.push(offset2 + position(&keys2, columns2[index]));
+    }
+
+    let (plan1, plan2) = if swap { (plan2, plan1) } else { (plan1, plan2) };
+    (Plan::Join(Join { keys: sorted, plan1: Box::new(plan1), plan2: Box::new(plan2) }), Some(output))
+}
+
+/// Restores the order of the values of a plan produced by `canonical`.
+fn restore<V: ExchangeData+Hash+Datum>(plan: Plan<V>, columns: Option<Vec<usize>>) -> Plan<V> {
+    match columns {
+        Some(columns) if columns.iter().enumerate().any(|(index, column)| index != *column) => plan.project(columns),
+        _ => plan,
+    }
+}
+
+/// The position of `column` among the values not at `keys`.
+fn position(keys: &[usize], column: usize) -> usize {
+    (0 .. column).filter(|index| !keys.contains(index)).count()
+}
@@ -5,8 +5,8 @@ use serde::{Deserialize, Serialize};
 use timely::dataflow::Scope;
 
 use differential_dataflow::{Collection, ExchangeData};
-use crate::plan::{Plan, Render};
-use crate::{TraceManager, Time, Diff, Datum};
+use crate::plan::{Arrangements, Plan, Render, Stamp};
+use crate::{Diff, Datum};
 
 /// What to compare against.
 ///
@@ -52,19 +52,139 @@ pub enum Predicate<Value> {
     Not(Box<Predicate<Value>>),
 }
 
-impl<Value: Ord> Predicate<Value> {
+impl<Value: Datum> Predicate<Value> {
     /// Indicates if the predicate is satisfied.
+    ///
+    /// Values are compared as by comparison expressions, so that numbers of different types are compared
+    /// by value. Comparisons with null, or between values of different types, are neither satisfied nor
+    /// unsatisfied, and neither are their complements.
     pub fn satisfied(&self, values: &[Value]) -> bool {
+        self.evaluate(values) == Some(true)
+    }
+    /// Evaluates the predicate, with `None` for predicates that are neither satisfied nor unsatisfied.
+    fn evaluate(&self, values: &[Value]) -> Option<bool> {
+        use std::cmp::Ordering;
+        let compare = |index: &usize, other: &SecondArgument<Value>| Value::compare(&values[*index], other.value(values));
+        match self {
+            Predicate::LessThan(index, other) => compare(index, other).map(|o| o == Ordering::Less),
+            Predicate::LessEqual(index, other) => compare(index, other).map(|o| o != Ordering::Greater),
+            Predicate::GreaterThan(index, other) => compare(index, other).map(|o| o == Ordering::Greater),
+            Predicate::GreaterEqual(index, other) => compare(index, other).map(|o| o != Ordering::Less),
+            Predicate::Equal(index, other) => compare(index, other).map(|o| o == Ordering::Equal),
+            Predicate::NotEqual(index, other) => compare(index, other).map(|o| o != Ordering::Equal),
+            Predicate::Any(predicates) => {
+                let results = predicates.iter().map(|p| p.evaluate(values)).collect::<Vec<_>>();
+                if results.contains(&Some(true)) { Some(true) }
+                else if results.contains(&None) { None }
+                else { Some(false) }
+            },
+            Predicate::All(predicates) => {
+                let results = predicates.iter().map(|p| p.evaluate(values)).collect::<Vec<_>>();
+                if results.contains(&Some(false)) { Some(false) }
+                else if results.contains(&None) { None }
+                else { Some(true) }
+            },
+            Predicate::Not(predicate) => predicate.evaluate(values).map(|satisfied| !satisfied),
+        }
+    }
+}
+
+impl<Value: Clone> Predicate<Value> {
+    /// The indices of the values the predicate examines.
+    pub fn positions(&self) -> Vec<usize> {
+        let mut positions = Vec::new();
+        self.remap(&mut |index| { positions.push(index); index });
+        positions.sort();
+        positions.dedup();
+        positions
+    }
+    /// The predicate with each index replaced by `logic(index)`.
+    pub fn remap(&self, logic: &mut impl FnMut(usize) -> usize) -> Self {
+        let mut remap = |index: &usize, other: &SecondArgument<Value>| {
+            let other = match other {
+                SecondArgument::Constant(value) => SecondArgument::Constant(value.clone()),
+                SecondArgument::Position(index) => SecondArgument::Position(logic(*index)),
+            };
+            (logic(*index), other)
+        };
+        match self {
+            Predicate::LessThan(index, other) => { let (i, o) = remap(index, other); Predicate::LessThan(i, o) },
+            Predicate::LessEqual(index, other) => { let (i, o) = remap(index, other); Predicate::LessEqual(i, o) },
+            Predicate::GreaterThan(index, other) => { let (i, o) = remap(index, other); Predicate::GreaterThan(i, o) },
+            Predicate::GreaterEqual(index, other) => { let (i, o) = remap(index, other); Predicate::GreaterEqual(i, o) },
+            Predicate::Equal(index, other) => { let (i, o) = remap(index, other); Predicate::Equal(i, o) },
+            Predicate::NotEqual(index, other) => { let (i, o) = remap(index, other); Predicate::NotEqual(i, o) },
+            Predicate::Any(predicates) => Predicate::Any(predicates.iter().map(|p| p.remap(&mut *logic)).collect()),
+            Predicate::All(predicates) => Predicate::All(predicates.iter().map(|p| p.remap(&mut *logic)).collect()),
+            Predicate::Not(predicate) => Predicate::Not(Box::new(predicate.remap(&mut *logic))),
+        }
+    }
+    /// The predicates that must all be satisfied for the predicate to be satisfied.
+    pub fn conjuncts(self) -> Vec<Self> {
+        match self {
+            Predicate::All(predicates) => predicates.into_iter().flat_map(|p| p.conjuncts()).collect(),
+            predicate => vec![predicate],
+        }
+    }
+    /// A predicate satisfied when all of `predicates` are, or `None` if there are none.
+    pub fn conjunction(mut predicates: Vec<Self>) -> Option<Self> {
+        match predicates.len() {
+            0 => None,
+            1 => predicates.pop(),
+            _ => Some(Predicate::All(predicates)),
+        }
+    }
+}
+
+impl<Value: Ord+Clone> Predicate<Value> {
+    /// An equivalent predicate in a normal form, so that equivalent predicates are more often equal.
+    ///
+    /// Nested lists are flattened, sorted, and deduplicated, negations of comparisons are replaced
+    /// by the complementary comparisons, and comparisons of two indices name the lesser index first.
+    pub fn normalize(self) -> Self {
         match self {
-            Predicate::LessThan(index, other) => values[*index].lt(other.value(values)),
-            Predicate::LessEqual(index, other) => values[*index].le(other.value(values)),
-            Predicate::GreaterThan(index, other) => values[*index].gt(other.value(values)),
-            Predicate::GreaterEqual(index, other) => values[*index].ge(other.value(values)),
-            Predicate::Equal(index, other) => values[*index].eq(other.value(values)),
-            Predicate::NotEqual(index, other) => values[*index].ne(other.value(values)),
-            Predicate::Any(predicates) => predicates.iter().any(|p| p.satisfied(values)),
-            Predicate::All(predicates) => predicates.iter().all(|p| p.satisfied(values)),
-            Predicate::Not(predicate) => !predicate.satisfied(values),
+            Predicate::LessThan(index, SecondArgument::Position(other)) if other < index => Predicate::GreaterThan(other, SecondArgument::Position(index)),
+            Predicate::LessEqual(index, SecondArgument::Position(other)) if other < index => Predicate::GreaterEqual(other, SecondArgument::Position(index)),
+            Predicate::GreaterThan(index, SecondArgument::Position(other)) if other < index => Predicate::LessThan(other, SecondArgument::Position(index)),
+            Predicate::GreaterEqual(index, SecondArgument::Position(other)) if other < index => Predicate::LessEqual(other, SecondArgument::Position(index)),
+            Predicate::Equal(index, SecondArgument::Position(other)) if other < index => Predicate::Equal(other, SecondArgument::Position(index)),
+            Predicate::NotEqual(index, SecondArgument::Position(other)) if other < index => Predicate::NotEqual(other, SecondArgument::Position(index)),
+            Predicate::Any(predicates) => {
+                let mut predicates =
+                predicates
+                    .into_iter()
+                    .flat_map(|predicate| match predicate.normalize() {
+                        Predicate::Any(predicates) => predicates,
+                        predicate => vec![predicate],
+                    })
+                    .collect::<Vec<_>>();
+                predicates.sort();
+                predicates.dedup();
+                if predicates.len() == 1 { predicates.pop().unwrap() } else { Predicate::Any(predicates) }
+            },
+            Predicate::All(predicates) => {
+                let mut predicates =
+                predicates
+                    .into_iter()
+                    .flat_map(|predicate| predicate.normalize().conjuncts())
+                    .collect::<Vec<_>>();
+                predicates.sort();
+                predicates.dedup();
+                if predicates.len() == 1 { predicates.pop().unwrap() } else { Predicate::All(predicates) }
+            },
+            Predicate::Not(predicate) => {
+                match predicate.normalize() {
+                    Predicate::LessThan(index, other) => Predicate::GreaterEqual(index, other).normalize(),
+                    Predicate::LessEqual(index, other) => Predicate::GreaterThan(index, other).normalize(),
+                    Predicate::GreaterThan(index, other) => Predicate::LessEqual(index, other).normalize(),
+                    Predicate::GreaterEqual(index, other) => Predicate::LessThan(index, other).normalize(),
+                    Predicate::Equal(index, other) => Predicate::NotEqual(index, other).normalize(),
+                    Predicate::NotEqual(index, other) => Predicate::Equal(index, other).normalize(),
+                    Predicate::Not(predicate) => *predicate,
+                    predicate => Predicate::Not(Box::new(predicate)),
+                }
+            },
+            predicate => predicate,
         }
     }
 }
@@ -84,12 +204,16 @@ impl<V: ExchangeData+Hash+Datum> Render for Filter<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S, A>(
         &self,
         scope: &mut S,
         collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
-        arrangements: &mut TraceManager<Self::Value>,
+        arrangements: &mut A,
     ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>,
     {
         let predicate = self.predicate.clone();
         self.plan
@@ -0,0 +1,177 @@
+//! Recursive expression plan.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+use timely::dataflow::Scope;
+use timely::order::Product;
+
+use differential_dataflow::{Collection, ExchangeData};
+use differential_dataflow::dynamic::feedback_summary;
+use differential_dataflow::dynamic::pointstamp::PointStamp;
+use crate::plan::{Arrangements, ArrangedKeysOnly, ArrangedKeysVals, Plan, Render, Rendered, Stamp};
+use crate::{Diff, Datum, Rule};
+
+/// A plan stage binding names to plans that may refer to each other,
+/// and producing the fixed point of one of the bindings.
+///
+/// Each binding starts empty, and in each round is replaced by its plan
+/// applied to the bindings of the previous round. Plans should consolidate
+/// or `distinct` their results, so that the rounds reach a fixed point.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Iterate<V: Datum> {
+    /// Bindings of names to plans, which refer to bindings by `Plan::Source`.
+    pub bindings: Vec<Rule<V>>,
+    /// The name of the binding to produce.
+    pub result: String,
+}
+
+impl<V: ExchangeData+Hash+Datum> Render for Iterate<V> {
+
+    type Value = V;
+
+    fn render<S, A>(
+        &self,
+        scope: &mut S,
+        collections: &mut HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
+        arrangements: &mut A,
+    ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>,
+    {
+        // Plans that do not refer to bindings are rendered outside the iteration, where they
+        // can use and contribute to the arrangements of the enclosing scope. Arrangements made
+        // within the iteration have different timestamps, and are shared only within it.
+        let names = self.bindings.iter().map(|binding| binding.name.clone()).collect::<Vec<_>>();
+        let mut invariants = Vec::new();
+        for binding in self.bindings.iter() {
+            collect_invariants(&binding.plan, &names, &mut invariants);
+        }
+        let invariants =
+        invariants
+            .into_iter()
+            .map(|plan| {
+                let collection = plan.render(scope, collections, arrangements);
+                (plan, collection)
+            })
+            .collect::<Vec<_>>();
+
+        arrangements.iterate(self, invariants, scope)
+    }
+}
+
+/// Renders the fixed point of `iterate` as the `level`th of nested iterations in `scope`.
+///
+/// Each nested iteration adds a coordinate to the timestamps of `scope`, rather than a scope of its own,
+/// so that rendering plans with any depth of nesting requires a finite number of scope types.
+pub(crate) fn fixed_point<S, T, V>(
+    iterate: &Iterate<V>,
+    invariants: Rendered<S, V>,
+    scope: &mut S,
+    level: usize,
+) -> Collection<S, Vec<V>, Diff>
+where
+    S: Scope<Timestamp = Product<T, PointStamp<u32>>>,
+    T: Stamp,
+    V: ExchangeData+Hash+Datum,
+{
+    use differential_dataflow::operators::iterate::Variable;
+
+    let mut within = invariants.into_iter().collect::<HashMap<_, _>>();
+    let variables =
+    iterate.bindings
+        .iter()
+        .map(|binding| {
+            let variable = Variable::new(scope, Product::new(Default::default(), feedback_summary::<u32>(level, 1)));
+            within.insert(Plan::Source(binding.name.clone()), (*variable).clone());
+            variable
+        })
+        .collect::<Vec<_>>();
+
+    let mut local = Local::new(level);
+    let results =
+    iterate.bindings
+        .iter()
+        .map(|binding| binding.plan.render(scope, &mut within, &mut local))
+        .collect::<Vec<_>>();
+
+    let mut output = None;
+    for ((binding, variable), result) in iterate.bindings.iter().zip(variables).zip(results) {
+        if binding.name == iterate.result {
+            output = Some(result.leave_dynamic(level));
+        }
+        variable.set(&result);
+    }
+    output.unwrap_or_else(|| panic!("Iterate result is not a binding: {:?}", iterate.result))
+}
+
+/// Collects the largest subplans of `plan` that do not refer to any of `names`.
+///
+/// Subplans of a nested `Iterate` that refer to its own bindings are not collected.
+fn collect_invariants<V: ExchangeData+Hash+Datum>(plan: &Plan<V>, names: &[String], invariants: &mut Vec<Plan<V>>) {
+    if !plan.refers_to(names) {
+        if !invariants.contains(plan) {
+            invariants.push(plan.clone());
+        }
+    }
+    else if let Plan::Iterate(iterate) = plan {
+        let mut names = names.to_vec();
+        names.extend(iterate.bindings.iter().map(|binding| binding.name.clone()));
+        for binding in iterate.bindings.iter() {
+            collect_invariants(&binding.plan, &names, invariants);
+        }
+    }
+    else {
+        for child in plan.children() {
+            collect_invariants(child, names, invariants);
+        }
+    }
+}
+
+/// Arrangements made within an iteration, shared by the plans rendered in the iteration.
+struct Local<S: Scope, V: ExchangeData+Datum> where S::Timestamp: Stamp {
+    /// The depth of the iteration, which nested iterations extend.
+    level: usize,
+    unkeyed: HashMap<Plan<V>, ArrangedKeysOnly<S, V>>,
+    keyed: HashMap<(Plan<V>, Vec<usize>), ArrangedKeysVals<S, V>>,
+}
+
+impl<S: Scope, V: ExchangeData+Datum> Local<S, V> where S::Timestamp: Stamp {
+    fn new(level: usize) -> Self {
+        Local { level, unkeyed: HashMap::new(), keyed: HashMap::new() }
+    }
+}
+
+impl<S, T, V> Arrangements<S> for Local<S, V>
+where
+    S: Scope<Timestamp = Product<T, PointStamp<u32>>>,
+    T: Stamp,
+    V: ExchangeData+Hash+Datum,
+{
+    type Value = V;
+
+    fn arranged_unkeyed(&mut self, plan: &Plan<V>, _scope: &mut S) -> Option<ArrangedKeysOnly<S, V>> {
+        self.unkeyed.get(plan).cloned()
+    }
+    fn install_unkeyed(&mut self, plan: &Plan<V>, arrangement: &ArrangedKeysOnly<S, V>) {
+        self.unkeyed.insert(plan.clone(), arrangement.clone());
+    }
+    fn arranged_keyed(&mut self, plan: &Plan<V>, keys: &[usize], _scope: &mut S) -> Option<ArrangedKeysVals<S, V>> {
+        self.keyed.get(&(plan.clone(), keys.to_vec())).cloned()
+    }
+    fn install_keyed(&mut self, plan: &Plan<V>, keys: &[usize], arrangement: &ArrangedKeysVals<S, V>) {
+        self.keyed.insert((plan.clone(), keys.to_vec()), arrangement.clone());
+    }
+    fn iterate(
+        &mut self,
+        iterate: &Iterate<V>,
+        invariants: Rendered<S, V>,
+        scope: &mut S,
+    ) -> Collection<S, Vec<V>, Diff>
+    {
+        fixed_point(iterate, invariants, scope, self.level + 1)
+    }
+}
@@ -6,8 +6,8 @@ use serde::{Deserialize, Serialize};
 use timely::dataflow::Scope;
 
 use differential_dataflow::{Collection, ExchangeData};
-use crate::plan::{Plan, Render};
-use crate::{TraceManager, Time, Diff, Datum};
+use crate::plan::{Arrangements, Plan, Render, Stamp};
+use crate::{Diff, Datum};
 
 /// A plan stage joining two source relations on the specified
 /// symbols. Throws if any of the join symbols isn't bound by both
@@ -26,19 +26,23 @@ impl<V: ExchangeData+Hash+Datum> Render for Join<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S, A>(
         &self,
         scope: &mut S,
         collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
-        arrangements: &mut TraceManager<Self::Value>,
+        arrangements: &mut A,
     ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>,
     {
         use differential_dataflow::operators::arrange::ArrangeByKey;
 
         // acquire arrangements for each input.
         let keys1 = self.keys.iter().map(|key| key.0).collect::<Vec<_>>();
-        let mut trace1 =
-        if let Some(arrangement) = arrangements.get_keyed(&self.plan1, &keys1[..]) {
+        let arrange1 =
+        if let Some(arrangement) = arrangements.arranged_keyed(&self.plan1, &keys1[..], scope) {
             arrangement
         }
         else {
@@ -60,14 +64,14 @@ impl<V: ExchangeData+Hash+Datum> Render for Join<V> {
                 )
                 .arrange_by_key();
 
-            arrangements.set_keyed(&self.plan1, &keys1[..], &arrangement.trace);
-            arrangement.trace
+            arrangements.install_keyed(&self.plan1, &keys1[..], &arrangement);
+            arrangement
         };
 
         // extract relevant fields for each index.
         let keys2 = self.keys.iter().map(|key| key.1).collect::<Vec<_>>();
-        let mut trace2 =
-        if let Some(arrangement) = arrangements.get_keyed(&self.plan2, &keys2[..]) {
+        let arrange2 =
+        if let Some(arrangement) = arrangements.arranged_keyed(&self.plan2, &keys2[..], scope) {
             arrangement
         }
         else {
@@ -89,13 +93,10 @@ impl<V: ExchangeData+Hash+Datum> Render for Join<V> {
                 )
                 .arrange_by_key();
 
-            arrangements.set_keyed(&self.plan2, &keys2[..], &arrangement.trace);
-            arrangement.trace
+            arrangements.install_keyed(&self.plan2, &keys2[..], &arrangement);
+            arrangement
         };
 
-        let arrange1 = trace1.import(scope);
-        let arrange2 = trace2.import(scope);
-
         arrange1
             .join_core(&arrange2, |keys, vals1, vals2| {
                 Some(
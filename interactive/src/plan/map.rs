@@ -6,14 +6,14 @@ use serde::{Deserialize, Serialize};
 use timely::dataflow::Scope;
 
 use differential_dataflow::{Collection, ExchangeData};
-use crate::plan::{Plan, Render};
-use crate::{TraceManager, Time, Diff, Datum};
+use crate::plan::{Arrangements, Plan, Render, Stamp};
+use crate::{Diff, Datum};
 
-/// A plan which retains values at specified locations.
+/// A plan which replaces each tuple with the results of expressions applied to it.
 ///
 /// The plan does not ascribe meaning to specific locations (e.g. bindings)
-/// to variable names, and simply selects out the indicated sequence of values,
-/// panicking if some input record is insufficiently long.
+/// to variable names, and simply evaluates the indicated sequence of expressions,
+/// each of which may panic if some input record is insufficiently long.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Map<V: Datum> {
     /// Sequence (and order) of indices to be retained.
@@ -25,12 +25,16 @@ pub struct Map<V: Datum> {
 impl<V: ExchangeData+Hash+Datum> Render for Map<V> {
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S, A>(
         &self,
         scope: &mut S,
         collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
-        arrangements: &mut TraceManager<Self::Value>,
+        arrangements: &mut A,
     ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>,
     {
         let expressions = self.expressions.clone();
 
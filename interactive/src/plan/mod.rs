@@ -4,23 +4,36 @@ use std::hash::Hash;
 use serde::{Deserialize, Serialize};
 
 use timely::dataflow::Scope;
+use timely::dataflow::scopes::ScopeParent;
+use timely::order::Product;
+use timely::progress::Timestamp;
 use differential_dataflow::{Collection, ExchangeData};
+use differential_dataflow::dynamic::pointstamp::PointStamp;
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::arrange::Arranged;
 
 use crate::{TraceManager, Time, Diff};
+use crate::manager::{TraceKeyHandle, TraceValHandle};
 
-// pub mod count;
+pub mod canonical;
 pub mod filter;
+pub mod iterate;
 pub mod join;
 pub mod map;
+pub mod optimize;
+pub mod reduce;
 pub mod sfw;
 
 use crate::Datum;
 
-// pub use self::count::Count;
+pub use self::canonical::canonicalize;
 pub use self::filter::{Filter, Predicate};
+pub use self::iterate::Iterate;
 pub use self::join::Join;
 pub use self::sfw::MultiwayJoin;
 pub use self::map::Map;
+pub use self::optimize::optimize;
+pub use self::reduce::{Reduce, Aggregate};
 
 /// A type that can be rendered as a collection.
 pub trait Render : Sized {
@@ -32,14 +45,103 @@ pub trait Render : Sized {
     ///
     /// This method has access to arranged data, and may rely on and update the set
     /// of arrangements based on the needs and offerings of the rendering process.
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S, A>(
         &self,
         scope: &mut S,
         collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
-        arrangements: &mut TraceManager<Self::Value>,
+        arrangements: &mut A,
+    ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>;
+}
+
+/// Timestamps of the scopes in which plans are rendered.
+///
+/// Arrangements read strictly before some times can be compacted only up to the `prior` of those times.
+pub trait Stamp : Lattice+Timestamp {
+    /// A time less than or equal to `self`, by which times strictly less than `self` may be advanced
+    /// and remain strictly less than `self`.
+    fn prior(&self) -> Self;
+}
+
+impl Stamp for Time {
+    fn prior(&self) -> Self { self.saturating_sub(Time::from_nanos(1)) }
+}
+
+impl<T: Stamp> Stamp for Product<T, PointStamp<u32>> {
+    fn prior(&self) -> Self { Product::new(self.outer.prior(), Timestamp::minimum()) }
+}
+
+/// An arrangement of records by themselves, in scope `S`.
+pub type ArrangedKeysOnly<S, V> = Arranged<S, TraceKeyHandle<Vec<V>, <S as ScopeParent>::Timestamp, Diff>>;
+/// An arrangement of records by the values at some indices, in scope `S`.
+pub type ArrangedKeysVals<S, V> = Arranged<S, TraceValHandle<Vec<V>, Vec<V>, <S as ScopeParent>::Timestamp, Diff>>;
+
+/// Plans and their collections, rendered in scope `S`.
+pub type Rendered<S, V> = Vec<(Plan<V>, Collection<S, Vec<V>, Diff>)>;
+
+/// Arrangements of the collections of plans, which rendering in a scope may use and contribute to.
+///
+/// The `TraceManager` provides the maintained arrangements to dataflows at the root of a worker,
+/// and iterative scopes keep the arrangements made for the plans rendered within them. As the
+/// two kinds of scope also nest iterations differently, each renders the fixed points of `Iterate`.
+pub trait Arrangements<S: Scope> where S::Timestamp: Stamp {
+
+    /// Value type of the arranged records.
+    type Value: ExchangeData+Datum;
+
+    /// An arrangement of the records of `plan` by themselves, if one is available in `scope`.
+    fn arranged_unkeyed(&mut self, plan: &Plan<Self::Value>, scope: &mut S) -> Option<ArrangedKeysOnly<S, Self::Value>>;
+    /// Offers an arrangement of the records of `plan` by themselves.
+    fn install_unkeyed(&mut self, plan: &Plan<Self::Value>, arrangement: &ArrangedKeysOnly<S, Self::Value>);
+    /// An arrangement of the records of `plan` by the values at `keys`, if one is available in `scope`.
+    fn arranged_keyed(&mut self, plan: &Plan<Self::Value>, keys: &[usize], scope: &mut S) -> Option<ArrangedKeysVals<S, Self::Value>>;
+    /// Offers an arrangement of the records of `plan` by the values at `keys`.
+    fn install_keyed(&mut self, plan: &Plan<Self::Value>, keys: &[usize], arrangement: &ArrangedKeysVals<S, Self::Value>);
+    /// Renders the fixed point of `iterate`, given the collections of the largest subplans that do not refer to its bindings.
+    fn iterate(
+        &mut self,
+        iterate: &Iterate<Self::Value>,
+        invariants: Rendered<S, Self::Value>,
+        scope: &mut S,
     ) -> Collection<S, Vec<Self::Value>, Diff>;
 }
 
+impl<S, V> Arrangements<S> for TraceManager<V>
+where
+    S: Scope<Timestamp = Time>,
+    V: ExchangeData+Hash+Datum,
+{
+    type Value = V;
+
+    fn arranged_unkeyed(&mut self, plan: &Plan<V>, scope: &mut S) -> Option<ArrangedKeysOnly<S, V>> {
+        self.get_unkeyed(plan).map(|mut trace| trace.import(scope))
+    }
+    fn install_unkeyed(&mut self, plan: &Plan<V>, arrangement: &ArrangedKeysOnly<S, V>) {
+        self.set_unkeyed(plan, &arrangement.trace);
+    }
+    fn arranged_keyed(&mut self, plan: &Plan<V>, keys: &[usize], scope: &mut S) -> Option<ArrangedKeysVals<S, V>> {
+        self.get_keyed(plan, keys).map(|mut trace| trace.import(scope))
+    }
+    fn install_keyed(&mut self, plan: &Plan<V>, keys: &[usize], arrangement: &ArrangedKeysVals<S, V>) {
+        self.set_keyed(plan, keys, &arrangement.trace);
+    }
+    fn iterate(
+        &mut self,
+        iterate: &Iterate<V>,
+        invariants: Rendered<S, V>,
+        scope: &mut S,
+    ) -> Collection<S, Vec<V>, Diff>
+    {
+        scope.iterative::<PointStamp<u32>, _, _>(|inner| {
+            let invariants = invariants.into_iter().map(|(plan, collection)| (plan, collection.enter(inner))).collect();
+            iterate::fixed_point(iterate, invariants, inner, 1).leave()
+        })
+    }
+}
+
 /// Possible query plan types.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Plan<V: Datum> {
@@ -59,6 +161,10 @@ pub enum Plan<V: Datum> {
     Negate(Box<Plan<V>>),
     /// Filters bindings by one of the built-in predicates
     Filter(Filter<V>),
+    /// Groups and aggregates
+    Reduce(Reduce<V>),
+    /// Fixed point of recursive bindings
+    Iterate(Iterate<V>),
     /// Sources data from another relation.
     Source(String),
     /// Prints resulting updates.
@@ -73,6 +179,13 @@ impl<V: ExchangeData+Hash+Datum> Plan<V> {
             plan: Box::new(self),
         })
     }
+    /// Replaces each tuple with the results of `expressions` applied to it.
+    pub fn map(self, expressions: Vec<V::Expression>) -> Self {
+        Plan::Map(Map {
+            expressions,
+            plan: Box::new(self),
+        })
+    }
     /// Reduces a collection to distinct tuples.
     pub fn distinct(self) -> Self {
         Plan::Distinct(Box::new(self))
@@ -121,6 +234,14 @@ impl<V: ExchangeData+Hash+Datum> Plan<V> {
     pub fn filter(self, predicate: Predicate<V>) -> Self {
         Plan::Filter(Filter { predicate, plan: Box::new(self) } )
     }
+    /// Groups tuples by the values at `keys`, and produces those values followed by `aggregates` of each group.
+    pub fn reduce(self, keys: Vec<usize>, aggregates: Vec<Aggregate>) -> Self {
+        Plan::Reduce(Reduce { keys, aggregates, plan: Box::new(self) })
+    }
+    /// Binds names to recursively defined plans, and produces the fixed point of the binding named `result`.
+    pub fn iterate(bindings: Vec<crate::Rule<V>>, result: &str) -> Self {
+        Plan::Iterate(Iterate { bindings, result: result.to_string() })
+    }
     /// Loads a source of data by name.
     pub fn source(name: &str) -> Self {
         Plan::Source(name.to_string())
@@ -129,6 +250,78 @@ impl<V: ExchangeData+Hash+Datum> Plan<V> {
     pub fn inspect(self, text: &str) -> Self {
         Plan::Inspect(text.to_string(), Box::new(self))
     }
+    /// The plans whose collections this plan's collection is computed from.
+    pub fn children(&self) -> Vec<&Plan<V>> {
+        match self {
+            Plan::Map(map) => vec![&*map.plan],
+            Plan::Distinct(plan) => vec![&**plan],
+            Plan::Concat(plans) => plans.iter().collect(),
+            Plan::Consolidate(plan) => vec![&**plan],
+            Plan::Join(join) => vec![&*join.plan1, &*join.plan2],
+            Plan::MultiwayJoin(join) => join.sources.iter().collect(),
+            Plan::Negate(plan) => vec![&**plan],
+            Plan::Filter(filter) => vec![&*filter.plan],
+            Plan::Reduce(reduce) => vec![&*reduce.plan],
+            Plan::Iterate(iterate) => iterate.bindings.iter().map(|binding| &binding.plan).collect(),
+            Plan::Source(_) => Vec::new(),
+            Plan::Inspect(_, plan) => vec![&**plan],
+        }
+    }
+    /// Indicates if the plan loads any of `names`, other than as bindings of its own.
+    pub fn refers_to(&self, names: &[String]) -> bool {
+        match self {
+            Plan::Source(name) => names.contains(name),
+            Plan::Iterate(iterate) => {
+                let names = names.iter().filter(|name| iterate.bindings.iter().all(|binding| &binding.name != *name)).cloned().collect::<Vec<_>>();
+                self.children().iter().any(|plan| plan.refers_to(&names[..]))
+            },
+            _ => self.children().iter().any(|plan| plan.refers_to(names)),
+        }
+    }
+    /// Checks that the plan can be rendered, reporting the first problem found.
+    ///
+    /// Each source must be bound by an enclosing `Iterate` or be reported as available by `bound`,
+    /// and each `Iterate` must produce one of its bindings.
+    pub fn validate(&self, bound: &dyn Fn(&str) -> bool) -> Result<(), String> {
+        match self {
+            Plan::Source(name) => {
+                if bound(name) { Ok(()) } else { Err(format!("Failed to find source collection: {:?}", name)) }
+            },
+            Plan::Iterate(iterate) => {
+                if iterate.bindings.iter().all(|binding| binding.name != iterate.result) {
+                    return Err(format!("Iterate result is not a binding: {:?}", iterate.result));
+                }
+                let bound = |name: &str| iterate.bindings.iter().any(|binding| binding.name == name) || bound(name);
+                iterate.bindings.iter().try_for_each(|binding| binding.plan.validate(&bound))
+            },
+            _ => self.children().into_iter().try_for_each(|plan| plan.validate(bound)),
+        }
+    }
+    /// The number of values in each record, if it can be determined.
+    ///
+    /// The number of values of a named source is reported by `sources`, if known.
+    pub fn arity(&self, sources: &impl Fn(&str) -> Option<usize>) -> Option<usize> {
+        match self {
+            Plan::Map(map) => Some(map.expressions.len()),
+            Plan::Distinct(plan) => plan.arity(sources),
+            Plan::Concat(plans) => plans.iter().find_map(|plan| plan.arity(sources)),
+            Plan::Consolidate(plan) => plan.arity(sources),
+            Plan::Join(join) => {
+                let keys1 = join.keys.iter().map(|key| key.0).collect::<std::collections::BTreeSet<_>>();
+                let keys2 = join.keys.iter().map(|key| key.1).collect::<std::collections::BTreeSet<_>>();
+                let values1 = join.plan1.arity(sources)? - keys1.len();
+                let values2 = join.plan2.arity(sources)? - keys2.len();
+                Some(join.keys.len() + values1 + values2)
+            },
+            Plan::MultiwayJoin(join) => Some(join.results.len()),
+            Plan::Negate(plan) => plan.arity(sources),
+            Plan::Filter(filter) => filter.plan.arity(sources),
+            Plan::Reduce(reduce) => Some(reduce.keys.len() + reduce.aggregates.len()),
+            Plan::Iterate(_) => None,
+            Plan::Source(name) => sources(name),
+            Plan::Inspect(_, plan) => plan.arity(sources),
+        }
+    }
     /// Convert the plan into a named rule.
     pub fn into_rule(self, name: &str) -> crate::Rule<V> {
         crate::Rule {
@@ -142,12 +335,16 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S, A>(
         &self,
         scope: &mut S,
         collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
-        arrangements: &mut TraceManager<Self::Value>,
+        arrangements: &mut A,
     ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>,
     {
         if collections.get(self).is_none() {
 
@@ -161,18 +358,18 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
                     use differential_dataflow::trace::implementations::{KeyBuilder, KeySpine};
 
                     let input =
-                    if let Some(mut trace) = arrangements.get_unkeyed(&self) {
-                        trace.import(scope)
+                    if let Some(arrangement) = arrangements.arranged_unkeyed(self, scope) {
+                        arrangement
                     }
                     else {
                         let input_arrangement = distinct.render(scope, collections, arrangements).arrange_by_self();
-                        arrangements.set_unkeyed(&distinct, &input_arrangement.trace);
+                        arrangements.install_unkeyed(distinct, &input_arrangement);
                         input_arrangement
                     };
 
                     let output = input.reduce_abelian::<_,_,_,KeyBuilder<_,_,_>,KeySpine<_,_,_>>("Distinct", move |_,_,t| t.push(((), 1)));
 
-                    arrangements.set_unkeyed(&self, &output.trace);
+                    arrangements.install_unkeyed(self, &output);
                     output.as_collection(|k,&()| k.clone())
 
                 },
@@ -192,8 +389,8 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
                         .as_collection()
                 }
                 Plan::Consolidate(consolidate) => {
-                    if let Some(mut trace) = arrangements.get_unkeyed(&self) {
-                        trace.import(scope).as_collection(|k,&()| k.clone())
+                    if let Some(arrangement) = arrangements.arranged_unkeyed(self, scope) {
+                        arrangement.as_collection(|k,&()| k.clone())
                     }
                     else {
                         consolidate.render(scope, collections, arrangements).consolidate()
@@ -205,11 +402,12 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
                     negate.render(scope, collections, arrangements).negate()
                 },
                 Plan::Filter(filter) => filter.render(scope, collections, arrangements),
+                Plan::Reduce(reduce) => reduce.render(scope, collections, arrangements),
+                Plan::Iterate(iterate) => iterate.render(scope, collections, arrangements),
                 Plan::Source(source) => {
                     arrangements
-                        .get_unkeyed(self)
-                        .expect(&format!("Failed to find source collection: {:?}", source))
-                        .import(scope)
+                        .arranged_unkeyed(self, scope)
+                        .unwrap_or_else(|| panic!("Failed to find source collection: {:?}", source))
                         .as_collection(|k,()| k.to_vec())
                 },
                 Plan::Inspect(text, plan) => {
@@ -0,0 +1,382 @@
+//! Rewriting of plans into equivalent plans that are cheaper to render.
+//!
+//! Some rewrites consult the arrangements of a `TraceManager`, which hold only the
+//! records of one worker. Plans rewritten this way must be shared with the other
+//! workers, rather than rewritten by each worker, so that all workers render the
+//! same dataflow. Rewriting against an empty `TraceManager` is the same for all workers.
+
+use std::collections::BTreeSet;
+use std::hash::Hash;
+
+use differential_dataflow::ExchangeData;
+use crate::plan::{Plan, Map, Filter, Join, MultiwayJoin, Reduce, Iterate, Predicate};
+use crate::{TraceManager, Datum, Rule};
+
+/// Rewrites `plan` into an equivalent plan that should be cheaper to render.
+///
+/// Filters and projections are pushed towards sources, and redundant `Consolidate` and
+/// `Distinct` stages are removed. The sources of multiway joins are ordered by the sizes
+/// of their maintained arrangements, and the keys of joins and reductions are ordered
+/// to match maintained keyed arrangements, so that they are re-used rather than rebuilt.
+pub fn optimize<V: ExchangeData+Hash+Datum>(plan: Plan<V>, traces: &TraceManager<V>) -> Plan<V> {
+
+    // Optimize inputs first, so that rewrites can inspect optimized inputs.
+    let plan =
+    match plan {
+        Plan::Map(map) => Plan::Map(Map { expressions: map.expressions, plan: Box::new(optimize(*map.plan, traces)) }),
+        Plan::Distinct(plan) => Plan::Distinct(Box::new(optimize(*plan, traces))),
+        Plan::Concat(plans) => Plan::Concat(plans.into_iter().map(|plan| optimize(plan, traces)).collect()),
+        Plan::Consolidate(plan) => Plan::Consolidate(Box::new(optimize(*plan, traces))),
+        Plan::Join(join) => Plan::Join(Join {
+            keys: join.keys,
+            plan1: Box::new(optimize(*join.plan1, traces)),
+            plan2: Box::new(optimize(*join.plan2, traces)),
+        }),
+        Plan::MultiwayJoin(join) => Plan::MultiwayJoin(MultiwayJoin {
+            results: join.results,
+            sources: join.sources.into_iter().map(|plan| optimize(plan, traces)).collect(),
+            equalities: join.equalities,
+        }),
+        Plan::Negate(plan) => Plan::Negate(Box::new(optimize(*plan, traces))),
+        Plan::Filter(filter) => Plan::Filter(Filter { predicate: filter.predicate, plan: Box::new(optimize(*filter.plan, traces)) }),
+        Plan::Reduce(reduce) => Plan::Reduce(Reduce {
+            keys: reduce.keys,
+            aggregates: reduce.aggregates,
+            plan: Box::new(optimize(*reduce.plan, traces)),
+        }),
+        Plan::Iterate(iterate) => {
+            // Bindings may shadow the names of maintained traces, whose statistics would mislead.
+            let empty = TraceManager::new();
+            Plan::Iterate(Iterate {
+                bindings: iterate.bindings.into_iter().map(|Rule { name, plan }| Rule { name, plan: optimize(plan, &empty) }).collect(),
+                result: iterate.result,
+            })
+        },
+        Plan::Source(name) => Plan::Source(name),
+        Plan::Inspect(text, plan) => Plan::Inspect(text, Box::new(optimize(*plan, traces))),
+    };
+
+    rewrite(plan, traces)
+}
+
+/// Rewrites the root of `plan`, whose inputs are already optimized.
+fn rewrite<V: ExchangeData+Hash+Datum>(plan: Plan<V>, traces: &TraceManager<V>) -> Plan<V> {
+    match plan {
+        // Distinct, consolidated, and reduced collections are already consolidated,
+        // and distinct and reduced collections are already distinct.
+        Plan::Distinct(plan) => match *plan {
+            Plan::Distinct(plan) => Plan::Distinct(plan),
+            Plan::Consolidate(plan) => rewrite(Plan::Distinct(plan), traces),
+            Plan::Reduce(reduce) => Plan::Reduce(reduce),
+            plan => Plan::Distinct(Box::new(plan)),
+        },
+        Plan::Consolidate(plan) => match *plan {
+            Plan::Distinct(plan) => Plan::Distinct(plan),
+            Plan::Consolidate(plan) => Plan::Consolidate(plan),
+            Plan::Reduce(reduce) => Plan::Reduce(reduce),
+            plan => Plan::Consolidate(Box::new(plan)),
+        },
+        Plan::Filter(filter) => push_filter(filter.predicate, *filter.plan, traces),
+        Plan::Map(map) => push_map(map.expressions, *map.plan, traces),
+        Plan::Join(join) => order_join_keys(join, traces),
+        Plan::Reduce(reduce) => order_reduce_keys(reduce, traces),
+        Plan::MultiwayJoin(join) => order_sources(join, traces),
+        plan => plan,
+    }
+}
+
+/// Filters `plan` by `predicate`, applying each conjunct as close to the sources as possible.
+fn push_filter<V: ExchangeData+Hash+Datum>(predicate: Predicate<V>, plan: Plan<V>, traces: &TraceManager<V>) -> Plan<V> {
+    match plan {
+        Plan::Filter(filter) => {
+            let mut predicates = filter.predicate.conjuncts();
+            predicates.extend(predicate.conjuncts());
+            push_filter(Predicate::All(predicates), *filter.plan, traces)
+        },
+        Plan::Concat(plans) => {
+            Plan::Concat(plans.into_iter().map(|plan| push_filter(predicate.clone(), plan, traces)).collect())
+        },
+        Plan::Negate(plan) => Plan::Negate(Box::new(push_filter(predicate, *plan, traces))),
+        Plan::Distinct(plan) => Plan::Distinct(Box::new(push_filter(predicate, *plan, traces))),
+        Plan::Consolidate(plan) => Plan::Consolidate(Box::new(push_filter(predicate, *plan, traces))),
+        Plan::Map(map) => {
+            // Conjuncts examining only projected values apply before the projection.
+            let mut below = Vec::new();
+            let mut above = Vec::new();
+            for conjunct in predicate.conjuncts() {
+                match remap(&conjunct, |position| map.expressions.get(position).and_then(V::projected)) {
+                    Some(conjunct) => below.push(conjunct),
+                    None => above.push(conjunct),
+                }
+            }
+            let plan = Plan::Map(Map { expressions: map.expressions, plan: Box::new(filter_below(below, *map.plan, traces)) });
+            filter_above(above, plan)
+        },
+        Plan::Join(join) => {
+            // Conjuncts examining only the values of one input apply to that input,
+            // and conjuncts examining only keys apply to both inputs.
+            let keys1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+            let keys2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+            let values1 = arity(&join.plan1, traces).map(|arity| arity - distinct(&keys1));
+            let input1 = |position: usize| {
+                if position < join.keys.len() { Some(keys1[position]) }
+                else if position - join.keys.len() < values1? { Some(non_key(&keys1, position - join.keys.len())) }
+                else { None }
+            };
+            let input2 = |position: usize| {
+                if position < join.keys.len() { Some(keys2[position]) }
+                else if position - join.keys.len() >= values1? { Some(non_key(&keys2, position - join.keys.len() - values1?)) }
+                else { None }
+            };
+            let mut below1 = Vec::new();
+            let mut below2 = Vec::new();
+            let mut above = Vec::new();
+            for conjunct in predicate.conjuncts() {
+                let conjunct1 = remap(&conjunct, input1);
+                let conjunct2 = remap(&conjunct, input2);
+                if conjunct1.is_none() && conjunct2.is_none() {
+                    above.push(conjunct);
+                }
+                below1.extend(conjunct1);
+                below2.extend(conjunct2);
+            }
+            let plan = Plan::Join(Join {
+                keys: join.keys,
+                plan1: Box::new(filter_below(below1, *join.plan1, traces)),
+                plan2: Box::new(filter_below(below2, *join.plan2, traces)),
+            });
+            filter_above(above, plan)
+        },
+        Plan::MultiwayJoin(join) => {
+            // Conjuncts examining only the attributes of one source apply to that source.
+            let mut below = vec![Vec::new(); join.sources.len()];
+            let mut above = Vec::new();
+            for conjunct in predicate.conjuncts() {
+                let inputs = conjunct.positions().iter().map(|position| join.results[*position].1).collect::<BTreeSet<_>>();
+                if inputs.len() == 1 {
+                    let input = *inputs.iter().next().unwrap();
+                    below[input].push(conjunct.remap(&mut |position| join.results[position].0));
+                }
+                else {
+                    above.push(conjunct);
+                }
+            }
+            let plan = Plan::MultiwayJoin(MultiwayJoin {
+                results: join.results,
+                sources: join.sources.into_iter().zip(below).map(|(plan, below)| filter_below(below, plan, traces)).collect(),
+                equalities: join.equalities,
+            });
+            filter_above(above, plan)
+        },
+        Plan::Reduce(reduce) => {
+            // Conjuncts examining only group values apply to the records of the group.
+            let mut below = Vec::new();
+            let mut above = Vec::new();
+            for conjunct in predicate.conjuncts() {
+                match remap(&conjunct, |position| reduce.keys.get(position).copied()) {
+                    Some(conjunct) => below.push(conjunct),
+                    None => above.push(conjunct),
+                }
+            }
+            let plan = Plan::Reduce(Reduce {
+                keys: reduce.keys,
+                aggregates: reduce.aggregates,
+                plan: Box::new(filter_below(below, *reduce.plan, traces)),
+            });
+            filter_above(above, plan)
+        },
+        plan => filter_above(predicate.conjuncts(), plan),
+    }
+}
+
+/// Filters `plan` by all of `predicates`, pushed towards the sources, if there are any.
+fn filter_below<V: ExchangeData+Hash+Datum>(predicates: Vec<Predicate<V>>, plan: Plan<V>, traces: &TraceManager<V>) -> Plan<V> {
+    match Predicate::conjunction(predicates) {
+        Some(predicate) => push_filter(predicate, plan, traces),
+        None => plan,
+    }
+}
+
+/// Filters `plan` by all of `predicates`, if there are any.
+fn filter_above<V: ExchangeData+Hash+Datum>(predicates: Vec<Predicate<V>>, plan: Plan<V>) -> Plan<V> {
+    match Predicate::conjunction(predicates) {
+        Some(predicate) => plan.filter(predicate),
+        None => plan,
+    }
+}
+
+/// Replaces each position of `predicate` by `logic(position)`, if `logic` maps every position.
+fn remap<V: Clone>(predicate: &Predicate<V>, logic: impl Fn(usize) -> Option<usize>) -> Option<Predicate<V>> {
+    if predicate.positions().into_iter().all(|position| logic(position).is_some()) {
+        Some(predicate.remap(&mut |position| logic(position).unwrap()))
+    }
+    else {
+        None
+    }
+}
+
+/// Applies `expressions` to `plan`, moving projections towards the sources.
+fn push_map<V: ExchangeData+Hash+Datum>(expressions: Vec<V::Expression>, plan: Plan<V>, traces: &TraceManager<V>) -> Plan<V> {
+    let projection = expressions.iter().map(V::projected).collect::<Option<Vec<_>>>();
+    match (projection, plan) {
+        // Projections that retain all values in order are not needed.
+        (Some(indices), plan) if indices.iter().enumerate().all(|(i, j)| i == *j) && arity(&plan, traces) == Some(indices.len()) => {
+            plan
+        },
+        // Projections select from the expressions of a map, or the results of a multiway join.
+        (Some(indices), Plan::Map(map)) => {
+            let expressions = indices.iter().map(|index| map.expressions[*index].clone()).collect();
+            push_map(expressions, *map.plan, traces)
+        },
+        (Some(indices), Plan::MultiwayJoin(join)) => {
+            Plan::MultiwayJoin(MultiwayJoin {
+                results: indices.iter().map(|index| join.results[*index]).collect(),
+                sources: join.sources,
+                equalities: join.equalities,
+            })
+        },
+        // Projections retaining the values a filter examines apply before the filter.
+        (Some(indices), Plan::Filter(filter)) if filter.predicate.positions().iter().all(|position| indices.contains(position)) => {
+            let predicate = filter.predicate.remap(&mut |position| indices.iter().position(|index| *index == position).unwrap());
+            let expressions = indices.into_iter().map(V::projection).collect();
+            push_map(expressions, *filter.plan, traces).filter(predicate)
+        },
+        (_, Plan::Concat(plans)) => {
+            Plan::Concat(plans.into_iter().map(|plan| push_map(expressions.clone(), plan, traces)).collect())
+        },
+        (_, Plan::Negate(plan)) => Plan::Negate(Box::new(push_map(expressions, *plan, traces))),
+        (_, plan) => plan.map(expressions),
+    }
+}
+
+/// Orders the keys of a join to match maintained keyed arrangements of its inputs.
+///
+/// The join then produces its keys in a different order, which a projection restores.
+fn order_join_keys<V: ExchangeData+Hash+Datum>(join: Join<V>, traces: &TraceManager<V>) -> Plan<V> {
+
+    let maintained1 = traces.keys(&join.plan1);
+    let maintained2 = traces.keys(&join.plan2);
+    let reused = |keys: &[(usize, usize)]| {
+        let keys1 = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        let keys2 = keys.iter().map(|key| key.1).collect::<Vec<_>>();
+        maintained1.contains(&keys1) as usize + maintained2.contains(&keys2) as usize
+    };
+
+    let mut best = join.keys.clone();
+    let candidates =
+    maintained1.iter().filter_map(|keys| permute(&join.keys, keys, |key| key.0))
+        .chain(maintained2.iter().filter_map(|keys| permute(&join.keys, keys, |key| key.1)));
+    for candidate in candidates {
+        if reused(&candidate) > reused(&best) {
+            best = candidate;
+        }
+    }
+
+    let plan = Plan::Join(join);
+    let arity = arity(&plan, traces);
+    match (plan, arity) {
+        (Plan::Join(join), Some(arity)) if best != join.keys => {
+            let projection =
+            join.keys
+                .iter()
+                .map(|key| best.iter().position(|other| other == key).unwrap())
+                .chain(join.keys.len() .. arity)
+                .collect();
+            Plan::Join(Join { keys: best, plan1: join.plan1, plan2: join.plan2 }).project(projection)
+        },
+        (plan, _) => plan,
+    }
+}
+
+/// Orders the group keys of a reduction to match a maintained keyed arrangement of its input.
+///
+/// The reduction then produces its keys in a different order, which a projection restores.
+fn order_reduce_keys<V: ExchangeData+Hash+Datum>(reduce: Reduce<V>, traces: &TraceManager<V>) -> Plan<V> {
+    let maintained = traces.keys(&reduce.plan);
+    let plan = Plan::Reduce(reduce);
+    if !traces.keys(&plan).is_empty() {
+        return plan;
+    }
+    match plan {
+        Plan::Reduce(reduce) if !maintained.contains(&reduce.keys) => {
+            let candidate =
+            maintained
+                .iter()
+                .find(|keys| keys.len() == reduce.keys.len() && keys.iter().all(|key| reduce.keys.contains(key)));
+            match candidate {
+                Some(keys) => {
+                    let projection =
+                    reduce.keys
+                        .iter()
+                        .map(|key| keys.iter().position(|other| other == key).unwrap())
+                        .chain(keys.len() .. keys.len() + reduce.aggregates.len())
+                        .collect();
+                    Plan::Reduce(Reduce { keys: keys.clone(), aggregates: reduce.aggregates, plan: reduce.plan }).project(projection)
+                },
+                None => Plan::Reduce(reduce),
+            }
+        },
+        plan => plan,
+    }
+}
+
+/// Orders the sources of a multiway join by the sizes of their maintained arrangements, smallest first.
+///
+/// Equality constraints are ordered to match, so that each delta query joins with smaller sources
+/// sooner. Sources without maintained arrangements are ordered last, in their existing order.
+fn order_sources<V: ExchangeData+Hash+Datum>(join: MultiwayJoin<V>, traces: &TraceManager<V>) -> Plan<V> {
+
+    let mut order = (0 .. join.sources.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| traces.statistics(&join.sources[*index]).unwrap_or(usize::MAX));
+    let mut rank = vec![0; order.len()];
+    for (new, old) in order.iter().enumerate() {
+        rank[*old] = new;
+    }
+
+    let mut equalities =
+    join.equalities
+        .iter()
+        .map(|class| {
+            let mut class = class.iter().map(|(attr, input)| (*attr, rank[*input])).collect::<Vec<_>>();
+            class.sort_by_key(|(_attr, input)| *input);
+            class
+        })
+        .collect::<Vec<_>>();
+    equalities.sort_by_key(|class| class.iter().map(|(_attr, input)| *input).min());
+
+    Plan::MultiwayJoin(MultiwayJoin {
+        results: join.results.iter().map(|(attr, input)| (*attr, rank[*input])).collect(),
+        sources: order.iter().map(|index| join.sources[*index].clone()).collect(),
+        equalities,
+    })
+}
+
+/// Reorders `keys` so that the keys `side` selects are `target`, if they are a permutation of them.
+fn permute(keys: &[(usize, usize)], target: &[usize], side: impl Fn(&(usize, usize)) -> usize) -> Option<Vec<(usize, usize)>> {
+    if target.len() != keys.len() {
+        return None;
+    }
+    let mut remaining = keys.to_vec();
+    target
+        .iter()
+        .map(|column| {
+            let position = remaining.iter().position(|key| side(key) == *column)?;
+            Some(remaining.remove(position))
+        })
+        .collect()
+}
+
+/// The number of values in each record of `plan`, if it can be determined.
+fn arity<V: ExchangeData+Hash+Datum>(plan: &Plan<V>, traces: &TraceManager<V>) -> Option<usize> {
+    plan.arity(&|name| traces.arity(&Plan::Source(name.to_string())))
+}
+
+/// The number of distinct indices in `keys`.
+fn distinct(keys: &[usize]) -> usize {
+    keys.iter().collect::<BTreeSet<_>>().len()
+}
+
+/// The index of the `position`-th value not among `keys`.
+fn non_key(keys: &[usize], position: usize) -> usize {
+    (0 ..).filter(|index| !keys.contains(index)).nth(position).unwrap()
+}
@@ -0,0 +1,133 @@
+//! Grouping and aggregation expression plan.
+
+use std::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+use timely::dataflow::Scope;
+
+use differential_dataflow::{Collection, ExchangeData};
+use crate::plan::{Arrangements, Plan, Render, Stamp};
+use crate::{Diff, Datum};
+
+/// An aggregate of the records in a group.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Aggregate {
+    /// The number of records.
+    Count,
+    /// The sum of the values at an index.
+    Sum(usize),
+    /// The least value at an index.
+    Min(usize),
+    /// The greatest value at an index.
+    Max(usize),
+    /// The average of the values at an index.
+    Avg(usize),
+}
+
+/// The remaining values of the records of a group, and their multiplicities.
+type GroupInput<'a, V> = [(&'a Vec<V>, Diff)];
+/// The aggregated records of a group, and their multiplicities.
+type GroupOutput<V> = Vec<(Vec<V>, Diff)>;
+
+/// A plan stage grouping records by the values at some indices,
+/// and producing for each group those values followed by aggregates
+/// of the records in the group.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Reduce<V: Datum> {
+    /// Indices of the values that identify a group.
+    pub keys: Vec<usize>,
+    /// Aggregates to produce for each group.
+    pub aggregates: Vec<Aggregate>,
+    /// Plan for the input.
+    pub plan: Box<Plan<V>>,
+}
+
+impl<V: ExchangeData+Hash+Datum> Reduce<V> {
+    /// Aggregates the records of a group, presented as the values at `keys` and the remaining values of each record.
+    pub(crate) fn logic(&self) -> impl FnMut(&Vec<V>, &GroupInput<V>, &mut GroupOutput<V>)+'static {
+        let keys = self.keys.clone();
+        let aggregates = self.aggregates.clone();
+        let mut records = Vec::new();
+        move |group, input, output| {
+            // Reassemble input records from the group and the remaining values.
+            records.clear();
+            for (values, diff) in input.iter() {
+                let mut values = values.iter();
+                let mut record = Vec::with_capacity(group.len() + values.len());
+                while let Some(value) =
+                    match keys.iter().position(|key| *key == record.len()) {
+                        Some(position) => Some(&group[position]),
+                        None => values.next(),
+                    }
+                {
+                    record.push(value.clone());
+                }
+                records.push((record, *diff));
+            }
+            let aggregates = aggregates.iter().map(|aggregate| V::aggregate(&records[..], aggregate)).collect();
+            output.push((aggregates, 1));
+        }
+    }
+}
+
+impl<V: ExchangeData+Hash+Datum> Render for Reduce<V> {
+
+    type Value = V;
+
+    fn render<S, A>(
+        &self,
+        scope: &mut S,
+        collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
+        arrangements: &mut A,
+    ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>,
+    {
+        use differential_dataflow::operators::arrange::ArrangeByKey;
+        use differential_dataflow::trace::implementations::{ValBuilder, ValSpine};
+
+        // The output is arranged by its leading group values, as a join would arrange it.
+        let plan = Plan::Reduce(self.clone());
+        let output_keys = (0 .. self.keys.len()).collect::<Vec<_>>();
+        if let Some(arrangement) = arrangements.arranged_keyed(&plan, &output_keys[..], scope) {
+            return arrangement
+                .as_collection(|keys, aggregates| keys.iter().cloned().chain(aggregates.iter().cloned()).collect());
+        }
+
+        // acquire an arrangement of the input by group.
+        let input =
+        if let Some(arrangement) = arrangements.arranged_keyed(&self.plan, &self.keys[..], scope) {
+            arrangement
+        }
+        else {
+            let keys = self.keys.clone();
+            let arrangement =
+            self.plan
+                .render(scope, collections, arrangements)
+                .map(move |tuple|
+                    (
+                        keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
+                        tuple
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(index,_value)| !keys.contains(index))
+                            .map(|(_index,value)| value)
+                            .collect::<Vec<_>>(),
+                    )
+                )
+                .arrange_by_key();
+
+            arrangements.install_keyed(&self.plan, &self.keys[..], &arrangement);
+            arrangement
+        };
+
+        let output =
+        input
+            .reduce_abelian::<_,_,_,ValBuilder<_,_,_,_>,ValSpine<_,_,_,_>>("Reduce", self.logic());
+
+        arrangements.install_keyed(&plan, &output_keys[..], &output);
+        output.as_collection(|keys, aggregates| keys.iter().cloned().chain(aggregates.iter().cloned()).collect())
+    }
+}
@@ -32,8 +32,8 @@ use timely::dataflow::Scope;
 use differential_dataflow::operators::arrange::{ArrangeBySelf, ArrangeByKey};
 
 use differential_dataflow::{Collection, ExchangeData};
-use crate::plan::{Plan, Render};
-use crate::{TraceManager, Time, Diff, Datum};
+use crate::plan::{Arrangements, Plan, Render, Stamp};
+use crate::{Diff, Datum};
 
 /// A multiway join of multiple relations.
 ///
@@ -67,12 +67,16 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
 
     type Value = V;
 
-    fn render<S: Scope<Timestamp = Time>>(
+    fn render<S, A>(
         &self,
         scope: &mut S,
         collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
-        arrangements: &mut TraceManager<Self::Value>,
+        arrangements: &mut A,
     ) -> Collection<S, Vec<Self::Value>, Diff>
+    where
+        S: Scope,
+        S::Timestamp: Stamp,
+        A: Arrangements<S, Value = Self::Value>,
     {
         // The idea here is the following:
         //
@@ -130,19 +134,19 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
             // println!("\tinitial attributes: {:?}", attributes);
 
             // Ensure the plan is rendered and cached.
-            if arrangements.get_unkeyed(&plan).is_none() {
-                // println!("\tbuilding/caching source plan");
-                let collection = plan.render(scope, collections, arrangements);
-                arrangements.set_unkeyed(plan, &collection.arrange_by_self().trace);
-            }
-            else {
+            let arrangement =
+            if let Some(arrangement) = arrangements.arranged_unkeyed(plan, scope) {
                 // println!("\tsource plan found");
+                arrangement
             }
+            else {
+                // println!("\tbuilding/caching source plan");
+                let arrangement = plan.render(scope, collections, arrangements).arrange_by_self();
+                arrangements.install_unkeyed(plan, &arrangement);
+                arrangement
+            };
             let changes =
-            arrangements
-                .get_unkeyed(&plan)
-                .expect("Surely we just ensured this")
-                .import(scope)
+            arrangement
                 .as_collection(|val,&()| val.clone())
                 .map(move |tuple| attributes_init.iter().map(|&(attr,_)|
                     tuple[attr].clone()).collect::<Vec<_>>()
@@ -199,7 +203,12 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
                 // Get a plan for the projection on to these few attributes.
                 let plan = self.sources[join_idx].clone().project(projection);
 
-                if arrangements.get_keyed(&plan, &keys[..]).is_none() {
+                let arrangement =
+                if let Some(arrangement) = arrangements.arranged_keyed(&plan, &keys[..], scope) {
+                    // println!("\tplan found: {:?}, {:?}", keys, plan);
+                    arrangement
+                }
+                else {
                     // println!("\tbuilding key: {:?}, plan: {:?}", keys, plan);
                     let keys_clone = keys.clone();
                     let arrangement =
@@ -207,16 +216,9 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
                         .map(move |tuple| (keys_clone.iter().map(|&i| tuple[i].clone()).collect::<Vec<_>>(), tuple))
                         .arrange_by_key();
 
-                    arrangements.set_keyed(&plan, &keys[..], &arrangement.trace);
-                }
-                else {
-                    // println!("\tplan found: {:?}, {:?}", keys, plan);
-                }
-
-                let arrangement =
-                arrangements
-                    .get_keyed(&plan, &keys[..])
-                    .expect("Surely we just ensured this");
+                    arrangements.install_keyed(&plan, &keys[..], &arrangement);
+                    arrangement
+                };
 
                 let key_selector = move |change: &Vec<V>|
                     priors.iter().map(|&p| change[p].clone()).collect::<Vec<_>>()
@@ -241,7 +243,7 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
                     .enter(inner)
                     ;
 
-                for (join_idx, key_selector, mut trace) in join_plan.into_iter() {
+                for (join_idx, key_selector, arrangement) in join_plan.into_iter() {
 
                     // Use alt or neu timestamps based on relative indices.
                     // Must have an `if` statement here as the two arrangement have different
@@ -249,11 +251,11 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
                     // tuple in the cursor.
                     changes =
                     if join_idx < index {
-                        let arrangement = trace.import(scope).enter_at(inner, |_,_,t| AltNeu::alt(t.clone()), |_| unimplemented!());
+                        let arrangement = arrangement.enter_at(inner, |_,_,t| AltNeu::alt(t.clone()), |t| t.time.clone());
                         differential_dogs3::operators::propose(&changes, arrangement, key_selector)
                     }
                     else {
-                        let arrangement = trace.import(scope).enter_at(inner, |_,_,t| AltNeu::neu(t.clone()), |_| unimplemented!());
+                        let arrangement = arrangement.enter_at(inner, |_,_,t| AltNeu::neu(t.clone()), |t| t.time.prior());
                         differential_dogs3::operators::propose(&changes, arrangement, key_selector)
                     }
                     .map(|(mut prefix, extensions)| { prefix.extend(extensions.into_iter()); prefix })
@@ -0,0 +1,448 @@
+//! A SQL frontend, which lowers statements into plans.
+//!
+//! Tables are created with `CREATE TABLE name (column type, ..)`, which creates an input of that name,
+//! and views with `CREATE VIEW name AS query`, which installs a rule of that name. Queries may use
+//! `SELECT [DISTINCT]`, `FROM` with comma, `CROSS JOIN`, and `[INNER] JOIN .. ON`, `WHERE`, and the
+//! set operations `UNION [ALL]`, `EXCEPT`, and `INTERSECT`. Views are maintained, so there is no
+//! `ORDER BY` or `LIMIT`.
+//!
+//! The names of the columns of tables and views are recorded in a `Catalog`, against which later
+//! statements are planned. Equalities between columns of different tables in `ON` and `WHERE`
+//! clauses become the keys of binary joins, in the order the tables are listed, and the remaining
+//! conditions filter the joined tuples. `GROUP BY` and the aggregates `count`, `sum`, `min`, `max`, and
+//! `avg` reduce the filtered tuples, after which `HAVING` filters the groups.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use differential_dataflow::ExchangeData;
+
+use crate::{Datum, Plan, Rule};
+use crate::plan::{Aggregate, Predicate, filter::SecondArgument};
+
+pub mod parse;
+
+use self::parse::{BinaryOp, Expr, Literal, QueryExpr, Select, SelectItem, SetOp, Statement, TableRef};
+
+/// Values that SQL literals may be converted into.
+pub trait FromLiteral : Sized {
+    /// Converts `literal` into a value, or describes why it cannot be.
+    fn from_literal(literal: &Literal) -> Result<Self, String>;
+}
+
+/// The names of the columns of tables and views.
+#[derive(Clone, Debug, Default)]
+pub struct Catalog {
+    /// Column names by table or view name.
+    pub relations: HashMap<String, Vec<String>>,
+}
+
+impl Catalog {
+    /// Creates a new empty catalog.
+    pub fn new() -> Self { Self::default() }
+
+    /// Column names for a relation of `arity` columns that were not named, `c0`, `c1`, and so on.
+    pub fn positional(arity: usize) -> Vec<String> {
+        (0 .. arity).map(|column| format!("c{}", column)).collect()
+    }
+}
+
+/// A planned statement.
+#[derive(Clone, Debug)]
+pub enum Planned<V: Datum> {
+    /// An input to create, by name.
+    CreateTable(String),
+    /// A rule to install.
+    CreateView(Rule<V>),
+}
+
+/// Plans the statements of `text`.
+///
+/// The columns of each created table and view are recorded in `catalog`, so that later statements,
+/// including those in `text`, can refer to them. If any statement fails to plan, `catalog` is unchanged.
+pub fn plan<V>(text: &str, catalog: &mut Catalog) -> Result<Vec<Planned<V>>, String>
+where
+    V: ExchangeData+Hash+Datum+FromLiteral,
+{
+    let mut staged = catalog.clone();
+    let mut planned = Vec::new();
+    for statement in parse::parse(text)? {
+        match statement {
+            Statement::CreateTable(name, columns) => {
+                register(&mut staged, &name, columns)?;
+                planned.push(Planned::CreateTable(name));
+            },
+            Statement::CreateView(name, columns, query) => {
+                let (plan, names) = plan_query::<V>(&query, &staged)?;
+                let names = match columns {
+                    Some(columns) if columns.len() != names.len() => {
+                        return Err(format!("view {} names {} columns, but its query produces {}", name, columns.len(), names.len()));
+                    },
+                    Some(columns) => columns,
+                    None => names,
+                };
+                register(&mut staged, &name, names)?;
+                planned.push(Planned::CreateView(plan.into_rule(&name)));
+            },
+        }
+    }
+    *catalog = staged;
+    Ok(planned)
+}
+
+fn register(catalog: &mut Catalog, name: &str, columns: Vec<String>) -> Result<(), String> {
+    if catalog.relations.contains_key(name) {
+        return Err(format!("relation already exists: {}", name));
+    }
+    catalog.relations.insert(name.to_string(), columns);
+    Ok(())
+}
+
+/// The names by which each column of a planned relation may be referenced.
+///
+/// A column may have several names, as when two columns are equated by a join and only one is retained.
+#[derive(Clone, Debug)]
+struct Columns {
+    names: Vec<Vec<(Option<String>, String)>>,
+}
+
+impl Columns {
+    /// Columns named `names`, qualified by `table`.
+    fn new(table: &str, names: &[String]) -> Self {
+        Columns { names: names.iter().map(|name| vec![(Some(table.to_string()), name.clone())]).collect() }
+    }
+
+    /// The position of the column named by `table` and `name`, if there is exactly one.
+    fn resolve(&self, table: &Option<String>, name: &str) -> Result<Option<usize>, String> {
+        let mut positions = (0 .. self.names.len()).filter(|&position| {
+            self.names[position].iter().any(|(qualifier, column)| {
+                column == name && (table.is_none() || table == qualifier)
+            })
+        });
+        match (positions.next(), positions.next()) {
+            (None, _) => Ok(None),
+            (Some(position), None) => Ok(Some(position)),
+            (Some(_), Some(_)) => Err(format!("ambiguous column: {}", display(table, name))),
+        }
+    }
+
+    /// The position of the column named by `table` and `name`, or an error if there is not exactly one.
+    fn position(&self, table: &Option<String>, name: &str) -> Result<usize, String> {
+        self.resolve(table, name)?.ok_or_else(|| format!("unknown column: {}", display(table, name)))
+    }
+
+    /// The name of the column at `position`.
+    fn name(&self, position: usize) -> String {
+        self.names[position][0].1.clone()
+    }
+}
+
+fn display(table: &Option<String>, name: &str) -> String {
+    match table {
+        Some(table) => format!("{}.{}", table, name),
+        None => name.to_string(),
+    }
+}
+
+/// Plans `query`, and returns the plan and the names of its columns.
+fn plan_query<V>(query: &QueryExpr, catalog: &Catalog) -> Result<(Plan<V>, Vec<String>), String>
+where
+    V: ExchangeData+Hash+Datum+FromLiteral,
+{
+    match query {
+        QueryExpr::Select(select) => plan_select(select, catalog),
+        QueryExpr::SetOp(op, all, left, right) => {
+            let (left, names) = plan_query(left, catalog)?;
+            let (right, right_names) = plan_query(right, catalog)?;
+            if names.len() != right_names.len() {
+                return Err(format!("set operation on queries with {} and {} columns", names.len(), right_names.len()));
+            }
+            let keys = (0 .. names.len()).map(|column| (column, column)).collect::<Vec<_>>();
+            let plan = match (op, all) {
+                (SetOp::Union, true) => left.concat(right),
+                (SetOp::Union, false) => left.concat(right).distinct(),
+                // Tuples of the left input that also occur in the right input, each once.
+                (SetOp::Intersect, false) => left.distinct().join(right.distinct(), keys),
+                (SetOp::Except, false) => {
+                    let left = left.distinct();
+                    left.clone().concat(left.join(right.distinct(), keys).negate())
+                },
+                (_, true) => return Err("EXCEPT ALL and INTERSECT ALL are not supported".to_string()),
+            };
+            Ok((plan, names))
+        },
+    }
+}
+
+fn plan_table<V>(table: &TableRef, catalog: &Catalog) -> Result<(Plan<V>, Columns), String>
+where
+    V: ExchangeData+Hash+Datum+FromLiteral,
+{
+    match table {
+        TableRef::Table(name, alias) => {
+            let names = catalog.relations.get(name).ok_or_else(|| format!("unknown relation: {}", name))?;
+            Ok((Plan::source(name), Columns::new(alias.as_ref().unwrap_or(name), names)))
+        },
+        TableRef::Subquery(query, alias) => {
+            let (plan, names) = plan_query(query, catalog)?;
+            Ok((plan, Columns::new(alias, &names)))
+        },
+    }
+}
+
+/// Adds the conjuncts of `expr` to `conjuncts`.
+fn conjuncts(expr: &Expr, conjuncts: &mut Vec<Expr>) {
+    match expr {
+        Expr::Binary(BinaryOp::And, left, right) => {
+            self::conjuncts(left, conjuncts);
+            self::conjuncts(right, conjuncts);
+        },
+        _ => conjuncts.push(expr.clone()),
+    }
+}
+
+fn plan_select<V>(select: &Select, catalog: &Catalog) -> Result<(Plan<V>, Vec<String>), String>
+where
+    V: ExchangeData+Hash+Datum+FromLiteral,
+{
+    let mut pending = Vec::new();
+    if let Some(selection) = &select.selection {
+        conjuncts(selection, &mut pending);
+    }
+
+    // Join the tables in order, keyed by the equalities between their columns.
+    let (mut plan, mut columns) = plan_table(&select.from[0].0, catalog)?;
+    for (table, on) in select.from.iter().skip(1) {
+        let (plan2, columns2) = plan_table(table, catalog)?;
+        if let Some(on) = on {
+            conjuncts(on, &mut pending);
+        }
+
+        let mut keys = Vec::new();
+        pending.retain(|conjunct| {
+            if let Expr::Binary(BinaryOp::Eq, left, right) = conjunct {
+                if let (Expr::Column(table1, name1), Expr::Column(table2, name2)) = (&**left, &**right) {
+                    let resolve = |columns: &Columns, table: &Option<String>, name: &str| columns.resolve(table, name).ok().flatten();
+                    let key = match (resolve(&columns, table1, name1), resolve(&columns2, table2, name2)) {
+                        (Some(position1), Some(position2)) => Some((position1, position2)),
+                        _ => match (resolve(&columns, table2, name2), resolve(&columns2, table1, name1)) {
+                            (Some(position1), Some(position2)) => Some((position1, position2)),
+                            _ => None,
+                        },
+                    };
+                    if let Some(key) = key {
+                        keys.push(key);
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+
+        // The join produces the keys, then the other columns of each input.
+        let mut names = Vec::new();
+        for (index, &(position1, position2)) in keys.iter().enumerate() {
+            let mut key_names = Vec::new();
+            if !keys[.. index].iter().any(|key| key.0 == position1) {
+                key_names.extend(columns.names[position1].iter().cloned());
+            }
+            if !keys[.. index].iter().any(|key| key.1 == position2) {
+                key_names.extend(columns2.names[position2].iter().cloned());
+            }
+            names.push(key_names);
+        }
+        names.extend((0 .. columns.names.len()).filter(|p| !keys.iter().any(|key| key.0 == *p)).map(|p| columns.names[p].clone()));
+        names.extend((0 .. columns2.names.len()).filter(|p| !keys.iter().any(|key| key.1 == *p)).map(|p| columns2.names[p].clone()));
+
+        plan = plan.join(plan2, keys);
+        columns = Columns { names };
+    }
+
+    if !pending.is_empty() {
+        let predicates = pending.iter().map(|conjunct| predicate(conjunct, &columns)).collect::<Result<Vec<_>, _>>()?;
+        plan = plan.filter(if predicates.len() == 1 { predicates.into_iter().next().unwrap() } else { Predicate::All(predicates) });
+    }
+
+    let mut items = select.items.clone();
+    let aggregates = items.iter().any(|item| matches!(item, SelectItem::Expr(expr, _) if contains_aggregate(expr)));
+    if aggregates || !select.group_by.is_empty() || select.having.is_some() {
+        (plan, columns, items) = plan_group(plan, columns, select)?;
+    }
+
+    let mut positions = Vec::new();
+    let mut names = Vec::new();
+    for item in items.iter() {
+        match item {
+            SelectItem::Wildcard => {
+                positions.extend(0 .. columns.names.len());
+                names.extend((0 .. columns.names.len()).map(|position| columns.name(position)));
+            },
+            SelectItem::QualifiedWildcard(name) => {
+                let table = Some(name.clone());
+                let matching = (0 .. columns.names.len()).filter(|&position| columns.names[position].iter().any(|(qualifier, _)| qualifier == &table)).collect::<Vec<_>>();
+                if matching.is_empty() {
+                    return Err(format!("unknown table: {}", name));
+                }
+                for position in matching {
+                    let name = columns.names[position].iter().find(|(qualifier, _)| qualifier == &table).unwrap().1.clone();
+                    positions.push(position);
+                    names.push(name);
+                }
+            },
+            SelectItem::Expr(Expr::Column(table, name), alias) => {
+                positions.push(columns.position(table, name)?);
+                names.push(alias.clone().unwrap_or_else(|| name.clone()));
+            },
+            SelectItem::Expr(expr, _) => {
+                return Err(format!("only columns may be selected: {:?}", expr));
+            },
+        }
+    }
+
+    if positions != (0 .. columns.names.len()).collect::<Vec<_>>() {
+        plan = plan.project(positions);
+    }
+    if select.distinct {
+        plan = plan.distinct();
+    }
+
+    Ok((plan, names))
+}
+
+/// The qualifier of the columns that hold aggregates, which the parser cannot produce.
+const AGGREGATE: &str = "\0aggregate";
+
+fn contains_aggregate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function { .. } => true,
+        Expr::Binary(_, left, right) => contains_aggregate(left) || contains_aggregate(right),
+        Expr::Not(expr) => contains_aggregate(expr),
+        Expr::Column(..) | Expr::Literal(_) => false,
+    }
+}
+
+/// Groups and aggregates the output of `plan`, and filters the groups by the `HAVING` clause.
+///
+/// Returns the grouped plan, its columns, and the selected items rewritten to refer to them.
+fn plan_group<V>(plan: Plan<V>, columns: Columns, select: &Select) -> Result<(Plan<V>, Columns, Vec<SelectItem>), String>
+where
+    V: ExchangeData+Hash+Datum+FromLiteral,
+{
+    let keys = select.group_by.iter().map(|expr| match expr {
+        Expr::Column(table, name) => columns.position(table, name),
+        _ => Err(format!("only columns may be grouped by: {:?}", expr)),
+    }).collect::<Result<Vec<_>, _>>()?;
+
+    let mut aggregates = Vec::new();
+    let items = select.items.iter().map(|item| match item {
+        SelectItem::Expr(expr, alias) => {
+            let alias = match (expr, alias) {
+                (Expr::Function { name, .. }, None) => Some(name.clone()),
+                _ => alias.clone(),
+            };
+            Ok(SelectItem::Expr(extract_aggregates(expr, &columns, &mut aggregates)?, alias))
+        },
+        _ => Err("wildcards may not be selected with GROUP BY or aggregates".to_string()),
+    }).collect::<Result<Vec<_>, String>>()?;
+    let having = select.having.as_ref().map(|having| extract_aggregates(having, &columns, &mut aggregates)).transpose()?;
+
+    // The reduced plan produces the grouped columns, then the aggregates.
+    let mut names = keys.iter().map(|key| columns.names[*key].clone()).collect::<Vec<_>>();
+    names.extend((0 .. aggregates.len()).map(|index| vec![(Some(AGGREGATE.to_string()), index.to_string())]));
+    let grouped = Columns { names };
+
+    for item in items.iter() {
+        if let SelectItem::Expr(Expr::Column(table, name), _) = item {
+            if grouped.resolve(table, name)?.is_none() {
+                return Err(format!("column {} must appear in GROUP BY or in an aggregate", display(table, name)));
+            }
+        }
+    }
+
+    let mut plan = plan.reduce(keys, aggregates);
+    if let Some(having) = having {
+        plan = plan.filter(predicate(&having, &grouped)?);
+    }
+    Ok((plan, grouped, items))
+}
+
+/// Replaces the aggregates in `expr` with references to the columns that will hold them.
+fn extract_aggregates(expr: &Expr, columns: &Columns, aggregates: &mut Vec<Aggregate>) -> Result<Expr, String> {
+    match expr {
+        Expr::Function { name, distinct, args } => {
+            if *distinct {
+                return Err(format!("DISTINCT aggregates are not supported: {}", name));
+            }
+            let column = match &args[..] {
+                [] => None,
+                [Expr::Column(table, column)] => Some(columns.position(table, column)?),
+                _ => return Err(format!("aggregates must apply to a single column: {}", name)),
+            };
+            let aggregate = match (name.as_str(), column) {
+                ("count", _) => Aggregate::Count,
+                ("sum", Some(column)) => Aggregate::Sum(column),
+                ("min", Some(column)) => Aggregate::Min(column),
+                ("max", Some(column)) => Aggregate::Max(column),
+                ("avg", Some(column)) => Aggregate::Avg(column),
+                _ => return Err(format!("unknown aggregate: {}", name)),
+            };
+            let index = match aggregates.iter().position(|other| other == &aggregate) {
+                Some(index) => index,
+                None => { aggregates.push(aggregate); aggregates.len() - 1 },
+            };
+            Ok(Expr::Column(Some(AGGREGATE.to_string()), index.to_string()))
+        },
+        Expr::Binary(op, left, right) => {
+            let left = extract_aggregates(left, columns, aggregates)?;
+            let right = extract_aggregates(right, columns, aggregates)?;
+            Ok(Expr::Binary(*op, Box::new(left), Box::new(right)))
+        },
+        Expr::Not(expr) => Ok(Expr::Not(Box::new(extract_aggregates(expr, columns, aggregates)?))),
+        Expr::Column(..) | Expr::Literal(_) => Ok(expr.clone()),
+    }
+}
+
+/// Converts a condition into a predicate on the columns of `columns`.
+fn predicate<V: FromLiteral>(expr: &Expr, columns: &Columns) -> Result<Predicate<V>, String> {
+    match expr {
+        Expr::Binary(BinaryOp::And, left, right) => Ok(Predicate::All(vec![predicate(left, columns)?, predicate(right, columns)?])),
+        Expr::Binary(BinaryOp::Or, left, right) => Ok(Predicate::Any(vec![predicate(left, columns)?, predicate(right, columns)?])),
+        Expr::Not(expr) => Ok(Predicate::Not(Box::new(predicate(expr, columns)?))),
+        Expr::Column(table, name) => {
+            Ok(Predicate::Equal(columns.position(table, name)?, SecondArgument::Constant(V::from_literal(&Literal::Boolean(true))?)))
+        },
+        Expr::Binary(op, left, right) => {
+            let (position, op, other) = match (&**left, &**right) {
+                (Expr::Column(table, name), other) => (columns.position(table, name)?, *op, other),
+                (other, Expr::Column(table, name)) => (columns.position(table, name)?, flip(*op), other),
+                _ => return Err(format!("comparisons must involve a column: {:?}", expr)),
+            };
+            let other = match other {
+                Expr::Column(table, name) => SecondArgument::Position(columns.position(table, name)?),
+                Expr::Literal(literal) => SecondArgument::Constant(V::from_literal(literal)?),
+                _ => return Err(format!("unsupported comparison: {:?}", expr)),
+            };
+            Ok(match op {
+                BinaryOp::Eq => Predicate::Equal(position, other),
+                BinaryOp::NotEq => Predicate::NotEqual(position, other),
+                BinaryOp::Lt => Predicate::LessThan(position, other),
+                BinaryOp::LtEq => Predicate::LessEqual(position, other),
+                BinaryOp::Gt => Predicate::GreaterThan(position, other),
+                BinaryOp::GtEq => Predicate::GreaterEqual(position, other),
+                BinaryOp::And | BinaryOp::Or => unreachable!(),
+            })
+        },
+        _ => Err(format!("unsupported condition: {:?}", expr)),
+    }
+}
+
+/// The comparison with its arguments exchanged.
+fn flip(op: BinaryOp) -> BinaryOp {
+    match op {
+        BinaryOp::Lt => BinaryOp::Gt,
+        BinaryOp::LtEq => BinaryOp::GtEq,
+        BinaryOp::Gt => BinaryOp::Lt,
+        BinaryOp::GtEq => BinaryOp::LtEq,
+        other => other,
+    }
+}
@@ -0,0 +1,484 @@
+//! Parsing of SQL statements.
+
+/// A literal value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Literal {
+    /// An integer.
+    Integer(i64),
+    /// A string, written in single quotes.
+    String(String),
+    /// `TRUE` or `FALSE`.
+    Boolean(bool),
+}
+
+/// Binary operators.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BinaryOp {
+    /// `AND`
+    And,
+    /// `OR`
+    Or,
+    /// `=`
+    Eq,
+    /// `<>` or `!=`
+    NotEq,
+    /// `<`
+    Lt,
+    /// `<=`
+    LtEq,
+    /// `>`
+    Gt,
+    /// `>=`
+    GtEq,
+}
+
+/// A scalar expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    /// A column, optionally qualified by the name or alias of its table.
+    Column(Option<String>, String),
+    /// A literal value.
+    Literal(Literal),
+    /// A binary operation.
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// The negation of a condition.
+    Not(Box<Expr>),
+    /// A function applied to arguments, as in `count(*)` or `sum(DISTINCT x)`.
+    Function {
+        /// The name of the function, in lower case.
+        name: String,
+        /// Set if the function applies to distinct arguments.
+        distinct: bool,
+        /// The arguments, which are empty for `*`.
+        args: Vec<Expr>,
+    },
+}
+
+/// An item of a `SELECT` list.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SelectItem {
+    /// `*`, all columns.
+    Wildcard,
+    /// `table.*`, all columns of a table.
+    QualifiedWildcard(String),
+    /// An expression, with an optional alias.
+    Expr(Expr, Option<String>),
+}
+
+/// A table in a `FROM` clause.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TableRef {
+    /// A named table or view, with an optional alias.
+    Table(String, Option<String>),
+    /// A subquery, with an alias.
+    Subquery(Box<QueryExpr>, String),
+}
+
+/// A `SELECT` query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Select {
+    /// Set for `SELECT DISTINCT`.
+    pub distinct: bool,
+    /// The selected items.
+    pub items: Vec<SelectItem>,
+    /// The joined tables, each with the condition of its `JOIN .. ON`, if any.
+    pub from: Vec<(TableRef, Option<Expr>)>,
+    /// The condition of the `WHERE` clause.
+    pub selection: Option<Expr>,
+    /// The expressions of the `GROUP BY` clause.
+    pub group_by: Vec<Expr>,
+    /// The condition of the `HAVING` clause.
+    pub having: Option<Expr>,
+}
+
+/// Set operations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SetOp {
+    /// `UNION`
+    Union,
+    /// `EXCEPT`
+    Except,
+    /// `INTERSECT`
+    Intersect,
+}
+
+/// A query, which may combine `SELECT` queries with set operations.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum QueryExpr {
+    /// A `SELECT` query.
+    Select(Box<Select>),
+    /// A set operation, with `ALL` if set, applied to two queries.
+    SetOp(SetOp, bool, Box<QueryExpr>, Box<QueryExpr>),
+}
+
+/// A SQL statement.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Statement {
+    /// `CREATE TABLE name (column type, ..)`, where types are ignored.
+    CreateTable(String, Vec<String>),
+    /// `CREATE VIEW name [(column, ..)] AS query`.
+    CreateView(String, Option<Vec<String>>, QueryExpr),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    /// An unquoted identifier or keyword.
+    Word(String),
+    /// An identifier in double quotes.
+    Quoted(String),
+    Integer(i64),
+    String(String),
+    Punct(&'static str),
+}
+
+/// Punctuation, with longer tokens before their prefixes.
+const PUNCTUATION: &[&str] = &["<>", "!=", "<=", ">=", "(", ")", ",", ".", ";", "*", "=", "<", ">"];
+
+/// Keywords that may not be used as aliases without `AS`.
+const RESERVED: &[&str] = &[
+    "SELECT", "DISTINCT", "ALL", "FROM", "WHERE", "GROUP", "BY", "HAVING", "UNION", "EXCEPT", "INTERSECT",
+    "JOIN", "INNER", "CROSS", "ON", "AS", "AND", "OR", "NOT", "ORDER", "LIMIT",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        }
+        else if c == '-' && chars.get(pos + 1) == Some(&'-') {
+            while pos < chars.len() && chars[pos] != '\n' { pos += 1; }
+        }
+        else if c == '\'' || c == '"' {
+            // Quotes are escaped by doubling them.
+            let mut string = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None => return Err("unterminated quotation".to_string()),
+                    Some(&q) if q == c && chars.get(pos + 1) == Some(&c) => { string.push(c); pos += 2; },
+                    Some(&q) if q == c => { pos += 1; break; },
+                    Some(&other) => { string.push(other); pos += 1; },
+                }
+            }
+            tokens.push(if c == '\'' { Token::String(string) } else { Token::Quoted(string) });
+        }
+        else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && chars[pos].is_ascii_digit() { pos += 1; }
+            let digits = chars[start .. pos].iter().collect::<String>();
+            tokens.push(Token::Integer(digits.parse().map_err(|_| format!("integer out of range: {}", digits))?));
+        }
+        else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') { pos += 1; }
+            tokens.push(Token::Word(chars[start .. pos].iter().collect()));
+        }
+        else if let Some(punct) = PUNCTUATION.iter().find(|punct| punct.chars().enumerate().all(|(i, p)| chars.get(pos + i) == Some(&p))) {
+            pos += punct.len();
+            tokens.push(Token::Punct(punct));
+        }
+        else {
+            return Err(format!("unexpected character: {:?}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a sequence of statements separated by semicolons.
+pub fn parse(text: &str) -> Result<Vec<Statement>, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+    let mut statements = Vec::new();
+    loop {
+        while parser.accept(";") { }
+        if parser.pos == parser.tokens.len() { break; }
+        statements.push(parser.statement()?);
+        if parser.pos < parser.tokens.len() {
+            parser.expect(";")?;
+        }
+    }
+    Ok(statements)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos) }
+
+    fn error<T>(&self, expected: &str) -> Result<T, String> {
+        match self.peek() {
+            Some(token) => Err(format!("expected {}, found {:?}", expected, token)),
+            None => Err(format!("expected {}, found end of input", expected)),
+        }
+    }
+
+    /// Consumes `punct` if it is the next token.
+    fn accept(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(Token::Punct(next)) if *next == punct => { self.pos += 1; true },
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.accept(punct) { Ok(()) } else { self.error(&format!("`{}`", punct)) }
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Consumes `keyword` if it is the next token.
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) { self.pos += 1; true } else { false }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.accept_keyword(keyword) { Ok(()) } else { self.error(keyword) }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Word(word)) if !RESERVED.iter().any(|keyword| word.eq_ignore_ascii_case(keyword)) => { self.pos += 1; Ok(word) },
+            Some(Token::Quoted(name)) => { self.pos += 1; Ok(name) },
+            _ => self.error("an identifier"),
+        }
+    }
+
+    /// Parses a parenthesized list of identifiers.
+    fn ident_list(&mut self) -> Result<Vec<String>, String> {
+        self.expect("(")?;
+        let mut idents = vec![self.ident()?];
+        while self.accept(",") {
+            idents.push(self.ident()?);
+        }
+        self.expect(")")?;
+        Ok(idents)
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("CREATE")?;
+        if self.accept_keyword("TABLE") {
+            let name = self.ident()?;
+            self.expect("(")?;
+            let mut columns = Vec::new();
+            loop {
+                columns.push(self.ident()?);
+                // Skip the type of the column, including any parenthesized parameters.
+                let mut depth = 0;
+                while depth > 0 || !(self.peek() == Some(&Token::Punct(",")) || self.peek() == Some(&Token::Punct(")"))) {
+                    match self.peek() {
+                        None => return self.error("`)`"),
+                        Some(Token::Punct("(")) => depth += 1,
+                        Some(Token::Punct(")")) => depth -= 1,
+                        _ => { },
+                    }
+                    self.pos += 1;
+                }
+                if self.accept(")") { break; }
+                self.expect(",")?;
+            }
+            Ok(Statement::CreateTable(name, columns))
+        }
+        else if self.accept_keyword("VIEW") {
+            let name = self.ident()?;
+            let columns = if self.peek() == Some(&Token::Punct("(")) { Some(self.ident_list()?) } else { None };
+            self.expect_keyword("AS")?;
+            Ok(Statement::CreateView(name, columns, self.query()?))
+        }
+        else {
+            self.error("TABLE or VIEW")
+        }
+    }
+
+    /// Parses set operations, which associate to the left.
+    fn query(&mut self) -> Result<QueryExpr, String> {
+        let mut query = self.query_primary()?;
+        loop {
+            let op =
+            if self.accept_keyword("UNION") { SetOp::Union }
+            else if self.accept_keyword("EXCEPT") { SetOp::Except }
+            else if self.accept_keyword("INTERSECT") { SetOp::Intersect }
+            else { break };
+            let all = self.accept_keyword("ALL");
+            if !all { self.accept_keyword("DISTINCT"); }
+            let right = self.query_primary()?;
+            query = QueryExpr::SetOp(op, all, Box::new(query), Box::new(right));
+        }
+        if self.peek_keyword("ORDER") || self.peek_keyword("LIMIT") {
+            return Err("ORDER BY and LIMIT are not supported by maintained views".to_string());
+        }
+        Ok(query)
+    }
+
+    fn query_primary(&mut self) -> Result<QueryExpr, String> {
+        if self.accept("(") {
+            let query = self.query()?;
+            self.expect(")")?;
+            return Ok(query);
+        }
+        self.expect_keyword("SELECT")?;
+        let distinct = self.accept_keyword("DISTINCT");
+        if !distinct { self.accept_keyword("ALL"); }
+
+        let mut items = Vec::new();
+        loop {
+            if self.accept("*") {
+                items.push(SelectItem::Wildcard);
+            }
+            else if matches!((self.tokens.get(self.pos + 1), self.tokens.get(self.pos + 2)), (Some(Token::Punct(".")), Some(Token::Punct("*")))) {
+                let table = self.ident()?;
+                self.pos += 2;
+                items.push(SelectItem::QualifiedWildcard(table));
+            }
+            else {
+                let expr = self.expr()?;
+                let alias = self.alias()?;
+                items.push(SelectItem::Expr(expr, alias));
+            }
+            if !self.accept(",") { break; }
+        }
+
+        self.expect_keyword("FROM")?;
+        let mut from = vec![(self.table_ref()?, None)];
+        loop {
+            if self.accept(",") {
+                from.push((self.table_ref()?, None));
+            }
+            else if self.accept_keyword("CROSS") {
+                self.expect_keyword("JOIN")?;
+                from.push((self.table_ref()?, None));
+            }
+            else if self.peek_keyword("JOIN") || self.peek_keyword("INNER") {
+                self.accept_keyword("INNER");
+                self.expect_keyword("JOIN")?;
+                let table = self.table_ref()?;
+                self.expect_keyword("ON")?;
+                from.push((table, Some(self.expr()?)));
+            }
+            else { break; }
+        }
+
+        let selection = if self.accept_keyword("WHERE") { Some(self.expr()?) } else { None };
+        let mut group_by = Vec::new();
+        if self.accept_keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            group_by.push(self.expr()?);
+            while self.accept(",") {
+                group_by.push(self.expr()?);
+            }
+        }
+        let having = if self.accept_keyword("HAVING") { Some(self.expr()?) } else { None };
+
+        Ok(QueryExpr::Select(Box::new(Select { distinct, items, from, selection, group_by, having })))
+    }
+
+    /// Parses an optional alias, with or without `AS`.
+    fn alias(&mut self) -> Result<Option<String>, String> {
+        if self.accept_keyword("AS") {
+            return Ok(Some(self.ident()?));
+        }
+        match self.peek() {
+            Some(Token::Word(word)) if !RESERVED.iter().any(|keyword| word.eq_ignore_ascii_case(keyword)) => Ok(Some(self.ident()?)),
+            Some(Token::Quoted(_)) => Ok(Some(self.ident()?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn table_ref(&mut self) -> Result<TableRef, String> {
+        if self.accept("(") {
+            let query = self.query()?;
+            self.expect(")")?;
+            let alias = self.alias()?.ok_or_else(|| "subqueries in FROM require an alias".to_string())?;
+            Ok(TableRef::Subquery(Box::new(query), alias))
+        }
+        else {
+            let name = self.ident()?;
+            Ok(TableRef::Table(name, self.alias()?))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.conjunction()?;
+        while self.accept_keyword("OR") {
+            expr = Expr::Binary(BinaryOp::Or, Box::new(expr), Box::new(self.conjunction()?));
+        }
+        Ok(expr)
+    }
+
+    fn conjunction(&mut self) -> Result<Expr, String> {
+        let mut expr = self.negation()?;
+        while self.accept_keyword("AND") {
+            expr = Expr::Binary(BinaryOp::And, Box::new(expr), Box::new(self.negation()?));
+        }
+        Ok(expr)
+    }
+
+    fn negation(&mut self) -> Result<Expr, String> {
+        if self.accept_keyword("NOT") {
+            Ok(Expr::Not(Box::new(self.negation()?)))
+        }
+        else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.operand()?;
+        let op = match self.peek() {
+            Some(Token::Punct("=")) => BinaryOp::Eq,
+            Some(Token::Punct("<>")) | Some(Token::Punct("!=")) => BinaryOp::NotEq,
+            Some(Token::Punct("<")) => BinaryOp::Lt,
+            Some(Token::Punct("<=")) => BinaryOp::LtEq,
+            Some(Token::Punct(">")) => BinaryOp::Gt,
+            Some(Token::Punct(">=")) => BinaryOp::GtEq,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.operand()?)))
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        match self.peek().cloned() {
+            Some(Token::Integer(value)) => { self.pos += 1; Ok(Expr::Literal(Literal::Integer(value))) },
+            Some(Token::String(value)) => { self.pos += 1; Ok(Expr::Literal(Literal::String(value))) },
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("TRUE") => { self.pos += 1; Ok(Expr::Literal(Literal::Boolean(true))) },
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("FALSE") => { self.pos += 1; Ok(Expr::Literal(Literal::Boolean(false))) },
+            Some(Token::Punct("(")) => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            },
+            _ => {
+                let name = self.ident()?;
+                if self.accept("(") {
+                    let name = name.to_lowercase();
+                    let distinct = self.accept_keyword("DISTINCT");
+                    let mut args = Vec::new();
+                    if !self.accept("*") {
+                        args.push(self.expr()?);
+                        while self.accept(",") {
+                            args.push(self.expr()?);
+                        }
+                    }
+                    self.expect(")")?;
+                    Ok(Expr::Function { name, distinct, args })
+                }
+                else if self.accept(".") {
+                    Ok(Expr::Column(Some(name), self.ident()?))
+                }
+                else {
+                    Ok(Expr::Column(None, name))
+                }
+            },
+        }
+    }
+}
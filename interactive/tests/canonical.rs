@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use interactive::{Command, Manager, Plan, Query};
+use interactive::concrete::Value;
+use interactive::plan::{Predicate, canonicalize};
+use interactive::plan::filter::SecondArgument;
+
+mod common;
+use common::{contents, pairs};
+
+/// Each edge, as its destination, its source, and the label of its destination.
+fn labelled() -> Plan<Value> {
+    Plan::source("edges").join(Plan::source("labels"), vec![(1, 0)])
+}
+
+/// The same records as `labelled`, from a join with its inputs swapped.
+fn labelled_swapped() -> Plan<Value> {
+    Plan::source("labels").join(Plan::source("edges"), vec![(0, 1)]).project(vec![0, 2, 1])
+}
+
+#[test]
+fn canonical_join_swapped() {
+    let mut arities = HashMap::new();
+    arities.insert("edges".to_string(), 2);
+    arities.insert("labels".to_string(), 2);
+
+    // Filters of the swapped join are renumbered to match.
+    let filter = |plan: Plan<Value>| plan.filter(Predicate::LessThan(1, SecondArgument::Position(2)));
+    assert_eq!(canonicalize(&labelled(), &arities), canonicalize(&labelled_swapped(), &arities));
+    assert_eq!(canonicalize(&filter(labelled()), &arities), canonicalize(&filter(labelled_swapped()), &arities));
+    assert_ne!(canonicalize(&labelled(), &arities), canonicalize(&labelled_swapped().project(vec![0, 2, 1]), &arities));
+
+    // Without the numbers of values of the sources, the inputs are not reordered.
+    assert_ne!(canonicalize(&labelled(), &HashMap::new()), canonicalize(&labelled_swapped(), &HashMap::new()));
+}
+
+#[test]
+fn canonical_arrangement_shared() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), pairs(&[(1, 2), (2, 3), (3, 1)])).execute(&mut manager, worker);
+        Command::CreateInput("labels".to_string(), pairs(&[(1, 10), (2, 20), (3, 30)])).execute(&mut manager, worker);
+        let installed = worker.installed_dataflows().len();
+
+        Query::new().add_rule(labelled().distinct().into_rule("a")).into_command().execute(&mut manager, worker);
+        assert!(manager.traces.contains_unkeyed(&labelled_swapped().distinct()));
+        Query::new().add_rule(labelled_swapped().distinct().into_rule("b")).into_command().execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        let expected = vec![
+            vec![Value::Usize(1), Value::Usize(3), Value::Usize(10)],
+            vec![Value::Usize(2), Value::Usize(1), Value::Usize(20)],
+            vec![Value::Usize(3), Value::Usize(2), Value::Usize(30)],
+        ];
+        assert_eq!(contents(&mut manager, "a"), Some(expected.clone()));
+        assert_eq!(contents(&mut manager, "b"), Some(expected));
+
+        // The second query imports the arrangement of the first, which outlives the first query.
+        assert!(manager.drop_query("a", worker));
+        assert_eq!(worker.installed_dataflows().len(), installed + 2);
+        assert!(manager.drop_query("b", worker));
+        assert_eq!(worker.installed_dataflows().len(), installed);
+    });
+}
@@ -0,0 +1,37 @@
+//! Helpers shared by the integration tests, each of which uses some of them.
+
+#![allow(dead_code)]
+
+use differential_dataflow::trace::TraceReader;
+use differential_dataflow::trace::cursor::Cursor;
+
+use interactive::{Manager, Plan};
+use interactive::concrete::Value;
+
+/// The records of the rule `name`, and their multiplicities, if it is maintained.
+pub fn multiplicities(manager: &mut Manager<Value>, name: &str) -> Option<Vec<(Vec<Value>, isize)>> {
+    let mut trace = manager.traces.get_unkeyed(&Plan::Source(name.to_string()))?;
+    let (mut cursor, storage) = trace.cursor();
+    let mut records =
+    cursor
+        .to_vec::<Vec<Value>, ()>(&storage)
+        .into_iter()
+        .map(|((record, ()), updates)| (record, updates.iter().map(|(_, diff)| diff).sum()))
+        .filter(|(_, diff)| *diff != 0)
+        .collect::<Vec<_>>();
+    records.sort();
+    Some(records)
+}
+
+/// The records of the rule `name`, if it is maintained.
+pub fn contents(manager: &mut Manager<Value>, name: &str) -> Option<Vec<Vec<Value>>> {
+    multiplicities(manager, name).map(|records| records.into_iter().map(|(record, _)| record).collect())
+}
+
+pub fn pair(x: usize, y: usize) -> Vec<Value> {
+    vec![Value::Usize(x), Value::Usize(y)]
+}
+
+pub fn pairs(pairs: &[(usize, usize)]) -> Vec<Vec<Value>> {
+    pairs.iter().map(|&(x, y)| pair(x, y)).collect()
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use interactive::concrete::{Float, Value};
+use interactive::concrete::expression::{BinaryOp, Expression, Function, Type, UnaryOp};
+use interactive::plan::Predicate;
+use interactive::plan::filter::SecondArgument;
+
+fn literal(value: Value) -> Expression {
+    Expression::Literal(value)
+}
+
+fn string(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+fn cast(value: Value, to: Type) -> Value {
+    Expression::Cast(Box::new(literal(value)), to).eval(&[])
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Value {
+    Expression::Binary(op, Box::new(literal(left)), Box::new(literal(right))).eval(&[])
+}
+
+#[test]
+fn expression_casts() {
+    assert_eq!(cast(string(" 42 "), Type::Usize), Value::Usize(42));
+    assert_eq!(cast(string("-42"), Type::Int), Value::Int(-42));
+    assert_eq!(cast(string("1.5"), Type::Float), Value::Float(Float(1.5)));
+    assert_eq!(cast(string("true"), Type::Bool), Value::Bool(true));
+    assert_eq!(cast(Value::Int(-1), Type::Usize), Value::Null);
+    assert_eq!(cast(Value::Float(Float(2.7)), Type::Int), Value::Int(2));
+    assert_eq!(cast(Value::Float(Float(-1.0)), Type::Usize), Value::Null);
+    assert_eq!(cast(Value::Float(Float(1e30)), Type::Int), Value::Null);
+    assert_eq!(cast(Value::Usize(0), Type::Bool), Value::Bool(false));
+    assert_eq!(cast(Value::Usize(7), Type::String), string("7"));
+    assert_eq!(cast(Value::Bytes(vec![0xff]), Type::String), Value::Null);
+    assert_eq!(cast(string("abc"), Type::Bytes), Value::Bytes(b"abc".to_vec()));
+    assert_eq!(cast(string("abc"), Type::Int), Value::Null);
+    assert_eq!(cast(Value::Null, Type::String), Value::Null);
+}
+
+#[test]
+fn expression_dates() {
+    assert_eq!(cast(string("1970-01-01"), Type::Date), Value::Date(0));
+    assert_eq!(cast(string("1969-12-31"), Type::Date), Value::Date(-1));
+    assert_eq!(cast(string("2000-03-01"), Type::Date), Value::Date(11017));
+    assert_eq!(cast(string("2024-02-29"), Type::Date), Value::Date(19782));
+    assert_eq!(cast(Value::Date(19782), Type::String), string("2024-02-29"));
+    assert_eq!(cast(Value::Date(-1), Type::String), string("1969-12-31"));
+
+    // Invalid dates do not parse.
+    for text in ["2023-02-29", "1900-02-29", "2024-13-01", "2024-00-10", "2024-04-31", "2024-01", "date"] {
+        assert_eq!(cast(string(text), Type::Date), Value::Null, "{}", text);
+    }
+
+    // Days added to and subtracted from dates, and the days between dates.
+    let date = cast(string("2024-02-28"), Type::Date);
+    assert_eq!(cast(binary(BinaryOp::Add, date.clone(), Value::Usize(1)), Type::String), string("2024-02-29"));
+    assert_eq!(cast(binary(BinaryOp::Add, date.clone(), Value::Int(2)), Type::String), string("2024-03-01"));
+    assert_eq!(cast(binary(BinaryOp::Sub, date.clone(), Value::Int(59)), Type::String), string("2023-12-31"));
+    assert_eq!(binary(BinaryOp::Sub, date.clone(), Value::Date(19782)), Value::Int(-1));
+    assert_eq!(binary(BinaryOp::Add, Value::Date(i32::MAX), Value::Usize(1)), Value::Null);
+    assert_eq!(binary(BinaryOp::Lt, date, Value::Date(19782)), Value::Bool(true));
+}
+
+#[test]
+fn expression_checked_arithmetic() {
+    assert_eq!(binary(BinaryOp::Add, Value::Usize(usize::MAX), Value::Usize(1)), Value::Null);
+    assert_eq!(binary(BinaryOp::Sub, Value::Usize(1), Value::Usize(2)), Value::Null);
+    assert_eq!(binary(BinaryOp::Mul, Value::Int(i64::MAX), Value::Int(2)), Value::Null);
+    assert_eq!(binary(BinaryOp::Div, Value::Int(i64::MIN), Value::Int(-1)), Value::Null);
+    assert_eq!(binary(BinaryOp::Div, Value::Int(1), Value::Int(0)), Value::Null);
+    assert_eq!(binary(BinaryOp::Rem, Value::Usize(1), Value::Usize(0)), Value::Null);
+    assert_eq!(binary(BinaryOp::Rem, Value::Usize(7), Value::Usize(3)), Value::Usize(1));
+    assert_eq!(Expression::Unary(UnaryOp::Neg, Box::new(literal(Value::Int(i64::MIN)))).eval(&[]), Value::Null);
+    assert_eq!(Expression::Unary(UnaryOp::Neg, Box::new(literal(Value::Usize(3)))).eval(&[]), Value::Int(-3));
+    assert_eq!(Expression::Function(Function::Abs, vec![literal(Value::Int(i64::MIN))]).eval(&[]), Value::Null);
+
+    // Numbers of different types are promoted to the more general type.
+    assert_eq!(binary(BinaryOp::Sub, Value::Usize(1), Value::Int(2)), Value::Int(-1));
+    assert_eq!(binary(BinaryOp::Add, Value::Int(1), Value::Float(Float(0.5))), Value::Float(Float(1.5)));
+    assert_eq!(binary(BinaryOp::Eq, Value::Usize(2), Value::Float(Float(2.0))), Value::Bool(true));
+    assert_eq!(binary(BinaryOp::Add, Value::Usize(usize::MAX), Value::Int(0)), Value::Null);
+
+    let second = Value::Duration(Duration::from_secs(1));
+    assert_eq!(binary(BinaryOp::Mul, second.clone(), Value::Usize(3)), Value::Duration(Duration::from_secs(3)));
+    assert_eq!(binary(BinaryOp::Div, second.clone(), Value::Usize(0)), Value::Null);
+    assert_eq!(binary(BinaryOp::Sub, second.clone(), Value::Duration(Duration::from_secs(2))), Value::Null);
+}
+
+#[test]
+fn expression_nulls() {
+    let null = || literal(Value::Null);
+    let boolean = |x| literal(Value::Bool(x));
+    let and = |left, right| Expression::Binary(BinaryOp::And, Box::new(left), Box::new(right));
+    let or = |left, right| Expression::Binary(BinaryOp::Or, Box::new(left), Box::new(right));
+
+    assert_eq!(binary(BinaryOp::Add, Value::Null, Value::Usize(1)), Value::Null);
+    assert_eq!(binary(BinaryOp::Eq, Value::Null, Value::Null), Value::Null);
+    assert_eq!(Expression::Unary(UnaryOp::Not, Box::new(null())).eval(&[]), Value::Null);
+    assert_eq!(Expression::Unary(UnaryOp::IsNull, Box::new(null())).eval(&[]), Value::Bool(true));
+    assert_eq!(Expression::Unary(UnaryOp::IsNull, Box::new(Expression::Column(0))).eval(&[Value::Usize(0)]), Value::Bool(false));
+
+    // Three-valued logic.
+    assert_eq!(and(null(), boolean(false)).eval(&[]), Value::Bool(false));
+    assert_eq!(and(null(), boolean(true)).eval(&[]), Value::Null);
+    assert_eq!(or(null(), boolean(true)).eval(&[]), Value::Bool(true));
+    assert_eq!(or(boolean(false), null()).eval(&[]), Value::Null);
+
+    // Functions of nulls are null, except for `Coalesce`.
+    let concat = Expression::Function(Function::Concat, vec![literal(string("a")), null()]);
+    assert_eq!(concat.eval(&[]), Value::Null);
+    let coalesce = Expression::Function(Function::Coalesce, vec![null(), Expression::Column(0), literal(string("b"))]);
+    assert_eq!(coalesce.eval(&[string("a")]), string("a"));
+    assert_eq!(coalesce.eval(&[Value::Null]), string("b"));
+
+    // Conditions that are null are not true.
+    let choice = Expression::If(Box::new(Expression::Column(0)), Box::new(literal(Value::Usize(1))), Box::new(literal(Value::Usize(2))));
+    assert_eq!(choice.eval(&[Value::Null]), Value::Usize(2));
+    assert_eq!(choice.eval(&[Value::Bool(true)]), Value::Usize(1));
+}
+
+#[test]
+fn predicate_comparisons() {
+    let constant = |value| SecondArgument::Constant(value);
+    let float = |x| Value::Float(Float(x));
+    let record = [Value::Int(-3), float(2.5), Value::Null, Value::Usize(2)];
+
+    // Numbers of different types are compared by value, as by comparison expressions.
+    assert!(Predicate::LessThan(0, constant(Value::Usize(1))).satisfied(&record));
+    assert!(Predicate::GreaterThan(1, constant(Value::Usize(2))).satisfied(&record));
+    assert!(Predicate::LessEqual(1, constant(Value::Int(3))).satisfied(&record));
+    assert!(Predicate::Equal(3, constant(float(2.0))).satisfied(&record));
+    assert!(Predicate::GreaterThan(1, SecondArgument::Position(3)).satisfied(&record));
+    assert!(!Predicate::Equal(0, constant(float(-3.5))).satisfied(&record));
+    for (index, literal) in [(0, Value::Int(-3)), (1, float(2.5)), (3, Value::Usize(2))] {
+        assert_eq!(
+            Predicate::GreaterThan(index, constant(literal.clone())).satisfied(&record),
+            binary(BinaryOp::Gt, record[index].clone(), literal) == Value::Bool(true),
+        );
+    }
+
+    // Comparisons with null are not satisfied, nor are their complements.
+    for op in [Predicate::LessThan, Predicate::LessEqual, Predicate::GreaterThan, Predicate::GreaterEqual, Predicate::Equal, Predicate::NotEqual] {
+        assert!(!op(2, constant(Value::Usize(1))).satisfied(&record));
+        assert!(!op(0, constant(Value::Null)).satisfied(&record));
+        assert!(!Predicate::Not(Box::new(op(2, constant(Value::Usize(1))))).satisfied(&record));
+    }
+    let unknown = Predicate::Equal(2, constant(Value::Usize(1)));
+    let known = Predicate::Equal(3, constant(Value::Usize(2)));
+    assert!(Predicate::Any(vec![unknown.clone(), known.clone()]).satisfied(&record));
+    assert!(!Predicate::All(vec![unknown.clone(), known.clone()]).satisfied(&record));
+    assert!(!Predicate::Not(Box::new(Predicate::All(vec![unknown, known]))).satisfied(&record));
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use differential_dataflow::trace::TraceReader;
+use differential_dataflow::trace::cursor::Cursor;
+
+use interactive::{Command, Manager, Plan, Query, Rule};
+use interactive::concrete::Value;
+
+/// Installs `edges` as an input, then `rule`, and returns the records of the rule if it was installed.
+fn install(edges: &[(usize, usize)], rule: Rule<Value>) -> Option<Vec<Vec<Value>>> {
+    let edges = edges.iter().map(|&(src, dst)| vec![Value::Usize(src), Value::Usize(dst)]).collect::<Vec<_>>();
+    timely::execute_directly(move |worker| {
+
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), edges).execute(&mut manager, worker);
+
+        let name = rule.name.clone();
+        Query::new().add_rule(rule).into_command().execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        let mut trace = manager.traces.get_unkeyed(&Plan::Source(name))?;
+        let (mut cursor, storage) = trace.cursor();
+        let mut records = Vec::new();
+        for ((record, ()), updates) in cursor.to_vec::<Vec<Value>, ()>(&storage) {
+            let count: isize = updates.iter().map(|(_, diff)| diff).sum();
+            assert!(count == 0 || count == 1, "{:?} has multiplicity {}", record, count);
+            if count == 1 {
+                records.push(record);
+            }
+        }
+        records.sort();
+        Some(records)
+    })
+}
+
+/// Pairs connected by a path in `edges`.
+fn closure(edges: &[(usize, usize)]) -> Vec<Vec<Value>> {
+    let mut pairs = edges.to_vec();
+    loop {
+        let mut next = pairs.clone();
+        for &(src, mid) in pairs.iter() {
+            next.extend(edges.iter().filter(|edge| edge.0 == mid).map(|edge| (src, edge.1)));
+        }
+        next.sort();
+        next.dedup();
+        if next == pairs { break; }
+        pairs = next;
+    }
+    pairs.into_iter().map(|(src, dst)| vec![Value::Usize(src), Value::Usize(dst)]).collect()
+}
+
+const EDGES: &[(usize, usize)] = &[(1, 2), (2, 3), (3, 4), (4, 2), (5, 1)];
+
+/// Extends the paths of `paths` by the edges of `edges`.
+fn reach(paths: &str, edges: &str) -> Plan<Value> {
+    Plan::source(edges)
+        .concat(Plan::source(paths).join(Plan::source(edges), vec![(1, 0)]).project(vec![1, 2]))
+        .distinct()
+}
+
+#[test]
+fn iterate_join() {
+    let plan = Plan::iterate(vec![reach("reach", "edges").into_rule("reach")], "reach");
+    assert_eq!(install(EDGES, plan.into_rule("result")), Some(closure(EDGES)));
+}
+
+#[test]
+fn iterate_multiway_join() {
+    let join = Plan::multiway_join(
+        vec![Plan::source("reach"), Plan::source("edges")],
+        vec![vec![(1, 0), (0, 1)]],
+        vec![(0, 0), (1, 1)],
+    );
+    let rule = Plan::source("edges").concat(join).distinct().into_rule("reach");
+    let plan = Plan::iterate(vec![rule], "reach");
+    assert_eq!(install(EDGES, plan.into_rule("result")), Some(closure(EDGES)));
+}
+
+#[test]
+fn iterate_nested() {
+    // The inner iteration refers to a binding of the outer iteration, and so iterates within it.
+    let inner = Plan::iterate(vec![reach("paths", "steps").into_rule("paths")], "paths");
+    let plan = Plan::iterate(vec![
+        Plan::source("edges").concat(Plan::source("steps")).distinct().into_rule("steps"),
+        inner.into_rule("closure"),
+    ], "closure");
+    assert_eq!(install(EDGES, plan.into_rule("result")), Some(closure(EDGES)));
+}
+
+#[test]
+fn iterate_invalid() {
+    // Invalid plans are reported and not installed, rather than panicking while rendering.
+    let plan = Plan::iterate(vec![reach("reach", "edges").into_rule("reach")], "missing");
+    assert_eq!(install(EDGES, plan.into_rule("result")), None);
+    let plan = Plan::iterate(vec![reach("reach", "missing").into_rule("reach")], "reach");
+    assert_eq!(install(EDGES, plan.into_rule("result")), None);
+}
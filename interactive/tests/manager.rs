@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use timely::communication::Allocate;
+use timely::worker::Worker;
+
+use differential_dataflow::capture::iterator::Iter;
+use differential_dataflow::capture::tcp::TcpSource;
+
+use interactive::{Command, Diff, Manager, Plan, Query, Rule};
+use interactive::concrete::Value;
+
+mod common;
+use common::{contents, pair};
+
+/// Creates the input `edges`, with a path of length two.
+fn create_edges<A: Allocate>(manager: &mut Manager<Value>, worker: &mut Worker<A>) {
+    Command::CreateInput("edges".to_string(), vec![pair(1, 2), pair(2, 3)]).execute(manager, worker);
+}
+
+/// Pairs connected by paths of length two, using arrangements of `edges`.
+fn two_hops() -> Plan<Value> {
+    Plan::source("edges").join(Plan::source("edges"), vec![(1, 0)]).project(vec![1, 2])
+}
+
+fn install<A: Allocate>(manager: &mut Manager<Value>, worker: &mut Worker<A>, rule: Rule<Value>) {
+    Query::new().add_rule(rule).into_command().execute(manager, worker);
+    Command::AdvanceTime(Duration::from_secs(1)).execute(manager, worker);
+}
+
+#[test]
+fn drop_query_shared_arrangements() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        create_edges(&mut manager, worker);
+        let installed = worker.installed_dataflows().len();
+
+        install(&mut manager, worker, two_hops().into_rule("two"));
+        install(&mut manager, worker, two_hops().project(vec![1, 0]).into_rule("owt"));
+        assert_eq!(worker.installed_dataflows().len(), installed + 2);
+        assert!(!manager.traces.keys(&Plan::source("edges")).is_empty());
+
+        // The second query imports the arrangements of `edges` the first query installed.
+        assert!(manager.drop_query("two", worker));
+        assert_eq!(contents(&mut manager, "two"), None);
+        assert_eq!(worker.installed_dataflows().len(), installed + 2);
+        assert!(!manager.traces.keys(&Plan::source("edges")).is_empty());
+
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+        assert_eq!(contents(&mut manager, "owt"), Some(vec![pair(3, 1)]));
+
+        assert!(manager.drop_query("owt", worker));
+        assert!(!manager.drop_query("owt", worker));
+        assert_eq!(worker.installed_dataflows().len(), installed);
+        assert!(manager.traces.keys(&Plan::source("edges")).is_empty());
+        assert_eq!(contents(&mut manager, "edges"), Some(vec![pair(1, 2), pair(2, 3)]));
+    });
+}
+
+#[test]
+fn drop_query_transitive() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        create_edges(&mut manager, worker);
+        let installed = worker.installed_dataflows().len();
+
+        // Each query imports the rule of the one before it.
+        install(&mut manager, worker, two_hops().into_rule("a"));
+        install(&mut manager, worker, Plan::source("a").project(vec![1, 0]).into_rule("b"));
+        install(&mut manager, worker, Plan::source("b").distinct().into_rule("c"));
+        assert_eq!(worker.installed_dataflows().len(), installed + 3);
+
+        assert!(manager.drop_query("a", worker));
+        assert!(manager.drop_query("b", worker));
+        assert_eq!(worker.installed_dataflows().len(), installed + 3);
+
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+        assert_eq!(contents(&mut manager, "c"), Some(vec![pair(3, 1)]));
+
+        // Dropping the last query releases the dataflows it kept alive.
+        assert!(manager.drop_query("c", worker));
+        assert_eq!(worker.installed_dataflows().len(), installed);
+        assert!(manager.traces.keys(&Plan::source("edges")).is_empty());
+    });
+}
+
+#[test]
+fn drop_query_subscribed() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        create_edges(&mut manager, worker);
+        let installed = worker.installed_dataflows().len();
+
+        install(&mut manager, worker, two_hops().into_rule("two"));
+        Command::Subscribe("two".to_string()).execute(&mut manager, worker);
+        assert_eq!(manager.subscriptions["two"].len(), 1);
+        assert_eq!(worker.installed_dataflows().len(), installed + 2);
+
+        // The subscription imports the rule, and is dropped with it.
+        assert!(manager.drop_query("two", worker));
+        assert!(manager.subscriptions.is_empty());
+        assert_eq!(worker.installed_dataflows().len(), installed);
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+
+        // Subscriptions to missing rules do not install dataflows.
+        Command::Subscribe("two".to_string()).execute(&mut manager, worker);
+        assert!(manager.subscriptions.is_empty());
+        assert_eq!(worker.installed_dataflows().len(), installed);
+    });
+}
+
+#[test]
+fn query_existing_name() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        create_edges(&mut manager, worker);
+        install(&mut manager, worker, two_hops().into_rule("two"));
+        let installed = worker.installed_dataflows().len();
+
+        // Queries may not replace maintained rules or inputs, nor bind a name twice.
+        install(&mut manager, worker, Plan::source("edges").into_rule("two"));
+        install(&mut manager, worker, Plan::source("two").into_rule("edges"));
+        Query::new()
+            .add_rule(Plan::source("edges").into_rule("twice"))
+            .add_rule(Plan::source("two").into_rule("twice"))
+            .into_command()
+            .execute(&mut manager, worker);
+
+        assert_eq!(worker.installed_dataflows().len(), installed);
+        assert_eq!(manager.queries.len(), 1);
+        assert_eq!(contents(&mut manager, "two"), Some(vec![pair(1, 3)]));
+        assert_eq!(contents(&mut manager, "twice"), None);
+
+        assert!(manager.drop_query("two", worker));
+        assert!(manager.queries.is_empty());
+    });
+}
+
+/// A connected pair of streams, the first as accepted by a server.
+fn connection() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind listener");
+    let client = TcpStream::connect(listener.local_addr().expect("no local address")).expect("failed to connect");
+    let (server, _) = listener.accept().expect("failed to accept");
+    (server, client)
+}
+
+#[test]
+fn subscribe_updates() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        create_edges(&mut manager, worker);
+        install(&mut manager, worker, two_hops().into_rule("two"));
+
+        let (server, client) = connection();
+        manager.connections.push_back(server);
+        Command::Subscribe("two".to_string()).execute(&mut manager, worker);
+        let mut updates: Iter<TcpSource<Vec<Value>, Duration, Diff>, _, _, _> = Iter::new(TcpSource::new(client).expect("failed to configure connection"));
+
+        let time = Duration::from_secs(1);
+        let changes = vec![(pair(3, 4), time, 1), (pair(1, 2), time, -1)];
+        Command::UpdateInput("edges".to_string(), changes).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+
+        // Apply received updates, as a client would, until they are complete through `time`.
+        let mut view = BTreeMap::new();
+        let mut complete = false;
+        for _ in 0 .. 1000 {
+            worker.step();
+            for (batch, frontier) in updates.by_ref() {
+                for (record, _time, diff) in batch {
+                    *view.entry(record).or_insert(0) += diff;
+                }
+                complete = complete || !frontier.less_equal(&time);
+            }
+            if complete { break; }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        view.retain(|_, diff| *diff != 0);
+
+        assert!(complete);
+        assert_eq!(view, BTreeMap::from([(pair(2, 4), 1)]));
+        assert_eq!(contents(&mut manager, "two"), Some(vec![pair(2, 4)]));
+    });
+}
+
+#[test]
+fn subscribe_missing_rule() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        let installed = worker.installed_dataflows().len();
+        let (server, mut client) = connection();
+        manager.connections.push_back(server);
+
+        // The connection is closed, rather than left awaiting updates.
+        Command::Subscribe("missing".to_string()).execute(&mut manager, worker);
+        assert!(manager.connections.is_empty());
+        assert!(manager.subscriptions.is_empty());
+        assert_eq!(worker.installed_dataflows().len(), installed);
+        assert_eq!(client.read(&mut [0u8; 16]).expect("read failed"), 0);
+    });
+}
+
+#[test]
+fn subscribe_failed_connection() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        create_edges(&mut manager, worker);
+        install(&mut manager, worker, two_hops().into_rule("two"));
+        let installed = worker.installed_dataflows().len();
+
+        let (server, client) = connection();
+        manager.connections.push_back(server);
+        Command::Subscribe("two".to_string()).execute(&mut manager, worker);
+        assert_eq!(worker.installed_dataflows().len(), installed + 1);
+        drop(client);
+
+        // Writes to the closed connection eventually fail, and the failure is reported once.
+        let mut failed = Vec::new();
+        for round in 2 .. 1000 {
+            let updates = vec![(pair(round, round + 1), Duration::from_secs(round as u64), 1)];
+            Command::UpdateInput("edges".to_string(), updates).execute(&mut manager, worker);
+            Command::AdvanceTime(Duration::from_secs(round as u64 + 1)).execute(&mut manager, worker);
+            worker.step();
+            failed = manager.failed_subscriptions();
+            if !failed.is_empty() { break; }
+        }
+        assert_eq!(failed.len(), 1);
+        assert!(manager.failed_subscriptions().is_empty());
+
+        Command::Unsubscribe(failed[0].0).execute(&mut manager, worker);
+        assert!(manager.subscriptions.is_empty());
+        assert_eq!(worker.installed_dataflows().len(), installed);
+        assert!(!manager.drop_subscription(failed[0].0, worker));
+    });
+}
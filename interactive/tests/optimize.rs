@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use interactive::{Command, Manager, Plan, Query, Rule};
+use interactive::concrete::Value;
+use interactive::plan::{Aggregate, Predicate, optimize};
+use interactive::plan::filter::SecondArgument;
+
+mod common;
+use common::{multiplicities, pairs};
+
+/// Installs `plan` as written and as optimized, after the rules of `prepare`, and checks that
+/// both produce the same records before and after updates to their inputs.
+///
+/// Returns the optimized plan.
+fn compare(plan: Plan<Value>, prepare: Vec<Rule<Value>>) -> Plan<Value> {
+    timely::execute_directly(move |worker| {
+
+        let mut manager = Manager::<Value>::new();
+        let edges = pairs(&[(1, 2), (2, 3), (3, 1), (2, 2), (3, 4)]);
+        let labels = pairs(&[(1, 10), (2, 10), (3, 20), (4, 20), (4, 20)]);
+        Command::CreateInput("edges".to_string(), edges).execute(&mut manager, worker);
+        Command::CreateInput("labels".to_string(), labels).execute(&mut manager, worker);
+        for rule in prepare {
+            Query::new().add_rule(rule).into_command().execute(&mut manager, worker);
+        }
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        // Plans are optimized against the maintained traces, as the server optimizes commands.
+        let optimized = optimize(plan.clone(), &manager.traces);
+        Query::new().add_rule(plan.into_rule("plain")).into_command().execute(&mut manager, worker);
+        Query::new().add_rule(optimized.clone().into_rule("optimized")).into_command().execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+        let plain = multiplicities(&mut manager, "plain");
+        assert!(!plain.as_ref().expect("rule not found").is_empty());
+        assert_eq!(plain, multiplicities(&mut manager, "optimized"));
+
+        let time = Duration::from_secs(2);
+        let edges = vec![(pairs(&[(4, 1)]).remove(0), time, 1), (pairs(&[(2, 2)]).remove(0), time, -1)];
+        let labels = vec![(pairs(&[(1, 20)]).remove(0), time, 1)];
+        Command::UpdateInput("edges".to_string(), edges).execute(&mut manager, worker);
+        Command::UpdateInput("labels".to_string(), labels).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(3)).execute(&mut manager, worker);
+        let updated = multiplicities(&mut manager, "plain");
+        assert_ne!(plain, updated);
+        assert_eq!(updated, multiplicities(&mut manager, "optimized"));
+
+        optimized
+    })
+}
+
+#[test]
+fn optimize_filter_join() {
+    // Records are the shared node, followed by the source and destination of a path of length two.
+    let plan =
+    Plan::source("edges")
+        .join(Plan::source("edges"), vec![(1, 0)])
+        .filter(Predicate::All(vec![
+            Predicate::NotEqual(0, SecondArgument::Constant(Value::Usize(1))),
+            Predicate::LessThan(2, SecondArgument::Constant(Value::Usize(4))),
+            Predicate::NotEqual(1, SecondArgument::Position(2)),
+        ]));
+    assert_ne!(compare(plan.clone(), Vec::new()), plan);
+}
+
+#[test]
+fn optimize_project_filter() {
+    let plan =
+    Plan::source("edges")
+        .concat(Plan::source("edges"))
+        .filter(Predicate::NotEqual(0, SecondArgument::Position(1)))
+        .project(vec![1, 0])
+        .project(vec![1])
+        .consolidate()
+        .distinct();
+    assert_ne!(compare(plan.clone(), Vec::new()), plan);
+}
+
+#[test]
+fn optimize_filter_reduce() {
+    let plan =
+    Plan::source("labels")
+        .reduce(vec![1], vec![Aggregate::Count, Aggregate::Sum(0)])
+        .filter(Predicate::All(vec![
+            Predicate::GreaterThan(0, SecondArgument::Constant(Value::Usize(10))),
+            Predicate::GreaterThan(1, SecondArgument::Constant(Value::Usize(1))),
+        ]));
+    assert_ne!(compare(plan.clone(), Vec::new()), plan);
+}
+
+#[test]
+fn optimize_multiway_join() {
+    // The source, destination, and label of the destination of each edge.
+    let plan =
+    Plan::multiway_join(
+        vec![Plan::source("edges"), Plan::source("labels")],
+        vec![vec![(1, 0), (0, 1)]],
+        vec![(0, 0), (1, 0), (1, 1)],
+    )
+    .filter(Predicate::Equal(2, SecondArgument::Constant(Value::Usize(20))))
+    .project(vec![0]);
+    assert_ne!(compare(plan.clone(), Vec::new()), plan);
+}
+
+#[test]
+fn optimize_maintained_keys() {
+    // Joins and reductions re-use arrangements by keys in a different order.
+    let prepare = vec![
+        Plan::source("labels").join(Plan::source("labels"), vec![(1, 1), (0, 0)]).into_rule("pairs"),
+    ];
+    let plan = Plan::source("labels").join(Plan::source("labels"), vec![(0, 0), (1, 1)]);
+    assert_ne!(compare(plan.clone(), prepare.clone()), plan);
+    let plan = Plan::source("labels").reduce(vec![0, 1], vec![Aggregate::Count]);
+    assert_ne!(compare(plan.clone(), prepare), plan);
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use interactive::{Command, Datum, Manager, Plan, Query};
+use interactive::concrete::Value;
+use interactive::plan::{Aggregate, Predicate};
+use interactive::plan::filter::SecondArgument;
+
+mod common;
+use common::multiplicities;
+
+#[test]
+fn aggregate_negative_multiplicities() {
+
+    let records = vec![
+        (vec![Value::Usize(3)], -2),
+        (vec![Value::Usize(1)], 1),
+        (vec![Value::Null], -1),
+    ];
+    assert_eq!(Value::aggregate(&records, &Aggregate::Count), Value::Int(-2));
+    assert_eq!(Value::aggregate(&records, &Aggregate::Sum(0)), Value::Int(-5));
+    assert_eq!(Value::aggregate(&records, &Aggregate::Avg(0)), Value::Int(5));
+    assert_eq!(Value::aggregate(&records, &Aggregate::Min(0)), Value::Usize(1));
+
+    let records = vec![(vec![Value::Usize(3)], 2), (vec![Value::Usize(1)], 1)];
+    assert_eq!(Value::aggregate(&records, &Aggregate::Count), Value::Usize(3));
+    assert_eq!(Value::aggregate(&records, &Aggregate::Sum(0)), Value::Usize(7));
+    assert_eq!(Value::aggregate(&records, &Aggregate::Avg(0)), Value::Usize(2));
+}
+
+#[test]
+fn reduce_arrangement_reused() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        let records = vec![(1, 10), (1, 20), (2, 30)];
+        let records = records.into_iter().map(|(x, y)| vec![Value::Usize(x), Value::Usize(y)]).collect();
+        Command::CreateInput("a".to_string(), records).execute(&mut manager, worker);
+
+        let reduce = Plan::source("a").reduce(vec![0], vec![Aggregate::Count, Aggregate::Sum(1)]);
+        Query::new()
+            .add_rule(reduce.clone().into_rule("totals"))
+            .into_command()
+            .execute(&mut manager, worker);
+
+        // The output of the reduction is arranged by its group key.
+        assert_eq!(manager.traces.keys(&reduce), vec![vec![0]]);
+
+        let filtered = reduce.clone().filter(Predicate::GreaterThan(1, SecondArgument::Constant(Value::Usize(1))));
+        Query::new()
+            .add_rule(filtered.into_rule("large"))
+            .into_command()
+            .execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        assert_eq!(multiplicities(&mut manager, "totals"), Some(vec![
+            (vec![Value::Usize(1), Value::Usize(2), Value::Usize(30)], 1),
+            (vec![Value::Usize(2), Value::Usize(1), Value::Usize(30)], 1),
+        ]));
+        assert_eq!(multiplicities(&mut manager, "large"), Some(vec![
+            (vec![Value::Usize(1), Value::Usize(2), Value::Usize(30)], 1),
+        ]));
+
+        // The second query imports the arrangement, which survives the query that installed it.
+        assert!(manager.drop_query("totals", worker));
+        assert_eq!(manager.traces.keys(&reduce), vec![vec![0]]);
+        assert!(manager.drop_query("large", worker));
+        assert!(manager.traces.keys(&reduce).is_empty());
+    });
+}
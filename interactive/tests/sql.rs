@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use interactive::{Command, Manager, Plan, Query};
+use interactive::concrete::Value;
+use interactive::plan::{Aggregate, Predicate};
+use interactive::plan::filter::SecondArgument;
+use interactive::sql::{self, Catalog, Planned};
+use interactive::sql::parse::{self, BinaryOp, Expr, Literal, QueryExpr, SelectItem, SetOp, Statement, TableRef};
+
+mod common;
+use common::{contents, pair};
+
+const TABLES: &str = "CREATE TABLE a (x int, y int); CREATE TABLE b (y int, z int);";
+
+/// Plans `TABLES` followed by `view`, and returns the plan of the view.
+fn plan_view(view: &str) -> Result<Plan<Value>, String> {
+    let mut catalog = Catalog::new();
+    let planned = sql::plan::<Value>(&format!("{} {}", TABLES, view), &mut catalog)?;
+    match planned.into_iter().last() {
+        Some(Planned::CreateView(rule)) => Ok(rule.plan),
+        other => panic!("expected a view, found {:?}", other),
+    }
+}
+
+fn column(table: &str, name: &str) -> Box<Expr> {
+    Box::new(Expr::Column(Some(table.to_string()), name.to_string()))
+}
+
+#[test]
+fn parse_select() {
+
+    let statements = parse::parse("CREATE VIEW v AS SELECT DISTINCT a.x, count(*) AS n FROM a JOIN b ON a.y = b.y WHERE a.x > 3 GROUP BY a.x HAVING count(*) > 1").unwrap();
+    let select = match &statements[..] {
+        [Statement::CreateView(name, None, QueryExpr::Select(select))] if name == "v" => select,
+        other => panic!("unexpected statements: {:?}", other),
+    };
+
+    let count = Expr::Function { name: "count".to_string(), distinct: false, args: Vec::new() };
+    assert!(select.distinct);
+    assert_eq!(select.items, vec![
+        SelectItem::Expr(*column("a", "x"), None),
+        SelectItem::Expr(count.clone(), Some("n".to_string())),
+    ]);
+    assert_eq!(select.from, vec![
+        (TableRef::Table("a".to_string(), None), None),
+        (TableRef::Table("b".to_string(), None), Some(Expr::Binary(BinaryOp::Eq, column("a", "y"), column("b", "y")))),
+    ]);
+    assert_eq!(select.selection, Some(Expr::Binary(BinaryOp::Gt, column("a", "x"), Box::new(Expr::Literal(Literal::Integer(3))))));
+    assert_eq!(select.group_by, vec![*column("a", "x")]);
+    assert_eq!(select.having, Some(Expr::Binary(BinaryOp::Gt, Box::new(count), Box::new(Expr::Literal(Literal::Integer(1))))));
+}
+
+#[test]
+fn parse_set_operations() {
+
+    let statements = parse::parse("CREATE VIEW v (c) AS SELECT x FROM a UNION ALL SELECT y FROM b EXCEPT SELECT z FROM b").unwrap();
+    match &statements[..] {
+        [Statement::CreateView(_, Some(columns), QueryExpr::SetOp(SetOp::Except, false, left, _))] => {
+            assert_eq!(columns, &vec!["c".to_string()]);
+            assert!(matches!(**left, QueryExpr::SetOp(SetOp::Union, true, _, _)));
+        },
+        other => panic!("unexpected statements: {:?}", other),
+    }
+
+    assert!(parse::parse("SELECT x FROM a").is_err());
+    assert!(parse::parse("CREATE VIEW v AS SELECT x FROM a WHERE").is_err());
+}
+
+#[test]
+fn plan_join_and_filter() {
+
+    // The join produces the key `y`, then `a.x`, then `b.z`.
+    let plan = plan_view("CREATE VIEW v AS SELECT a.x, b.z FROM a JOIN b ON a.y = b.y WHERE a.x > 3;").unwrap();
+    let expected =
+    Plan::source("a")
+        .join(Plan::source("b"), vec![(1, 0)])
+        .filter(Predicate::GreaterThan(1, SecondArgument::Constant(Value::Usize(3))))
+        .project(vec![1, 2]);
+    assert_eq!(plan, expected);
+
+    // Equalities in `WHERE` also key joins, and other conditions filter the joined tuples.
+    let plan = plan_view("CREATE VIEW v AS SELECT * FROM a, b WHERE b.y = a.y AND a.x <> b.z;").unwrap();
+    let expected =
+    Plan::source("a")
+        .join(Plan::source("b"), vec![(1, 0)])
+        .filter(Predicate::NotEqual(1, SecondArgument::Position(2)));
+    assert_eq!(plan, expected);
+}
+
+#[test]
+fn plan_group_by_and_having() {
+
+    let plan = plan_view("CREATE VIEW v AS SELECT x, count(*) AS n, sum(y) FROM a GROUP BY x HAVING count(*) > 1;").unwrap();
+    let expected =
+    Plan::source("a")
+        .reduce(vec![0], vec![Aggregate::Count, Aggregate::Sum(1)])
+        .filter(Predicate::GreaterThan(1, SecondArgument::Constant(Value::Usize(1))));
+    assert_eq!(plan, expected);
+
+    let error = plan_view("CREATE VIEW v AS SELECT y, count(*) FROM a GROUP BY x;").unwrap_err();
+    assert!(error.contains("must appear in GROUP BY"), "{}", error);
+}
+
+#[test]
+fn plan_union_and_except() {
+
+    let left = Plan::source("a").project(vec![0]);
+    let right = Plan::source("b").project(vec![0]);
+
+    let plan = plan_view("CREATE VIEW v AS SELECT x FROM a UNION SELECT y FROM b;").unwrap();
+    assert_eq!(plan, left.clone().concat(right.clone()).distinct());
+
+    let plan = plan_view("CREATE VIEW v AS SELECT x FROM a UNION ALL SELECT y FROM b;").unwrap();
+    assert_eq!(plan, left.clone().concat(right.clone()));
+
+    let plan = plan_view("CREATE VIEW v AS SELECT x FROM a EXCEPT SELECT y FROM b;").unwrap();
+    let distinct = left.distinct();
+    assert_eq!(plan, distinct.clone().concat(distinct.join(right.distinct(), vec![(0, 0)]).negate()));
+
+    let error = plan_view("CREATE VIEW v AS SELECT x FROM a UNION SELECT * FROM b;").unwrap_err();
+    assert!(error.contains("set operation"), "{}", error);
+}
+
+#[test]
+fn catalog_records_views() {
+
+    let mut catalog = Catalog::new();
+    let planned = sql::plan::<Value>(&format!("{} CREATE VIEW v (p, q) AS SELECT * FROM b;", TABLES), &mut catalog).unwrap();
+    assert!(matches!(&planned[0], Planned::CreateTable(name) if name == "a"));
+    assert!(matches!(&planned[2], Planned::CreateView(rule) if rule.name == "v" && rule.plan == Plan::source("b")));
+    assert_eq!(catalog.relations["v"], vec!["p".to_string(), "q".to_string()]);
+
+    // Later statements refer to earlier views, and a failed statement leaves the catalog unchanged.
+    assert!(sql::plan::<Value>("CREATE VIEW w AS SELECT p FROM v WHERE q = 'x';", &mut catalog).is_ok());
+    assert!(sql::plan::<Value>("CREATE VIEW u AS SELECT p FROM w; CREATE VIEW t AS SELECT r FROM w;", &mut catalog).is_err());
+    assert!(!catalog.relations.contains_key("u"));
+
+    let error = sql::plan::<Value>("CREATE VIEW t AS SELECT a.* FROM v;", &mut catalog).unwrap_err();
+    assert!(error.contains("unknown table: a"), "{}", error);
+}
+
+#[test]
+fn execute_views() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), vec![pair(1, 2), pair(2, 3), pair(3, 4)]).execute(&mut manager, worker);
+
+        // Views refer to tables, to inputs by the positions of their columns, and to earlier views.
+        Query::new()
+            .add_sql("CREATE TABLE labels (node int, label int);")
+            .add_sql("CREATE VIEW two (src, dst) AS SELECT e1.c0, e2.c1 FROM edges e1 JOIN edges e2 ON e1.c1 = e2.c0;")
+            .add_sql("CREATE VIEW labelled AS SELECT two.src, labels.label FROM two JOIN labels ON two.dst = labels.node WHERE labels.label > 5;")
+            .into_command()
+            .execute(&mut manager, worker);
+
+        let updates = vec![(pair(3, 10), Duration::from_secs(0), 1), (pair(4, 2), Duration::from_secs(0), 1)];
+        Command::UpdateInput("labels".to_string(), updates).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        assert_eq!(manager.catalog.relations["edges"], vec!["c0".to_string(), "c1".to_string()]);
+        assert_eq!(contents(&mut manager, "two"), Some(vec![pair(1, 3), pair(2, 4)]));
+        assert_eq!(contents(&mut manager, "labelled"), Some(vec![pair(1, 10)]));
+    });
+}
+
+#[test]
+fn execute_failed_query() {
+    timely::execute_directly(|worker| {
+
+        let mut manager = Manager::<Value>::new();
+        let installed = worker.installed_dataflows().len();
+
+        // A rule that fails to validate leaves the catalog unchanged, and creates no tables.
+        Query::new()
+            .add_sql("CREATE TABLE t (x int); CREATE VIEW v AS SELECT x FROM t;")
+            .add_rule(Plan::source("missing").into_rule("w"))
+            .into_command()
+            .execute(&mut manager, worker);
+
+        assert!(manager.catalog.relations.is_empty());
+        assert!(manager.inputs.sessions.is_empty());
+        assert_eq!(worker.installed_dataflows().len(), installed);
+        assert_eq!(contents(&mut manager, "v"), None);
+
+        // The same statements succeed without the failed rule.
+        Query::new()
+            .add_sql("CREATE TABLE t (x int); CREATE VIEW v AS SELECT x FROM t;")
+            .into_command()
+            .execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+        assert_eq!(contents(&mut manager, "v"), Some(Vec::new()));
+    });
+}